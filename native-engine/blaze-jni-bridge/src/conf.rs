@@ -44,6 +44,17 @@ define_conf!(IntConf, PARTIAL_AGG_SKIPPING_MIN_ROWS);
 define_conf!(BooleanConf, PARQUET_ENABLE_PAGE_FILTERING);
 define_conf!(BooleanConf, PARQUET_ENABLE_BLOOM_FILTER);
 define_conf!(StringConf, SPARK_IO_COMPRESSION_CODEC);
+define_conf!(IntConf, SPARK_IO_COMPRESSION_ZSTD_LEVEL);
+define_conf!(BooleanConf, SPARK_SHUFFLE_COMPRESS);
+define_conf!(IntConf, SHUFFLE_SKEWED_PARTITION_SPILL_THRESHOLD);
+define_conf!(BooleanConf, SHUFFLE_PARTITION_SIZE_HISTOGRAM_ENABLE);
+define_conf!(IntConf, SHUFFLE_HASH_VALIDATION_SAMPLE_INTERVAL);
+define_conf!(IntConf, SHUFFLE_BYPASS_MERGE_THRESHOLD);
+define_conf!(IntConf, SHUFFLE_BYPASS_PARTITION_BUFFER_SIZE);
+define_conf!(BooleanConf, SHUFFLE_MERGE_SPILLS_BY_FILE_COPY);
+define_conf!(BooleanConf, SPILL_COMPRESSION_ENABLE);
+define_conf!(IntConf, OUTPUT_MAX_IN_FLIGHT_BATCHES);
+define_conf!(IntConf, PROJECT_BATCH_MEM_SIZE);
 
 pub trait BooleanConf {
     fn key(&self) -> &'static str;
@@ -53,7 +53,7 @@ use datafusion_ext_commons::downcast_any;
 use datafusion_ext_exprs::{
     bloom_filter_might_contain::BloomFilterMightContainExpr, cast::TryCastExpr,
     get_indexed_field::GetIndexedFieldExpr, get_map_value::GetMapValueExpr,
-    named_struct::NamedStructExpr, row_num::RowNumExpr,
+    named_struct::NamedStructExpr, row_num::RowNumExpr, spark_coalesce::SparkCoalesceExpr,
     spark_scalar_subquery_wrapper::SparkScalarSubqueryWrapperExpr,
     spark_udf_wrapper::SparkUDFWrapperExpr, string_contains::StringContainsExpr,
     string_ends_with::StringEndsWithExpr, string_starts_with::StringStartsWithExpr,
@@ -79,7 +79,10 @@ use datafusion_ext_plans::{
     project_exec::ProjectExec,
     rename_columns_exec::RenameColumnsExec,
     rss_shuffle_writer_exec::RssShuffleWriterExec,
-    shuffle_writer_exec::ShuffleWriterExec,
+    sample_exec::SampleExec,
+    shuffle::checksum::ShuffleChecksumAlgorithm,
+    shuffle_reader_exec::ShuffleReaderExec,
+    shuffle_writer_exec::{ShuffleWriterExec, DEFAULT_HASH_SEED},
     sort_exec::SortExec,
     sort_merge_join_exec::SortMergeJoinExec,
     window::{WindowExpr, WindowFunction, WindowRankType},
@@ -268,12 +271,22 @@ impl TryInto<Arc<dyn ExecutionPlan>> for &protobuf::PhysicalPlanNode {
                     shuffle_writer.output_partitioning.as_ref(),
                 )?;
 
-                Ok(Arc::new(ShuffleWriterExec::try_new(
+                let mut shuffle_writer_exec = ShuffleWriterExec::try_new(
                     input,
                     output_partitioning.unwrap(),
                     shuffle_writer.output_data_file.clone(),
                     shuffle_writer.output_index_file.clone(),
-                )?))
+                    parse_protobuf_hash_seed(shuffle_writer.output_partitioning.as_ref()),
+                )?;
+                if !shuffle_writer.output_checksum_file.is_empty() {
+                    shuffle_writer_exec = shuffle_writer_exec.with_output_checksum_file(
+                        shuffle_writer.output_checksum_file.clone(),
+                        ShuffleChecksumAlgorithm::try_from_name(
+                            &shuffle_writer.checksum_algorithm,
+                        )?,
+                    );
+                }
+                Ok(Arc::new(shuffle_writer_exec))
             }
             PhysicalPlanType::RssShuffleWriter(rss_shuffle_writer) => {
                 let input: Arc<dyn ExecutionPlan> =
@@ -287,6 +300,7 @@ impl TryInto<Arc<dyn ExecutionPlan>> for &protobuf::PhysicalPlanNode {
                     input,
                     output_partitioning.unwrap(),
                     rss_shuffle_writer.rss_partition_writer_resource_id.clone(),
+                    parse_protobuf_hash_seed(rss_shuffle_writer.output_partitioning.as_ref()),
                 )?))
             }
             PhysicalPlanType::IpcWriter(ipc_writer) => {
@@ -305,6 +319,24 @@ impl TryInto<Arc<dyn ExecutionPlan>> for &protobuf::PhysicalPlanNode {
                     schema,
                 )))
             }
+            PhysicalPlanType::ShuffleReader(shuffle_reader) => {
+                let schema = Arc::new(convert_required!(shuffle_reader.schema)?);
+                let mut shuffle_reader_exec = ShuffleReaderExec::new(
+                    shuffle_reader.num_partitions as usize,
+                    shuffle_reader.data_file.clone(),
+                    shuffle_reader.index_file.clone(),
+                    schema,
+                );
+                if !shuffle_reader.checksum_file.is_empty() {
+                    shuffle_reader_exec = shuffle_reader_exec.with_checksum_file(
+                        shuffle_reader.checksum_file.clone(),
+                        ShuffleChecksumAlgorithm::try_from_name(
+                            &shuffle_reader.checksum_algorithm,
+                        )?,
+                    );
+                }
+                Ok(Arc::new(shuffle_reader_exec))
+            }
             PhysicalPlanType::Debug(debug) => {
                 let input: Arc<dyn ExecutionPlan> = convert_box_required!(debug.input)?;
                 Ok(Arc::new(DebugExec::new(input, debug.debug_id.clone())))
@@ -523,7 +555,7 @@ impl TryInto<Arc<dyn ExecutionPlan>> for &protobuf::PhysicalPlanNode {
             }
             PhysicalPlanType::Limit(limit) => {
                 let input: Arc<dyn ExecutionPlan> = convert_box_required!(limit.input)?;
-                Ok(Arc::new(LimitExec::new(input, limit.limit)))
+                Ok(Arc::new(LimitExec::new(input, limit.limit, limit.offset)))
             }
             PhysicalPlanType::FfiReader(ffi_reader) => {
                 let schema = Arc::new(convert_required!(ffi_reader.schema)?);
@@ -533,9 +565,23 @@ impl TryInto<Arc<dyn ExecutionPlan>> for &protobuf::PhysicalPlanNode {
                     schema,
                 )))
             }
+            PhysicalPlanType::Sample(sample) => {
+                let input: Arc<dyn ExecutionPlan> = convert_box_required!(sample.input)?;
+                Ok(Arc::new(SampleExec::try_new(
+                    input,
+                    sample.lower_bound,
+                    sample.upper_bound,
+                    sample.with_replacement,
+                    sample.seed,
+                )?))
+            }
             PhysicalPlanType::CoalesceBatches(coalesce_batches) => {
                 let input: Arc<dyn ExecutionPlan> = convert_box_required!(coalesce_batches.input)?;
-                Ok(Arc::new(LimitExec::new(input, coalesce_batches.batch_size)))
+                Ok(Arc::new(LimitExec::new(
+                    input,
+                    coalesce_batches.batch_size,
+                    0,
+                )))
             }
             PhysicalPlanType::Expand(expand) => {
                 let schema = Arc::new(convert_required!(expand.schema)?);
@@ -599,6 +645,21 @@ impl TryInto<Arc<dyn ExecutionPlan>> for &protobuf::PhysicalPlanNode {
                                 protobuf::WindowFunction::DenseRank => {
                                     WindowFunction::RankLike(WindowRankType::DenseRank)
                                 }
+                                protobuf::WindowFunction::Ntile => {
+                                    WindowFunction::RankLike(WindowRankType::Ntile)
+                                }
+                                protobuf::WindowFunction::PercentRank => {
+                                    WindowFunction::RankLike(WindowRankType::PercentRank)
+                                }
+                                protobuf::WindowFunction::CumeDist => {
+                                    WindowFunction::RankLike(WindowRankType::CumeDist)
+                                }
+                                protobuf::WindowFunction::NthValue => WindowFunction::NthValue {
+                                    ignore_nulls: false,
+                                },
+                                protobuf::WindowFunction::NthValueIgnoresNull => {
+                                    WindowFunction::NthValue { ignore_nulls: true }
+                                }
                             },
                             protobuf::WindowFunctionType::Agg => match w.agg_func() {
                                 protobuf::AggFunction::Min => WindowFunction::Agg(AggFunction::Min),
@@ -623,6 +684,12 @@ impl TryInto<Arc<dyn ExecutionPlan>> for &protobuf::PhysicalPlanNode {
                                 protobuf::AggFunction::BloomFilter => {
                                     WindowFunction::Agg(AggFunction::BloomFilter)
                                 }
+                                protobuf::AggFunction::TrySum => {
+                                    WindowFunction::Agg(AggFunction::TrySum)
+                                }
+                                protobuf::AggFunction::TryAvg => {
+                                    WindowFunction::Agg(AggFunction::TryAvg)
+                                }
                                 protobuf::AggFunction::BrickhouseCollect => {
                                     WindowFunction::Agg(AggFunction::BrickhouseCollect)
                                 }
@@ -1002,6 +1069,7 @@ fn try_parse_physical_expr(
                     .iter()
                     .map(|x| try_parse_physical_expr(x, input_schema))
                     .collect::<Result<Vec<_>, _>>()?,
+                e.deterministic,
             )?),
             ExprType::SparkScalarSubqueryWrapperExpr(e) => {
                 Arc::new(SparkScalarSubqueryWrapperExpr::try_new(
@@ -1037,6 +1105,12 @@ fn try_parse_physical_expr(
                 try_parse_physical_expr_box_required(&e.bloom_filter_expr, input_schema)?,
                 try_parse_physical_expr_box_required(&e.value_expr, input_schema)?,
             )),
+            ExprType::SparkCoalesceExpr(e) => Arc::new(SparkCoalesceExpr::try_new(
+                e.args
+                    .iter()
+                    .map(|x| try_parse_physical_expr(x, input_schema))
+                    .collect::<Result<Vec<_>, _>>()?,
+            )?),
             ExprType::ScAndExpr(e) => {
                 let l = try_parse_physical_expr_box_required(&e.left, input_schema)?;
                 let r = try_parse_physical_expr_box_required(&e.right, input_schema)?;
@@ -1096,6 +1170,9 @@ pub fn parse_protobuf_hash_partitioning(
     partitioning: Option<&protobuf::PhysicalHashRepartition>,
 ) -> Result<Option<Partitioning>, PlanSerDeError> {
     match partitioning {
+        Some(part) if part.round_robin => Ok(Some(Partitioning::RoundRobinBatch(
+            part.partition_count.try_into().unwrap(),
+        ))),
         Some(hash_part) => {
             let expr = hash_part
                 .hash_expr
@@ -1115,6 +1192,14 @@ pub fn parse_protobuf_hash_partitioning(
     }
 }
 
+// the seed is only set with hash partitioning
+pub fn parse_protobuf_hash_seed(partitioning: Option<&protobuf::PhysicalHashRepartition>) -> i32 {
+    match partitioning {
+        Some(hash_part) if !hash_part.hash_expr.is_empty() => hash_part.hash_seed,
+        _ => DEFAULT_HASH_SEED,
+    }
+}
+
 impl TryFrom<&protobuf::PartitionedFile> for PartitionedFile {
     type Error = PlanSerDeError;
 
@@ -136,6 +136,8 @@ impl From<protobuf::AggFunction> for AggFunction {
             protobuf::AggFunction::First => AggFunction::First,
             protobuf::AggFunction::FirstIgnoresNull => AggFunction::FirstIgnoresNull,
             protobuf::AggFunction::BloomFilter => AggFunction::BloomFilter,
+            protobuf::AggFunction::TrySum => AggFunction::TrySum,
+            protobuf::AggFunction::TryAvg => AggFunction::TryAvg,
             protobuf::AggFunction::BrickhouseCollect => AggFunction::BrickhouseCollect,
             protobuf::AggFunction::BrickhouseCombineUnique => AggFunction::BrickhouseCombineUnique,
         }
@@ -21,6 +21,7 @@ use std::{
 use arrow::{
     array::*,
     buffer::{Buffer, MutableBuffer},
+    compute::{cast, take},
     datatypes::*,
     record_batch::{RecordBatch, RecordBatchOptions},
 };
@@ -153,6 +154,7 @@ pub fn write_array<W: Write>(array: &dyn Array, output: &mut W) -> Result<()> {
         DataType::List(_field) => write_list_array(as_list_array(array), output)?,
         DataType::Map(..) => write_map_array(as_map_array(array), output)?,
         DataType::Struct(_) => write_struct_array(as_struct_array(array), output)?,
+        DataType::Dictionary(..) => write_dictionary_array(array.as_any_dictionary(), output)?,
         other => df_unimplemented_err!("unsupported data type: {other}")?,
     }
     Ok(())
@@ -199,6 +201,9 @@ pub fn read_array<R: Read>(
             read_map_array(num_rows, input, map_field, *is_sorted)?
         }
         DataType::Struct(fields) => read_struct_array(num_rows, input, fields)?,
+        DataType::Dictionary(_, value_type) => {
+            read_dictionary_array(num_rows, input, data_type, value_type)?
+        }
         other => df_unimplemented_err!("unsupported data type: {other}")?,
     })
 }
@@ -509,6 +514,51 @@ fn read_struct_array<R: Read>(num_rows: usize, input: &mut R, fields: &Fields) -
     Ok(make_array(array_data))
 }
 
+fn write_dictionary_array<W: Write>(array: &dyn AnyDictionaryArray, output: &mut W) -> Result<()> {
+    // only values referenced by valid keys are written, so that every serialized
+    // batch is self-contained without carrying the whole shared dictionary of
+    // sliced/taken arrays
+    let key_nulls = array.keys().logical_nulls();
+    let mut value_ids = vec![u32::MAX; array.values().len()];
+    let mut used_values = vec![];
+    let keys: UInt32Array = array
+        .normalized_keys()
+        .into_iter()
+        .enumerate()
+        .map(|(i, key)| {
+            if key_nulls.as_ref().is_some_and(|nulls| nulls.is_null(i)) {
+                return None;
+            }
+            if value_ids[key] == u32::MAX {
+                value_ids[key] = used_values.len() as u32;
+                used_values.push(key as u32);
+            }
+            Some(value_ids[key])
+        })
+        .collect();
+    let values = take(array.values(), &UInt32Array::from(used_values), None)?;
+
+    write_len(values.len(), output)?;
+    write_array(&values, output)?;
+    write_array(&keys, output)?;
+    Ok(())
+}
+
+fn read_dictionary_array<R: Read>(
+    num_rows: usize,
+    input: &mut R,
+    data_type: &DataType,
+    value_type: &DataType,
+) -> Result<ArrayRef> {
+    let num_values = read_len(input)?;
+    let values = read_array(input, value_type, num_values)?;
+    let keys = read_array(input, &DataType::UInt32, num_rows)?;
+    let dict = DictionaryArray::try_new(as_primitive_array::<UInt32Type>(&keys).clone(), values)?;
+
+    // cast to the original key type
+    Ok(cast(&dict, data_type)?)
+}
+
 fn write_boolean_array<W: Write>(array: &BooleanArray, output: &mut W) -> Result<()> {
     let array_data = array.to_data();
     if let Some(null_buffer) = array_data.nulls() {
@@ -847,6 +897,42 @@ mod test {
         assert_eq!(name_batch(decoded_batch, &sliced.schema()).unwrap(), sliced);
     }
 
+    #[test]
+    fn test_write_and_read_batch_for_dictionary() {
+        let values = (0..1000)
+            .map(|i| format!("value-{}", i % 3))
+            .collect::<Vec<_>>();
+        let dict: DictionaryArray<Int8Type> = values
+            .iter()
+            .enumerate()
+            .map(|(i, v)| (i % 7 != 0).then_some(v.as_str()))
+            .collect();
+        let dict_array: ArrayRef = Arc::new(dict);
+        let batch =
+            RecordBatch::try_from_iter_with_nullable(vec![("dict", dict_array.clone(), true)])
+                .unwrap();
+
+        // test read after write, dictionary is preserved
+        let mut buf = vec![];
+        write_batch(&batch, &mut buf).unwrap();
+        let mut cursor = Cursor::new(buf);
+        let decoded_batch = read_batch(&mut cursor).unwrap();
+        assert_eq!(decoded_batch.column(0).data_type(), dict_array.data_type());
+        assert_eq!(name_batch(decoded_batch, &batch.schema()).unwrap(), batch);
+
+        // test read after write sliced, unused dictionary values are not written
+        let sliced = batch.slice(1, 2);
+        let mut buf = vec![];
+        write_batch(&sliced, &mut buf).unwrap();
+        let mut cursor = Cursor::new(buf);
+        let decoded_batch = read_batch(&mut cursor).unwrap();
+        assert_eq!(
+            decoded_batch.column(0).as_any_dictionary().values().len(),
+            2
+        );
+        assert_eq!(name_batch(decoded_batch, &sliced.schema()).unwrap(), sliced);
+    }
+
     #[test]
     fn test_write_and_read_batch_for_struct() {
         let c1: ArrayRef = Arc::new(BooleanArray::from(vec![false, false, true, true]));
@@ -16,12 +16,15 @@
 #![feature(slice_swap_unchecked)]
 #![feature(vec_into_raw_parts)]
 
+use arrow::record_batch::RecordBatch;
 use blaze_jni_bridge::{
     conf::{IntConf, BATCH_SIZE},
     is_jni_bridge_inited,
 };
 use once_cell::sync::OnceCell;
 
+use crate::array_size::ArraySize;
+
 pub mod array_size;
 pub mod bytes_arena;
 pub mod cast;
@@ -36,6 +39,7 @@ pub mod spark_bit_array;
 pub mod spark_bloom_filter;
 pub mod spark_hash;
 pub mod streams;
+pub mod timezone;
 pub mod uda;
 
 #[macro_export]
@@ -113,6 +117,27 @@ pub fn compute_suggested_batch_size_for_kway_merge(mem_size: usize, num_rows: us
     compute_batch_size_with_target_mem_size(mem_size, num_rows, suggested_batch_mem_size)
 }
 
+/// splits a batch into slices whose estimated memory sizes are about the
+/// target memory size
+pub fn split_batch_by_estimated_size(
+    batch: RecordBatch,
+    target_mem_size: usize,
+) -> Vec<RecordBatch> {
+    let num_rows = batch.num_rows();
+    let sub_batch_size = compute_batch_size_with_target_mem_size(
+        batch.get_array_mem_size(),
+        num_rows,
+        target_mem_size,
+    );
+    if num_rows <= sub_batch_size {
+        return vec![batch];
+    }
+    (0..num_rows)
+        .step_by(sub_batch_size)
+        .map(|start| batch.slice(start, sub_batch_size.min(num_rows - start)))
+        .collect()
+}
+
 fn compute_batch_size_with_target_mem_size(
     mem_size: usize,
     num_rows: usize,
@@ -127,3 +152,26 @@ fn compute_batch_size_with_target_mem_size(
     let est_sub_batch_size = target_mem_size / est_mem_size_per_row.max(16);
     est_sub_batch_size.min(batch_size).max(batch_size_min)
 }
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        compute_suggested_batch_size_for_kway_merge, compute_suggested_batch_size_for_output,
+    };
+
+    #[test]
+    fn test_suggested_batch_size_not_degenerate() {
+        // 10 rows of 5000 int64 columns, should not be split into single-row batches
+        let mem_size = 10 * 5000 * 8;
+        assert!(compute_suggested_batch_size_for_output(mem_size, 10) >= 10);
+        assert!(compute_suggested_batch_size_for_kway_merge(mem_size, 10) >= 10);
+
+        // extremely wide rows are still emitted in batches of reasonable sizes
+        let mem_size = 10 * 1073741824;
+        assert_eq!(compute_suggested_batch_size_for_output(mem_size, 10), 20);
+        assert_eq!(
+            compute_suggested_batch_size_for_kway_merge(mem_size, 10),
+            20
+        );
+    }
+}
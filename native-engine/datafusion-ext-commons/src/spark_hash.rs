@@ -19,8 +19,8 @@ use std::sync::Arc;
 use arrow::{
     array::*,
     datatypes::{
-        ArrowDictionaryKeyType, ArrowNativeType, DataType, Int16Type, Int32Type, Int64Type,
-        Int8Type, TimeUnit,
+        ArrowDictionaryKeyType, ArrowNativeType, ArrowNativeTypeOp, DataType, Int16Type, Int32Type,
+        Int64Type, Int8Type, TimeUnit,
     },
 };
 use datafusion::error::Result;
@@ -67,23 +67,43 @@ macro_rules! hash_array_primitive {
 }
 
 macro_rules! hash_array_decimal {
-    ($array_type:ident, $column:ident, $hashes:ident, $h:expr) => {
+    ($array_type:ident, $column:ident, $precision:expr, $hashes:ident, $h:expr) => {
         let array = $column.as_any().downcast_ref::<$array_type>().unwrap();
 
         if array.null_count() == 0 {
             for (i, hash) in $hashes.iter_mut().enumerate() {
-                *hash = $h(array.value(i).to_le_bytes().as_ref(), *hash);
+                *hash = hash_decimal(array.value(i), $precision, *hash, $h);
             }
         } else {
             for (i, hash) in $hashes.iter_mut().enumerate() {
                 if !array.is_null(i) {
-                    *hash = $h(array.value(i).to_le_bytes().as_ref(), *hash);
+                    *hash = hash_decimal(array.value(i), $precision, *hash, $h);
                 }
             }
         }
     };
 }
 
+/// Hash a decimal like spark: the unscaled long value if precision <= 18,
+/// otherwise bytes of java.math.BigInteger.toByteArray() of the unscaled value
+#[inline]
+fn hash_decimal<T>(value: i128, precision: u8, hash: T, h: impl Fn(&[u8], T) -> T) -> T {
+    if precision <= 18 {
+        return h((value as i64).to_le_bytes().as_ref(), hash);
+    }
+
+    // minimal big-endian two's-complement representation
+    let bytes = value.to_be_bytes();
+    let mut start = 0;
+    while start < bytes.len() - 1
+        && ((bytes[start] == 0x00 && bytes[start + 1] & 0x80 == 0)
+            || (bytes[start] == 0xff && bytes[start + 1] & 0x80 != 0))
+    {
+        start += 1;
+    }
+    h(&bytes[start..], hash)
+}
+
 /// Hash the values in a dictionary array
 fn create_hashes_dictionary<K: ArrowDictionaryKeyType, T: num::PrimInt>(
     array: &ArrayRef,
@@ -221,8 +241,8 @@ fn hash_array<T: num::PrimInt>(
         DataType::LargeUtf8 => {
             hash_array!(LargeStringArray, array, hashes_buffer, h);
         }
-        DataType::Decimal128(..) => {
-            hash_array_decimal!(Decimal128Array, array, hashes_buffer, h);
+        DataType::Decimal128(precision, _) => {
+            hash_array_decimal!(Decimal128Array, array, *precision, hashes_buffer, h);
         }
         DataType::Dictionary(index_type, _) => match &**index_type {
             DataType::Int8 => create_hashes_dictionary::<Int8Type, _>(array, hashes_buffer, h)?,
@@ -258,9 +278,9 @@ macro_rules! hash_one_binary {
 }
 
 macro_rules! hash_one_decimal {
-    ($array_type:ident, $column:ident, $hash:ident, $idx:ident, $h:expr) => {
+    ($array_type:ident, $column:ident, $precision:expr, $hash:ident, $idx:ident, $h:expr) => {
         let array = $column.as_any().downcast_ref::<$array_type>().unwrap();
-        *$hash = $h(array.value($idx as usize).to_le_bytes().as_ref(), *$hash);
+        *$hash = hash_decimal(array.value($idx as usize), $precision, *$hash, $h);
     };
 }
 
@@ -300,13 +320,13 @@ fn hash_one<T: num::PrimInt>(
             DataType::Float64 => {
                 hash_one_primitive!(Float64Array, col, f64, hash, idx, h);
             }
-            DataType::Timestamp(TimeUnit::Second, None) => {
+            DataType::Timestamp(TimeUnit::Second, _) => {
                 hash_one_primitive!(TimestampSecondArray, col, i64, hash, idx, h);
             }
-            DataType::Timestamp(TimeUnit::Millisecond, None) => {
+            DataType::Timestamp(TimeUnit::Millisecond, _) => {
                 hash_one_primitive!(TimestampMillisecondArray, col, i64, hash, idx, h);
             }
-            DataType::Timestamp(TimeUnit::Microsecond, None) => {
+            DataType::Timestamp(TimeUnit::Microsecond, _) => {
                 hash_one_primitive!(TimestampMicrosecondArray, col, i64, hash, idx, h);
             }
             DataType::Timestamp(TimeUnit::Nanosecond, _) => {
@@ -330,8 +350,8 @@ fn hash_one<T: num::PrimInt>(
             DataType::LargeUtf8 => {
                 hash_one_binary!(LargeStringArray, col, hash, idx, h);
             }
-            DataType::Decimal128(..) => {
-                hash_one_decimal!(Decimal128Array, col, hash, idx, h);
+            DataType::Decimal128(precision, _) => {
+                hash_one_decimal!(Decimal128Array, col, *precision, hash, idx, h);
             }
             DataType::List(..) => {
                 let list_array = col.as_any().downcast_ref::<ListArray>().unwrap();
@@ -363,10 +383,18 @@ fn hash_one<T: num::PrimInt>(
 }
 
 pub fn pmod(hash: i32, n: usize) -> usize {
-    let n = n as i32;
-    let r = hash % n;
-    let result = if r < 0 { (r + n) % n } else { r };
-    result as usize
+    pmod_native(hash, n as i32) as usize
+}
+
+/// positive modulo, same as org.apache.spark.sql.catalyst.expressions.Pmod
+#[inline]
+pub fn pmod_native<T: ArrowNativeTypeOp>(a: T, n: T) -> T {
+    let r = a.mod_wrapping(n);
+    if r.is_lt(T::ZERO) {
+        r.add_wrapping(n).mod_wrapping(n)
+    } else {
+        r
+    }
 }
 
 #[cfg(test)]
@@ -375,8 +403,8 @@ mod tests {
 
     use arrow::{
         array::{
-            make_array, Array, ArrayData, ArrayRef, Int32Array, Int64Array, Int8Array, MapArray,
-            StringArray, StructArray, UInt32Array,
+            make_array, Array, ArrayData, ArrayRef, Decimal128Array, Int32Array, Int64Array,
+            Int8Array, MapArray, StringArray, StructArray, UInt32Array,
         },
         buffer::Buffer,
         datatypes::{DataType, Field, ToByteSlice},
@@ -473,6 +501,53 @@ mod tests {
         assert_eq!(hashes, expected);
     }
 
+    #[test]
+    fn test_decimal() {
+        let small = Arc::new(
+            Decimal128Array::from(vec![Some(12345), Some(-12345), Some(0), None])
+                .with_precision_and_scale(10, 2)
+                .unwrap(),
+        ) as ArrayRef;
+        let large = Arc::new(
+            Decimal128Array::from(vec![
+                Some(10_i128.pow(20)),
+                Some(-(10_i128.pow(20))),
+                Some(0),
+                Some(-1),
+                Some(128),
+                Some(10_i128.pow(37)),
+            ])
+            .with_precision_and_scale(38, 0)
+            .unwrap(),
+        ) as ArrayRef;
+
+        // generated with Murmur3Hash(Seq(Literal(Decimal(...))), 42).eval(), small
+        // decimals are hashed as unscaled longs and large decimals as the bytes
+        // of BigInteger.toByteArray()
+        let mut hashes = vec![42; 4];
+        create_murmur3_hashes(&[small], &mut hashes).unwrap();
+        let expected: Vec<i32> = [0x5467c2e0_u32, 0x8b3434e6, 0x9c67b85d, 42]
+            .into_iter()
+            .map(|v| v as i32)
+            .collect();
+        assert_eq!(hashes, expected);
+
+        let mut hashes = vec![42; 6];
+        create_murmur3_hashes(&[large], &mut hashes).unwrap();
+        let expected: Vec<i32> = [
+            0x2a285eda_u32,
+            0x364339a0,
+            0xd1497b27,
+            0x535b391c,
+            0xdf8d1626,
+            0x0ce5d0a0,
+        ]
+        .into_iter()
+        .map(|v| v as i32)
+        .collect();
+        assert_eq!(hashes, expected);
+    }
+
     #[test]
     fn test_str() {
         let i = Arc::new(StringArray::from(vec!["hello", "bar", "", "😁", "天地"]));
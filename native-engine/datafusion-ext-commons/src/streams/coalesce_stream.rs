@@ -47,6 +47,13 @@ pub trait CoalesceInput {
         input: SendableRecordBatchStream,
         metrics: &BaselineMetrics,
     ) -> Result<SendableRecordBatchStream>;
+
+    fn coalesce_with_batch_mem_size(
+        &self,
+        input: SendableRecordBatchStream,
+        batch_mem_size: usize,
+        metrics: &BaselineMetrics,
+    ) -> Result<SendableRecordBatchStream>;
 }
 
 impl CoalesceInput for Arc<TaskContext> {
@@ -70,6 +77,18 @@ impl CoalesceInput for Arc<TaskContext> {
     ) -> Result<SendableRecordBatchStream> {
         self.coalesce_input(input, batch_size(), metrics)
     }
+
+    fn coalesce_with_batch_mem_size(
+        &self,
+        input: SendableRecordBatchStream,
+        batch_mem_size: usize,
+        metrics: &BaselineMetrics,
+    ) -> Result<SendableRecordBatchStream> {
+        Ok(Box::pin(
+            CoalesceStream::new(input, batch_size(), metrics.elapsed_compute().clone())
+                .with_batch_mem_size(batch_mem_size),
+        ))
+    }
 }
 
 pub struct CoalesceStream {
@@ -78,6 +97,7 @@ pub struct CoalesceStream {
     staging_rows: usize,
     staging_batches_mem_size: usize,
     batch_size: usize,
+    batch_mem_size: usize,
     elapsed_compute: Time,
 }
 
@@ -89,10 +109,16 @@ impl CoalesceStream {
             staging_rows: 0,
             staging_batches_mem_size: 0,
             batch_size,
+            batch_mem_size: suggested_output_batch_mem_size(),
             elapsed_compute,
         }
     }
 
+    pub fn with_batch_mem_size(mut self, batch_mem_size: usize) -> Self {
+        self.batch_mem_size = batch_mem_size;
+        self
+    }
+
     fn coalesce(&mut self) -> Result<RecordBatch> {
         // better concat_batches() implementation that releases old batch columns asap.
         let schema = self.input.schema();
@@ -122,7 +148,7 @@ impl CoalesceStream {
     }
 
     fn should_flush(&self) -> bool {
-        let size_limit = suggested_output_batch_mem_size();
+        let size_limit = self.batch_mem_size;
         let (batch_size_limit, mem_size_limit) = if self.staging_batches.len() > 1 {
             (self.batch_size, size_limit)
         } else {
@@ -0,0 +1,101 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use arrow::array::timezone::Tz;
+use datafusion::{common::Result, physical_plan::ColumnarValue, scalar::ScalarValue};
+
+use crate::df_execution_err;
+
+/// returns the timezone given by the timezone argument of a timezone-sensitive
+/// function. like spark's TimeZoneAwareExpression, the converter passes the
+/// expression's timeZoneId as a literal argument, so that the timezone always
+/// follows the session or query that built the expression. defaults to UTC if
+/// not given.
+pub fn timezone_arg(arg: Option<&ColumnarValue>) -> Result<Tz> {
+    match arg {
+        Some(ColumnarValue::Scalar(ScalarValue::Utf8(Some(tz)))) => parse_timezone(tz),
+        None => parse_timezone("UTC"),
+        Some(other) => df_execution_err!("timezone must be a literal string, got {other:?}"),
+    }
+}
+
+/// parses a spark timezone id, supporting both region-based ids (like
+/// "America/Los_Angeles") and offset-based ids (like "+08:00").
+pub fn parse_timezone(tz: &str) -> Result<Tz> {
+    let normalized = match tz {
+        "Z" => "UTC",
+        tz => tz
+            .strip_prefix("UTC")
+            .or_else(|| tz.strip_prefix("GMT"))
+            .filter(|offset| offset.starts_with(['+', '-']))
+            .unwrap_or(tz),
+    };
+    match normalized.parse::<Tz>() {
+        Ok(tz) => Ok(tz),
+        Err(err) => df_execution_err!("invalid timezone {tz:?}: {err}"),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::{NaiveDate, Offset, TimeZone};
+    use datafusion::{common::Result, physical_plan::ColumnarValue, scalar::ScalarValue};
+
+    use crate::timezone::{parse_timezone, timezone_arg};
+
+    #[test]
+    fn test_parse_timezone() -> Result<()> {
+        let dt = NaiveDate::from_ymd_opt(2024, 1, 1)
+            .unwrap()
+            .and_hms_opt(0, 0, 0)
+            .unwrap();
+        let offset_secs = |tz: &str| -> Result<i32> {
+            let tz = parse_timezone(tz)?;
+            Ok(tz.offset_from_utc_datetime(&dt).fix().local_minus_utc())
+        };
+
+        assert_eq!(offset_secs("UTC")?, 0);
+        assert_eq!(offset_secs("Z")?, 0);
+        assert_eq!(offset_secs("Asia/Shanghai")?, 8 * 3600);
+        assert_eq!(offset_secs("America/Los_Angeles")?, -8 * 3600);
+        assert_eq!(offset_secs("+08:00")?, 8 * 3600);
+        assert_eq!(offset_secs("GMT+08:00")?, 8 * 3600);
+        assert_eq!(offset_secs("UTC-05:30")?, -(5 * 3600 + 1800));
+        assert!(parse_timezone("Invalid/Zone").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_timezone_arg() -> Result<()> {
+        let dt = NaiveDate::from_ymd_opt(2024, 7, 1)
+            .unwrap()
+            .and_hms_opt(12, 0, 0)
+            .unwrap();
+        let offset_secs = |arg: Option<&ColumnarValue>| -> Result<i32> {
+            let tz = timezone_arg(arg)?;
+            Ok(tz.offset_from_utc_datetime(&dt).fix().local_minus_utc())
+        };
+
+        // every expression uses its own timezone
+        let shanghai = ColumnarValue::Scalar(ScalarValue::from("Asia/Shanghai"));
+        let los_angeles = ColumnarValue::Scalar(ScalarValue::from("America/Los_Angeles"));
+        assert_eq!(offset_secs(Some(&shanghai))?, 8 * 3600);
+        assert_eq!(offset_secs(Some(&los_angeles))?, -7 * 3600);
+        assert_eq!(offset_secs(None)?, 0);
+
+        let null_tz = ColumnarValue::Scalar(ScalarValue::Utf8(None));
+        assert!(timezone_arg(Some(&null_tz)).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,212 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    any::Any,
+    fmt::{Display, Formatter},
+    hash::{Hash, Hasher},
+    sync::Arc,
+};
+
+use arrow::{
+    array::Array,
+    compute::{is_not_null, is_null, kernels::zip::zip},
+    datatypes::{DataType, Schema},
+    record_batch::RecordBatch,
+};
+use datafusion::{
+    common::Result,
+    logical_expr::ColumnarValue,
+    physical_expr::{PhysicalExpr, PhysicalExprRef},
+};
+use datafusion_ext_commons::df_execution_err;
+use itertools::Itertools;
+
+use crate::down_cast_any_ref;
+
+/// implements org.apache.spark.sql.catalyst.expressions.Coalesce with
+/// short-circuiting: each argument is only evaluated on rows for which all
+/// previous arguments are null.
+#[derive(Debug, Hash)]
+pub struct SparkCoalesceExpr {
+    args: Vec<PhysicalExprRef>,
+}
+
+impl PartialEq<dyn Any> for SparkCoalesceExpr {
+    fn eq(&self, other: &dyn Any) -> bool {
+        down_cast_any_ref(other)
+            .downcast_ref::<Self>()
+            .map(|x| {
+                self.args.len() == x.args.len()
+                    && self.args.iter().zip(&x.args).all(|(a, b)| a.eq(b))
+            })
+            .unwrap_or(false)
+    }
+}
+
+impl SparkCoalesceExpr {
+    pub fn try_new(args: Vec<PhysicalExprRef>) -> Result<Self> {
+        if args.is_empty() {
+            return df_execution_err!("coalesce: requires at least one argument");
+        }
+        Ok(Self { args })
+    }
+
+    pub fn args(&self) -> &[PhysicalExprRef] {
+        &self.args
+    }
+}
+
+impl Display for SparkCoalesceExpr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Coalesce({})", self.args.iter().join(", "))
+    }
+}
+
+impl PhysicalExpr for SparkCoalesceExpr {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn data_type(&self, input_schema: &Schema) -> Result<DataType> {
+        // arguments are coerced to their common type by spark, skip null
+        // literals which are typed as Null
+        for arg in &self.args {
+            let data_type = arg.data_type(input_schema)?;
+            if data_type != DataType::Null {
+                return Ok(data_type);
+            }
+        }
+        Ok(DataType::Null)
+    }
+
+    fn nullable(&self, input_schema: &Schema) -> Result<bool> {
+        for arg in &self.args {
+            if !arg.nullable(input_schema)? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    fn evaluate(&self, batch: &RecordBatch) -> Result<ColumnarValue> {
+        let mut result = self.args[0].evaluate(batch)?;
+        for arg in &self.args[1..] {
+            let current = match result {
+                ColumnarValue::Scalar(scalar) if !scalar.is_null() => {
+                    return Ok(ColumnarValue::Scalar(scalar));
+                }
+                ColumnarValue::Scalar(_) => {
+                    result = arg.evaluate(batch)?;
+                    continue;
+                }
+                ColumnarValue::Array(array) => array,
+            };
+            if current.null_count() == 0 {
+                return Ok(ColumnarValue::Array(current));
+            }
+
+            // evaluate next argument only on the remaining null rows
+            let remaining = arg
+                .evaluate_selection(batch, &is_null(&current)?)?
+                .into_array(batch.num_rows())?;
+            result = ColumnarValue::Array(zip(&is_not_null(&current)?, &current, &remaining)?);
+        }
+        Ok(result)
+    }
+
+    fn children(&self) -> Vec<PhysicalExprRef> {
+        self.args.clone()
+    }
+
+    fn with_new_children(
+        self: Arc<Self>,
+        children: Vec<PhysicalExprRef>,
+    ) -> Result<PhysicalExprRef> {
+        Ok(Arc::new(Self::try_new(children)?))
+    }
+
+    fn dyn_hash(&self, state: &mut dyn Hasher) {
+        let mut s = state;
+        self.hash(&mut s);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use arrow::{
+        array::{ArrayRef, Int32Array},
+        record_batch::RecordBatch,
+    };
+    use datafusion::{
+        common::{Result, ScalarValue},
+        logical_expr::Operator,
+        physical_expr::{
+            expressions::{binary, col, lit},
+            PhysicalExpr,
+        },
+    };
+
+    use crate::spark_coalesce::SparkCoalesceExpr;
+
+    #[test]
+    fn test_coalesce_short_circuit() -> Result<()> {
+        let batch = RecordBatch::try_from_iter_with_nullable(vec![
+            (
+                "a",
+                Arc::new(Int32Array::from(vec![Some(1), None, None, Some(4)])) as ArrayRef,
+                true,
+            ),
+            (
+                "x",
+                Arc::new(Int32Array::from(vec![10, 20, 30, 40])) as ArrayRef,
+                false,
+            ),
+            (
+                "y",
+                Arc::new(Int32Array::from(vec![0, 2, 3, 0])) as ArrayRef,
+                false,
+            ),
+        ])?;
+        let schema = batch.schema();
+
+        // x / y fails on rows where y is zero, and these rows are already
+        // filled by a
+        let divide = binary(
+            col("x", &schema)?,
+            Operator::Divide,
+            col("y", &schema)?,
+            &schema,
+        )?;
+        assert!(divide.evaluate(&batch).is_err());
+
+        let coalesce = SparkCoalesceExpr::try_new(vec![col("a", &schema)?, divide])?;
+        let output = coalesce.evaluate(&batch)?.into_array(batch.num_rows())?;
+        let expected: ArrayRef = Arc::new(Int32Array::from(vec![1, 10, 10, 4]));
+        assert_eq!(&output, &expected);
+
+        // leading null literals are skipped
+        let coalesce = SparkCoalesceExpr::try_new(vec![
+            lit(ScalarValue::Int32(None)),
+            col("a", &schema)?,
+            lit(ScalarValue::Int32(Some(-1))),
+        ])?;
+        let output = coalesce.evaluate(&batch)?.into_array(batch.num_rows())?;
+        let expected: ArrayRef = Arc::new(Int32Array::from(vec![1, -1, -1, 4]));
+        assert_eq!(&output, &expected);
+        Ok(())
+    }
+}
@@ -44,6 +44,7 @@ pub struct SparkUDFWrapperExpr {
     pub return_type: DataType,
     pub return_nullable: bool,
     pub params: Vec<Arc<dyn PhysicalExpr>>,
+    pub deterministic: bool,
     pub import_schema: SchemaRef,
     pub params_schema: OnceCell<SchemaRef>,
     pub num_threads: usize,
@@ -59,6 +60,7 @@ impl PartialEq<dyn Any> for SparkUDFWrapperExpr {
                     && self.serialized == x.serialized
                     && self.return_type == x.return_type
                     && self.return_nullable == x.return_nullable
+                    && self.deterministic == x.deterministic
             })
             .unwrap_or(false)
     }
@@ -70,6 +72,7 @@ impl SparkUDFWrapperExpr {
         return_type: DataType,
         return_nullable: bool,
         params: Vec<Arc<dyn PhysicalExpr>>,
+        deterministic: bool,
     ) -> Result<Self> {
         let num_threads = conf::UDF_WRAPPER_NUM_THREADS.value()? as usize;
         Ok(Self {
@@ -77,6 +80,7 @@ impl SparkUDFWrapperExpr {
             return_type: return_type.clone(),
             return_nullable,
             params,
+            deterministic,
             import_schema: Arc::new(Schema::new(vec![Field::new("", return_type, true)])),
             params_schema: OnceCell::new(),
             num_threads,
@@ -211,6 +215,7 @@ impl PhysicalExpr for SparkUDFWrapperExpr {
             self.return_type.clone(),
             self.return_nullable.clone(),
             children,
+            self.deterministic,
         )?))
     }
 
@@ -16,18 +16,37 @@
 
 use std::sync::Arc;
 
-use datafusion::{common::Result, logical_expr::ScalarFunctionImplementation};
+use datafusion::{
+    common::Result,
+    logical_expr::{ColumnarValue, ScalarFunctionImplementation},
+};
 use datafusion_ext_commons::df_unimplemented_err;
 
 mod brickhouse;
+pub mod regexp_cache;
+mod spark_array;
+mod spark_base64;
+mod spark_bitwise;
+mod spark_cast_struct;
 mod spark_check_overflow;
+mod spark_crc32;
+mod spark_dates;
+mod spark_format_number;
 pub mod spark_get_json_object;
+mod spark_hex;
 mod spark_make_array;
 mod spark_make_decimal;
 mod spark_murmur3_hash;
 mod spark_null_if;
+mod spark_overlay;
+mod spark_pad;
+mod spark_pmod;
+mod spark_regexp;
+mod spark_round;
+mod spark_sha2;
 mod spark_strings;
 mod spark_unscaled_value;
+mod spark_width_bucket;
 mod spark_xxhash64;
 
 pub fn create_spark_ext_function(name: &str) -> Result<ScalarFunctionImplementation> {
@@ -38,8 +57,27 @@ pub fn create_spark_ext_function(name: &str) -> Result<ScalarFunctionImplementat
         "UnscaledValue" => Arc::new(spark_unscaled_value::spark_unscaled_value),
         "MakeDecimal" => Arc::new(spark_make_decimal::spark_make_decimal),
         "CheckOverflow" => Arc::new(spark_check_overflow::spark_check_overflow),
+        "Round" => Arc::new(spark_round::spark_round),
+        "BRound" => Arc::new(spark_round::spark_bround),
+        "CastStruct" => Arc::new(spark_cast_struct::spark_cast_struct),
+        "CastTimestampToDate" => Arc::new(spark_dates::spark_cast_timestamp_to_date),
+        "UnixSeconds" => Arc::new(spark_dates::spark_unix_seconds),
+        "UnixMillis" => Arc::new(spark_dates::spark_unix_millis),
+        "UnixMicros" => Arc::new(spark_dates::spark_unix_micros),
+        "FromUnixTime" => Arc::new(spark_dates::spark_from_unixtime),
+        "DateTrunc" => Arc::new(spark_dates::spark_date_trunc),
+        "MonthsBetween" => Arc::new(spark_dates::spark_months_between),
+        "LastDay" => Arc::new(spark_dates::spark_last_day),
+        "NextDay" => Arc::new(spark_dates::spark_next_day),
+        "FormatNumber" => Arc::new(spark_format_number::spark_format_number),
         "Murmur3Hash" => Arc::new(spark_murmur3_hash::spark_murmur3_hash),
         "XxHash64" => Arc::new(spark_xxhash64::spark_xxhash64),
+        "Hex" => Arc::new(spark_hex::spark_hex),
+        "Unhex" => Arc::new(spark_hex::spark_unhex),
+        "Sha2" => Arc::new(spark_sha2::spark_sha2),
+        "Crc32" => Arc::new(spark_crc32::spark_crc32),
+        "Base64" => Arc::new(spark_base64::spark_base64),
+        "UnBase64" => Arc::new(spark_base64::spark_unbase64),
         "GetJsonObject" => Arc::new(spark_get_json_object::spark_get_json_object),
         "GetParsedJsonObject" => Arc::new(spark_get_json_object::spark_get_parsed_json_object),
         "ParseJson" => Arc::new(spark_get_json_object::spark_parse_json),
@@ -51,6 +89,35 @@ pub fn create_spark_ext_function(name: &str) -> Result<ScalarFunctionImplementat
         "StringConcatWs" => Arc::new(spark_strings::string_concat_ws),
         "StringLower" => Arc::new(spark_strings::string_lower),
         "StringUpper" => Arc::new(spark_strings::string_upper),
+        "StringSubstringIndex" => Arc::new(spark_strings::string_substring_index),
+        "StringTranslate" => Arc::new(spark_strings::string_translate),
+        "StringLevenshtein" => Arc::new(spark_strings::string_levenshtein),
+        "StringSoundex" => Arc::new(spark_strings::string_soundex),
+        "StringInstr" => Arc::new(spark_strings::string_instr),
+        "StringLocate" => Arc::new(spark_strings::string_locate),
+        "Overlay" => Arc::new(spark_overlay::spark_overlay),
+        "LPad" => Arc::new(spark_pad::spark_lpad),
+        "RPad" => Arc::new(spark_pad::spark_rpad),
+        "BitCount" => Arc::new(spark_bitwise::spark_bit_count),
+        "ShiftLeft" => Arc::new(spark_bitwise::spark_shiftleft),
+        "ShiftRight" => Arc::new(spark_bitwise::spark_shiftright),
+        "ShiftRightUnsigned" => Arc::new(spark_bitwise::spark_shiftrightunsigned),
+        "Pmod" => Arc::new(spark_pmod::spark_pmod),
+        "ArrayContains" => Arc::new(spark_array::spark_array_contains),
+        "ArrayDistinct" => Arc::new(spark_array::spark_array_distinct),
+        "WidthBucket" => Arc::new(spark_width_bucket::spark_width_bucket),
+        "RegexpExtract" => {
+            let cache = regexp_cache::RegexCache::default();
+            Arc::new(move |args: &[ColumnarValue]| {
+                spark_regexp::regexp_extract(args, &cache)
+            })
+        }
+        "RegexpReplace" => {
+            let cache = regexp_cache::RegexCache::default();
+            Arc::new(move |args: &[ColumnarValue]| {
+                spark_regexp::regexp_replace(args, &cache)
+            })
+        }
         "BrickhouseArrayUnion" => Arc::new(brickhouse::array_union::array_union),
         _ => df_unimplemented_err!("spark ext function not implemented: {name}")?,
     })
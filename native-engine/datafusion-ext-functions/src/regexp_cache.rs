@@ -0,0 +1,286 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    borrow::Cow,
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+};
+
+use datafusion::common::Result;
+use datafusion_ext_commons::df_execution_err;
+use regex::{Regex, RegexBuilder};
+
+const DEFAULT_REGEXP_CACHE_CAPACITY: usize = 64;
+
+/// compiled pattern cache shared by all regexp functions of one evaluator.
+///
+/// constant patterns always hit the most-recently-used slot and are compiled
+/// only once. per-row patterns (pattern comes from a column) are kept in a
+/// bounded LRU list, so memory stays bounded even with high-cardinality
+/// pattern columns.
+pub struct RegexCache {
+    capacity: usize,
+    entries: Mutex<VecDeque<(String, String, Arc<Regex>)>>,
+}
+
+impl Default for RegexCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_REGEXP_CACHE_CAPACITY)
+    }
+}
+
+impl RegexCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            entries: Mutex::new(VecDeque::with_capacity(capacity.max(1))),
+        }
+    }
+
+    /// get a compiled regex of the pattern and flags, compiling and caching
+    /// it if not found.
+    ///
+    /// supported flags are java/spark style: i (case insensitive), m (multi
+    /// line), s (dot matches new line) and x (ignore whitespace).
+    pub fn get(&self, pattern: &str, flags: &str) -> Result<Arc<Regex>> {
+        let mut entries = self.entries.lock().unwrap();
+
+        // fast path: constant pattern always hits the front
+        if let Some((p, f, regex)) = entries.front()
+            && p == pattern
+            && f == flags
+        {
+            return Ok(regex.clone());
+        }
+
+        if let Some(idx) = entries
+            .iter()
+            .position(|(p, f, _)| p == pattern && f == flags)
+        {
+            let entry = entries.remove(idx).expect("index out of bounds");
+            let regex = entry.2.clone();
+            entries.push_front(entry);
+            return Ok(regex);
+        }
+
+        let regex = Arc::new(compile_regex(pattern, flags)?);
+        if entries.len() >= self.capacity {
+            entries.pop_back();
+        }
+        entries.push_front((pattern.to_string(), flags.to_string(), regex.clone()));
+        Ok(regex)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+fn compile_regex(pattern: &str, flags: &str) -> Result<Regex> {
+    let mut builder = RegexBuilder::new(&translate_java_regex(pattern));
+    for flag in flags.chars() {
+        match flag {
+            'i' => builder.case_insensitive(true),
+            'm' => builder.multi_line(true),
+            's' => builder.dot_matches_new_line(true),
+            'x' => builder.ignore_whitespace(true),
+            other => return df_execution_err!("unsupported regexp flag: {other}"),
+        };
+    }
+    match builder.build() {
+        Ok(regex) => Ok(regex),
+        Err(err) => df_execution_err!("invalid regexp pattern {pattern:?}: {err}"),
+    }
+}
+
+/// translates java regexp syntax which is not supported by the regex crate:
+/// \Q...\E quotations and posix character classes like \p{Alpha}. \d, \w
+/// and \s are translated to their ascii-only java meanings. other unsupported
+/// syntax (like lookarounds) is rejected by NativeConverters.
+fn translate_java_regex(pattern: &str) -> Cow<str> {
+    if !pattern.contains('\\') {
+        return Cow::Borrowed(pattern);
+    }
+
+    let mut translated = String::with_capacity(pattern.len());
+    let mut rest = pattern;
+    while let Some(pos) = rest.find('\\') {
+        translated.push_str(&rest[..pos]);
+        rest = &rest[pos..];
+
+        if let Some(quoted) = rest.strip_prefix("\\Q") {
+            let (literal, remaining) = quoted.split_once("\\E").unwrap_or((quoted, ""));
+            translated.push_str(&regex::escape(literal));
+            rest = remaining;
+            continue;
+        }
+        if let Some(braced) = rest.strip_prefix("\\p{").or_else(|| rest.strip_prefix("\\P{"))
+            && let Some((name, remaining)) = braced.split_once('}')
+            && let Some(class) = posix_class(name)
+        {
+            translated.push_str(if rest[1..].starts_with('P') { "[^" } else { "[" });
+            translated.push_str(class);
+            translated.push(']');
+            rest = remaining;
+            continue;
+        }
+        if let Some(c @ ('d' | 'D' | 'w' | 'W' | 's' | 'S')) = rest[1..].chars().next() {
+            let class = match c.to_ascii_lowercase() {
+                'd' => "0-9",
+                'w' => "a-zA-Z0-9_",
+                _ => " \\t\\n\\x0B\\f\\r",
+            };
+            translated.push_str(if c.is_ascii_uppercase() { "[^" } else { "[" });
+            translated.push_str(class);
+            translated.push(']');
+            rest = &rest[2..];
+            continue;
+        }
+
+        // keep other escapes, including the escaped char
+        let escape_len = rest[1..]
+            .chars()
+            .next()
+            .map(|c| 1 + c.len_utf8())
+            .unwrap_or(1);
+        translated.push_str(&rest[..escape_len]);
+        rest = &rest[escape_len..];
+    }
+    translated.push_str(rest);
+    Cow::Owned(translated)
+}
+
+/// posix character classes of java.util.regex.Pattern (us-ascii only)
+fn posix_class(name: &str) -> Option<&'static str> {
+    Some(match name {
+        "Lower" => "a-z",
+        "Upper" => "A-Z",
+        "ASCII" => "\\x00-\\x7F",
+        "Alpha" => "a-zA-Z",
+        "Digit" => "0-9",
+        "Alnum" => "a-zA-Z0-9",
+        "Punct" => "!-/:-@\\[-`{-~",
+        "Graph" => "!-~",
+        "Print" => " -~",
+        "Blank" => " \\t",
+        "Cntrl" => "\\x00-\\x1F\\x7F",
+        "XDigit" => "0-9a-fA-F",
+        "Space" => " \\t\\n\\x0B\\f\\r",
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use datafusion::common::Result;
+
+    use crate::regexp_cache::{compile_regex, translate_java_regex, RegexCache};
+
+    #[test]
+    fn test_regexp_cache_constant_pattern() -> Result<()> {
+        let cache = RegexCache::default();
+        let r1 = cache.get(r"(\d+)-(\d+)", "")?;
+        let r2 = cache.get(r"(\d+)-(\d+)", "")?;
+        assert_eq!(cache.len(), 1);
+        assert_eq!(r1.as_str(), r2.as_str());
+        assert!(r2.is_match("100-200"));
+
+        // same pattern with different flags is cached separately
+        let r3 = cache.get("abc", "i")?;
+        assert!(r3.is_match("ABC"));
+        assert!(!cache.get("abc", "")?.is_match("ABC"));
+        assert_eq!(cache.len(), 3);
+        Ok(())
+    }
+
+    #[test]
+    fn test_regexp_cache_lru_eviction() -> Result<()> {
+        let cache = RegexCache::new(2);
+        cache.get("a", "")?;
+        cache.get("b", "")?;
+        cache.get("a", "")?; // a becomes most recently used
+        cache.get("c", "")?; // evicts b
+        assert_eq!(cache.len(), 2);
+
+        let entries = cache.entries.lock().unwrap();
+        let patterns = entries.iter().map(|e| e.0.as_str()).collect::<Vec<_>>();
+        assert_eq!(patterns, vec!["c", "a"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_regexp_cache_invalid() {
+        let cache = RegexCache::default();
+        assert!(cache.get("(unclosed", "").is_err());
+        assert!(cache.get("a", "q").is_err());
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn test_translate_java_regex() -> Result<()> {
+        assert_eq!(translate_java_regex(r"(a+)-(b+)"), r"(a+)-(b+)");
+        assert_eq!(translate_java_regex(r"(\d+)-(\d+)"), r"([0-9]+)-([0-9]+)");
+        assert_eq!(translate_java_regex(r"\Q1+1\E=(\d)"), r"1\+1=([0-9])");
+        assert_eq!(translate_java_regex(r"\Q(unclosed"), r"\(unclosed");
+        assert_eq!(
+            translate_java_regex(r"\p{Alpha}+\P{Digit}"),
+            r"[a-zA-Z]+[^0-9]"
+        );
+        assert_eq!(
+            translate_java_regex(r"[\p{Lower}_]\\p{L}"),
+            r"[[a-z]_]\\p{L}"
+        );
+
+        let regex = compile_regex(r"^\Q[x]\E\p{Punct}\p{L}$", "")?;
+        assert!(regex.is_match("[x]!é"));
+        assert!(!regex.is_match("[x]aé"));
+
+        // \d, \w and \s are ascii-only in java
+        let regex = compile_regex(r"^\d+\w\s\S\W$", "")?;
+        assert!(regex.is_match("123a\t!!"));
+        assert!(!regex.is_match("١٢٣a\t!!"));
+        assert!(!regex.is_match("123é\t!!"));
+        assert!(!regex.is_match("123a\u{3000}!!"));
+        assert!(regex.is_match("123a é!"));
+        assert!(compile_regex(r"[\d_]", "")?.is_match("_"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_regexp_cache_hits() -> Result<()> {
+        // constant-pattern filter over many batches compiles only once
+        let pattern = r"^([a-z0-9_.+-]+)@([\da-z.-]+)\.([a-z.]{2,6})$";
+        let cache = RegexCache::default();
+        let compiled = cache.get(pattern, "")?;
+        for _ in 0..1000 {
+            let regex = cache.get(pattern, "")?;
+            assert!(Arc::ptr_eq(&regex, &compiled));
+            assert!(regex.is_match("someone@example.com"));
+        }
+
+        // non-front entries are hit as well
+        let other = cache.get("other", "")?;
+        assert!(Arc::ptr_eq(&cache.get(pattern, "")?, &compiled));
+        assert!(Arc::ptr_eq(&cache.get("other", "")?, &other));
+        assert_eq!(cache.len(), 2);
+        Ok(())
+    }
+}
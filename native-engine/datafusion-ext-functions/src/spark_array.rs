@@ -0,0 +1,201 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{collections::HashSet, sync::Arc};
+
+use arrow::{
+    array::*,
+    buffer::OffsetBuffer,
+    compute::take,
+    datatypes::DataType,
+    row::{RowConverter, SortField},
+};
+use datafusion::{
+    common::{cast::as_list_array, Result, ScalarValue},
+    physical_plan::ColumnarValue,
+};
+use datafusion_ext_commons::df_execution_err;
+
+use crate::spark_hex::map_array;
+
+/// implements org.apache.spark.sql.catalyst.expressions.ArrayContains
+///
+/// returns null if the array or value is null, or if the value is not found
+/// and the array contains null elements.
+pub fn spark_array_contains(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    let num_rows = args.iter().find_map(|arg| match arg {
+        ColumnarValue::Array(array) => Some(array.len()),
+        ColumnarValue::Scalar(_) => None,
+    });
+    let arrays = args[0].clone().into_array(num_rows.unwrap_or(1))?;
+    let arrays = as_list_array(&arrays)?;
+    let values = args[1].clone().into_array(num_rows.unwrap_or(1))?;
+    if arrays.value_type() != *values.data_type() {
+        return df_execution_err!(
+            "array_contains: element type {} does not match value type {}",
+            arrays.value_type(),
+            values.data_type(),
+        );
+    }
+
+    // elements and values are compared in row format
+    let converter = RowConverter::new(vec![SortField::new(values.data_type().clone())])?;
+    let elements = arrays.values();
+    let element_rows = converter.convert_columns(&[elements.clone()])?;
+    let value_rows = converter.convert_columns(&[values.clone()])?;
+    let offsets = arrays.value_offsets();
+
+    let output = (0..arrays.len())
+        .map(|i| {
+            if arrays.is_null(i) || values.is_null(i) {
+                return None;
+            }
+            let mut has_null = false;
+            for j in offsets[i] as usize..offsets[i + 1] as usize {
+                if elements.is_null(j) {
+                    has_null = true;
+                } else if element_rows.row(j) == value_rows.row(i) {
+                    return Some(true);
+                }
+            }
+            (!has_null).then_some(false)
+        })
+        .collect::<BooleanArray>();
+
+    let output: ArrayRef = Arc::new(output);
+    Ok(match num_rows {
+        Some(_) => ColumnarValue::Array(output),
+        None => ColumnarValue::Scalar(ScalarValue::try_from_array(&output, 0)?),
+    })
+}
+
+/// implements org.apache.spark.sql.catalyst.expressions.ArrayDistinct
+///
+/// duplicated elements (including nulls) are removed, the first occurrences
+/// are kept in their original order.
+pub fn spark_array_distinct(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    map_array(&args[0], |array| {
+        let arrays = as_list_array(array)?;
+        let DataType::List(field) = arrays.data_type() else {
+            unreachable!("array_distinct: input is not a list")
+        };
+        let converter = RowConverter::new(vec![SortField::new(field.data_type().clone())])?;
+        let element_rows = converter.convert_columns(&[arrays.values().clone()])?;
+        let offsets = arrays.value_offsets();
+
+        let mut distinct_indices = vec![];
+        let mut distinct_offsets = vec![0i32];
+        let mut seen = HashSet::new();
+        for i in 0..arrays.len() {
+            if arrays.is_valid(i) {
+                seen.clear();
+                for j in offsets[i] as usize..offsets[i + 1] as usize {
+                    if seen.insert(element_rows.row(j)) {
+                        distinct_indices.push(j as u32);
+                    }
+                }
+            }
+            distinct_offsets.push(distinct_indices.len() as i32);
+        }
+        let distinct_values = take(arrays.values(), &UInt32Array::from(distinct_indices), None)?;
+        Ok(Arc::new(ListArray::try_new(
+            field.clone(),
+            OffsetBuffer::new(distinct_offsets.into()),
+            distinct_values,
+            arrays.nulls().cloned(),
+        )?))
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use arrow::{array::*, datatypes::Int32Type};
+    use datafusion::{
+        common::{Result, ScalarValue},
+        physical_plan::ColumnarValue,
+    };
+
+    use crate::spark_array::{spark_array_contains, spark_array_distinct};
+
+    #[test]
+    fn test_array_contains() -> Result<()> {
+        let arrays = ListArray::from_iter_primitive::<Int32Type, _, _>(vec![
+            Some(vec![Some(1), Some(2), Some(3)]),
+            Some(vec![Some(1), None, Some(3)]),
+            Some(vec![Some(2), None]),
+            Some(vec![]),
+            None,
+            Some(vec![Some(2)]),
+        ]);
+        let r = spark_array_contains(&[
+            ColumnarValue::Array(Arc::new(arrays)),
+            ColumnarValue::Array(Arc::new(Int32Array::from(vec![
+                Some(2),
+                Some(2),
+                Some(2),
+                Some(2),
+                Some(2),
+                None,
+            ]))),
+        ])?
+        .into_array(6)?;
+        assert_eq!(
+            r.as_boolean(),
+            &BooleanArray::from(vec![
+                Some(true),
+                None, // not found with a null element
+                Some(true),
+                Some(false),
+                None,
+                None,
+            ]),
+        );
+
+        // scalar value
+        let arrays = ListArray::from_iter_primitive::<Int32Type, _, _>(vec![
+            Some(vec![Some(1), Some(2)]),
+            Some(vec![Some(3)]),
+        ]);
+        let r = spark_array_contains(&[
+            ColumnarValue::Array(Arc::new(arrays)),
+            ColumnarValue::Scalar(ScalarValue::Int32(Some(3))),
+        ])?
+        .into_array(2)?;
+        assert_eq!(r.as_boolean(), &BooleanArray::from(vec![false, true]));
+        Ok(())
+    }
+
+    #[test]
+    fn test_array_distinct() -> Result<()> {
+        let arrays = ListArray::from_iter_primitive::<Int32Type, _, _>(vec![
+            Some(vec![Some(3), Some(1), Some(3), Some(2), Some(1)]),
+            Some(vec![None, Some(1), None, Some(1)]),
+            None,
+            Some(vec![]),
+        ]);
+        let r = spark_array_distinct(&[ColumnarValue::Array(Arc::new(arrays))])?.into_array(4)?;
+        assert_eq!(
+            r.as_list::<i32>(),
+            &ListArray::from_iter_primitive::<Int32Type, _, _>(vec![
+                Some(vec![Some(3), Some(1), Some(2)]),
+                Some(vec![None, Some(1)]),
+                None,
+                Some(vec![]),
+            ]),
+        );
+        Ok(())
+    }
+}
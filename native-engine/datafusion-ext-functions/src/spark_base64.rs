@@ -0,0 +1,207 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+
+use arrow::{array::*, datatypes::*};
+use base64::{
+    alphabet,
+    engine::{general_purpose, DecodePaddingMode, GeneralPurpose, GeneralPurposeConfig},
+    Engine,
+};
+use datafusion::{common::Result, physical_plan::ColumnarValue};
+use datafusion_ext_commons::df_execution_err;
+
+use crate::spark_hex::map_array;
+
+/// line length of spark's (mime) base64 output
+const BASE64_LINE_LEN: usize = 76;
+
+/// like spark, padding is optional when decoding
+const LENIENT_STANDARD: GeneralPurpose = GeneralPurpose::new(
+    &alphabet::STANDARD,
+    GeneralPurposeConfig::new().with_decode_padding_mode(DecodePaddingMode::Indifferent),
+);
+
+/// spark's base64(): standard base64 of a binary (or string), wrapped into
+/// lines of 76 chars.
+pub fn spark_base64(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    map_array(&args[0], |array| {
+        Ok(Arc::new(match array.data_type() {
+            DataType::Binary => StringArray::from_iter(
+                array
+                    .as_binary::<i32>()
+                    .iter()
+                    .map(|v| v.map(base64_encode)),
+            ),
+            DataType::Utf8 => StringArray::from_iter(
+                array
+                    .as_string::<i32>()
+                    .iter()
+                    .map(|v| v.map(|v| base64_encode(v.as_bytes()))),
+            ),
+            other => return df_execution_err!("base64: unsupported type: {other}"),
+        }))
+    })
+}
+
+/// spark's unbase64(): decodes base64 into binary, whitespaces (including
+/// line breaks) are ignored. returns null on invalid input.
+pub fn spark_unbase64(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    map_array(&args[0], |array| {
+        Ok(Arc::new(match array.data_type() {
+            DataType::Utf8 => BinaryArray::from_iter(
+                array
+                    .as_string::<i32>()
+                    .iter()
+                    .map(|v| v.and_then(|v| base64_decode(v.as_bytes()))),
+            ),
+            DataType::Binary => BinaryArray::from_iter(
+                array
+                    .as_binary::<i32>()
+                    .iter()
+                    .map(|v| v.and_then(base64_decode)),
+            ),
+            other => return df_execution_err!("unbase64: unsupported type: {other}"),
+        }))
+    })
+}
+
+fn base64_encode(bytes: &[u8]) -> String {
+    let encoded = general_purpose::STANDARD.encode(bytes);
+    if encoded.len() <= BASE64_LINE_LEN {
+        return encoded;
+    }
+
+    // encoded string is pure ascii, so splitting by bytes is safe. lines are
+    // separated by \r\n like java's mime encoder
+    let mut wrapped =
+        String::with_capacity(encoded.len() + encoded.len() / BASE64_LINE_LEN * 2);
+    for (i, line) in encoded.as_bytes().chunks(BASE64_LINE_LEN).enumerate() {
+        if i > 0 {
+            wrapped.push_str("\r\n");
+        }
+        wrapped.push_str(std::str::from_utf8(line).expect("base64 output is ascii"));
+    }
+    wrapped
+}
+
+fn base64_decode(encoded: &[u8]) -> Option<Vec<u8>> {
+    if encoded.iter().any(|c| c.is_ascii_whitespace()) {
+        let stripped = encoded
+            .iter()
+            .copied()
+            .filter(|c| !c.is_ascii_whitespace())
+            .collect::<Vec<_>>();
+        return LENIENT_STANDARD.decode(stripped).ok();
+    }
+    LENIENT_STANDARD.decode(encoded).ok()
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use arrow::array::*;
+    use datafusion::{
+        common::{
+            cast::{as_binary_array, as_string_array},
+            Result, ScalarValue,
+        },
+        physical_plan::ColumnarValue,
+    };
+
+    use crate::spark_base64::{spark_base64, spark_unbase64};
+
+    #[test]
+    fn test_base64_round_trip() -> Result<()> {
+        let input = BinaryArray::from(vec![
+            Some(b"Spark SQL".as_ref()),
+            Some(b"\x00\xff\x10".as_ref()),
+            Some(b"".as_ref()),
+            None,
+        ]);
+        let encoded = spark_base64(&[ColumnarValue::Array(Arc::new(input.clone()))])?;
+        let encoded = encoded.into_array(4)?;
+        assert_eq!(
+            as_string_array(&encoded)?,
+            &StringArray::from(vec![Some("U3BhcmsgU1FM"), Some("AP8Q"), Some(""), None]),
+        );
+
+        let decoded = spark_unbase64(&[ColumnarValue::Array(encoded)])?;
+        assert_eq!(as_binary_array(&decoded.into_array(4)?)?, &input);
+
+        // string input and missing padding
+        let r = spark_base64(&[ColumnarValue::Scalar(ScalarValue::from("ab"))])?;
+        match r {
+            ColumnarValue::Scalar(ScalarValue::Utf8(Some(s))) => assert_eq!(s, "YWI="),
+            other => panic!("unexpected result: {other:?}"),
+        }
+        let r = spark_unbase64(&[ColumnarValue::Scalar(ScalarValue::from("YWI"))])?;
+        match r {
+            ColumnarValue::Scalar(ScalarValue::Binary(Some(bytes))) => assert_eq!(bytes, b"ab"),
+            other => panic!("unexpected result: {other:?}"),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_base64_line_wrapping() -> Result<()> {
+        // 100 bytes are encoded into 136 chars, wrapped into 76 + 60
+        let input = vec![b'x'; 100];
+        let r = spark_base64(&[ColumnarValue::Scalar(ScalarValue::Binary(Some(
+            input.clone(),
+        )))])?;
+        let encoded = match r {
+            ColumnarValue::Scalar(ScalarValue::Utf8(Some(s))) => s,
+            other => panic!("unexpected result: {other:?}"),
+        };
+        let lines = encoded.split("\r\n").collect::<Vec<_>>();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].len(), 76);
+        assert_eq!(lines[1].len(), 60);
+
+        // wrapped output (also with \n and spaces) is decoded back
+        for encoded in [
+            encoded.clone(),
+            encoded.replace("\r\n", "\n "),
+            lines.concat(),
+        ] {
+            let r = spark_unbase64(&[ColumnarValue::Scalar(ScalarValue::from(encoded))])?;
+            match r {
+                ColumnarValue::Scalar(ScalarValue::Binary(Some(bytes))) => {
+                    assert_eq!(bytes, input)
+                }
+                other => panic!("unexpected result: {other:?}"),
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_unbase64_invalid() -> Result<()> {
+        let r = spark_unbase64(&[ColumnarValue::Array(Arc::new(StringArray::from(vec![
+            Some("U3Bhcms="),
+            Some("U3Bhcms*"),
+            Some("U3Bhcms=="),
+            Some("U"),
+            None,
+        ])))])?;
+        assert_eq!(
+            as_binary_array(&r.into_array(5)?)?,
+            &BinaryArray::from(vec![Some(b"Spark".as_ref()), None, None, None, None]),
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,214 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+
+use arrow::{array::*, compute::binary, datatypes::*};
+use datafusion::{
+    common::{cast::as_int32_array, Result, ScalarValue},
+    physical_plan::ColumnarValue,
+};
+use datafusion_ext_commons::df_execution_err;
+
+use crate::spark_hex::map_array;
+
+/// spark's bit_count(): number of set bits of an integral (or boolean) value.
+/// like spark, values are sign-extended to 64 bits before counting.
+pub fn spark_bit_count(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    map_array(&args[0], |array| {
+        let bit_count = |v: i64| v.count_ones() as i32;
+        Ok(Arc::new(match array.data_type() {
+            DataType::Boolean => {
+                Int32Array::from_iter(array.as_boolean().iter().map(|v| v.map(|v| v as i32)))
+            }
+            DataType::Int8 => array
+                .as_primitive::<Int8Type>()
+                .unary(|v| bit_count(v as i64)),
+            DataType::Int16 => array
+                .as_primitive::<Int16Type>()
+                .unary(|v| bit_count(v as i64)),
+            DataType::Int32 => array
+                .as_primitive::<Int32Type>()
+                .unary(|v| bit_count(v as i64)),
+            DataType::Int64 => array.as_primitive::<Int64Type>().unary(bit_count),
+            other => return df_execution_err!("bit_count: unsupported type: {other}"),
+        }))
+    })
+}
+
+/// spark's shiftleft(x, n), n is taken modulo the bit width of x like java
+pub fn spark_shiftleft(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    spark_shift(
+        args,
+        "shiftleft",
+        |v, n| v.wrapping_shl(n as u32),
+        |v, n| v.wrapping_shl(n as u32),
+    )
+}
+
+/// spark's shiftright(x, n): arithmetic (sign-preserving) right shift
+pub fn spark_shiftright(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    spark_shift(
+        args,
+        "shiftright",
+        |v, n| v.wrapping_shr(n as u32),
+        |v, n| v.wrapping_shr(n as u32),
+    )
+}
+
+/// spark's shiftrightunsigned(x, n): logical (zero-filling) right shift
+pub fn spark_shiftrightunsigned(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    spark_shift(
+        args,
+        "shiftrightunsigned",
+        |v, n| (v as u32).wrapping_shr(n as u32) as i32,
+        |v, n| (v as u64).wrapping_shr(n as u32) as i64,
+    )
+}
+
+fn spark_shift(
+    args: &[ColumnarValue],
+    name: &str,
+    shift_i32: impl Fn(i32, i32) -> i32,
+    shift_i64: impl Fn(i64, i32) -> i64,
+) -> Result<ColumnarValue> {
+    let num_rows = args.iter().find_map(|arg| match arg {
+        ColumnarValue::Array(array) => Some(array.len()),
+        ColumnarValue::Scalar(_) => None,
+    });
+    let values = args[0].clone().into_array(num_rows.unwrap_or(1))?;
+    let shifts = args[1].clone().into_array(num_rows.unwrap_or(1))?;
+    let shifts = as_int32_array(&shifts)?;
+
+    let output: ArrayRef = match values.data_type() {
+        DataType::Int32 => {
+            let output: Int32Array = binary(values.as_primitive::<Int32Type>(), shifts, shift_i32)?;
+            Arc::new(output)
+        }
+        DataType::Int64 => {
+            let output: Int64Array = binary(values.as_primitive::<Int64Type>(), shifts, shift_i64)?;
+            Arc::new(output)
+        }
+        other => return df_execution_err!("{name}: unsupported type: {other}"),
+    };
+    Ok(match num_rows {
+        Some(_) => ColumnarValue::Array(output),
+        None => ColumnarValue::Scalar(ScalarValue::try_from_array(&output, 0)?),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use arrow::{
+        array::*,
+        datatypes::{Int32Type, Int64Type},
+    };
+    use datafusion::{
+        common::{Result, ScalarValue},
+        physical_plan::ColumnarValue,
+    };
+
+    use crate::spark_bitwise::{
+        spark_bit_count, spark_shiftleft, spark_shiftright, spark_shiftrightunsigned,
+    };
+
+    #[test]
+    fn test_bit_count() -> Result<()> {
+        let r = spark_bit_count(&[ColumnarValue::Array(Arc::new(Int32Array::from(vec![
+            Some(0),
+            Some(7),
+            Some(-1),
+            Some(i32::MIN),
+            None,
+        ])))])?;
+        assert_eq!(
+            r.into_array(5)?.as_primitive::<Int32Type>(),
+            &Int32Array::from(vec![Some(0), Some(3), Some(64), Some(33), None]),
+        );
+
+        let r = spark_bit_count(&[ColumnarValue::Array(Arc::new(BooleanArray::from(vec![
+            Some(true),
+            Some(false),
+            None,
+        ])))])?;
+        assert_eq!(
+            r.into_array(3)?.as_primitive::<Int32Type>(),
+            &Int32Array::from(vec![Some(1), Some(0), None]),
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_shift() -> Result<()> {
+        let values = ColumnarValue::Array(Arc::new(Int32Array::from(vec![
+            Some(-8),
+            Some(1),
+            Some(1),
+            Some(-1),
+            None,
+        ])));
+        let shifts = ColumnarValue::Array(Arc::new(Int32Array::from(vec![
+            Some(1),
+            Some(33),
+            Some(-1),
+            Some(28),
+            Some(1),
+        ])));
+        let shift = |f: fn(&[ColumnarValue]) -> Result<ColumnarValue>| -> Result<Int32Array> {
+            let r = f(&[values.clone(), shifts.clone()])?.into_array(5)?;
+            Ok(r.as_primitive::<Int32Type>().clone())
+        };
+
+        // n is taken modulo 32: 33 -> 1, -1 -> 31
+        assert_eq!(
+            shift(spark_shiftleft)?,
+            Int32Array::from(vec![
+                Some(-16),
+                Some(2),
+                Some(i32::MIN),
+                Some(-268435456),
+                None
+            ]),
+        );
+        assert_eq!(
+            shift(spark_shiftright)?,
+            Int32Array::from(vec![Some(-4), Some(0), Some(0), Some(-1), None]),
+        );
+        assert_eq!(
+            shift(spark_shiftrightunsigned)?,
+            Int32Array::from(vec![Some(2147483644), Some(0), Some(0), Some(15), None]),
+        );
+
+        // bigint with scalar n, taken modulo 64
+        let r = spark_shiftrightunsigned(&[
+            ColumnarValue::Array(Arc::new(Int64Array::from(vec![-1, 256]))),
+            ColumnarValue::Scalar(ScalarValue::Int32(Some(68))),
+        ])?;
+        assert_eq!(
+            r.into_array(2)?.as_primitive::<Int64Type>(),
+            &Int64Array::from(vec![0x0fff_ffff_ffff_ffff, 16]),
+        );
+        let r = spark_shiftright(&[
+            ColumnarValue::Scalar(ScalarValue::Int64(Some(-256))),
+            ColumnarValue::Scalar(ScalarValue::Int32(Some(4))),
+        ])?;
+        match r {
+            ColumnarValue::Scalar(ScalarValue::Int64(Some(v))) => assert_eq!(v, -16),
+            other => panic!("unexpected result: {other:?}"),
+        }
+        Ok(())
+    }
+}
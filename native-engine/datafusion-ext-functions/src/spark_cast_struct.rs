@@ -0,0 +1,117 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use arrow::datatypes::DataType;
+use datafusion::{
+    common::{Result, ScalarValue},
+    physical_plan::ColumnarValue,
+};
+use datafusion_ext_commons::{cast::cast_scan_input_array, df_execution_err};
+
+/// reshapes a struct value to the target struct type, matching fields by name
+/// and recursively casting each child. target fields missing in the input are
+/// filled with nulls.
+///
+/// args[0] is the struct value, args[1] is a value (typically null) whose
+/// data type is the target struct type.
+pub fn spark_cast_struct(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    let to_type = args[1].data_type();
+    if !matches!(to_type, DataType::Struct(_)) {
+        return df_execution_err!("cast_struct: target type must be struct, got {to_type}");
+    }
+
+    Ok(match &args[0] {
+        ColumnarValue::Scalar(scalar) => {
+            let array = scalar.to_array()?;
+            let casted = cast_scan_input_array(&array, &to_type)?;
+            ColumnarValue::Scalar(ScalarValue::try_from_array(&casted, 0)?)
+        }
+        ColumnarValue::Array(array) => {
+            ColumnarValue::Array(cast_scan_input_array(array, &to_type)?)
+        }
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use arrow::{array::*, datatypes::*};
+    use datafusion::{
+        common::{cast::as_struct_array, Result, ScalarValue},
+        physical_plan::ColumnarValue,
+    };
+
+    use crate::spark_cast_struct::spark_cast_struct;
+
+    fn input_struct() -> ArrayRef {
+        Arc::new(StructArray::from(vec![
+            (
+                Arc::new(Field::new("a", DataType::Int32, true)),
+                Arc::new(Int32Array::from(vec![Some(1), None, Some(3)])) as ArrayRef,
+            ),
+            (
+                Arc::new(Field::new("b", DataType::Utf8, true)),
+                Arc::new(StringArray::from(vec![Some("x"), Some("y"), None])) as ArrayRef,
+            ),
+        ]))
+    }
+
+    fn target_type(fields: Vec<Field>) -> ColumnarValue {
+        let to_type = DataType::Struct(Fields::from(fields));
+        ColumnarValue::Scalar(ScalarValue::try_from(&to_type).unwrap())
+    }
+
+    #[test]
+    fn test_cast_struct_reorder_fields() -> Result<()> {
+        let to_type = target_type(vec![
+            Field::new("b", DataType::Utf8, true),
+            Field::new("a", DataType::Int64, true),
+        ]);
+        let casted =
+            spark_cast_struct(&[ColumnarValue::Array(input_struct()), to_type])?.into_array(3)?;
+        let casted = as_struct_array(&casted)?;
+
+        assert_eq!(casted.column_names(), vec!["b", "a"]);
+        assert_eq!(
+            casted.column(0).as_ref(),
+            &StringArray::from(vec![Some("x"), Some("y"), None]) as &dyn Array,
+        );
+        assert_eq!(
+            casted.column(1).as_ref(),
+            &Int64Array::from(vec![Some(1), None, Some(3)]) as &dyn Array,
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_cast_struct_missing_fields() -> Result<()> {
+        let to_type = target_type(vec![
+            Field::new("a", DataType::Int32, true),
+            Field::new("c", DataType::Float64, false),
+        ]);
+        let casted =
+            spark_cast_struct(&[ColumnarValue::Array(input_struct()), to_type])?.into_array(3)?;
+        let casted = as_struct_array(&casted)?;
+
+        assert_eq!(casted.column_names(), vec!["a", "c"]);
+        assert_eq!(
+            casted.column(0).as_ref(),
+            &Int32Array::from(vec![Some(1), None, Some(3)]) as &dyn Array,
+        );
+        assert_eq!(casted.column(1).null_count(), 3);
+        assert!(casted.fields()[1].is_nullable());
+        Ok(())
+    }
+}
@@ -12,15 +12,26 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::{cmp::Ordering, sync::Arc};
+use std::{cmp::Ordering, str::FromStr, sync::Arc};
 
-use arrow::array::*;
+use arrow::{
+    array::*,
+    compute::cast,
+    datatypes::{
+        i256, DataType, Decimal128Type, Decimal256Type, DecimalType, DECIMAL256_MAX_PRECISION,
+    },
+};
 use datafusion::{
-    common::{Result, ScalarValue},
+    common::{DataFusionError, Result, ScalarValue},
     physical_plan::ColumnarValue,
 };
+use datafusion_ext_commons::df_execution_err;
 
 /// implements org.apache.spark.sql.catalyst.expressions.CheckOverflow
+///
+/// args[3] (optional, defaults to false) indicates ansi mode: overflowed values
+/// produce an error instead of null.
+/// args[4] (optional, defaults to HALF_UP) is the rounding mode name.
 pub fn spark_check_overflow(args: &[ColumnarValue]) -> Result<ColumnarValue> {
     let to_precision = match &args[1] {
         &ColumnarValue::Scalar(ScalarValue::Int32(Some(precision))) => precision as u8,
@@ -30,43 +41,124 @@ pub fn spark_check_overflow(args: &[ColumnarValue]) -> Result<ColumnarValue> {
         &ColumnarValue::Scalar(ScalarValue::Int32(Some(scale))) => scale as i8,
         _ => unreachable!("check_overflow.scale is not int32 value"),
     };
+    let ansi = match args.get(3) {
+        Some(&ColumnarValue::Scalar(ScalarValue::Boolean(Some(ansi)))) => ansi,
+        None => false,
+        _ => unreachable!("check_overflow.ansi is not boolean value"),
+    };
+    let rounding_mode = match args.get(4) {
+        Some(ColumnarValue::Scalar(ScalarValue::Utf8(Some(mode)))) => mode.parse()?,
+        None => RoundingMode::default(),
+        _ => unreachable!("check_overflow.rounding_mode is not string value"),
+    };
     assert!(
         to_precision >= 1,
         "check_overflow: illegal precision: {}",
         to_precision
     );
 
+    let change_precision = |v: i128, precision: u8, scale: i8| -> Result<Option<i128>> {
+        let changed = change_precision_with_rounding(
+            v,
+            precision,
+            scale,
+            to_precision,
+            to_scale,
+            rounding_mode,
+        );
+        if ansi && changed.is_none() {
+            return df_execution_err!(
+                "Decimal(expanded,{},{precision},{scale}) cannot be represented as Decimal({to_precision}, {to_scale}).",
+                Decimal128Type::format_decimal(v, precision, scale),
+            );
+        }
+        Ok(changed)
+    };
+    let change_precision_i256 = |v: i256, precision: u8, scale: i8| -> Result<Option<i256>> {
+        let changed = change_precision_with_rounding_i256(
+            v,
+            precision,
+            scale,
+            to_precision,
+            to_scale,
+            rounding_mode,
+        );
+        if ansi && changed.is_none() {
+            return df_execution_err!(
+                "Decimal(expanded,{},{precision},{scale}) cannot be represented as Decimal({to_precision}, {to_scale}).",
+                Decimal256Type::format_decimal(v, precision, scale),
+            );
+        }
+        Ok(changed)
+    };
+
     Ok(match &args[0] {
         ColumnarValue::Scalar(scalar) => match scalar {
             ScalarValue::Decimal128(Some(i128_val), precision, scale) => {
                 ColumnarValue::Scalar(ScalarValue::Decimal128(
-                    change_precision_round_half_up(
-                        *i128_val,
-                        *precision,
-                        *scale,
-                        to_precision,
-                        to_scale,
-                    ),
+                    change_precision(*i128_val, *precision, *scale)?,
+                    to_precision,
+                    to_scale,
+                ))
+            }
+            ScalarValue::Decimal256(i256_val, precision, scale) => {
+                ColumnarValue::Scalar(ScalarValue::Decimal256(
+                    match i256_val {
+                        Some(v) => change_precision_i256(*v, *precision, *scale)?,
+                        None => None,
+                    },
                     to_precision,
                     to_scale,
                 ))
             }
             _ => ColumnarValue::Scalar(ScalarValue::Decimal128(None, to_precision, to_scale)),
         },
+        ColumnarValue::Array(array) if matches!(array.data_type(), DataType::Decimal256(..)) => {
+            let array = array.as_any().downcast_ref::<Decimal256Array>().unwrap();
+            let mut output = Decimal256Builder::with_capacity(array.len());
+
+            for v in array.into_iter() {
+                match v {
+                    Some(v) => {
+                        output.append_option(change_precision_i256(
+                            v,
+                            array.precision(),
+                            array.scale(),
+                        )?);
+                    }
+                    None => output.append_null(),
+                }
+            }
+            ColumnarValue::Array(Arc::new(
+                output
+                    .finish()
+                    .with_precision_and_scale(to_precision, to_scale)?,
+            ))
+        }
         ColumnarValue::Array(array) => {
+            // dictionary-encoded decimals are unpacked to their declared decimal type
+            let array = match array.data_type() {
+                DataType::Decimal128(..) => array.clone(),
+                DataType::Dictionary(_, value_type)
+                    if matches!(value_type.as_ref(), DataType::Decimal128(..)) =>
+                {
+                    cast(array, value_type)?
+                }
+                other => {
+                    return df_execution_err!("check_overflow: expect decimal input, got {other}");
+                }
+            };
             let array = array.as_any().downcast_ref::<Decimal128Array>().unwrap();
             let mut output = Decimal128Builder::with_capacity(array.len());
 
             for v in array.into_iter() {
                 match v {
                     Some(v) => {
-                        output.append_option(change_precision_round_half_up(
+                        output.append_option(change_precision(
                             v,
                             array.precision(),
                             array.scale(),
-                            to_precision,
-                            to_scale,
-                        ));
+                        )?);
                     }
                     None => output.append_null(),
                 }
@@ -80,13 +172,61 @@ pub fn spark_check_overflow(args: &[ColumnarValue]) -> Result<ColumnarValue> {
     })
 }
 
+/// rounding modes supported in
+/// org.apache.spark.sql.types.Decimal.changePrecision
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RoundingMode {
+    #[default]
+    HalfUp,
+    HalfEven,
+    Floor,
+    Ceiling,
+}
+
+impl FromStr for RoundingMode {
+    type Err = DataFusionError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s {
+            "HALF_UP" => RoundingMode::HalfUp,
+            "HALF_EVEN" => RoundingMode::HalfEven,
+            "FLOOR" => RoundingMode::Floor,
+            "CEILING" => RoundingMode::Ceiling,
+            other => df_execution_err!("check_overflow: unsupported rounding mode: {other}")?,
+        })
+    }
+}
+
+impl RoundingMode {
+    /// returns the value (-1, 0 or 1) to add to a quotient truncated towards
+    /// zero. dropped_sign is the sign of the dropped digits,
+    /// dropped_vs_half compares the dropped digits to half of the divisor.
+    pub(crate) fn round_increment(
+        self,
+        dropped_sign: Ordering,
+        dropped_vs_half: Ordering,
+        odd: bool,
+    ) -> i8 {
+        let away_from_zero = dropped_sign as i8;
+        match self {
+            RoundingMode::HalfUp if dropped_vs_half != Ordering::Less => away_from_zero,
+            RoundingMode::HalfEven if dropped_vs_half == Ordering::Greater => away_from_zero,
+            RoundingMode::HalfEven if dropped_vs_half == Ordering::Equal && odd => away_from_zero,
+            RoundingMode::Floor if dropped_sign == Ordering::Less => -1,
+            RoundingMode::Ceiling if dropped_sign == Ordering::Greater => 1,
+            _ => 0,
+        }
+    }
+}
+
 /// implements org.apache.spark.sql.types.Decimal.changePrecision
-fn change_precision_round_half_up(
+pub(crate) fn change_precision_with_rounding(
     mut i128_val: i128,
     precision: u8,
     scale: i8,
     to_precision: u8,
     to_scale: i8,
+    rounding_mode: RoundingMode,
 ) -> Option<i128> {
     let max_spark_precision = 38;
 
@@ -101,9 +241,11 @@ fn change_precision_round_half_up(
             // % and / always round to 0
             let dropped_digits = i128_val % pow10diff;
             i128_val /= pow10diff;
-            if dropped_digits.abs() * 2 >= pow10diff {
-                i128_val += if dropped_digits < 0 { -1 } else { 1 };
-            }
+            i128_val += rounding_mode.round_increment(
+                dropped_digits.cmp(&0),
+                (dropped_digits.abs() * 2).cmp(&pow10diff),
+                i128_val % 2 != 0,
+            ) as i128;
         }
         Ordering::Greater => {
             // We might be able to multiply i128_val by a power of 10 and not overflow, but
@@ -122,14 +264,67 @@ fn change_precision_round_half_up(
     }
     Some(i128_val)
 }
+
+/// same as change_precision_with_rounding(), for decimal256 values
+fn change_precision_with_rounding_i256(
+    mut i256_val: i256,
+    precision: u8,
+    scale: i8,
+    to_precision: u8,
+    to_scale: i8,
+    rounding_mode: RoundingMode,
+) -> Option<i256> {
+    let ten = i256::from_i128(10);
+
+    if to_precision == precision && to_scale == scale {
+        return Some(i256_val);
+    }
+    match to_scale.cmp(&scale) {
+        Ordering::Less => {
+            let diff = scale - to_scale;
+            let pow10diff = ten.checked_pow(diff as u32)?;
+            // % and / always round to 0
+            let dropped_digits = i256_val.checked_rem(pow10diff)?;
+            i256_val = i256_val.checked_div(pow10diff)?;
+            let increment = rounding_mode.round_increment(
+                dropped_digits.cmp(&i256::ZERO),
+                dropped_digits
+                    .wrapping_abs()
+                    .checked_mul(i256::from_i128(2))?
+                    .cmp(&pow10diff),
+                i256_val.checked_rem(i256::from_i128(2))? != i256::ZERO,
+            );
+            i256_val = i256_val.checked_add(i256::from_i128(increment as i128))?;
+        }
+        Ordering::Greater => {
+            let diff = to_scale - scale;
+            i256_val = i256_val.checked_mul(ten.checked_pow(diff as u32)?)?;
+        }
+        _ => {}
+    }
+
+    // check whether the i256_val overflows max precision supported in decimal256
+    let p = ten.checked_pow(u32::min(
+        to_precision as u32,
+        DECIMAL256_MAX_PRECISION as u32,
+    ))?;
+    if i256_val <= p.wrapping_neg() || i256_val >= p {
+        return None;
+    }
+    Some(i256_val)
+}
+
 #[cfg(test)]
 mod test {
     use std::{error::Error, sync::Arc};
 
-    use arrow::array::{ArrayRef, Decimal128Array};
+    use arrow::{
+        array::{ArrayRef, Decimal128Array, Decimal256Array, DictionaryArray, Int64Array},
+        datatypes::{i256, Int32Type},
+    };
     use datafusion::{common::ScalarValue, logical_expr::ColumnarValue};
 
-    use crate::spark_check_overflow::spark_check_overflow;
+    use crate::spark_check_overflow::{spark_check_overflow, RoundingMode};
 
     #[test]
     fn test_check_overflow() -> Result<(), Box<dyn Error>> {
@@ -155,4 +350,128 @@ mod test {
         assert_eq!(&result, &expected);
         Ok(())
     }
+
+    #[test]
+    fn test_check_overflow_ansi() -> Result<(), Box<dyn Error>> {
+        let array = Decimal128Array::from(vec![Some(13245), Some(123213244568923), None])
+            .with_precision_and_scale(20, 8)?;
+        let args = |ansi: bool| {
+            vec![
+                ColumnarValue::Array(Arc::new(array.clone())),
+                ColumnarValue::Scalar(ScalarValue::Int32(Some(10))), // precision
+                ColumnarValue::Scalar(ScalarValue::Int32(Some(5))),  // scale
+                ColumnarValue::Scalar(ScalarValue::Boolean(Some(ansi))),
+            ]
+        };
+
+        // non-ansi: overflowed value becomes null
+        let result = spark_check_overflow(&args(false))?.into_array(3)?;
+        let expected =
+            Decimal128Array::from(vec![Some(13), None, None]).with_precision_and_scale(10, 5)?;
+        let expected: ArrayRef = Arc::new(expected);
+        assert_eq!(&result, &expected);
+
+        // ansi: overflowed value raises an error
+        let err = spark_check_overflow(&args(true)).unwrap_err();
+        assert!(err.to_string().contains(
+            "Decimal(expanded,1232132.44568923,20,8) cannot be represented as Decimal(10, 5)."
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_check_overflow_decimal256() -> Result<(), Box<dyn Error>> {
+        // 10^40 overflows decimal128
+        let big = i256::from_i128(10).pow_wrapping(40);
+        let array = Decimal256Array::from(vec![Some(big), Some(i256::from_i128(12345)), None])
+            .with_precision_and_scale(50, 0)?;
+
+        let result = spark_check_overflow(&vec![
+            ColumnarValue::Array(Arc::new(array)),
+            ColumnarValue::Scalar(ScalarValue::Int32(Some(45))), // precision
+            ColumnarValue::Scalar(ScalarValue::Int32(Some(2))),  // scale
+        ])?
+        .into_array(3)?;
+
+        let expected = Decimal256Array::from(vec![
+            Some(big.wrapping_mul(i256::from_i128(100))),
+            Some(i256::from_i128(1234500)),
+            None,
+        ])
+        .with_precision_and_scale(45, 2)?;
+        let expected: ArrayRef = Arc::new(expected);
+        assert_eq!(&result, &expected);
+
+        // scalar value overflowing the target precision
+        let result = spark_check_overflow(&vec![
+            ColumnarValue::Scalar(ScalarValue::Decimal256(Some(big), 50, 0)),
+            ColumnarValue::Scalar(ScalarValue::Int32(Some(40))), // precision
+            ColumnarValue::Scalar(ScalarValue::Int32(Some(2))),  // scale
+        ])?;
+        assert!(matches!(
+            result,
+            ColumnarValue::Scalar(ScalarValue::Decimal256(None, 40, 2))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_check_overflow_rounding_modes() -> Result<(), Box<dyn Error>> {
+        // exact-half ties: 0.25, 0.35, -0.25, -0.35 and a non-tie 0.26
+        let array = Decimal128Array::from(vec![Some(25), Some(35), Some(-25), Some(-35), Some(26)])
+            .with_precision_and_scale(10, 2)?;
+        let check = |mode: Option<&str>| -> Result<ArrayRef, Box<dyn Error>> {
+            let mut args = vec![
+                ColumnarValue::Array(Arc::new(array.clone())),
+                ColumnarValue::Scalar(ScalarValue::Int32(Some(10))), // precision
+                ColumnarValue::Scalar(ScalarValue::Int32(Some(1))),  // scale
+                ColumnarValue::Scalar(ScalarValue::Boolean(Some(false))),
+            ];
+            if let Some(mode) = mode {
+                args.push(ColumnarValue::Scalar(ScalarValue::from(mode)));
+            }
+            Ok(spark_check_overflow(&args)?.into_array(5)?)
+        };
+        let expected = |values: Vec<i128>| -> Result<ArrayRef, Box<dyn Error>> {
+            Ok(Arc::new(
+                Decimal128Array::from(values).with_precision_and_scale(10, 1)?,
+            ))
+        };
+
+        assert_eq!(RoundingMode::default(), RoundingMode::HalfUp);
+        assert_eq!(check(None)?, expected(vec![3, 4, -3, -4, 3])?);
+        assert_eq!(check(Some("HALF_UP"))?, expected(vec![3, 4, -3, -4, 3])?);
+        assert_eq!(check(Some("HALF_EVEN"))?, expected(vec![2, 4, -2, -4, 3])?);
+        assert_eq!(check(Some("FLOOR"))?, expected(vec![2, 3, -3, -4, 2])?);
+        assert_eq!(check(Some("CEILING"))?, expected(vec![3, 4, -2, -3, 3])?);
+        assert!(check(Some("UNKNOWN")).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_check_overflow_non_decimal_input() -> Result<(), Box<dyn Error>> {
+        let args = |array: ArrayRef| {
+            vec![
+                ColumnarValue::Array(array),
+                ColumnarValue::Scalar(ScalarValue::Int32(Some(10))), // precision
+                ColumnarValue::Scalar(ScalarValue::Int32(Some(1))),  // scale
+            ]
+        };
+
+        // dictionary-encoded decimals
+        let values = Decimal128Array::from(vec![1234, 5678]).with_precision_and_scale(10, 2)?;
+        let dict = DictionaryArray::<Int32Type>::try_new(vec![0, 1, 0].into(), Arc::new(values))?;
+        let result = spark_check_overflow(&args(Arc::new(dict)))?.into_array(3)?;
+        let expected: ArrayRef =
+            Arc::new(Decimal128Array::from(vec![123, 568, 123]).with_precision_and_scale(10, 1)?);
+        assert_eq!(&result, &expected);
+
+        // non-decimal input produces an error instead of panicking
+        let err = spark_check_overflow(&args(Arc::new(Int64Array::from(vec![1, 2]))));
+        assert!(err
+            .unwrap_err()
+            .to_string()
+            .contains("check_overflow: expect decimal input, got Int64"));
+        Ok(())
+    }
 }
@@ -0,0 +1,96 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+
+use arrow::{array::*, datatypes::*};
+use datafusion::{
+    common::{Result, ScalarValue},
+    physical_plan::ColumnarValue,
+};
+use datafusion_ext_commons::df_execution_err;
+
+/// spark's crc32(): crc-32 (ieee) checksum of a binary (or string) as an
+/// unsigned value in bigint, same as java.util.zip.CRC32.
+pub fn spark_crc32(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    let crc32 = |array: &ArrayRef| -> Result<Int64Array> {
+        let checksum = |v: &[u8]| crc32fast::hash(v) as i64;
+        Ok(match array.data_type() {
+            DataType::Binary => array
+                .as_binary::<i32>()
+                .iter()
+                .map(|v| v.map(checksum))
+                .collect(),
+            DataType::Utf8 => array
+                .as_string::<i32>()
+                .iter()
+                .map(|v| v.map(|v| checksum(v.as_bytes())))
+                .collect(),
+            other => return df_execution_err!("crc32: unsupported type: {other}"),
+        })
+    };
+    Ok(match &args[0] {
+        ColumnarValue::Array(array) => ColumnarValue::Array(Arc::new(crc32(array)?)),
+        ColumnarValue::Scalar(scalar) => {
+            let checksums = crc32(&scalar.to_array()?)?;
+            ColumnarValue::Scalar(ScalarValue::try_from_array(&checksums, 0)?)
+        }
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use arrow::array::*;
+    use datafusion::{
+        common::{cast::as_int64_array, Result, ScalarValue},
+        physical_plan::ColumnarValue,
+    };
+
+    use crate::spark_crc32::spark_crc32;
+
+    #[test]
+    fn test_crc32() -> Result<()> {
+        let r = spark_crc32(&[ColumnarValue::Array(Arc::new(StringArray::from(vec![
+            Some("Spark"),
+            Some("ABC"),
+            Some("The quick brown fox jumps over the lazy dog"),
+            Some(""),
+            None,
+        ])))])?;
+        assert_eq!(
+            as_int64_array(&r.into_array(5)?)?,
+            &Int64Array::from(vec![
+                Some(1557323817),
+                Some(2743272264),
+                Some(1095738169),
+                Some(0),
+                None,
+            ]),
+        );
+
+        // binary input
+        let r = spark_crc32(&[ColumnarValue::Scalar(ScalarValue::Binary(Some(
+            b"123456789".to_vec(),
+        )))])?;
+        match r {
+            ColumnarValue::Scalar(ScalarValue::Int64(Some(checksum))) => {
+                assert_eq!(checksum, 0xcbf43926)
+            }
+            other => panic!("unexpected result: {other:?}"),
+        }
+        Ok(())
+    }
+}
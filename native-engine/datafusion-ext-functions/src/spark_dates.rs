@@ -0,0 +1,939 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+
+use arrow::{
+    array::{timezone::Tz, *},
+    datatypes::*,
+    temporal_conversions::{timestamp_s_to_datetime, timestamp_us_to_datetime, EPOCH_DAYS_FROM_CE},
+};
+use chrono::{
+    format::{Item, StrftimeItems},
+    Datelike, Duration, NaiveDate, NaiveDateTime, Offset, TimeZone, Timelike,
+};
+use datafusion::{
+    common::{Result, ScalarValue},
+    physical_plan::ColumnarValue,
+};
+use datafusion_ext_commons::{
+    df_execution_err,
+    timezone::timezone_arg,
+};
+
+/// casts timestamp to date in the timezone given by the second argument (the
+/// expression's timeZoneId), which is what spark does, instead of simply
+/// truncating the utc timestamp to days.
+pub fn spark_cast_timestamp_to_date(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    let tz = timezone_arg(args.get(1))?;
+    Ok(match &args[0] {
+        ColumnarValue::Array(array) => {
+            ColumnarValue::Array(Arc::new(cast_timestamp_to_date(array, &tz)?))
+        }
+        ColumnarValue::Scalar(scalar) => {
+            let dates = cast_timestamp_to_date(&scalar.to_array()?, &tz)?;
+            ColumnarValue::Scalar(ScalarValue::try_from_array(&dates, 0)?)
+        }
+    })
+}
+
+fn cast_timestamp_to_date(array: &ArrayRef, tz: &Tz) -> Result<Date32Array> {
+    let micros = match array.data_type() {
+        DataType::Timestamp(TimeUnit::Microsecond, _) => {
+            array.as_primitive::<TimestampMicrosecondType>()
+        }
+        other => return df_execution_err!("cast_timestamp_to_date: unsupported type: {other}"),
+    };
+    Ok(micros.unary_opt::<_, Date32Type>(|us| {
+        let local_date = tz
+            .from_utc_datetime(&timestamp_us_to_datetime(us)?)
+            .date_naive();
+        Some(local_date.num_days_from_ce() - EPOCH_DAYS_FROM_CE)
+    }))
+}
+
+/// spark's unix_seconds(): number of seconds since epoch of a timestamp.
+pub fn spark_unix_seconds(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    timestamp_to_unix(args, 1_000_000)
+}
+
+/// spark's unix_millis(): number of milliseconds since epoch of a timestamp.
+pub fn spark_unix_millis(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    timestamp_to_unix(args, 1_000)
+}
+
+/// spark's unix_micros(): number of microseconds since epoch of a timestamp.
+pub fn spark_unix_micros(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    timestamp_to_unix(args, 1)
+}
+
+fn timestamp_to_unix(args: &[ColumnarValue], micros_per_unit: i64) -> Result<ColumnarValue> {
+    Ok(match &args[0] {
+        ColumnarValue::Array(array) => {
+            ColumnarValue::Array(Arc::new(cast_timestamp_to_unix(array, micros_per_unit)?))
+        }
+        ColumnarValue::Scalar(scalar) => {
+            let units = cast_timestamp_to_unix(&scalar.to_array()?, micros_per_unit)?;
+            ColumnarValue::Scalar(ScalarValue::try_from_array(&units, 0)?)
+        }
+    })
+}
+
+fn cast_timestamp_to_unix(array: &ArrayRef, micros_per_unit: i64) -> Result<Int64Array> {
+    let micros = match array.data_type() {
+        DataType::Timestamp(TimeUnit::Microsecond, _) => {
+            array.as_primitive::<TimestampMicrosecondType>()
+        }
+        other => return df_execution_err!("timestamp_to_unix: unsupported type: {other}"),
+    };
+    // spark uses Math.floorDiv(), so pre-epoch values are rounded down
+    Ok(micros.unary::<_, Int64Type>(|us| us.div_euclid(micros_per_unit)))
+}
+
+/// spark's from_unixtime(): formats seconds since epoch with a datetime
+/// pattern (defaults to "yyyy-MM-dd HH:mm:ss") in the timezone given by the
+/// third argument (the expression's timeZoneId).
+pub fn spark_from_unixtime(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    let pattern = match args.get(1) {
+        Some(ColumnarValue::Scalar(ScalarValue::Utf8(pattern))) => pattern.as_deref(),
+        Some(_) => return df_execution_err!("from_unixtime: pattern must be a literal string"),
+        None => Some("yyyy-MM-dd HH:mm:ss"),
+    };
+    let tz = timezone_arg(args.get(2))?;
+    let strftime = pattern.map(convert_datetime_pattern).transpose()?;
+
+    let from_unixtime = |array: &ArrayRef| -> Result<StringArray> {
+        let seconds = match array.data_type() {
+            DataType::Int64 => array.as_primitive::<Int64Type>(),
+            other => return df_execution_err!("from_unixtime: unsupported type: {other}"),
+        };
+        let Some(strftime) = &strftime else {
+            return Ok(StringArray::new_null(seconds.len()));
+        };
+        let items = StrftimeItems::new(strftime).collect::<Vec<_>>();
+        Ok(seconds
+            .iter()
+            .map(|secs| {
+                let local_datetime = tz.from_utc_datetime(&timestamp_s_to_datetime(secs?)?);
+                Some(local_datetime.format_with_items(items.iter()).to_string())
+            })
+            .collect())
+    };
+    Ok(match &args[0] {
+        ColumnarValue::Array(array) => ColumnarValue::Array(Arc::new(from_unixtime(array)?)),
+        ColumnarValue::Scalar(scalar) => {
+            let formatted = from_unixtime(&scalar.to_array()?)?;
+            ColumnarValue::Scalar(ScalarValue::try_from_array(&formatted, 0)?)
+        }
+    })
+}
+
+/// converts a spark datetime pattern into a chrono strftime string. only the
+/// commonly used pattern letters are supported.
+fn convert_datetime_pattern(pattern: &str) -> Result<String> {
+    let mut strftime = String::new();
+    let mut chars = pattern.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            // quoted text, two single quotes represent a single quote
+            '\'' => {
+                if chars.next_if_eq(&'\'').is_some() {
+                    strftime.push('\'');
+                    continue;
+                }
+                loop {
+                    match chars.next() {
+                        Some('\'') if chars.next_if_eq(&'\'').is_some() => strftime.push('\''),
+                        Some('\'') => break,
+                        Some('%') => strftime.push_str("%%"),
+                        Some(c) => strftime.push(c),
+                        None => {
+                            return df_execution_err!("unterminated quote in pattern: {pattern:?}")
+                        }
+                    }
+                }
+            }
+            c if c.is_ascii_alphabetic() => {
+                let mut count = 1;
+                while chars.next_if_eq(&c).is_some() {
+                    count += 1;
+                }
+                let spec = match (c, count) {
+                    ('y', 2) => "%y",
+                    ('y', _) => "%Y",
+                    ('M', 1) => "%-m",
+                    ('M', 2) => "%m",
+                    ('M', 3) => "%b",
+                    ('M', _) => "%B",
+                    ('d', 1) => "%-d",
+                    ('d', 2) => "%d",
+                    ('D', 1) => "%-j",
+                    ('D', 3) => "%j",
+                    ('H', 1) => "%-H",
+                    ('H', 2) => "%H",
+                    ('h', 1) => "%-I",
+                    ('h', 2) => "%I",
+                    ('m', 1) => "%-M",
+                    ('m', 2) => "%M",
+                    ('s', 1) => "%-S",
+                    ('s', 2) => "%S",
+                    ('a', 1) => "%p",
+                    ('E', 1..=3) => "%a",
+                    ('E', _) => "%A",
+                    ('z', 1..=3) => "%Z",
+                    ('Z', 1..=3) => "%z",
+                    ('S', _) => {
+                        // input has no fractional seconds
+                        strftime.push_str(&"0".repeat(count));
+                        continue;
+                    }
+                    _ => {
+                        let letters = c.to_string().repeat(count);
+                        return df_execution_err!("unsupported pattern {letters:?} in {pattern:?}");
+                    }
+                };
+                strftime.push_str(spec);
+            }
+            '%' => strftime.push_str("%%"),
+            c => strftime.push(c),
+        }
+    }
+    if StrftimeItems::new(&strftime).any(|item| item == Item::Error) {
+        return df_execution_err!("unsupported datetime pattern: {pattern:?}");
+    }
+    Ok(strftime)
+}
+
+/// spark's date_trunc(fmt, ts) and trunc(date, fmt): truncates a timestamp (in
+/// the timezone given by the third argument, the expression's timeZoneId)
+/// or a date to the start of the given unit. weeks start on monday. unknown
+/// units (and units finer than a week for dates) produce null.
+pub fn spark_date_trunc(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    let unit = match &args[0] {
+        ColumnarValue::Scalar(ScalarValue::Utf8(unit)) => {
+            unit.as_deref().and_then(TruncUnit::parse)
+        }
+        _ => return df_execution_err!("date_trunc: unit must be a literal string"),
+    };
+    let tz = timezone_arg(args.get(2))?;
+    Ok(match &args[1] {
+        ColumnarValue::Array(array) => ColumnarValue::Array(date_trunc(array, unit, &tz)?),
+        ColumnarValue::Scalar(scalar) => {
+            let truncated = date_trunc(&scalar.to_array()?, unit, &tz)?;
+            ColumnarValue::Scalar(ScalarValue::try_from_array(&truncated, 0)?)
+        }
+    })
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+enum TruncUnit {
+    Microsecond,
+    Millisecond,
+    Second,
+    Minute,
+    Hour,
+    Day,
+    Week,
+    Month,
+    Quarter,
+    Year,
+}
+
+impl TruncUnit {
+    /// same as DateTimeUtils.parseTruncLevel()
+    fn parse(unit: &str) -> Option<Self> {
+        Some(match unit.to_uppercase().as_str() {
+            "MICROSECOND" => Self::Microsecond,
+            "MILLISECOND" => Self::Millisecond,
+            "SECOND" => Self::Second,
+            "MINUTE" => Self::Minute,
+            "HOUR" => Self::Hour,
+            "DAY" | "DD" => Self::Day,
+            "WEEK" => Self::Week,
+            "MON" | "MONTH" | "MM" => Self::Month,
+            "QUARTER" => Self::Quarter,
+            "YEAR" | "YYYY" | "YY" => Self::Year,
+            _ => return None,
+        })
+    }
+
+    fn trunc_date(&self, date: NaiveDate) -> NaiveDate {
+        match self {
+            Self::Week => date - Duration::days(date.weekday().num_days_from_monday() as i64),
+            Self::Month => date.with_day(1).unwrap(),
+            Self::Quarter => date
+                .with_day(1)
+                .and_then(|date| date.with_month(date.month0() / 3 * 3 + 1))
+                .unwrap(),
+            Self::Year => date.with_ordinal(1).unwrap(),
+            _ => date,
+        }
+    }
+
+    fn trunc_datetime(&self, datetime: NaiveDateTime) -> NaiveDateTime {
+        let time = datetime.time();
+        match self {
+            Self::Microsecond | Self::Millisecond => datetime,
+            Self::Second => datetime.with_nanosecond(0).unwrap(),
+            Self::Minute => datetime
+                .date()
+                .and_hms_opt(time.hour(), time.minute(), 0)
+                .unwrap(),
+            Self::Hour => datetime.date().and_hms_opt(time.hour(), 0, 0).unwrap(),
+            _ => self
+                .trunc_date(datetime.date())
+                .and_hms_opt(0, 0, 0)
+                .unwrap(),
+        }
+    }
+}
+
+fn date_trunc(array: &ArrayRef, unit: Option<TruncUnit>, tz: &Tz) -> Result<ArrayRef> {
+    match array.data_type() {
+        DataType::Date32 => {
+            let dates = array.as_primitive::<Date32Type>();
+            let unit = unit.filter(|unit| *unit >= TruncUnit::Week);
+            Ok(Arc::new(dates.unary_opt::<_, Date32Type>(|days| {
+                let date = NaiveDate::from_num_days_from_ce_opt(days + EPOCH_DAYS_FROM_CE)?;
+                Some(unit?.trunc_date(date).num_days_from_ce() - EPOCH_DAYS_FROM_CE)
+            })))
+        }
+        DataType::Timestamp(TimeUnit::Microsecond, tz_opt) => {
+            let micros = array.as_primitive::<TimestampMicrosecondType>();
+            let truncated = micros.unary_opt::<_, TimestampMicrosecondType>(|us| match unit? {
+                TruncUnit::Microsecond => Some(us),
+                TruncUnit::Millisecond => Some(us - us.rem_euclid(1000)),
+                unit => {
+                    let local = tz.from_utc_datetime(&timestamp_us_to_datetime(us)?);
+                    let truncated = unit.trunc_datetime(local.naive_local());
+                    Some(local_to_utc(tz, truncated).and_utc().timestamp_micros())
+                }
+            });
+            Ok(Arc::new(truncated.with_timezone_opt(tz_opt.clone())))
+        }
+        other => df_execution_err!("date_trunc: unsupported type: {other}"),
+    }
+}
+
+/// spark's months_between(end, start, round_off): months between two
+/// timestamps (or dates) in the timezone given by the fourth argument (the
+/// expression's timeZoneId). the remaining days and time are counted in 31-day
+/// months, except that the result is whole if both are on the same day of month
+/// or both on the last day of month. round_off (defaults to true) rounds the
+/// result to 8 decimal places.
+pub fn spark_months_between(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    let round_off = match args.get(2) {
+        Some(ColumnarValue::Scalar(ScalarValue::Boolean(Some(round_off)))) => *round_off,
+        Some(_) => return df_execution_err!("months_between: round_off must be a literal boolean"),
+        None => true,
+    };
+    let tz = timezone_arg(args.get(3))?;
+    let num_rows = args[..2].iter().find_map(|arg| match arg {
+        ColumnarValue::Array(array) => Some(array.len()),
+        ColumnarValue::Scalar(_) => None,
+    });
+    let end = split_dates(&args[0].clone().into_array(num_rows.unwrap_or(1))?, &tz)?;
+    let start = split_dates(&args[1].clone().into_array(num_rows.unwrap_or(1))?, &tz)?;
+
+    let output = end
+        .into_iter()
+        .zip(start)
+        .map(|(end, start)| Some(months_between(end?, start?, round_off)))
+        .collect::<Float64Array>();
+    Ok(match num_rows {
+        Some(_) => ColumnarValue::Array(Arc::new(output)),
+        None => ColumnarValue::Scalar(ScalarValue::try_from_array(&output, 0)?),
+    })
+}
+
+/// splits timestamps (or dates) into local dates and seconds in the day
+fn split_dates(array: &ArrayRef, tz: &Tz) -> Result<Vec<Option<(NaiveDate, i64)>>> {
+    Ok(match array.data_type() {
+        DataType::Date32 => array
+            .as_primitive::<Date32Type>()
+            .iter()
+            .map(|days| {
+                let date = NaiveDate::from_num_days_from_ce_opt(days? + EPOCH_DAYS_FROM_CE)?;
+                Some((date, 0))
+            })
+            .collect(),
+        DataType::Timestamp(TimeUnit::Microsecond, _) => array
+            .as_primitive::<TimestampMicrosecondType>()
+            .iter()
+            .map(|us| {
+                let us = us?;
+                let date = tz
+                    .from_utc_datetime(&timestamp_us_to_datetime(us)?)
+                    .date_naive();
+                let day_start = local_to_utc(tz, date.and_hms_opt(0, 0, 0)?);
+                let seconds_in_day = (us - day_start.and_utc().timestamp_micros()) / 1000000;
+                Some((date, seconds_in_day))
+            })
+            .collect(),
+        other => return df_execution_err!("months_between: unsupported type: {other}"),
+    })
+}
+
+/// same as DateTimeUtils.monthsBetween()
+fn months_between(end: (NaiveDate, i64), start: (NaiveDate, i64), round_off: bool) -> f64 {
+    const SECONDS_PER_DAY: i64 = 86400;
+    let ((date1, seconds_in_day1), (date2, seconds_in_day2)) = (end, start);
+    let is_last_day = |date: NaiveDate| date.succ_opt().map(|next| next.day()) == Some(1);
+
+    let months1 = date1.year() as i64 * 12 + date1.month() as i64;
+    let months2 = date2.year() as i64 * 12 + date2.month() as i64;
+    let month_diff = (months1 - months2) as f64;
+    if date1.day() == date2.day() || (is_last_day(date1) && is_last_day(date2)) {
+        return month_diff;
+    }
+
+    let days_diff = date1.day() as i64 - date2.day() as i64;
+    let seconds_diff = days_diff * SECONDS_PER_DAY + seconds_in_day1 - seconds_in_day2;
+    let diff = month_diff + seconds_diff as f64 / (31 * SECONDS_PER_DAY) as f64;
+    if round_off {
+        // same as java's Math.round()
+        (diff * 1e8 + 0.5).floor() / 1e8
+    } else {
+        diff
+    }
+}
+
+/// spark's last_day(): the last day of the month which the date belongs to.
+pub fn spark_last_day(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    let last_day = |array: &ArrayRef| -> Result<Date32Array> {
+        let dates = match array.data_type() {
+            DataType::Date32 => array.as_primitive::<Date32Type>(),
+            other => return df_execution_err!("last_day: unsupported type: {other}"),
+        };
+        Ok(dates.unary_opt::<_, Date32Type>(|days| {
+            let date = NaiveDate::from_num_days_from_ce_opt(days + EPOCH_DAYS_FROM_CE)?;
+            let first_day_of_next_month = match date.month() {
+                12 => NaiveDate::from_ymd_opt(date.year() + 1, 1, 1)?,
+                month => NaiveDate::from_ymd_opt(date.year(), month + 1, 1)?,
+            };
+            Some(first_day_of_next_month.num_days_from_ce() - EPOCH_DAYS_FROM_CE - 1)
+        }))
+    };
+    Ok(match &args[0] {
+        ColumnarValue::Array(array) => ColumnarValue::Array(Arc::new(last_day(array)?)),
+        ColumnarValue::Scalar(scalar) => {
+            let dates = last_day(&scalar.to_array()?)?;
+            ColumnarValue::Scalar(ScalarValue::try_from_array(&dates, 0)?)
+        }
+    })
+}
+
+/// spark's next_day(): the first date later than the given date that falls on
+/// the given day of week. unknown day of week names produce null.
+pub fn spark_next_day(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    let num_rows = args.iter().find_map(|arg| match arg {
+        ColumnarValue::Array(array) => Some(array.len()),
+        ColumnarValue::Scalar(_) => None,
+    });
+    let dates = args[0].clone().into_array(num_rows.unwrap_or(1))?;
+    let dates = match dates.data_type() {
+        DataType::Date32 => dates.as_primitive::<Date32Type>(),
+        other => return df_execution_err!("next_day: unsupported type: {other}"),
+    };
+    let day_of_week_names = args[1].clone().into_array(num_rows.unwrap_or(1))?;
+    let day_of_week_names = match day_of_week_names.data_type() {
+        DataType::Utf8 => day_of_week_names.as_string::<i32>(),
+        other => return df_execution_err!("next_day: unsupported day of week type: {other}"),
+    };
+
+    let output = dates
+        .iter()
+        .zip(day_of_week_names)
+        .map(|(days, day_of_week_name)| {
+            let days = days?;
+            let day_of_week = parse_day_of_week(day_of_week_name?)?;
+            let date = NaiveDate::from_num_days_from_ce_opt(days + EPOCH_DAYS_FROM_CE)?;
+            let current_day_of_week = date.weekday().num_days_from_monday() as i32;
+            Some(days + (day_of_week - current_day_of_week - 1).rem_euclid(7) + 1)
+        })
+        .collect::<Date32Array>();
+    Ok(match num_rows {
+        Some(_) => ColumnarValue::Array(Arc::new(output)),
+        None => ColumnarValue::Scalar(ScalarValue::try_from_array(&output, 0)?),
+    })
+}
+
+/// same as DateTimeUtils.getDayOfWeekFromString(), monday = 0
+fn parse_day_of_week(name: &str) -> Option<i32> {
+    Some(match name.to_uppercase().as_str() {
+        "MO" | "MON" | "MONDAY" => 0,
+        "TU" | "TUE" | "TUESDAY" => 1,
+        "WE" | "WED" | "WEDNESDAY" => 2,
+        "TH" | "THU" | "THURSDAY" => 3,
+        "FR" | "FRI" | "FRIDAY" => 4,
+        "SA" | "SAT" | "SATURDAY" => 5,
+        "SU" | "SUN" | "SUNDAY" => 6,
+        _ => return None,
+    })
+}
+
+/// same as java's LocalDateTime.atZone(): the earlier offset is used for
+/// overlaps, and local times in a gap are shifted forward by the gap length.
+fn local_to_utc(tz: &Tz, local: NaiveDateTime) -> NaiveDateTime {
+    match tz.from_local_datetime(&local).earliest() {
+        Some(datetime) => datetime.naive_utc(),
+        None => {
+            let offset_before_gap = tz.offset_from_utc_datetime(&(local - Duration::days(1)));
+            local - Duration::seconds(offset_before_gap.fix().local_minus_utc() as i64)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use arrow::array::*;
+    use datafusion::{
+        common::{
+            cast::{
+                as_date32_array, as_float64_array, as_int64_array, as_string_array,
+                as_timestamp_microsecond_array,
+            },
+            Result, ScalarValue,
+        },
+        physical_plan::ColumnarValue,
+    };
+
+    use crate::spark_dates::{
+        spark_cast_timestamp_to_date, spark_date_trunc, spark_from_unixtime, spark_last_day,
+        spark_months_between, spark_next_day, spark_unix_micros, spark_unix_millis,
+        spark_unix_seconds,
+    };
+
+    #[test]
+    fn test_cast_timestamp_to_date() -> Result<()> {
+        // 2024-01-01 15:59:59.999999 UTC and 2024-01-01 16:00:00 UTC
+        let before_midnight = 1704124799999999;
+        let after_midnight = 1704124800000000;
+        let timestamps: ArrayRef = Arc::new(TimestampMicrosecondArray::from(vec![
+            Some(before_midnight),
+            Some(after_midnight),
+            Some(-1),
+            None,
+        ]));
+        let date_2024_01_01 = 19723;
+
+        // utc
+        let dates = spark_cast_timestamp_to_date(&[
+            ColumnarValue::Array(timestamps.clone()),
+            ColumnarValue::Scalar(ScalarValue::from("UTC")),
+        ])?
+        .into_array(4)?;
+        assert_eq!(
+            as_date32_array(&dates)?,
+            &Date32Array::from(vec![
+                Some(date_2024_01_01),
+                Some(date_2024_01_01),
+                Some(-1),
+                None
+            ]),
+        );
+
+        // midnight in Asia/Shanghai (UTC+8) is 16:00 UTC
+        let dates = spark_cast_timestamp_to_date(&[
+            ColumnarValue::Array(timestamps.clone()),
+            ColumnarValue::Scalar(ScalarValue::from("Asia/Shanghai")),
+        ])?
+        .into_array(4)?;
+        assert_eq!(
+            as_date32_array(&dates)?,
+            &Date32Array::from(vec![
+                Some(date_2024_01_01),
+                Some(date_2024_01_01 + 1),
+                Some(0),
+                None
+            ]),
+        );
+
+        // negative offset
+        let dates = spark_cast_timestamp_to_date(&[
+            ColumnarValue::Array(timestamps),
+            ColumnarValue::Scalar(ScalarValue::from("America/Los_Angeles")),
+        ])?
+        .into_array(4)?;
+        assert_eq!(
+            as_date32_array(&dates)?,
+            &Date32Array::from(vec![
+                Some(date_2024_01_01),
+                Some(date_2024_01_01),
+                Some(-1),
+                None
+            ]),
+        );
+
+        // scalar
+        let date = spark_cast_timestamp_to_date(&[
+            ColumnarValue::Scalar(ScalarValue::TimestampMicrosecond(
+                Some(after_midnight),
+                None,
+            )),
+            ColumnarValue::Scalar(ScalarValue::from("+08:00")),
+        ])?;
+        match date {
+            ColumnarValue::Scalar(ScalarValue::Date32(Some(date))) => {
+                assert_eq!(date, date_2024_01_01 + 1)
+            }
+            other => panic!("unexpected result: {other:?}"),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_unix_seconds_millis_micros() -> Result<()> {
+        let timestamps: ArrayRef = Arc::new(TimestampMicrosecondArray::from(vec![
+            Some(0),
+            Some(1),
+            Some(1704124799999999),
+            Some(-1),
+            Some(-1_000_000),
+            Some(-1_500_001),
+            None,
+        ]));
+
+        let seconds = spark_unix_seconds(&[ColumnarValue::Array(timestamps.clone())])?;
+        assert_eq!(
+            as_int64_array(&seconds.into_array(7)?)?,
+            &Int64Array::from(vec![
+                Some(0),
+                Some(0),
+                Some(1704124799),
+                Some(-1),
+                Some(-1),
+                Some(-2),
+                None
+            ]),
+        );
+
+        let millis = spark_unix_millis(&[ColumnarValue::Array(timestamps.clone())])?;
+        assert_eq!(
+            as_int64_array(&millis.into_array(7)?)?,
+            &Int64Array::from(vec![
+                Some(0),
+                Some(0),
+                Some(1704124799999),
+                Some(-1),
+                Some(-1000),
+                Some(-1501),
+                None
+            ]),
+        );
+
+        let micros = spark_unix_micros(&[ColumnarValue::Array(timestamps)])?;
+        assert_eq!(
+            as_int64_array(&micros.into_array(7)?)?,
+            &Int64Array::from(vec![
+                Some(0),
+                Some(1),
+                Some(1704124799999999),
+                Some(-1),
+                Some(-1_000_000),
+                Some(-1_500_001),
+                None
+            ]),
+        );
+
+        // scalar
+        let seconds = spark_unix_seconds(&[ColumnarValue::Scalar(
+            ScalarValue::TimestampMicrosecond(Some(-1), Some("UTC".into())),
+        )])?;
+        match seconds {
+            ColumnarValue::Scalar(ScalarValue::Int64(Some(seconds))) => assert_eq!(seconds, -1),
+            other => panic!("unexpected result: {other:?}"),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_from_unixtime() -> Result<()> {
+        let seconds: ArrayRef = Arc::new(Int64Array::from(vec![
+            Some(0),
+            Some(1704124800),
+            Some(-1),
+            None,
+        ]));
+
+        // default pattern in default timezone (UTC)
+        let formatted = spark_from_unixtime(&[ColumnarValue::Array(seconds.clone())])?;
+        assert_eq!(
+            as_string_array(&formatted.into_array(4)?)?,
+            &StringArray::from(vec![
+                Some("1970-01-01 00:00:00"),
+                Some("2024-01-01 16:00:00"),
+                Some("1969-12-31 23:59:59"),
+                None,
+            ]),
+        );
+
+        // custom pattern with explicit timezone
+        let formatted = spark_from_unixtime(&[
+            ColumnarValue::Array(seconds.clone()),
+            ColumnarValue::Scalar(ScalarValue::from("yyyy/MM/dd'T'HH:mm:ss 'o''clock'")),
+            ColumnarValue::Scalar(ScalarValue::from("Asia/Shanghai")),
+        ])?;
+        assert_eq!(
+            as_string_array(&formatted.into_array(4)?)?,
+            &StringArray::from(vec![
+                Some("1970/01/01T08:00:00 o'clock"),
+                Some("2024/01/02T00:00:00 o'clock"),
+                Some("1970/01/01T07:59:59 o'clock"),
+                None,
+            ]),
+        );
+
+        // around dst start in America/Los_Angeles
+        let formatted = spark_from_unixtime(&[
+            ColumnarValue::Array(Arc::new(Int64Array::from(vec![1710064799, 1710064800]))),
+            ColumnarValue::Scalar(ScalarValue::from("yyyy-MM-dd HH:mm:ss")),
+            ColumnarValue::Scalar(ScalarValue::from("America/Los_Angeles")),
+        ])?;
+        assert_eq!(
+            as_string_array(&formatted.into_array(2)?)?,
+            &StringArray::from(vec!["2024-03-10 01:59:59", "2024-03-10 03:00:00"]),
+        );
+
+        // null pattern and unsupported pattern
+        let formatted = spark_from_unixtime(&[
+            ColumnarValue::Array(seconds.clone()),
+            ColumnarValue::Scalar(ScalarValue::Utf8(None)),
+        ])?;
+        assert_eq!(formatted.into_array(4)?.null_count(), 4);
+        assert!(spark_from_unixtime(&[
+            ColumnarValue::Array(seconds),
+            ColumnarValue::Scalar(ScalarValue::from("yyyy-QQ")),
+        ])
+        .is_err());
+
+        // scalar
+        let formatted =
+            spark_from_unixtime(&[ColumnarValue::Scalar(ScalarValue::Int64(Some(1704124800)))])?;
+        match formatted {
+            ColumnarValue::Scalar(ScalarValue::Utf8(Some(formatted))) => {
+                assert_eq!(formatted, "2024-01-01 16:00:00")
+            }
+            other => panic!("unexpected result: {other:?}"),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_date_trunc() -> Result<()> {
+        let date_trunc = |unit: &str, timestamp: i64, tz: &str| -> Result<Option<i64>> {
+            let truncated = spark_date_trunc(&[
+                ColumnarValue::Scalar(ScalarValue::from(unit)),
+                ColumnarValue::Array(Arc::new(TimestampMicrosecondArray::from(vec![timestamp]))),
+                ColumnarValue::Scalar(ScalarValue::from(tz)),
+            ])?
+            .into_array(1)?;
+            let truncated = as_timestamp_microsecond_array(&truncated)?;
+            Ok(truncated.is_valid(0).then(|| truncated.value(0)))
+        };
+
+        // 2023-01-01 12:34:56.789 UTC, a sunday
+        let ts = 1672576496789000;
+        let ts_2023_01_01 = 1672531200000000;
+        assert_eq!(date_trunc("YEAR", ts, "UTC")?, Some(ts_2023_01_01));
+        assert_eq!(date_trunc("quarter", ts, "UTC")?, Some(ts_2023_01_01));
+        assert_eq!(date_trunc("MM", ts, "UTC")?, Some(ts_2023_01_01));
+        assert_eq!(date_trunc("WEEK", ts, "UTC")?, Some(1672012800000000)); // 2022-12-26
+        assert_eq!(date_trunc("DAY", ts, "UTC")?, Some(ts_2023_01_01));
+        assert_eq!(date_trunc("HOUR", ts, "UTC")?, Some(1672574400000000));
+        assert_eq!(date_trunc("MINUTE", ts, "UTC")?, Some(1672576440000000));
+        assert_eq!(date_trunc("SECOND", ts, "UTC")?, Some(1672576496000000));
+        assert_eq!(date_trunc("MILLISECOND", ts, "UTC")?, Some(ts));
+        assert_eq!(date_trunc("DECADE", ts, "UTC")?, None);
+
+        // 2023-12-31 20:00:00 UTC, which is 2024-01-01 04:00:00 (monday) in
+        // Asia/Shanghai
+        let ts = 1704052800000000;
+        assert_eq!(date_trunc("MONTH", ts, "UTC")?, Some(1701388800000000)); // 2023-12-01
+        assert_eq!(date_trunc("WEEK", ts, "UTC")?, Some(1703462400000000)); // 2023-12-25
+        assert_eq!(date_trunc("QUARTER", ts, "UTC")?, Some(1696118400000000)); // 2023-10-01
+        let ts_2024_01_01_shanghai = 1704038400000000;
+        assert_eq!(
+            date_trunc("YEAR", ts, "Asia/Shanghai")?,
+            Some(ts_2024_01_01_shanghai)
+        );
+        assert_eq!(
+            date_trunc("MONTH", ts, "Asia/Shanghai")?,
+            Some(ts_2024_01_01_shanghai)
+        );
+        assert_eq!(
+            date_trunc("WEEK", ts, "Asia/Shanghai")?,
+            Some(ts_2024_01_01_shanghai)
+        );
+
+        // dates
+        let date_2023_01_01 = 19358;
+        let dates = spark_date_trunc(&[
+            ColumnarValue::Scalar(ScalarValue::from("WEEK")),
+            ColumnarValue::Array(Arc::new(Date32Array::from(vec![
+                Some(date_2023_01_01),
+                Some(-1),
+                None,
+            ]))),
+        ])?
+        .into_array(3)?;
+        assert_eq!(
+            as_date32_array(&dates)?,
+            &Date32Array::from(vec![Some(date_2023_01_01 - 6), Some(-3), None]),
+        );
+        let dates = spark_date_trunc(&[
+            ColumnarValue::Scalar(ScalarValue::from("DAY")),
+            ColumnarValue::Scalar(ScalarValue::Date32(Some(date_2023_01_01))),
+        ])?;
+        match dates {
+            ColumnarValue::Scalar(ScalarValue::Date32(None)) => {}
+            other => panic!("unexpected result: {other:?}"),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_months_between() -> Result<()> {
+        let months_between = |end: i64, start: i64, round_off: bool| -> Result<Option<f64>> {
+            let r = spark_months_between(&[
+                ColumnarValue::Array(Arc::new(TimestampMicrosecondArray::from(vec![end]))),
+                ColumnarValue::Scalar(ScalarValue::TimestampMicrosecond(Some(start), None)),
+                ColumnarValue::Scalar(ScalarValue::Boolean(Some(round_off))),
+            ])?
+            .into_array(1)?;
+            let r = as_float64_array(&r)?;
+            Ok(r.is_valid(0).then(|| r.value(0)))
+        };
+        let ts = |s: &str| -> i64 {
+            chrono::NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S")
+                .unwrap()
+                .and_utc()
+                .timestamp_micros()
+        };
+
+        // same day of month, time is ignored
+        assert_eq!(
+            months_between(ts("2024-03-15 10:00:00"), ts("2024-01-15 23:00:00"), true)?,
+            Some(2.0)
+        );
+        assert_eq!(
+            months_between(ts("2023-01-15 00:00:00"), ts("2024-03-15 00:00:00"), true)?,
+            Some(-14.0)
+        );
+
+        // both on the last day of month
+        assert_eq!(
+            months_between(ts("2024-02-29 12:00:00"), ts("2024-01-31 00:00:00"), true)?,
+            Some(1.0)
+        );
+        assert_eq!(
+            months_between(ts("2024-04-30 00:00:00"), ts("2024-03-31 08:00:00"), true)?,
+            Some(1.0)
+        );
+
+        // fractional result
+        assert_eq!(
+            months_between(ts("1997-02-28 10:30:00"), ts("1996-10-30 00:00:00"), true)?,
+            Some(3.94959677)
+        );
+        assert_eq!(
+            months_between(ts("1997-02-28 10:30:00"), ts("1996-10-30 00:00:00"), false)?,
+            Some(3.9495967741935485)
+        );
+        assert_eq!(
+            months_between(ts("2024-02-28 00:00:00"), ts("2024-02-29 00:00:00"), true)?,
+            Some(-0.03225806)
+        );
+
+        // dates and nulls
+        let r = spark_months_between(&[
+            ColumnarValue::Array(Arc::new(Date32Array::from(vec![
+                Some(19813), // 2024-03-31
+                Some(19808), // 2024-03-26
+                None,
+            ]))),
+            ColumnarValue::Scalar(ScalarValue::Date32(Some(19782))), // 2024-02-29
+        ])?
+        .into_array(3)?;
+        assert_eq!(
+            as_float64_array(&r)?.iter().collect::<Vec<_>>(),
+            vec![Some(1.0), Some(0.90322581), None]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_last_day() -> Result<()> {
+        let r = spark_last_day(&[ColumnarValue::Array(Arc::new(Date32Array::from(vec![
+            Some(19753), // 2024-01-31
+            Some(19769), // 2024-02-16
+            Some(19405), // 2023-02-17
+            Some(19706), // 2023-12-15
+            None,
+        ])))])?
+        .into_array(5)?;
+        assert_eq!(
+            as_date32_array(&r)?.iter().collect::<Vec<_>>(),
+            vec![
+                Some(19753), // 2024-01-31
+                Some(19782), // 2024-02-29
+                Some(19416), // 2023-02-28
+                Some(19722), // 2023-12-31
+                None,
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_next_day() -> Result<()> {
+        // 2024-01-01 is a monday
+        let dates = ColumnarValue::Array(Arc::new(Date32Array::from(vec![
+            Some(19723), // 2024-01-01
+            Some(19726), // 2024-01-04
+            None,
+        ])));
+        let next_day = |day_of_week: &str| -> Result<Vec<Option<i32>>> {
+            let r = spark_next_day(&[
+                dates.clone(),
+                ColumnarValue::Scalar(ScalarValue::from(day_of_week)),
+            ])?
+            .into_array(3)?;
+            Ok(as_date32_array(&r)?.iter().collect())
+        };
+        assert_eq!(next_day("Mon")?, vec![Some(19730), Some(19730), None]);
+        assert_eq!(next_day("tuesday")?, vec![Some(19724), Some(19731), None]);
+        assert_eq!(next_day("TH")?, vec![Some(19726), Some(19733), None]);
+        assert_eq!(next_day("sun")?, vec![Some(19729), Some(19729), None]);
+        assert_eq!(next_day("xyz")?, vec![None, None, None]);
+
+        // day of week column
+        let r = spark_next_day(&[
+            ColumnarValue::Scalar(ScalarValue::Date32(Some(19723))),
+            ColumnarValue::Array(Arc::new(StringArray::from(vec![
+                Some("Fri"),
+                None,
+                Some("Monday"),
+            ]))),
+        ])?
+        .into_array(3)?;
+        assert_eq!(
+            as_date32_array(&r)?.iter().collect::<Vec<_>>(),
+            vec![Some(19727), None, Some(19730)]
+        );
+        Ok(())
+    }
+}
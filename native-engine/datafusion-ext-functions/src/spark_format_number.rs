@@ -0,0 +1,300 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+
+use arrow::{array::*, datatypes::*};
+use datafusion::{
+    common::{Result, ScalarValue},
+    physical_plan::ColumnarValue,
+};
+use datafusion_ext_commons::df_execution_err;
+
+/// spark's format_number(x, d): formats x like '#,###,###.##' with d decimal
+/// places. same as java's DecimalFormat used by spark, rounding is half-even
+/// and negative d produces null.
+pub fn spark_format_number(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    let d = match &args[1] {
+        ColumnarValue::Scalar(ScalarValue::Int32(Some(d))) if *d >= 0 => *d as usize,
+        ColumnarValue::Scalar(ScalarValue::Int32(_)) => {
+            return Ok(match &args[0] {
+                ColumnarValue::Array(array) => {
+                    ColumnarValue::Array(new_null_array(&DataType::Utf8, array.len()))
+                }
+                ColumnarValue::Scalar(_) => ColumnarValue::Scalar(ScalarValue::Utf8(None)),
+            });
+        }
+        _ => return df_execution_err!("format_number: decimal places must be a literal int"),
+    };
+
+    let format_array = |array: &ArrayRef| -> Result<StringArray> {
+        macro_rules! format_integers {
+            ($arrowty:ty) => {{
+                array
+                    .as_primitive::<$arrowty>()
+                    .iter()
+                    .map(|v| v.map(|v| format_integer(v as i128, d)))
+                    .collect()
+            }};
+        }
+        Ok(match array.data_type() {
+            DataType::Int8 => format_integers!(Int8Type),
+            DataType::Int16 => format_integers!(Int16Type),
+            DataType::Int32 => format_integers!(Int32Type),
+            DataType::Int64 => format_integers!(Int64Type),
+            DataType::Float32 => array
+                .as_primitive::<Float32Type>()
+                .iter()
+                .map(|v| v.map(|v| format_float(v as f64, d)))
+                .collect(),
+            DataType::Float64 => array
+                .as_primitive::<Float64Type>()
+                .iter()
+                .map(|v| v.map(|v| format_float(v, d)))
+                .collect(),
+            &DataType::Decimal128(_, scale) => array
+                .as_primitive::<Decimal128Type>()
+                .iter()
+                .map(|v| v.map(|v| format_decimal(v, scale, d)))
+                .collect(),
+            other => return df_execution_err!("format_number: unsupported type: {other}"),
+        })
+    };
+    Ok(match &args[0] {
+        ColumnarValue::Array(array) => ColumnarValue::Array(Arc::new(format_array(array)?)),
+        ColumnarValue::Scalar(scalar) => {
+            let formatted = format_array(&scalar.to_array()?)?;
+            ColumnarValue::Scalar(ScalarValue::try_from_array(&formatted, 0)?)
+        }
+    })
+}
+
+fn format_integer(v: i128, d: usize) -> String {
+    group_thousands(v < 0, &v.unsigned_abs().to_string(), d)
+}
+
+fn format_float(v: f64, d: usize) -> String {
+    if v.is_nan() {
+        return "NaN".to_string();
+    }
+    if v.is_infinite() {
+        return if v < 0.0 { "-∞" } else { "∞" }.to_string();
+    }
+    // rust formats the exact binary value with half-even rounding, which is
+    // the same as DecimalFormat. note that -0.0 keeps its sign like java
+    let digits = format!("{:.*}", d, v.abs()).replace('.', "");
+    group_thousands(v.is_sign_negative(), &digits, d)
+}
+
+fn format_decimal(unscaled: i128, scale: i8, d: usize) -> String {
+    let abs = unscaled.unsigned_abs();
+    let scale = scale.max(0) as usize;
+    let digits = if scale > d {
+        let divisor = 10u128.pow((scale - d) as u32);
+        let (quotient, remainder) = (abs / divisor, abs % divisor);
+        let half = divisor / 2;
+        if remainder > half || (remainder == half && quotient % 2 == 1) {
+            (quotient + 1).to_string()
+        } else {
+            quotient.to_string()
+        }
+    } else {
+        format!("{abs}{}", "0".repeat(d - scale))
+    };
+    group_thousands(unscaled < 0, &digits, d)
+}
+
+/// formats digits with the last d digits as the fractional part, inserting
+/// commas every three integer digits
+fn group_thousands(negative: bool, digits: &str, d: usize) -> String {
+    let digits = format!("{digits:0>width$}", width = d + 1);
+    let (int_part, frac_part) = digits.split_at(digits.len() - d);
+
+    let mut formatted = String::with_capacity(digits.len() + digits.len() / 3 + 2);
+    if negative {
+        formatted.push('-');
+    }
+    for (i, c) in int_part.chars().enumerate() {
+        if i > 0 && (int_part.len() - i) % 3 == 0 {
+            formatted.push(',');
+        }
+        formatted.push(c);
+    }
+    if d > 0 {
+        formatted.push('.');
+        formatted.push_str(frac_part);
+    }
+    formatted
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use arrow::array::*;
+    use datafusion::{
+        common::{cast::as_string_array, Result, ScalarValue},
+        physical_plan::ColumnarValue,
+    };
+
+    use crate::spark_format_number::spark_format_number;
+
+    fn format_number(input: ArrayRef, d: Option<i32>) -> Result<Vec<Option<String>>> {
+        let num_rows = input.len();
+        let r = spark_format_number(&[
+            ColumnarValue::Array(input),
+            ColumnarValue::Scalar(ScalarValue::Int32(d)),
+        ])?;
+        Ok(as_string_array(&r.into_array(num_rows)?)?
+            .iter()
+            .map(|v| v.map(|v| v.to_string()))
+            .collect())
+    }
+
+    fn some(s: &str) -> Option<String> {
+        Some(s.to_string())
+    }
+
+    #[test]
+    fn test_format_number_integers() -> Result<()> {
+        let input: ArrayRef = Arc::new(Int64Array::from(vec![
+            Some(0),
+            Some(999),
+            Some(1000),
+            Some(-1234567),
+            Some(i64::MIN),
+            None,
+        ]));
+        assert_eq!(
+            format_number(input.clone(), Some(2))?,
+            vec![
+                some("0.00"),
+                some("999.00"),
+                some("1,000.00"),
+                some("-1,234,567.00"),
+                some("-9,223,372,036,854,775,808.00"),
+                None,
+            ]
+        );
+        assert_eq!(
+            format_number(input.clone(), Some(0))?,
+            vec![
+                some("0"),
+                some("999"),
+                some("1,000"),
+                some("-1,234,567"),
+                some("-9,223,372,036,854,775,808"),
+                None,
+            ]
+        );
+
+        // negative or null d produces null
+        assert_eq!(format_number(input.clone(), Some(-1))?, vec![None; 6]);
+        assert_eq!(format_number(input, None)?, vec![None; 6]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_format_number_decimals() -> Result<()> {
+        let input: ArrayRef = Arc::new(
+            Decimal128Array::from(vec![
+                Some(123456785),
+                Some(123456795),
+                Some(-5),
+                Some(99999999),
+                None,
+            ])
+            .with_precision_and_scale(10, 4)?,
+        );
+        assert_eq!(
+            format_number(input.clone(), Some(3))?,
+            vec![
+                some("12,345.678"),
+                some("12,345.680"),
+                some("-0.000"),
+                some("10,000.000"),
+                None,
+            ]
+        );
+        assert_eq!(
+            format_number(input.clone(), Some(0))?,
+            vec![
+                some("12,346"),
+                some("12,346"),
+                some("-0"),
+                some("10,000"),
+                None
+            ]
+        );
+        assert_eq!(
+            format_number(input, Some(6))?,
+            vec![
+                some("12,345.678500"),
+                some("12,345.679500"),
+                some("-0.000500"),
+                some("9,999.999900"),
+                None,
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_format_number_floats() -> Result<()> {
+        let input: ArrayRef = Arc::new(Float64Array::from(vec![
+            Some(2.5),
+            Some(3.5),
+            Some(1234.125),
+            Some(-0.001),
+            Some(f64::NAN),
+            Some(f64::NEG_INFINITY),
+            None,
+        ]));
+        assert_eq!(
+            format_number(input.clone(), Some(0))?,
+            vec![
+                some("2"),
+                some("4"),
+                some("1,234"),
+                some("-0"),
+                some("NaN"),
+                some("-∞"),
+                None,
+            ]
+        );
+        assert_eq!(
+            format_number(input, Some(2))?,
+            vec![
+                some("2.50"),
+                some("3.50"),
+                some("1,234.12"),
+                some("-0.00"),
+                some("NaN"),
+                some("-∞"),
+                None,
+            ]
+        );
+
+        // scalar input
+        let r = spark_format_number(&[
+            ColumnarValue::Scalar(ScalarValue::Int32(Some(1234567))),
+            ColumnarValue::Scalar(ScalarValue::Int32(Some(1))),
+        ])?;
+        match r {
+            ColumnarValue::Scalar(ScalarValue::Utf8(Some(s))) => assert_eq!(s, "1,234,567.0"),
+            other => panic!("unexpected result: {other:?}"),
+        }
+        Ok(())
+    }
+}
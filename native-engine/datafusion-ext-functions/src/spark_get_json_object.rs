@@ -352,6 +352,23 @@ impl HiveGetJsonObjectMatcher {
             }
             Some('[') => {
                 chars.next();
+
+                // quoted child name like $['aaa'], which may contain '.' or '['
+                if chars.peek().cloned() == Some('\'') {
+                    chars.next();
+                    let mut child_name = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('\'') => break,
+                            Some(c) => child_name.push(c),
+                            None => return Err(HiveGetJsonObjectError::InvalidJsonPath),
+                        }
+                    }
+                    if child_name.is_empty() || chars.next() != Some(']') {
+                        return Err(HiveGetJsonObjectError::InvalidJsonPath);
+                    }
+                    return Ok(Some(Self::Child(child_name)));
+                }
                 let mut index_str = String::new();
                 loop {
                     match chars.peek() {
@@ -504,7 +521,8 @@ mod test {
     use datafusion::{common::ScalarValue, logical_expr::ColumnarValue};
 
     use crate::spark_get_json_object::{
-        spark_get_parsed_json_object, spark_parse_json, HiveGetJsonObjectEvaluator,
+        spark_get_json_object, spark_get_parsed_json_object, spark_parse_json,
+        HiveGetJsonObjectEvaluator,
     };
 
     #[test]
@@ -698,4 +716,39 @@ mod test {
         assert_eq!(v, Some(r#"[200,300,400,500,"other"]"#));
         Ok(())
     }
+
+    #[test]
+    fn test_get_json_object() -> Result<(), Box<dyn Error>> {
+        let input = Arc::new(StringArray::from(vec![
+            Some(r#"{"a": {"b": [1, {"c": "x"}], "d.e": true}, "f": null}"#),
+            Some(r#"{"a": {"b": "#), // malformed
+            None,
+        ]));
+        let get = |path: &str| -> Result<Vec<Option<String>>, Box<dyn Error>> {
+            let r = spark_get_json_object(&[
+                ColumnarValue::Array(input.clone()),
+                ColumnarValue::Scalar(ScalarValue::from(path)),
+            ])?
+            .into_array(3)?;
+            Ok(r.as_string::<i32>()
+                .iter()
+                .map(|v| v.map(|s| s.to_string()))
+                .collect())
+        };
+        let some = |s: &str| Some(s.to_string());
+
+        assert_eq!(get("$.a.b")?, vec![some(r#"[1,{"c":"x"}]"#), None, None]);
+        assert_eq!(get("$.a.b[0]")?, vec![some("1"), None, None]);
+        assert_eq!(get("$.a.b[1].c")?, vec![some("x"), None, None]);
+        assert_eq!(
+            get("$['a']['b'][1]")?,
+            vec![some(r#"{"c":"x"}"#), None, None]
+        );
+        assert_eq!(get("$.a['d.e']")?, vec![some("true"), None, None]);
+        assert_eq!(get("$.a.b[2]")?, vec![None, None, None]);
+        assert_eq!(get("$.a.missing")?, vec![None, None, None]);
+        assert_eq!(get("$.f")?, vec![None, None, None]);
+        assert_eq!(get("$.a['b")?, vec![None, None, None]);
+        Ok(())
+    }
 }
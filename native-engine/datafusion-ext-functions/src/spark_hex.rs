@@ -0,0 +1,193 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{fmt::Write, sync::Arc};
+
+use arrow::{array::*, datatypes::*};
+use datafusion::{
+    common::{Result, ScalarValue},
+    physical_plan::ColumnarValue,
+};
+use datafusion_ext_commons::df_execution_err;
+
+/// spark's hex(): uppercase hex of a long (as unsigned 64-bit value, without
+/// leading zeros), or of the bytes of a string/binary.
+pub fn spark_hex(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    map_array(&args[0], |array| {
+        Ok(Arc::new(match array.data_type() {
+            DataType::Int64 => StringArray::from_iter(
+                array
+                    .as_primitive::<Int64Type>()
+                    .iter()
+                    .map(|v| v.map(|v| format!("{:X}", v as u64))),
+            ),
+            DataType::Utf8 => StringArray::from_iter(
+                array
+                    .as_string::<i32>()
+                    .iter()
+                    .map(|v| v.map(|v| hex_bytes(v.as_bytes()))),
+            ),
+            DataType::Binary => {
+                StringArray::from_iter(array.as_binary::<i32>().iter().map(|v| v.map(hex_bytes)))
+            }
+            other => return df_execution_err!("hex: unsupported type: {other}"),
+        }))
+    })
+}
+
+/// spark's unhex(): parses a hex string into binary, returns null if the input
+/// contains non-hex chars. like spark, odd-length input is padded with a
+/// leading zero.
+pub fn spark_unhex(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    map_array(&args[0], |array| {
+        Ok(Arc::new(match array.data_type() {
+            DataType::Utf8 => BinaryArray::from_iter(
+                array
+                    .as_string::<i32>()
+                    .iter()
+                    .map(|v| v.and_then(|v| unhex_bytes(v.as_bytes()))),
+            ),
+            other => return df_execution_err!("unhex: unsupported type: {other}"),
+        }))
+    })
+}
+
+pub(crate) fn map_array(
+    arg: &ColumnarValue,
+    f: impl Fn(&ArrayRef) -> Result<ArrayRef>,
+) -> Result<ColumnarValue> {
+    Ok(match arg {
+        ColumnarValue::Array(array) => ColumnarValue::Array(f(array)?),
+        ColumnarValue::Scalar(scalar) => {
+            let output = f(&scalar.to_array()?)?;
+            ColumnarValue::Scalar(ScalarValue::try_from_array(&output, 0)?)
+        }
+    })
+}
+
+fn hex_bytes(bytes: &[u8]) -> String {
+    let mut hex = String::with_capacity(bytes.len() * 2);
+    for b in bytes {
+        let _ = write!(hex, "{b:02X}");
+    }
+    hex
+}
+
+fn unhex_bytes(hex: &[u8]) -> Option<Vec<u8>> {
+    let digit = |c: u8| (c as char).to_digit(16).map(|d| d as u8);
+    let (head, tail) = hex.split_at(hex.len() % 2);
+    let mut bytes = Vec::with_capacity(hex.len().div_ceil(2));
+    if let Some(&c) = head.first() {
+        bytes.push(digit(c)?);
+    }
+    for pair in tail.chunks_exact(2) {
+        bytes.push((digit(pair[0])? << 4) | digit(pair[1])?);
+    }
+    Some(bytes)
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use arrow::array::*;
+    use datafusion::{
+        common::{
+            cast::{as_binary_array, as_string_array},
+            Result, ScalarValue,
+        },
+        physical_plan::ColumnarValue,
+    };
+
+    use crate::spark_hex::{spark_hex, spark_unhex};
+
+    #[test]
+    fn test_hex() -> Result<()> {
+        let r = spark_hex(&[ColumnarValue::Array(Arc::new(Int64Array::from(vec![
+            Some(17),
+            Some(0),
+            Some(-1),
+            Some(i64::MIN),
+            None,
+        ])))])?;
+        assert_eq!(
+            as_string_array(&r.into_array(5)?)?,
+            &StringArray::from(vec![
+                Some("11"),
+                Some("0"),
+                Some("FFFFFFFFFFFFFFFF"),
+                Some("8000000000000000"),
+                None,
+            ]),
+        );
+
+        let r = spark_hex(&[ColumnarValue::Array(Arc::new(StringArray::from(vec![
+            Some("Spark SQL"),
+            Some(""),
+            None,
+        ])))])?;
+        assert_eq!(
+            as_string_array(&r.into_array(3)?)?,
+            &StringArray::from(vec![Some("537061726B2053514C"), Some(""), None]),
+        );
+
+        let r = spark_hex(&[ColumnarValue::Scalar(ScalarValue::Binary(Some(vec![
+            0x00, 0xab, 0xff,
+        ])))])?;
+        match r {
+            ColumnarValue::Scalar(ScalarValue::Utf8(Some(hex))) => assert_eq!(hex, "00ABFF"),
+            other => panic!("unexpected result: {other:?}"),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_unhex() -> Result<()> {
+        let r = spark_unhex(&[ColumnarValue::Array(Arc::new(StringArray::from(vec![
+            Some("537061726B2053514C"),
+            Some("00abFF"),
+            Some("F"),
+            Some(""),
+            Some("GG"),
+            Some("1 "),
+            Some("é0"),
+            None,
+        ])))])?;
+        assert_eq!(
+            as_binary_array(&r.into_array(8)?)?,
+            &BinaryArray::from(vec![
+                Some(b"Spark SQL".as_ref()),
+                Some(&[0x00, 0xab, 0xff][..]),
+                Some(&[0x0f][..]),
+                Some(&[][..]),
+                None,
+                None,
+                None,
+                None,
+            ]),
+        );
+
+        // unhex(hex(x)) == x for binary
+        let input = ColumnarValue::Array(Arc::new(BinaryArray::from(vec![
+            Some(b"\x00\x01blaze\xfe".as_ref()),
+            None,
+        ])));
+        let r = spark_unhex(&[spark_hex(&[input.clone()])?])?;
+        assert_eq!(
+            as_binary_array(&r.into_array(2)?)?,
+            as_binary_array(&input.into_array(2)?)?,
+        );
+        Ok(())
+    }
+}
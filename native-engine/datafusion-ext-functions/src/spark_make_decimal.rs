@@ -20,7 +20,9 @@ use datafusion::{
     physical_plan::ColumnarValue,
 };
 
-/// implements org.apache.spark.sql.catalyst.expressions.MakeDecimal
+/// implements org.apache.spark.sql.catalyst.expressions.MakeDecimal: builds a
+/// decimal from an unscaled long value, or null if the value exceeds the target
+/// precision (nullOnOverflow).
 pub fn spark_make_decimal(args: &[ColumnarValue]) -> Result<ColumnarValue> {
     let precision = match &args[1] {
         &ColumnarValue::Scalar(ScalarValue::Int32(Some(precision))) => precision as u8,
@@ -36,10 +38,17 @@ pub fn spark_make_decimal(args: &[ColumnarValue]) -> Result<ColumnarValue> {
         precision
     );
 
+    // the same as Decimal.setOrNull()
+    let max_unscaled = i128::pow(10, u32::min(precision as u32, 38));
+    let make_decimal = |v: i64| -> Option<i128> {
+        let v = v as i128;
+        (v > -max_unscaled && v < max_unscaled).then_some(v)
+    };
+
     Ok(match &args[0] {
         ColumnarValue::Scalar(scalar) => match scalar {
             ScalarValue::Int64(Some(v)) => {
-                ColumnarValue::Scalar(ScalarValue::Decimal128(Some(*v as i128), precision, scale))
+                ColumnarValue::Scalar(ScalarValue::Decimal128(make_decimal(*v), precision, scale))
             }
             _ => ColumnarValue::Scalar(ScalarValue::Decimal128(None, precision, scale)),
         },
@@ -48,10 +57,7 @@ pub fn spark_make_decimal(args: &[ColumnarValue]) -> Result<ColumnarValue> {
             let mut output = Decimal128Builder::with_capacity(array.len());
 
             for v in array.into_iter() {
-                match v {
-                    Some(v) => output.append_value(v as i128),
-                    None => output.append_null(),
-                }
+                output.append_option(v.and_then(make_decimal));
             }
             ColumnarValue::Array(Arc::new(
                 output.finish().with_precision_and_scale(precision, scale)?,
@@ -59,6 +65,7 @@ pub fn spark_make_decimal(args: &[ColumnarValue]) -> Result<ColumnarValue> {
         }
     })
 }
+
 #[cfg(test)]
 mod test {
     use std::{error::Error, sync::Arc};
@@ -84,9 +91,9 @@ mod test {
         ])?
         .into_array(5)?;
         let expected = Decimal128Array::from(vec![
-            Some(12342132145623),
+            None, // overflowed
             Some(13245),
-            Some(123213244568923),
+            None, // overflowed
             Some(1234567890),
             None,
         ])
@@ -96,4 +103,34 @@ mod test {
         assert_eq!(&result, &expected);
         Ok(())
     }
+
+    #[test]
+    fn test_make_decimal_scalar() -> Result<(), Box<dyn Error>> {
+        let make_decimal = |v: Option<i64>| {
+            spark_make_decimal(&vec![
+                ColumnarValue::Scalar(ScalarValue::Int64(v)),
+                ColumnarValue::Scalar(ScalarValue::Int32(Some(5))), // precision
+                ColumnarValue::Scalar(ScalarValue::Int32(Some(2))), // scale
+            ])
+        };
+        let expect = |v: Option<i128>| ScalarValue::Decimal128(v, 5, 2);
+
+        assert!(matches!(
+            make_decimal(Some(-99999))?,
+            ColumnarValue::Scalar(v) if v == expect(Some(-99999))
+        ));
+        assert!(matches!(
+            make_decimal(None)?,
+            ColumnarValue::Scalar(v) if v == expect(None)
+        ));
+        assert!(matches!(
+            make_decimal(Some(100000))?,
+            ColumnarValue::Scalar(v) if v == expect(None)
+        ));
+        assert!(matches!(
+            make_decimal(Some(i64::MIN))?,
+            ColumnarValue::Scalar(v) if v == expect(None)
+        ));
+        Ok(())
+    }
 }
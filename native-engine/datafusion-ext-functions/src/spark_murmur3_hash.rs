@@ -54,7 +54,7 @@ mod test {
     use std::{error::Error, sync::Arc};
 
     use arrow::array::{ArrayRef, Int32Array, Int64Array, StringArray};
-    use datafusion::logical_expr::ColumnarValue;
+    use datafusion::{common::ScalarValue, logical_expr::ColumnarValue};
 
     use crate::spark_murmur3_hash::spark_murmur3_hash;
 
@@ -97,4 +97,52 @@ mod test {
         assert_eq!(&result, &expected);
         Ok(())
     }
+
+    #[test]
+    fn test_murmur3_hash_int32_with_nulls() -> Result<(), Box<dyn Error>> {
+        let result = spark_murmur3_hash(&vec![ColumnarValue::Array(Arc::new(Int32Array::from(
+            vec![Some(1), Some(0), Some(-1), None],
+        )))])?
+        .into_array(4)?;
+
+        // null values leave the seed unchanged
+        let expected = Int32Array::from(vec![
+            Some(-559580957),
+            Some(933211791),
+            Some(-1604776387),
+            Some(42),
+        ]);
+        let expected: ArrayRef = Arc::new(expected);
+
+        assert_eq!(&result, &expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_murmur3_hash_multiple_columns() -> Result<(), Box<dyn Error>> {
+        // hash(i, s)
+        let result = spark_murmur3_hash(&vec![
+            ColumnarValue::Array(Arc::new(Int32Array::from(vec![Some(1), Some(1), None]))),
+            ColumnarValue::Array(Arc::new(StringArray::from(vec![
+                Some("a"),
+                None,
+                Some("abc"),
+            ]))),
+        ])?
+        .into_array(3)?;
+
+        let expected = Int32Array::from(vec![Some(-936062819), Some(-559580957), Some(1322437556)]);
+        let expected: ArrayRef = Arc::new(expected);
+        assert_eq!(&result, &expected);
+
+        // hash(1, 'a') with scalar arguments
+        let result = spark_murmur3_hash(&vec![
+            ColumnarValue::Scalar(ScalarValue::Int32(Some(1))),
+            ColumnarValue::Scalar(ScalarValue::from("a")),
+        ])?
+        .into_array(1)?;
+        let expected: ArrayRef = Arc::new(Int32Array::from(vec![-936062819]));
+        assert_eq!(&result, &expected);
+        Ok(())
+    }
 }
@@ -21,47 +21,27 @@ use arrow::{
 };
 use datafusion::{
     common::{Result, ScalarValue},
+    logical_expr::type_coercion::binary::comparison_coercion,
     physical_plan::ColumnarValue,
 };
-use datafusion_ext_commons::{df_execution_err, df_unimplemented_err};
+use datafusion_ext_commons::{cast::cast, df_execution_err, df_unimplemented_err};
 
+/// implements nullif(a, b): null if a equals b, otherwise a. a and b are
+/// compared in their common type, and the result keeps the type of a
 pub fn spark_null_if(args: &[ColumnarValue]) -> Result<ColumnarValue> {
-    // copied from https://docs.rs/datafusion-functions/36.0.0/src/datafusion_functions/core/nullif.rs.html
-    // will use ScalarUDF in the future
     if args.len() != 2 {
         return df_execution_err!(
             "{:?} args were supplied but NULLIF takes exactly two args",
             args.len()
         );
     }
+    let num_rows = num_rows(args);
+    let lhs = args[0].clone().into_array(num_rows.unwrap_or(1))?;
+    let compared = coerce_to_common_type(args, num_rows.unwrap_or(1))?;
 
-    let (lhs, rhs) = (&args[0], &args[1]);
-
-    match (lhs, rhs) {
-        (ColumnarValue::Array(lhs), ColumnarValue::Scalar(rhs)) => {
-            let rhs = rhs.to_scalar()?;
-            let array = nullif(lhs, &eq(&lhs, &rhs)?)?;
-
-            Ok(ColumnarValue::Array(array))
-        }
-        (ColumnarValue::Array(lhs), ColumnarValue::Array(rhs)) => {
-            let array = nullif(lhs, &eq(&lhs, &rhs)?)?;
-            Ok(ColumnarValue::Array(array))
-        }
-        (ColumnarValue::Scalar(lhs), ColumnarValue::Array(rhs)) => {
-            let lhs = lhs.to_array_of_size(rhs.len())?;
-            let array = nullif(&lhs, &eq(&lhs, &rhs)?)?;
-            Ok(ColumnarValue::Array(array))
-        }
-        (ColumnarValue::Scalar(lhs), ColumnarValue::Scalar(rhs)) => {
-            let val: ScalarValue = match lhs.eq(rhs) {
-                true => lhs.data_type().try_into()?,
-                false => lhs.clone(),
-            };
-
-            Ok(ColumnarValue::Scalar(val))
-        }
-    }
+    // null if not comparable (either side is null), a is kept
+    let equals = eq(&compared[0], &compared[1])?;
+    to_columnar_value(num_rows, nullif(&lhs, &equals)?)
 }
 
 /// used to avoid DivideByZero error in divide/modulo
@@ -126,14 +106,97 @@ pub fn spark_null_if_zero(args: &[ColumnarValue]) -> Result<ColumnarValue> {
     })
 }
 
+/// returns number of rows, or None if all arguments are scalars
+pub(crate) fn num_rows(args: &[ColumnarValue]) -> Option<usize> {
+    args.iter().find_map(|arg| match arg {
+        ColumnarValue::Array(array) => Some(array.len()),
+        ColumnarValue::Scalar(_) => None,
+    })
+}
+
+/// converts arguments into arrays of their common type
+pub(crate) fn coerce_to_common_type(
+    args: &[ColumnarValue],
+    num_rows: usize,
+) -> Result<Vec<ArrayRef>> {
+    let arrays = args
+        .iter()
+        .map(|arg| arg.clone().into_array(num_rows))
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut common_type = DataType::Null;
+    for array in &arrays {
+        common_type = match comparison_coercion(&common_type, array.data_type()) {
+            Some(data_type) => data_type,
+            None => {
+                return df_execution_err!(
+                    "cannot find common type of {common_type} and {}",
+                    array.data_type()
+                );
+            }
+        };
+    }
+    arrays
+        .into_iter()
+        .map(|array| match array.data_type() == &common_type {
+            true => Ok(array),
+            false => cast(&array, &common_type),
+        })
+        .collect()
+}
+
+pub(crate) fn to_columnar_value(num_rows: Option<usize>, array: ArrayRef) -> Result<ColumnarValue> {
+    Ok(match num_rows {
+        Some(_) => ColumnarValue::Array(array),
+        None => ColumnarValue::Scalar(ScalarValue::try_from_array(&array, 0)?),
+    })
+}
+
 #[cfg(test)]
 mod test {
     use std::{error::Error, sync::Arc};
 
-    use arrow::array::{ArrayRef, Decimal128Array, Float32Array, Int32Array};
+    use arrow::array::{ArrayRef, Decimal128Array, Float32Array, Int32Array, Int64Array};
     use datafusion::{common::ScalarValue, logical_expr::ColumnarValue};
 
-    use crate::spark_null_if::spark_null_if_zero;
+    use crate::spark_null_if::{spark_null_if, spark_null_if_zero};
+
+    #[test]
+    fn test_null_if() -> Result<(), Box<dyn Error>> {
+        let result = spark_null_if(&vec![
+            ColumnarValue::Array(Arc::new(Int32Array::from(vec![
+                Some(1),
+                Some(2),
+                None,
+                None,
+                Some(5),
+            ]))),
+            ColumnarValue::Array(Arc::new(Int64Array::from(vec![
+                Some(1),
+                Some(3),
+                Some(3),
+                None,
+                None,
+            ]))),
+        ])?
+        .into_array(5)?;
+
+        // result keeps the type of the first argument
+        let expected = Int32Array::from(vec![None, Some(2), None, None, Some(5)]);
+        let expected: ArrayRef = Arc::new(expected);
+        assert_eq!(&result, &expected);
+
+        // both are null
+        let result = spark_null_if(&vec![
+            ColumnarValue::Scalar(ScalarValue::Int32(None)),
+            ColumnarValue::Scalar(ScalarValue::Int32(None)),
+        ])?;
+        assert!(matches!(
+            result,
+            ColumnarValue::Scalar(ScalarValue::Int32(None))
+        ));
+        Ok(())
+    }
 
     #[test]
     fn test_null_if_zero_int() -> Result<(), Box<dyn Error>> {
@@ -0,0 +1,233 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{ops::Range, sync::Arc};
+
+use arrow::{array::*, datatypes::*};
+use datafusion::{
+    common::{cast::as_int32_array, Result, ScalarValue},
+    physical_plan::ColumnarValue,
+};
+use datafusion_ext_commons::df_execution_err;
+use itertools::izip;
+
+/// spark's overlay(input, replace, pos[, len]): replaces len chars (or bytes
+/// for binary) of input starting at 1-based pos with replace. negative or
+/// missing len means the length of replace.
+pub fn spark_overlay(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    let num_rows = args.iter().find_map(|arg| match arg {
+        ColumnarValue::Array(array) => Some(array.len()),
+        ColumnarValue::Scalar(_) => None,
+    });
+    let arrays = args
+        .iter()
+        .map(|arg| arg.clone().into_array(num_rows.unwrap_or(1)))
+        .collect::<Result<Vec<_>>>()?;
+    let positions = as_int32_array(&arrays[2])?;
+    let lens = match arrays.get(3) {
+        Some(lens) => as_int32_array(lens)?.iter().collect(),
+        None => vec![Some(-1); positions.len()],
+    };
+
+    let output: ArrayRef = match (arrays[0].data_type(), arrays[1].data_type()) {
+        (DataType::Utf8, DataType::Utf8) => Arc::new(StringArray::from_iter(
+            izip!(
+                arrays[0].as_string::<i32>(),
+                arrays[1].as_string::<i32>(),
+                positions,
+                lens,
+            )
+            .map(|(input, replace, pos, len)| Some(overlay_string(input?, replace?, pos?, len?))),
+        )),
+        (DataType::Binary, DataType::Binary) => Arc::new(BinaryArray::from_iter(
+            izip!(
+                arrays[0].as_binary::<i32>(),
+                arrays[1].as_binary::<i32>(),
+                positions,
+                lens,
+            )
+            .map(|(input, replace, pos, len)| Some(overlay_binary(input?, replace?, pos?, len?))),
+        )),
+        (input_type, replace_type) => {
+            return df_execution_err!("overlay: unsupported types: {input_type}, {replace_type}");
+        }
+    };
+    Ok(match num_rows {
+        Some(_) => ColumnarValue::Array(output),
+        None => ColumnarValue::Scalar(ScalarValue::try_from_array(&output, 0)?),
+    })
+}
+
+fn overlay_string(input: &str, replace: &str, pos: i32, len: i32) -> String {
+    let boundaries = input
+        .char_indices()
+        .map(|(i, _)| i)
+        .chain(std::iter::once(input.len()))
+        .collect::<Vec<_>>();
+    let (prefix, suffix) = overlay_ranges(boundaries.len() - 1, replace.chars().count(), pos, len);
+    let mut output = String::with_capacity(input.len() + replace.len());
+    output.push_str(&input[boundaries[prefix.start]..boundaries[prefix.end]]);
+    output.push_str(replace);
+    output.push_str(&input[boundaries[suffix.start]..boundaries[suffix.end]]);
+    output
+}
+
+fn overlay_binary(input: &[u8], replace: &[u8], pos: i32, len: i32) -> Vec<u8> {
+    let (prefix, suffix) = overlay_ranges(input.len(), replace.len(), pos, len);
+    [&input[prefix], replace, &input[suffix]].concat()
+}
+
+/// same as spark's Overlay.calculate(), returns the ranges of input kept
+/// before and after replace
+fn overlay_ranges(
+    num_units: usize,
+    num_replace_units: usize,
+    pos: i32,
+    len: i32,
+) -> (Range<usize>, Range<usize>) {
+    let len = if len >= 0 {
+        len
+    } else {
+        num_replace_units as i32
+    };
+    let prefix = substring_sql(num_units, 1, pos as i64 - 1);
+    let suffix = substring_sql(num_units, pos as i64 + len as i64, i32::MAX as i64);
+    (prefix, suffix)
+}
+
+/// same as UTF8String.substringSQL(), pos is 1-based and negative pos counts
+/// from the end
+fn substring_sql(num_units: usize, pos: i64, len: i64) -> Range<usize> {
+    let num_units = num_units as i64;
+    let start = match pos {
+        pos if pos > 0 => pos - 1,
+        pos if pos < 0 => num_units + pos,
+        _ => 0,
+    };
+    let end = (start + len).min(num_units);
+    let start = start.max(0);
+    if start >= end {
+        return 0..0;
+    }
+    start as usize..end as usize
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use arrow::array::*;
+    use datafusion::{
+        common::{
+            cast::{as_binary_array, as_string_array},
+            Result, ScalarValue,
+        },
+        physical_plan::ColumnarValue,
+    };
+
+    use crate::spark_overlay::spark_overlay;
+
+    fn overlay(input: &str, replace: &str, pos: i32, len: Option<i32>) -> Result<String> {
+        let mut args = vec![
+            ColumnarValue::Scalar(ScalarValue::from(input)),
+            ColumnarValue::Scalar(ScalarValue::from(replace)),
+            ColumnarValue::Scalar(ScalarValue::Int32(Some(pos))),
+        ];
+        if let Some(len) = len {
+            args.push(ColumnarValue::Scalar(ScalarValue::Int32(Some(len))));
+        }
+        match spark_overlay(&args)? {
+            ColumnarValue::Scalar(ScalarValue::Utf8(Some(s))) => Ok(s),
+            other => panic!("unexpected result: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_overlay_string() -> Result<()> {
+        // mid-string
+        assert_eq!(overlay("Spark SQL", "_", 6, None)?, "Spark_SQL");
+        assert_eq!(overlay("Spark SQL", "CORE", 7, None)?, "Spark CORE");
+        assert_eq!(overlay("Spark SQL", "ANSI ", 7, Some(0))?, "Spark ANSI SQL");
+        assert_eq!(
+            overlay("Spark SQL", "tructured", 2, Some(4))?,
+            "Structured SQL"
+        );
+        assert_eq!(overlay("Spark SQL", "_", 6, Some(-1))?, "Spark_SQL");
+
+        // pos past the end appends
+        assert_eq!(overlay("Spark", " SQL", 10, None)?, "Spark SQL");
+        assert_eq!(overlay("Spark", "!", 6, Some(0))?, "Spark!");
+
+        // zero and negative pos
+        assert_eq!(overlay("Spark", "_", 0, None)?, "_Spark");
+        assert_eq!(overlay("Spark", "_", -2, None)?, "_k");
+
+        // chars instead of bytes
+        assert_eq!(overlay("数据砖头", "仓库", 3, None)?, "数据仓库");
+        assert_eq!(overlay("数据砖头", "_", 2, Some(0))?, "数_据砖头");
+
+        // nulls
+        let r = spark_overlay(&[
+            ColumnarValue::Array(Arc::new(StringArray::from(vec![
+                Some("abc"),
+                None,
+                Some("abc"),
+                Some("abc"),
+            ]))),
+            ColumnarValue::Scalar(ScalarValue::from("X")),
+            ColumnarValue::Array(Arc::new(Int32Array::from(vec![
+                Some(2),
+                Some(2),
+                None,
+                Some(2),
+            ]))),
+            ColumnarValue::Array(Arc::new(Int32Array::from(vec![
+                Some(1),
+                Some(1),
+                Some(1),
+                None,
+            ]))),
+        ])?;
+        assert_eq!(
+            as_string_array(&r.into_array(4)?)?,
+            &StringArray::from(vec![Some("aXc"), None, None, None]),
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_overlay_binary() -> Result<()> {
+        let r = spark_overlay(&[
+            ColumnarValue::Array(Arc::new(BinaryArray::from(vec![
+                Some(b"Spark SQL".as_ref()),
+                Some(b"Spark SQL".as_ref()),
+                Some("砖".as_bytes()),
+                None,
+            ]))),
+            ColumnarValue::Scalar(ScalarValue::Binary(Some(b"__".to_vec()))),
+            ColumnarValue::Array(Arc::new(Int32Array::from(vec![6, 20, 2, 1]))),
+            ColumnarValue::Array(Arc::new(Int32Array::from(vec![0, -1, 1, 1]))),
+        ])?;
+        assert_eq!(
+            as_binary_array(&r.into_array(4)?)?,
+            &BinaryArray::from(vec![
+                Some(b"Spark__ SQL".as_ref()),
+                Some(b"Spark SQL__".as_ref()),
+                Some(b"\xe7__\x96".as_ref()),
+                None,
+            ]),
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,227 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+
+use arrow::{array::*, datatypes::*};
+use datafusion::{
+    common::{Result, ScalarValue},
+    physical_plan::ColumnarValue,
+};
+use datafusion_ext_commons::df_execution_err;
+
+use crate::spark_hex::map_array;
+
+/// spark's lpad(input, len, pad): left-pads input with pad to len chars (or
+/// bytes for binary). input longer than len is truncated, and input is kept
+/// unchanged if pad is empty.
+pub fn spark_lpad(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    spark_pad(args, true)
+}
+
+/// spark's rpad(input, len, pad): same as lpad() but pads on the right.
+pub fn spark_rpad(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    spark_pad(args, false)
+}
+
+fn spark_pad(args: &[ColumnarValue], is_left: bool) -> Result<ColumnarValue> {
+    let name = if is_left { "lpad" } else { "rpad" };
+    let len = match &args[1] {
+        ColumnarValue::Scalar(ScalarValue::Int32(len)) => *len,
+        _ => df_execution_err!("{name}: len only supports literal int32")?,
+    };
+    let pad = match &args[2] {
+        ColumnarValue::Scalar(pad) => pad.clone(),
+        _ => df_execution_err!("{name}: pad only supports literal")?,
+    };
+
+    map_array(&args[0], |array| {
+        let len = match len {
+            Some(len) if !pad.is_null() => len,
+            _ => return Ok(new_null_array(array.data_type(), array.len())),
+        };
+        Ok(match (array.data_type(), &pad) {
+            (DataType::Utf8, ScalarValue::Utf8(Some(pad))) => Arc::new(StringArray::from_iter(
+                array
+                    .as_string::<i32>()
+                    .iter()
+                    .map(|v| v.map(|v| pad_string(v, len, pad, is_left))),
+            )),
+            (DataType::Binary, ScalarValue::Binary(Some(pad))) => Arc::new(BinaryArray::from_iter(
+                array
+                    .as_binary::<i32>()
+                    .iter()
+                    .map(|v| v.map(|v| pad_binary(v, len, pad, is_left))),
+            )),
+            (data_type, pad) => {
+                let pad_type = pad.data_type();
+                df_execution_err!("{name}: unsupported types: {data_type}, {pad_type}")?
+            }
+        })
+    })
+}
+
+/// same as UTF8String.lpad()/rpad(), len is measured in chars
+fn pad_string(s: &str, len: i32, pad: &str, is_left: bool) -> String {
+    let len = len.max(0) as usize;
+    let num_chars = s.chars().count();
+    if num_chars >= len || pad.is_empty() {
+        return s.chars().take(len).collect();
+    }
+    let padding = pad.chars().cycle().take(len - num_chars);
+    if is_left {
+        padding.chain(s.chars()).collect()
+    } else {
+        s.chars().chain(padding).collect()
+    }
+}
+
+/// same as ByteArray.lpad()/rpad(), len is measured in bytes
+fn pad_binary(b: &[u8], len: i32, pad: &[u8], is_left: bool) -> Vec<u8> {
+    let len = len.max(0) as usize;
+    if b.len() >= len || pad.is_empty() {
+        return b[..len.min(b.len())].to_vec();
+    }
+    let padding = pad.iter().copied().cycle().take(len - b.len());
+    if is_left {
+        padding.chain(b.iter().copied()).collect()
+    } else {
+        b.iter().copied().chain(padding).collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use arrow::array::*;
+    use datafusion::{
+        common::{
+            cast::{as_binary_array, as_string_array},
+            Result, ScalarValue,
+        },
+        physical_plan::ColumnarValue,
+    };
+
+    use crate::spark_pad::{spark_lpad, spark_rpad};
+
+    fn pad_args(input: Vec<Option<&str>>, len: i32, pad: &str) -> Vec<ColumnarValue> {
+        vec![
+            ColumnarValue::Array(Arc::new(StringArray::from(input))),
+            ColumnarValue::Scalar(ScalarValue::Int32(Some(len))),
+            ColumnarValue::Scalar(ScalarValue::from(pad)),
+        ]
+    }
+
+    #[test]
+    fn test_pad_string() -> Result<()> {
+        let input = vec![Some("hi"), Some("hello"), Some("数据"), Some(""), None];
+
+        // padding with a multi-char pad which does not divide evenly
+        let r = spark_lpad(&pad_args(input.clone(), 5, "ab"))?;
+        assert_eq!(
+            as_string_array(&r.into_array(5)?)?,
+            &StringArray::from(vec![
+                Some("abahi"),
+                Some("hello"),
+                Some("aba数据"),
+                Some("ababa"),
+                None,
+            ]),
+        );
+        let r = spark_rpad(&pad_args(input.clone(), 5, "ab"))?;
+        assert_eq!(
+            as_string_array(&r.into_array(5)?)?,
+            &StringArray::from(vec![
+                Some("hiaba"),
+                Some("hello"),
+                Some("数据aba"),
+                Some("ababa"),
+                None,
+            ]),
+        );
+
+        // truncation
+        let r = spark_lpad(&pad_args(input.clone(), 1, "ab"))?;
+        assert_eq!(
+            as_string_array(&r.into_array(5)?)?,
+            &StringArray::from(vec![Some("h"), Some("h"), Some("数"), Some("a"), None]),
+        );
+        let r = spark_rpad(&pad_args(input.clone(), -1, "ab"))?;
+        assert_eq!(
+            as_string_array(&r.into_array(5)?)?,
+            &StringArray::from(vec![Some(""), Some(""), Some(""), Some(""), None]),
+        );
+
+        // empty pad
+        let r = spark_lpad(&pad_args(input.clone(), 4, ""))?;
+        assert_eq!(
+            as_string_array(&r.into_array(5)?)?,
+            &StringArray::from(vec![Some("hi"), Some("hell"), Some("数据"), Some(""), None]),
+        );
+
+        // null len
+        let r = spark_rpad(&[
+            ColumnarValue::Scalar(ScalarValue::from("hi")),
+            ColumnarValue::Scalar(ScalarValue::Int32(None)),
+            ColumnarValue::Scalar(ScalarValue::from("ab")),
+        ])?;
+        match r {
+            ColumnarValue::Scalar(ScalarValue::Utf8(None)) => {}
+            other => panic!("unexpected result: {other:?}"),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_pad_binary() -> Result<()> {
+        let args = |len: i32| {
+            vec![
+                ColumnarValue::Array(Arc::new(BinaryArray::from(vec![
+                    Some(b"\x01\x02".as_ref()),
+                    Some("砖".as_bytes()),
+                    None,
+                ]))),
+                ColumnarValue::Scalar(ScalarValue::Int32(Some(len))),
+                ColumnarValue::Scalar(ScalarValue::Binary(Some(b"\xaa\xbb".to_vec()))),
+            ]
+        };
+        let r = spark_lpad(&args(5))?;
+        assert_eq!(
+            as_binary_array(&r.into_array(3)?)?,
+            &BinaryArray::from(vec![
+                Some(b"\xaa\xbb\xaa\x01\x02".as_ref()),
+                Some(b"\xaa\xbb\xe7\xa0\x96".as_ref()),
+                None,
+            ]),
+        );
+        let r = spark_rpad(&args(5))?;
+        assert_eq!(
+            as_binary_array(&r.into_array(3)?)?,
+            &BinaryArray::from(vec![
+                Some(b"\x01\x02\xaa\xbb\xaa".as_ref()),
+                Some(b"\xe7\xa0\x96\xaa\xbb".as_ref()),
+                None,
+            ]),
+        );
+
+        // truncated in bytes
+        let r = spark_lpad(&args(1))?;
+        assert_eq!(
+            as_binary_array(&r.into_array(3)?)?,
+            &BinaryArray::from(vec![Some(b"\x01".as_ref()), Some(b"\xe7".as_ref()), None]),
+        );
+        Ok(())
+    }
+}
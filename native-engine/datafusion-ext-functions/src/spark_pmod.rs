@@ -0,0 +1,175 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+
+use arrow::{array::*, datatypes::*};
+use datafusion::{
+    common::{Result, ScalarValue},
+    physical_plan::ColumnarValue,
+};
+use datafusion_ext_commons::{df_execution_err, spark_hash::pmod_native};
+
+/// implements org.apache.spark.sql.catalyst.expressions.Pmod
+///
+/// both operands are casted to the result type before calling.
+/// args[2] (optional, defaults to false) indicates ansi mode: zero divisors
+/// produce an error instead of null.
+pub fn spark_pmod(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    let ansi = match args.get(2) {
+        Some(&ColumnarValue::Scalar(ScalarValue::Boolean(Some(ansi)))) => ansi,
+        None => false,
+        _ => unreachable!("pmod.ansi is not boolean value"),
+    };
+    let num_rows = args[..2].iter().find_map(|arg| match arg {
+        ColumnarValue::Array(array) => Some(array.len()),
+        ColumnarValue::Scalar(_) => None,
+    });
+    let lhs = args[0].clone().into_array(num_rows.unwrap_or(1))?;
+    let rhs = args[1].clone().into_array(num_rows.unwrap_or(1))?;
+    if lhs.data_type() != rhs.data_type() {
+        return df_execution_err!(
+            "pmod: operands must have the same type, got {} and {}",
+            lhs.data_type(),
+            rhs.data_type(),
+        );
+    }
+
+    let output = match lhs.data_type() {
+        DataType::Int8 => pmod_array::<Int8Type>(&lhs, &rhs, ansi)?,
+        DataType::Int16 => pmod_array::<Int16Type>(&lhs, &rhs, ansi)?,
+        DataType::Int32 => pmod_array::<Int32Type>(&lhs, &rhs, ansi)?,
+        DataType::Int64 => pmod_array::<Int64Type>(&lhs, &rhs, ansi)?,
+        DataType::Float32 => pmod_array::<Float32Type>(&lhs, &rhs, ansi)?,
+        DataType::Float64 => pmod_array::<Float64Type>(&lhs, &rhs, ansi)?,
+        DataType::Decimal128(..) => pmod_array::<Decimal128Type>(&lhs, &rhs, ansi)?,
+        other => return df_execution_err!("pmod: unsupported type: {other}"),
+    };
+    Ok(match num_rows {
+        Some(_) => ColumnarValue::Array(output),
+        None => ColumnarValue::Scalar(ScalarValue::try_from_array(&output, 0)?),
+    })
+}
+
+fn pmod_array<T: ArrowPrimitiveType>(
+    lhs: &ArrayRef,
+    rhs: &ArrayRef,
+    ansi: bool,
+) -> Result<ArrayRef> {
+    let lhs = lhs.as_primitive::<T>();
+    let rhs = rhs.as_primitive::<T>();
+    let output = lhs
+        .iter()
+        .zip(rhs.iter())
+        .map(|(a, n)| match (a, n) {
+            (Some(_), Some(n)) if n.is_zero() => {
+                if ansi {
+                    return df_execution_err!("Division by zero");
+                }
+                Ok(None)
+            }
+            (Some(a), Some(n)) => Ok(Some(pmod_native(a, n))),
+            _ => Ok(None),
+        })
+        .collect::<Result<PrimitiveArray<T>>>()?;
+
+    // keep precision/scale of decimals
+    Ok(Arc::new(output.with_data_type(lhs.data_type().clone())))
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use arrow::{array::*, datatypes::*};
+    use datafusion::{
+        common::{Result, ScalarValue},
+        physical_plan::ColumnarValue,
+    };
+
+    use crate::spark_pmod::spark_pmod;
+
+    #[test]
+    fn test_pmod_int() -> Result<()> {
+        let lhs = ColumnarValue::Array(Arc::new(Int32Array::from(vec![
+            Some(-7),
+            Some(7),
+            Some(-7),
+            Some(i32::MIN),
+            None,
+            Some(7),
+        ])));
+        let rhs = ColumnarValue::Array(Arc::new(Int32Array::from(vec![
+            Some(3),
+            Some(-3),
+            Some(-3),
+            Some(-1),
+            Some(3),
+            Some(0),
+        ])));
+
+        // non-ansi: zero divisor produces null
+        let r = spark_pmod(&[lhs.clone(), rhs.clone()])?.into_array(6)?;
+        assert_eq!(
+            r.as_primitive::<Int32Type>(),
+            &Int32Array::from(vec![Some(2), Some(1), Some(-1), Some(0), None, None]),
+        );
+
+        // ansi: zero divisor produces an error
+        let ansi = ColumnarValue::Scalar(ScalarValue::Boolean(Some(true)));
+        assert!(spark_pmod(&[lhs, rhs, ansi.clone()]).is_err());
+
+        let r = spark_pmod(&[
+            ColumnarValue::Scalar(ScalarValue::Int64(Some(-10))),
+            ColumnarValue::Scalar(ScalarValue::Int64(Some(4))),
+            ansi,
+        ])?;
+        match r {
+            ColumnarValue::Scalar(ScalarValue::Int64(Some(v))) => assert_eq!(v, 2),
+            other => panic!("unexpected result: {other:?}"),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_pmod_float_and_decimal() -> Result<()> {
+        let r = spark_pmod(&[
+            ColumnarValue::Array(Arc::new(Float64Array::from(vec![-5.5, 5.5, -5.5]))),
+            ColumnarValue::Array(Arc::new(Float64Array::from(vec![2.0, 2.0, 0.0]))),
+        ])?
+        .into_array(3)?;
+        assert_eq!(
+            r.as_primitive::<Float64Type>(),
+            &Float64Array::from(vec![Some(0.5), Some(1.5), None]),
+        );
+
+        let decimals = |values: Vec<Option<i128>>| -> Result<ColumnarValue> {
+            Ok(ColumnarValue::Array(Arc::new(
+                Decimal128Array::from(values).with_precision_and_scale(10, 2)?,
+            )))
+        };
+        let r = spark_pmod(&[
+            decimals(vec![Some(-750), Some(525), Some(-750), None])?,
+            decimals(vec![Some(200), Some(200), Some(0), Some(200)])?,
+        ])?
+        .into_array(4)?;
+        assert_eq!(r.data_type(), &DataType::Decimal128(10, 2));
+        assert_eq!(
+            r.as_primitive::<Decimal128Type>(),
+            &Decimal128Array::from(vec![Some(50), Some(125), None, None])
+                .with_precision_and_scale(10, 2)?,
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,329 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{borrow::Cow, sync::Arc};
+
+use arrow::array::{Array, StringArray};
+use datafusion::{
+    common::{cast::as_string_array, Result, ScalarValue},
+    physical_plan::ColumnarValue,
+};
+use datafusion_ext_commons::df_execution_err;
+use regex::Regex;
+
+use crate::regexp_cache::RegexCache;
+
+/// spark's regexp_extract(str, regexp, idx): extracts the idx-th group of the
+/// first match, or an empty string if the regexp does not match.
+pub fn regexp_extract(args: &[ColumnarValue], cache: &RegexCache) -> Result<ColumnarValue> {
+    let idx = match args.get(2) {
+        Some(ColumnarValue::Scalar(ScalarValue::Int32(Some(idx)))) => *idx,
+        Some(ColumnarValue::Scalar(ScalarValue::Int32(None))) => {
+            return Ok(ColumnarValue::Scalar(ScalarValue::Utf8(None)));
+        }
+        Some(_) => return df_execution_err!("regexp_extract: group index must be a literal int"),
+        None => 1,
+    };
+    if idx < 0 {
+        return df_execution_err!(
+            "regexp_extract: the specified group index cannot be less than zero"
+        );
+    }
+    let idx = idx as usize;
+    let const_regex = get_const_regex(&args[1], cache)?;
+
+    map_strings(&args[..2], |row| {
+        let regex = get_regex(&const_regex, cache, row[1])?;
+        let num_groups = regex.captures_len() - 1;
+        if idx > num_groups {
+            return df_execution_err!(
+                "regexp_extract: regex group count is {num_groups}, \
+                    but the specified group index is {idx}"
+            );
+        }
+        Ok(regex
+            .captures(row[0])
+            .and_then(|captures| captures.get(idx))
+            .map(|matched| matched.as_str().to_string())
+            .unwrap_or_default())
+    })
+}
+
+/// spark's regexp_replace(str, regexp, rep): replaces all matches of regexp
+/// with rep, in which $n refers to the n-th captured group.
+pub fn regexp_replace(args: &[ColumnarValue], cache: &RegexCache) -> Result<ColumnarValue> {
+    let const_regex = get_const_regex(&args[1], cache)?;
+    let const_replacement = match (&const_regex, &args[2]) {
+        (Some(regex), ColumnarValue::Scalar(ScalarValue::Utf8(Some(replacement)))) => Some(
+            translate_java_replacement(replacement, regex.captures_len() - 1)?,
+        ),
+        _ => None,
+    };
+
+    map_strings(&args[..3], |row| {
+        let regex = get_regex(&const_regex, cache, row[1])?;
+        let replacement = match &const_replacement {
+            Some(replacement) => Cow::Borrowed(replacement.as_str()),
+            None => Cow::Owned(translate_java_replacement(
+                row[2],
+                regex.captures_len() - 1,
+            )?),
+        };
+        Ok(regex.replace_all(row[0], &*replacement).into_owned())
+    })
+}
+
+/// translates a java replacement string (see Matcher.appendReplacement()) to
+/// the syntax of the regex crate.
+fn translate_java_replacement(replacement: &str, num_groups: usize) -> Result<String> {
+    let mut translated = String::with_capacity(replacement.len());
+    let mut chars = replacement.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('$') => translated.push_str("$$"),
+                Some(c) => translated.push(c),
+                None => {
+                    return df_execution_err!("regexp_replace: character to be escaped is missing")
+                }
+            },
+            '$' => match chars.next() {
+                Some('{') => {
+                    let name = chars.by_ref().take_while(|c| *c != '}').collect::<String>();
+                    translated.push_str(&format!("${{{name}}}"));
+                }
+                Some(c) if c.is_ascii_digit() => {
+                    // same as java: take as many digits as form a valid group number
+                    let mut group = c as usize - '0' as usize;
+                    if group > num_groups {
+                        return df_execution_err!("regexp_replace: no group {group}");
+                    }
+                    while let Some(digit) = chars.peek().and_then(|c| c.to_digit(10)) {
+                        let next_group = group * 10 + digit as usize;
+                        if next_group > num_groups {
+                            break;
+                        }
+                        group = next_group;
+                        chars.next();
+                    }
+                    translated.push_str(&format!("${{{group}}}"));
+                }
+                _ => return df_execution_err!("regexp_replace: illegal group reference"),
+            },
+            c => translated.push(c),
+        }
+    }
+    Ok(translated)
+}
+
+fn get_const_regex(pattern: &ColumnarValue, cache: &RegexCache) -> Result<Option<Arc<Regex>>> {
+    match pattern {
+        ColumnarValue::Scalar(ScalarValue::Utf8(Some(pattern))) => {
+            Ok(Some(cache.get(pattern, "")?))
+        }
+        _ => Ok(None),
+    }
+}
+
+fn get_regex(
+    const_regex: &Option<Arc<Regex>>,
+    cache: &RegexCache,
+    pattern: &str,
+) -> Result<Arc<Regex>> {
+    match const_regex {
+        Some(regex) => Ok(regex.clone()),
+        None => cache.get(pattern, ""),
+    }
+}
+
+/// evaluates a string function row by row, null in any argument produces null.
+fn map_strings(
+    args: &[ColumnarValue],
+    mut f: impl FnMut(&[&str]) -> Result<String>,
+) -> Result<ColumnarValue> {
+    let num_rows = args.iter().find_map(|arg| match arg {
+        ColumnarValue::Array(array) => Some(array.len()),
+        ColumnarValue::Scalar(_) => None,
+    });
+    let arrays = args
+        .iter()
+        .map(|arg| arg.clone().into_array(num_rows.unwrap_or(1)))
+        .collect::<Result<Vec<_>>>()?;
+    let string_arrays = arrays
+        .iter()
+        .map(|array| as_string_array(array))
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut row = Vec::with_capacity(string_arrays.len());
+    let output = (0..num_rows.unwrap_or(1))
+        .map(|i| {
+            row.clear();
+            for strings in &string_arrays {
+                if strings.is_null(i) {
+                    return Ok(None);
+                }
+                row.push(strings.value(i));
+            }
+            f(&row).map(Some)
+        })
+        .collect::<Result<StringArray>>()?;
+
+    Ok(match num_rows {
+        Some(_) => ColumnarValue::Array(Arc::new(output)),
+        None => ColumnarValue::Scalar(ScalarValue::try_from_array(&output, 0)?),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use arrow::array::StringArray;
+    use datafusion::{
+        common::{cast::as_string_array, Result, ScalarValue},
+        physical_plan::ColumnarValue,
+    };
+
+    use crate::{
+        regexp_cache::RegexCache,
+        spark_regexp::{regexp_extract, regexp_replace},
+    };
+
+    #[test]
+    fn test_regexp_extract() -> Result<()> {
+        let cache = RegexCache::default();
+        let input = ColumnarValue::Array(Arc::new(StringArray::from(vec![
+            Some("100-200"),
+            Some("foo"),
+            Some("300-"),
+            None,
+        ])));
+        let extract = |pattern: &str, idx: i32| -> Result<Vec<Option<String>>> {
+            let r = regexp_extract(
+                &[
+                    input.clone(),
+                    ColumnarValue::Scalar(ScalarValue::from(pattern)),
+                    ColumnarValue::Scalar(ScalarValue::Int32(Some(idx))),
+                ],
+                &cache,
+            )?
+            .into_array(4)?;
+            Ok(as_string_array(&r)?
+                .iter()
+                .map(|s| s.map(|s| s.to_string()))
+                .collect())
+        };
+        let some = |s: &str| Some(s.to_string());
+
+        assert_eq!(
+            extract(r"(\d+)-(\d+)", 1)?,
+            vec![some("100"), some(""), some(""), None]
+        );
+        assert_eq!(
+            extract(r"(\d+)-(\d+)?", 2)?,
+            vec![some("200"), some(""), some(""), None]
+        );
+        assert_eq!(
+            extract(r"(\d+)-(\d+)", 0)?,
+            vec![some("100-200"), some(""), some(""), None]
+        );
+        assert!(extract(r"(\d+)-(\d+)", 3).is_err());
+        assert!(extract(r"(\d+)-(\d+)", -1).is_err());
+        assert!(extract(r"(\d+", 1).is_err());
+
+        // java regexp syntax
+        assert_eq!(
+            extract(r"\Q-\E(\p{Digit}+)", 1)?,
+            vec![some("200"), some(""), some(""), None]
+        );
+
+        // scalar input and default group index
+        let r = regexp_extract(
+            &[
+                ColumnarValue::Scalar(ScalarValue::from("key=value")),
+                ColumnarValue::Scalar(ScalarValue::from("(\\w+)=(\\w+)")),
+            ],
+            &cache,
+        )?;
+        match r {
+            ColumnarValue::Scalar(ScalarValue::Utf8(Some(s))) => assert_eq!(s, "key"),
+            other => panic!("unexpected result: {other:?}"),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_regexp_replace() -> Result<()> {
+        let cache = RegexCache::default();
+        let replace = |input: &str, pattern: &str, replacement: &str| -> Result<Option<String>> {
+            let r = regexp_replace(
+                &[
+                    ColumnarValue::Array(Arc::new(StringArray::from(vec![input]))),
+                    ColumnarValue::Scalar(ScalarValue::from(pattern)),
+                    ColumnarValue::Scalar(ScalarValue::from(replacement)),
+                ],
+                &cache,
+            )?
+            .into_array(1)?;
+            Ok(as_string_array(&r)?
+                .iter()
+                .next()
+                .unwrap()
+                .map(|s| s.to_string()))
+        };
+        let some = |s: &str| Some(s.to_string());
+
+        // global replacement
+        assert_eq!(replace("a1b22c333", r"\d+", "#")?, some("a#b#c#"));
+        assert_eq!(replace("abc", "x", "#")?, some("abc"));
+
+        // backreferences
+        assert_eq!(
+            replace("2024-01-15", r"(\d+)-(\d+)-(\d+)", "$3/$2/$1")?,
+            some("15/01/2024")
+        );
+        assert_eq!(replace("ab", "(a)(b)", "$10$2")?, some("a0b"));
+        assert_eq!(replace("ab", "(?<x>a)", "[${x}]")?, some("[a]b"));
+        assert_eq!(replace("ab", "(a)", r"\$1\\")?, some(r"$1\b"));
+        assert!(replace("ab", "(a)", "$2").is_err());
+        assert!(replace("ab", "(a)", "$x").is_err());
+
+        // empty match inserts replacement between every character
+        assert_eq!(replace("abc", "", "-")?, some("-a-b-c-"));
+
+        // non-constant replacement and nulls
+        let r = regexp_replace(
+            &[
+                ColumnarValue::Array(Arc::new(StringArray::from(vec![
+                    Some("k=v"),
+                    Some("k=v"),
+                    None,
+                ]))),
+                ColumnarValue::Scalar(ScalarValue::from("(\\w)=(\\w)")),
+                ColumnarValue::Array(Arc::new(StringArray::from(vec![
+                    Some("$2=$1"),
+                    None,
+                    Some("$1"),
+                ]))),
+            ],
+            &cache,
+        )?
+        .into_array(3)?;
+        assert_eq!(
+            as_string_array(&r)?.iter().collect::<Vec<_>>(),
+            vec![Some("v=k"), None, None]
+        );
+        Ok(())
+    }
+}
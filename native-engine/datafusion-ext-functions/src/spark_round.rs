@@ -0,0 +1,328 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+
+use arrow::{array::*, datatypes::*};
+use datafusion::{
+    common::{Result, ScalarValue},
+    physical_plan::ColumnarValue,
+};
+use datafusion_ext_commons::df_execution_err;
+
+use crate::spark_check_overflow::{change_precision_with_rounding, RoundingMode};
+
+/// spark's round(x, scale): rounds half up to scale decimal places, negative
+/// scale rounds the integral part.
+///
+/// for decimal input, args[2] and args[3] are the precision and scale of the
+/// output decimal type.
+pub fn spark_round(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    round(args, RoundingMode::HalfUp)
+}
+
+/// spark's bround(x, scale): same as round() but rounds half to even.
+pub fn spark_bround(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    round(args, RoundingMode::HalfEven)
+}
+
+fn round(args: &[ColumnarValue], mode: RoundingMode) -> Result<ColumnarValue> {
+    let scale = match &args[1] {
+        ColumnarValue::Scalar(ScalarValue::Int32(scale)) => *scale,
+        _ => return df_execution_err!("round: scale must be a literal int"),
+    };
+    let output_type = match (args[0].data_type(), args.get(2), args.get(3)) {
+        (
+            DataType::Decimal128(..),
+            Some(ColumnarValue::Scalar(ScalarValue::Int32(Some(precision)))),
+            Some(ColumnarValue::Scalar(ScalarValue::Int32(Some(scale)))),
+        ) => DataType::Decimal128(*precision as u8, *scale as i8),
+        (DataType::Decimal128(..), ..) => {
+            return df_execution_err!("round: output precision and scale are required for decimal");
+        }
+        (data_type, ..) => data_type,
+    };
+
+    let round_array = |array: &ArrayRef| -> Result<ArrayRef> {
+        let Some(scale) = scale else {
+            return Ok(new_null_array(&output_type, array.len()));
+        };
+        macro_rules! round_integers {
+            ($arrowty:ty, $ty:ty) => {{
+                Arc::new(
+                    array
+                        .as_primitive::<$arrowty>()
+                        .unary::<_, $arrowty>(|v| round_integer(v as i128, scale, mode) as $ty),
+                )
+            }};
+        }
+        // floats are widened to double first, like spark does
+        macro_rules! round_floats {
+            ($arrowty:ty, $ty:ty, $to_f64:expr) => {{
+                Arc::new(array.as_primitive::<$arrowty>().unary::<_, $arrowty>(|v| {
+                    if v.is_finite() {
+                        round_float(&format!("{:e}", $to_f64(v)), scale, mode)
+                            .parse::<$ty>()
+                            .unwrap_or(v)
+                    } else {
+                        v
+                    }
+                }))
+            }};
+        }
+        Ok(match (array.data_type(), &output_type) {
+            (DataType::Int8, _) => round_integers!(Int8Type, i8),
+            (DataType::Int16, _) => round_integers!(Int16Type, i16),
+            (DataType::Int32, _) => round_integers!(Int32Type, i32),
+            (DataType::Int64, _) => round_integers!(Int64Type, i64),
+            (DataType::Float32, _) => round_floats!(Float32Type, f32, f64::from),
+            (DataType::Float64, _) => round_floats!(Float64Type, f64, std::convert::identity),
+            (
+                &DataType::Decimal128(precision, decimal_scale),
+                &DataType::Decimal128(to_precision, to_scale),
+            ) => Arc::new(
+                array
+                    .as_primitive::<Decimal128Type>()
+                    .unary_opt::<_, Decimal128Type>(|v| {
+                        let (rounded, rounded_scale) =
+                            round_decimal(v, decimal_scale, scale, mode)?;
+                        change_precision_with_rounding(
+                            rounded,
+                            precision,
+                            rounded_scale,
+                            to_precision,
+                            to_scale,
+                            mode,
+                        )
+                    })
+                    .with_precision_and_scale(to_precision, to_scale)?,
+            ),
+            (other, _) => return df_execution_err!("round: unsupported type: {other}"),
+        })
+    };
+    Ok(match &args[0] {
+        ColumnarValue::Array(array) => ColumnarValue::Array(round_array(array)?),
+        ColumnarValue::Scalar(scalar) => {
+            let rounded = round_array(&scalar.to_array()?)?;
+            ColumnarValue::Scalar(ScalarValue::try_from_array(&rounded, 0)?)
+        }
+    })
+}
+
+/// divides v by 10^num_dropped_digits, rounding the dropped digits
+fn round_unscaled(v: i128, num_dropped_digits: u32, mode: RoundingMode) -> i128 {
+    if num_dropped_digits > 38 {
+        return 0; // all digits are less than half of the divisor
+    }
+    let divisor = 10i128.pow(num_dropped_digits);
+    let (quotient, dropped) = (v / divisor, v % divisor);
+    quotient
+        + mode.round_increment(
+            dropped.cmp(&0),
+            (dropped.unsigned_abs() * 2).cmp(&divisor.unsigned_abs()),
+            quotient % 2 != 0,
+        ) as i128
+}
+
+/// integers are unchanged unless scale is negative, overflowed values wrap
+/// like BigDecimal.toLong()
+fn round_integer(v: i128, scale: i32, mode: RoundingMode) -> i128 {
+    if scale >= 0 {
+        return v;
+    }
+    let num_dropped_digits = scale.unsigned_abs();
+    match round_unscaled(v, num_dropped_digits, mode) {
+        0 => 0,
+        quotient => quotient.wrapping_mul(10i128.pow(num_dropped_digits)),
+    }
+}
+
+/// rounds an unscaled decimal to scale, returns the rounded unscaled value and
+/// its scale (0 if scale is negative)
+fn round_decimal(v: i128, decimal_scale: i8, scale: i32, mode: RoundingMode) -> Option<(i128, i8)> {
+    if scale >= decimal_scale as i32 {
+        return Some((v, decimal_scale));
+    }
+    let num_dropped_digits = (decimal_scale as i32 - scale) as u32;
+    let rounded = round_unscaled(v, num_dropped_digits, mode);
+    if scale >= 0 {
+        return Some((rounded, scale as i8));
+    }
+    match rounded {
+        0 => Some((0, 0)),
+        rounded => Some((
+            rounded.checked_mul(10i128.checked_pow(scale.unsigned_abs())?)?,
+            0,
+        )),
+    }
+}
+
+/// rounds a float in exponent notation (like "1.25e-1"), returning the result
+/// in the same notation. spark rounds the shortest decimal representation of
+/// the float instead of its exact binary value, so round(0.285, 2) is 0.29.
+fn round_float(exp_repr: &str, scale: i32, mode: RoundingMode) -> String {
+    let (mantissa, exp) = exp_repr.split_once('e').unwrap_or((exp_repr, "0"));
+    let exp = exp.parse::<i32>().unwrap_or(0);
+    let digits = mantissa
+        .chars()
+        .filter(|c| c.is_ascii_digit())
+        .collect::<String>();
+    let num_frac_digits = digits.len() as i32 - 1 - exp;
+    if num_frac_digits <= scale {
+        return exp_repr.to_string();
+    }
+    let mut unscaled = digits.parse::<i128>().unwrap_or(0);
+    if mantissa.starts_with('-') {
+        unscaled = -unscaled;
+    }
+    match round_unscaled(unscaled, (num_frac_digits - scale) as u32, mode) {
+        0 => "0".to_string(), // no negative zero in BigDecimal
+        rounded => format!("{rounded}e{}", -scale),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use arrow::{array::*, datatypes::*};
+    use datafusion::{
+        common::{Result, ScalarValue},
+        physical_plan::ColumnarValue,
+    };
+
+    use crate::spark_round::{spark_bround, spark_round};
+
+    fn round_f64(v: f64, scale: i32, half_even: bool) -> Result<f64> {
+        let args = [
+            ColumnarValue::Scalar(ScalarValue::Float64(Some(v))),
+            ColumnarValue::Scalar(ScalarValue::Int32(Some(scale))),
+        ];
+        let r = if half_even {
+            spark_bround(&args)?
+        } else {
+            spark_round(&args)?
+        };
+        match r {
+            ColumnarValue::Scalar(ScalarValue::Float64(Some(v))) => Ok(v),
+            other => panic!("unexpected result: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_bround_vs_round() -> Result<()> {
+        assert_eq!(round_f64(2.5, 0, false)?, 3.0);
+        assert_eq!(round_f64(3.5, 0, false)?, 4.0);
+        assert_eq!(round_f64(2.5, 0, true)?, 2.0);
+        assert_eq!(round_f64(3.5, 0, true)?, 4.0);
+        assert_eq!(round_f64(-2.5, 0, false)?, -3.0);
+        assert_eq!(round_f64(-2.5, 0, true)?, -2.0);
+
+        // rounds the shortest decimal representation
+        assert_eq!(round_f64(0.285, 2, false)?, 0.29);
+        assert_eq!(round_f64(0.125, 2, true)?, 0.12);
+        assert_eq!(round_f64(1.23456, 10, true)?, 1.23456);
+        assert_eq!(round_f64(1e-300, 2, false)?, 0.0);
+
+        // float is rounded as its double value, 0.285f is 0.2849999964237213
+        let r = spark_round(&[
+            ColumnarValue::Scalar(ScalarValue::Float32(Some(0.285))),
+            ColumnarValue::Scalar(ScalarValue::Int32(Some(2))),
+        ])?;
+        assert!(matches!(r, ColumnarValue::Scalar(ScalarValue::Float32(Some(v))) if v == 0.28));
+
+        // negative scale
+        assert_eq!(round_f64(125.0, -1, false)?, 130.0);
+        assert_eq!(round_f64(125.0, -1, true)?, 120.0);
+        assert_eq!(round_f64(1.5e300, -300, true)?, 2e300);
+        assert!(round_f64(f64::NAN, 0, true)?.is_nan());
+
+        // integers and nulls
+        let r = spark_bround(&[
+            ColumnarValue::Array(Arc::new(Int32Array::from(vec![
+                Some(25),
+                Some(35),
+                Some(-25),
+                Some(7),
+                None,
+            ]))),
+            ColumnarValue::Scalar(ScalarValue::Int32(Some(-1))),
+        ])?;
+        assert_eq!(
+            r.into_array(5)?.as_primitive::<Int32Type>(),
+            &Int32Array::from(vec![Some(20), Some(40), Some(-20), Some(10), None]),
+        );
+        let r = spark_round(&[
+            ColumnarValue::Array(Arc::new(Int64Array::from(vec![Some(25), None]))),
+            ColumnarValue::Scalar(ScalarValue::Int32(None)),
+        ])?;
+        assert_eq!(
+            r.into_array(2)?.as_primitive::<Int64Type>(),
+            &Int64Array::from(vec![None, None]),
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_bround_decimal() -> Result<()> {
+        let input: ArrayRef = Arc::new(
+            Decimal128Array::from(vec![Some(250), Some(350), Some(-251), Some(9950), None])
+                .with_precision_and_scale(5, 2)?,
+        );
+        let round =
+            |scale: i32, precision: i32, output_scale: i32, half_even: bool| -> Result<ArrayRef> {
+                let args = [
+                    ColumnarValue::Array(input.clone()),
+                    ColumnarValue::Scalar(ScalarValue::Int32(Some(scale))),
+                    ColumnarValue::Scalar(ScalarValue::Int32(Some(precision))),
+                    ColumnarValue::Scalar(ScalarValue::Int32(Some(output_scale))),
+                ];
+                let r = if half_even {
+                    spark_bround(&args)?
+                } else {
+                    spark_round(&args)?
+                };
+                r.into_array(5)
+            };
+
+        assert_eq!(
+            round(0, 4, 0, true)?.as_primitive::<Decimal128Type>(),
+            &Decimal128Array::from(vec![Some(2), Some(4), Some(-3), Some(100), None])
+                .with_precision_and_scale(4, 0)?,
+        );
+        assert_eq!(
+            round(0, 4, 0, false)?.as_primitive::<Decimal128Type>(),
+            &Decimal128Array::from(vec![Some(3), Some(4), Some(-3), Some(100), None])
+                .with_precision_and_scale(4, 0)?,
+        );
+        assert_eq!(
+            round(1, 5, 1, true)?.as_primitive::<Decimal128Type>(),
+            &Decimal128Array::from(vec![Some(25), Some(35), Some(-25), Some(995), None])
+                .with_precision_and_scale(5, 1)?,
+        );
+        assert_eq!(
+            round(-2, 4, 0, true)?.as_primitive::<Decimal128Type>(),
+            &Decimal128Array::from(vec![Some(0), Some(0), Some(0), Some(100), None])
+                .with_precision_and_scale(4, 0)?,
+        );
+
+        // overflowed values produce null
+        assert_eq!(
+            round(0, 2, 0, true)?.as_primitive::<Decimal128Type>(),
+            &Decimal128Array::from(vec![Some(2), Some(4), Some(-3), None, None])
+                .with_precision_and_scale(2, 0)?,
+        );
+        Ok(())
+    }
+}
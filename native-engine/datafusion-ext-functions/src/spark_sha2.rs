@@ -0,0 +1,139 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+
+use arrow::{array::*, datatypes::*};
+use datafusion::{
+    common::{Result, ScalarValue},
+    physical_plan::ColumnarValue,
+};
+use datafusion_ext_commons::df_execution_err;
+use sha2::{Digest, Sha224, Sha256, Sha384, Sha512};
+
+/// spark's sha2(): lowercase hex digest of a binary (or string) with the given
+/// bit length. bit length 0 means 256, unsupported bit lengths produce null.
+pub fn spark_sha2(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    let bit_length = match &args[1] {
+        ColumnarValue::Scalar(ScalarValue::Int32(bit_length)) => *bit_length,
+        _ => return df_execution_err!("sha2: bit length must be a literal int"),
+    };
+    let digest: fn(&[u8]) -> String = match bit_length {
+        Some(224) => |v| format!("{:x}", Sha224::digest(v)),
+        Some(0) | Some(256) => |v| format!("{:x}", Sha256::digest(v)),
+        Some(384) => |v| format!("{:x}", Sha384::digest(v)),
+        Some(512) => |v| format!("{:x}", Sha512::digest(v)),
+        _ => {
+            return Ok(match &args[0] {
+                ColumnarValue::Array(array) => {
+                    ColumnarValue::Array(new_null_array(&DataType::Utf8, array.len()))
+                }
+                ColumnarValue::Scalar(_) => ColumnarValue::Scalar(ScalarValue::Utf8(None)),
+            });
+        }
+    };
+
+    let sha2 = |array: &ArrayRef| -> Result<StringArray> {
+        Ok(match array.data_type() {
+            DataType::Binary => array
+                .as_binary::<i32>()
+                .iter()
+                .map(|v| v.map(digest))
+                .collect(),
+            DataType::Utf8 => array
+                .as_string::<i32>()
+                .iter()
+                .map(|v| v.map(|v| digest(v.as_bytes())))
+                .collect(),
+            other => return df_execution_err!("sha2: unsupported type: {other}"),
+        })
+    };
+    Ok(match &args[0] {
+        ColumnarValue::Array(array) => ColumnarValue::Array(Arc::new(sha2(array)?)),
+        ColumnarValue::Scalar(scalar) => {
+            let digests = sha2(&scalar.to_array()?)?;
+            ColumnarValue::Scalar(ScalarValue::try_from_array(&digests, 0)?)
+        }
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use arrow::array::*;
+    use datafusion::{
+        common::{cast::as_string_array, Result, ScalarValue},
+        physical_plan::ColumnarValue,
+    };
+
+    use crate::spark_sha2::spark_sha2;
+
+    #[test]
+    fn test_sha2() -> Result<()> {
+        let sha2 = |bit_length: i32| -> Result<Vec<Option<String>>> {
+            let r = spark_sha2(&[
+                ColumnarValue::Array(Arc::new(BinaryArray::from(vec![
+                    Some(b"abc".as_ref()),
+                    None,
+                ]))),
+                ColumnarValue::Scalar(ScalarValue::Int32(Some(bit_length))),
+            ])?;
+            Ok(as_string_array(&r.into_array(2)?)?
+                .iter()
+                .map(|v| v.map(|v| v.to_string()))
+                .collect())
+        };
+        let digest = |s: &str| vec![Some(s.to_string()), None];
+
+        assert_eq!(
+            sha2(224)?,
+            digest("23097d223405d8228642a477bda255b32aadbce4bda0b3f7e36c9da7")
+        );
+        assert_eq!(
+            sha2(256)?,
+            digest("ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad")
+        );
+        assert_eq!(sha2(0)?, sha2(256)?);
+        assert_eq!(
+            sha2(384)?,
+            digest(
+                "cb00753f45a35e8bb5a03d699ac65007272c32ab0eded1631a8b605a43ff5bed\
+                    8086072ba1e7cc2358baeca134c825a7"
+            )
+        );
+        assert_eq!(
+            sha2(512)?,
+            digest(
+                "ddaf35a193617abacc417349ae20413112e6fa4e89a97ea20a9eeee64b55d39a\
+                    2192992a274fc1a836ba3c23a3feebbd454d4423643ce80e2a9ac94fa54ca49f"
+            )
+        );
+        assert_eq!(sha2(128)?, vec![None, None]);
+
+        // string input
+        let r = spark_sha2(&[
+            ColumnarValue::Scalar(ScalarValue::from("abc")),
+            ColumnarValue::Scalar(ScalarValue::Int32(Some(256))),
+        ])?;
+        match r {
+            ColumnarValue::Scalar(ScalarValue::Utf8(Some(digest))) => assert_eq!(
+                digest,
+                "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+            ),
+            other => panic!("unexpected result: {other:?}"),
+        }
+        Ok(())
+    }
+}
@@ -12,10 +12,12 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::sync::Arc;
+use std::{collections::HashMap, sync::Arc};
 
 use arrow::{
-    array::{Array, ArrayRef, AsArray, ListArray, ListBuilder, StringArray, StringBuilder},
+    array::{
+        Array, ArrayRef, AsArray, Int32Array, ListArray, ListBuilder, StringArray, StringBuilder,
+    },
     datatypes::DataType,
 };
 use datafusion::{
@@ -107,6 +109,282 @@ pub fn string_split(args: &[ColumnarValue]) -> Result<ColumnarValue> {
     Ok(ColumnarValue::Array(Arc::new(splitted_builder.finish())))
 }
 
+/// substring_index() function compatible with spark
+/// substring_index('a.b.c', '.', 2) = 'a.b'
+/// substring_index('a.b.c', '.', -2) = 'b.c'
+pub fn string_substring_index(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    let string_array = args[0].clone().into_array(1)?;
+    let delim = match &args[1] {
+        ColumnarValue::Scalar(ScalarValue::Utf8(Some(delim))) => delim,
+        ColumnarValue::Scalar(scalar) if scalar.is_null() => {
+            return Ok(ColumnarValue::Scalar(ScalarValue::Utf8(None)));
+        }
+        _ => df_execution_err!("string_substring_index delim only supports literal string")?,
+    };
+    let count = match &args[2] {
+        ColumnarValue::Scalar(ScalarValue::Int32(Some(count))) => *count,
+        ColumnarValue::Scalar(scalar) if scalar.is_null() => {
+            return Ok(ColumnarValue::Scalar(ScalarValue::Utf8(None)));
+        }
+        _ => df_execution_err!("string_substring_index count only supports literal int32")?,
+    };
+
+    let substring_array: ArrayRef = Arc::new(StringArray::from_iter(
+        as_string_array(&string_array)?
+            .into_iter()
+            .map(|s| s.map(|s| substring_index(s, delim, count))),
+    ));
+    Ok(ColumnarValue::Array(substring_array))
+}
+
+/// same as UTF8String.subStringIndex(), note that occurrences of delim may
+/// overlap
+fn substring_index<'a>(s: &'a str, delim: &str, count: i32) -> &'a str {
+    if delim.is_empty() || count == 0 {
+        return "";
+    }
+    let (bytes, delim_bytes) = (s.as_bytes(), delim.as_bytes());
+    let is_delim_at = |pos: usize| bytes[pos..].starts_with(delim_bytes);
+
+    if count > 0 {
+        let mut positions = (0..bytes.len()).filter(|&pos| is_delim_at(pos));
+        match positions.nth(count as usize - 1) {
+            Some(pos) => &s[..pos],
+            None => s,
+        }
+    } else {
+        let mut positions = (0..bytes.len()).rev().filter(|&pos| is_delim_at(pos));
+        match positions.nth(count.unsigned_abs() as usize - 1) {
+            Some(pos) => &s[pos + delim.len()..],
+            None => s,
+        }
+    }
+}
+
+/// translate() function compatible with spark
+/// translate('AaBbCc', 'abc', '12') = 'A1B2C'
+pub fn string_translate(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    let string_array = args[0].clone().into_array(1)?;
+    let (from, to) = match (&args[1], &args[2]) {
+        (
+            ColumnarValue::Scalar(ScalarValue::Utf8(Some(from))),
+            ColumnarValue::Scalar(ScalarValue::Utf8(Some(to))),
+        ) => (from, to),
+        (ColumnarValue::Scalar(from), ColumnarValue::Scalar(to))
+            if from.is_null() || to.is_null() =>
+        {
+            return Ok(ColumnarValue::Scalar(ScalarValue::Utf8(None)));
+        }
+        _ => df_execution_err!("string_translate from/to only supports literal string")?,
+    };
+
+    // maps chars in from to the chars at the same position in to, or to None
+    // (deleted) if to is shorter. the first occurrence wins for duplicated chars.
+    let mut dict: HashMap<char, Option<char>> = HashMap::new();
+    let mut to_chars = to.chars();
+    for from_char in from.chars() {
+        let to_char = to_chars.next();
+        dict.entry(from_char).or_insert(to_char);
+    }
+
+    let translated_array: ArrayRef = Arc::new(StringArray::from_iter(
+        as_string_array(&string_array)?.into_iter().map(|s| {
+            s.map(|s| {
+                s.chars()
+                    .filter_map(|c| dict.get(&c).cloned().unwrap_or(Some(c)))
+                    .collect::<String>()
+            })
+        }),
+    ));
+    Ok(ColumnarValue::Array(translated_array))
+}
+
+/// levenshtein() function compatible with spark, the distance is computed over
+/// chars instead of bytes. with the optional threshold, -1 is returned if the
+/// distance is greater than the threshold.
+pub fn string_levenshtein(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    let num_rows = args
+        .iter()
+        .find_map(|arg| match arg {
+            ColumnarValue::Array(array) => Some(array.len()),
+            ColumnarValue::Scalar(_) => None,
+        })
+        .unwrap_or(1);
+    let left_array = args[0].clone().into_array(num_rows)?;
+    let right_array = args[1].clone().into_array(num_rows)?;
+    let threshold = match args.get(2) {
+        Some(ColumnarValue::Scalar(ScalarValue::Int32(Some(threshold)))) => Some(*threshold),
+        Some(ColumnarValue::Scalar(scalar)) if scalar.is_null() => {
+            return Ok(ColumnarValue::Scalar(ScalarValue::Int32(None)));
+        }
+        Some(_) => df_execution_err!("string_levenshtein threshold only supports literal int32")?,
+        None => None,
+    };
+
+    let mut chars_buf = vec![];
+    let mut row_buf = vec![];
+    let distance_array: ArrayRef = Arc::new(Int32Array::from_iter(
+        as_string_array(&left_array)?
+            .into_iter()
+            .zip(as_string_array(&right_array)?)
+            .map(|(left, right)| {
+                let distance = levenshtein(left?, right?, &mut chars_buf, &mut row_buf) as i32;
+                Some(match threshold {
+                    Some(threshold) if distance > threshold => -1,
+                    _ => distance,
+                })
+            }),
+    ));
+    Ok(ColumnarValue::Array(distance_array))
+}
+
+/// computes levenshtein distance with a single dp row of the shorter string
+fn levenshtein(a: &str, b: &str, chars_buf: &mut Vec<char>, row_buf: &mut Vec<usize>) -> usize {
+    let (a, b) = if a.chars().count() < b.chars().count() {
+        (b, a)
+    } else {
+        (a, b)
+    };
+    chars_buf.clear();
+    chars_buf.extend(b.chars());
+    row_buf.clear();
+    row_buf.extend(0..=chars_buf.len());
+
+    for (i, a_char) in a.chars().enumerate() {
+        let mut diagonal = row_buf[0];
+        row_buf[0] = i + 1;
+        for (j, &b_char) in chars_buf.iter().enumerate() {
+            let above = row_buf[j + 1];
+            row_buf[j + 1] = if a_char == b_char {
+                diagonal
+            } else {
+                1 + diagonal.min(above).min(row_buf[j])
+            };
+            diagonal = above;
+        }
+    }
+    row_buf[chars_buf.len()]
+}
+
+/// soundex() function compatible with spark, strings not starting with an
+/// ascii letter are returned unchanged.
+pub fn string_soundex(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    let string_array = args[0].clone().into_array(1)?;
+    let soundex_array: ArrayRef = Arc::new(StringArray::from_iter(
+        as_string_array(&string_array)?
+            .into_iter()
+            .map(|s| s.map(soundex)),
+    ));
+    Ok(ColumnarValue::Array(soundex_array))
+}
+
+/// same as spark's UTF8String.soundex(): codes of H and W are ignored and do
+/// not separate duplicated codes, while other non-letters do.
+fn soundex(s: &str) -> String {
+    const US_ENGLISH_MAPPING: &[u8; 26] = b"01230127022455012623017202";
+
+    let bytes = s.as_bytes();
+    let first = match bytes.first() {
+        Some(b) if b.is_ascii_alphabetic() => b.to_ascii_uppercase(),
+        _ => return s.to_string(),
+    };
+    let mut sx = [first, b'0', b'0', b'0'];
+    let mut sxi = 1;
+    let mut last_code = US_ENGLISH_MAPPING[(first - b'A') as usize];
+
+    for b in &bytes[1..] {
+        if !b.is_ascii_alphabetic() {
+            last_code = b'0';
+            continue;
+        }
+        let code = US_ENGLISH_MAPPING[(b.to_ascii_uppercase() - b'A') as usize];
+        if code == b'7' {
+            continue;
+        }
+        if code != b'0' && code != last_code {
+            sx[sxi] = code;
+            sxi += 1;
+            if sxi > 3 {
+                break;
+            }
+        }
+        last_code = code;
+    }
+    sx.iter().map(|&b| b as char).collect()
+}
+
+/// instr() function compatible with spark, returns the 1-based char position
+/// of the first occurrence of substr in str, or 0 if not found.
+/// instr('数据砖头', '砖') = 3
+pub fn string_instr(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    let num_rows = args
+        .iter()
+        .find_map(|arg| match arg {
+            ColumnarValue::Array(array) => Some(array.len()),
+            ColumnarValue::Scalar(_) => None,
+        })
+        .unwrap_or(1);
+    let string_array = args[0].clone().into_array(num_rows)?;
+    let substr_array = args[1].clone().into_array(num_rows)?;
+
+    let position_array: ArrayRef = Arc::new(Int32Array::from_iter(
+        as_string_array(&string_array)?
+            .into_iter()
+            .zip(as_string_array(&substr_array)?)
+            .map(|(s, substr)| Some(locate(s?, substr?, 0))),
+    ));
+    Ok(ColumnarValue::Array(position_array))
+}
+
+/// locate() function compatible with spark, returns the 1-based char position
+/// of the first occurrence of substr in str at or after pos, or 0 if not found
+/// or pos < 1. null pos returns 0 (same as hive).
+/// locate('a', 'banana', 3) = 4
+pub fn string_locate(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    let num_rows = args
+        .iter()
+        .find_map(|arg| match arg {
+            ColumnarValue::Array(array) => Some(array.len()),
+            ColumnarValue::Scalar(_) => None,
+        })
+        .unwrap_or(1);
+    let substr_array = args[0].clone().into_array(num_rows)?;
+    let string_array = args[1].clone().into_array(num_rows)?;
+    let pos_array = match args.get(2) {
+        Some(pos) => pos.clone().into_array(num_rows)?,
+        None => ColumnarValue::Scalar(ScalarValue::Int32(Some(1))).into_array(num_rows)?,
+    };
+
+    let position_array: ArrayRef = Arc::new(Int32Array::from_iter(
+        as_string_array(&substr_array)?
+            .into_iter()
+            .zip(as_string_array(&string_array)?)
+            .zip(as_int32_array(&pos_array)?)
+            .map(|((substr, s), pos)| match pos {
+                None => Some(0),
+                Some(pos) if pos < 1 => Some(0),
+                Some(pos) => Some(locate(s?, substr?, pos as usize - 1)),
+            }),
+    ));
+    Ok(ColumnarValue::Array(position_array))
+}
+
+/// same as UTF8String.indexOf(substr, start) + 1, positions are counted in
+/// chars. note that an empty substr is always found at position 1.
+fn locate(s: &str, substr: &str, start: usize) -> i32 {
+    if substr.is_empty() {
+        return 1;
+    }
+    let start_offset = match s.char_indices().nth(start) {
+        Some((offset, _)) => offset,
+        None => return 0,
+    };
+    match s[start_offset..].find(substr) {
+        Some(pos) => (start + s[start_offset..][..pos].chars().count() + 1) as i32,
+        None => 0,
+    }
+}
+
 /// concat() function compatible with spark (returns null if any param is null)
 /// concat('abcde', 2, 22) = 'abcde222
 /// concat('abcde', 2, NULL, 22) = NULL
@@ -224,7 +502,7 @@ pub fn string_concat_ws(args: &[ColumnarValue]) -> Result<ColumnarValue> {
                         }
                     }
                     if let ScalarValue::List(l) = scalar
-                        && l.data_type() == &DataType::Utf8
+                        && l.value_type() == DataType::Utf8
                     {
                         if l.is_null(0) {
                             return Ok(Arg::Ignore);
@@ -315,7 +593,10 @@ pub fn string_concat_ws(args: &[ColumnarValue]) -> Result<ColumnarValue> {
 mod test {
     use std::sync::Arc;
 
-    use arrow::array::{Int32Array, ListBuilder, StringArray, StringBuilder};
+    use arrow::{
+        array::{AsArray, Int32Array, ListBuilder, StringArray, StringBuilder},
+        datatypes::{DataType, Int32Type},
+    };
     use datafusion::{
         common::{
             cast::{as_list_array, as_string_array},
@@ -325,8 +606,9 @@ mod test {
     };
 
     use crate::spark_strings::{
-        string_concat, string_concat_ws, string_lower, string_repeat, string_space, string_split,
-        string_upper,
+        string_concat, string_concat_ws, string_instr, string_levenshtein, string_locate,
+        string_lower, string_repeat, string_soundex, string_space, string_split,
+        string_substring_index, string_translate, string_upper,
     };
 
     #[test]
@@ -478,6 +760,7 @@ mod test {
         );
         Ok(())
     }
+
     #[test]
     fn test_string_concat_ws() -> Result<()> {
         // positive case
@@ -514,4 +797,329 @@ mod test {
         );
         Ok(())
     }
+
+    #[test]
+    fn test_string_concat_ws_nulls_and_lists() -> Result<()> {
+        // nulls in the middle are skipped, list elements are flattened in order
+        let r = string_concat_ws(&vec![
+            ColumnarValue::Scalar(ScalarValue::from(",")),
+            ColumnarValue::Scalar(ScalarValue::from("a")),
+            ColumnarValue::Array(Arc::new(StringArray::from(vec![None, Some("b")]))),
+            ColumnarValue::Scalar(ScalarValue::List(ScalarValue::new_list(
+                &[
+                    ScalarValue::from("x"),
+                    ScalarValue::Utf8(None),
+                    ScalarValue::from("y"),
+                ],
+                &DataType::Utf8,
+            ))),
+            ColumnarValue::Array(Arc::new({
+                let mut list_builder = ListBuilder::new(StringBuilder::new());
+                list_builder.append_value([Some("c"), None, Some("d")]);
+                list_builder.append_value([None::<&str>]);
+                list_builder.finish()
+            })),
+        ])?;
+        let s = r.into_array(2)?;
+        assert_eq!(
+            as_string_array(&s)?.into_iter().collect::<Vec<_>>(),
+            vec![Some("a,x,y,c,d"), Some("a,b,x,y")]
+        );
+
+        // all literals
+        let r = string_concat_ws(&vec![
+            ColumnarValue::Scalar(ScalarValue::from("-")),
+            ColumnarValue::Scalar(ScalarValue::from("a")),
+            ColumnarValue::Scalar(ScalarValue::Utf8(None)),
+            ColumnarValue::Scalar(ScalarValue::from("b")),
+        ])?;
+        assert_eq!(r.into_array(1)?.as_string::<i32>().value(0), "a-b");
+
+        // null separator
+        let r = string_concat_ws(&vec![
+            ColumnarValue::Scalar(ScalarValue::Utf8(None)),
+            ColumnarValue::Array(Arc::new(StringArray::from(vec![Some("a"), None]))),
+        ])?;
+        assert_eq!(r.into_array(2)?.null_count(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_string_substring_index() -> Result<()> {
+        let substring_index = |delim: &str, count: i32| -> Result<Vec<Option<String>>> {
+            let r = string_substring_index(&vec![
+                ColumnarValue::Array(Arc::new(StringArray::from(vec![
+                    Some("www.apache.org"),
+                    Some("a::b::::c"),
+                    Some(""),
+                    None,
+                ]))),
+                ColumnarValue::Scalar(ScalarValue::from(delim)),
+                ColumnarValue::Scalar(ScalarValue::from(count)),
+            ])?;
+            Ok(as_string_array(&r.into_array(4)?)?
+                .into_iter()
+                .map(|s| s.map(|s| s.to_string()))
+                .collect())
+        };
+        let some = |s: &str| Some(s.to_string());
+
+        // positive count
+        assert_eq!(
+            substring_index(".", 2)?,
+            vec![some("www.apache"), some("a::b::::c"), some(""), None]
+        );
+        // negative count
+        assert_eq!(
+            substring_index(".", -2)?,
+            vec![some("apache.org"), some("a::b::::c"), some(""), None]
+        );
+        // count larger than number of delimiters
+        assert_eq!(
+            substring_index(".", 5)?,
+            vec![some("www.apache.org"), some("a::b::::c"), some(""), None]
+        );
+        assert_eq!(
+            substring_index(".", -5)?,
+            vec![some("www.apache.org"), some("a::b::::c"), some(""), None]
+        );
+        // zero count and empty delimiter
+        assert_eq!(
+            substring_index(".", 0)?,
+            vec![some(""), some(""), some(""), None]
+        );
+        assert_eq!(
+            substring_index("", 1)?,
+            vec![some(""), some(""), some(""), None]
+        );
+        // multi-char delimiter, occurrences may overlap like in spark
+        assert_eq!(
+            substring_index("::", 2)?,
+            vec![some("www.apache.org"), some("a::b"), some(""), None]
+        );
+        assert_eq!(
+            substring_index("::", 3)?,
+            vec![some("www.apache.org"), some("a::b:"), some(""), None]
+        );
+        assert_eq!(
+            substring_index("::", -1)?,
+            vec![some("www.apache.org"), some("c"), some(""), None]
+        );
+
+        // null delimiter
+        let r = string_substring_index(&vec![
+            ColumnarValue::Scalar(ScalarValue::from("a.b")),
+            ColumnarValue::Scalar(ScalarValue::Utf8(None)),
+            ColumnarValue::Scalar(ScalarValue::from(1)),
+        ])?;
+        assert!(r.into_array(1)?.is_null(0));
+        Ok(())
+    }
+
+    #[test]
+    fn test_string_translate() -> Result<()> {
+        let translate = |from: &str, to: &str| -> Result<Vec<Option<String>>> {
+            let r = string_translate(&vec![
+                ColumnarValue::Array(Arc::new(StringArray::from(vec![
+                    Some("AaBbCc"),
+                    Some("中文abc"),
+                    None,
+                ]))),
+                ColumnarValue::Scalar(ScalarValue::from(from)),
+                ColumnarValue::Scalar(ScalarValue::from(to)),
+            ])?;
+            Ok(as_string_array(&r.into_array(3)?)?
+                .into_iter()
+                .map(|s| s.map(|s| s.to_string()))
+                .collect())
+        };
+        let some = |s: &str| Some(s.to_string());
+
+        // simple substitution
+        assert_eq!(
+            translate("abc", "123")?,
+            vec![some("A1B2C3"), some("中文123"), None]
+        );
+        // deletion when to is shorter
+        assert_eq!(
+            translate("abc", "1")?,
+            vec![some("A1BC"), some("中文1"), None]
+        );
+        // first occurrence wins for duplicated chars
+        assert_eq!(
+            translate("aba", "123")?,
+            vec![some("A1B2Cc"), some("中文12c"), None]
+        );
+        // unicode chars
+        assert_eq!(
+            translate("文a", "字")?,
+            vec![some("ABbCc"), some("中字bc"), None]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_string_levenshtein() -> Result<()> {
+        let r = string_levenshtein(&vec![
+            ColumnarValue::Array(Arc::new(StringArray::from(vec![
+                Some("kitten"),
+                Some("abc"),
+                Some("abc"),
+                Some(""),
+                Some("中文字符"),
+                Some("abc"),
+                None,
+            ]))),
+            ColumnarValue::Array(Arc::new(StringArray::from(vec![
+                Some("sitting"),
+                Some("abc"),
+                Some("abxc"),
+                Some("abc"),
+                Some("中国字"),
+                None,
+                Some("abc"),
+            ]))),
+        ])?;
+        assert_eq!(
+            r.into_array(7)?.as_primitive::<Int32Type>(),
+            &Int32Array::from(vec![
+                Some(3),
+                Some(0),
+                Some(1),
+                Some(3),
+                Some(2),
+                None,
+                None
+            ]),
+        );
+
+        // scalar arg and threshold
+        let r = string_levenshtein(&vec![
+            ColumnarValue::Array(Arc::new(StringArray::from(vec!["kitten", "sitten"]))),
+            ColumnarValue::Scalar(ScalarValue::from("sitting")),
+            ColumnarValue::Scalar(ScalarValue::from(2)),
+        ])?;
+        assert_eq!(
+            r.into_array(2)?.as_primitive::<Int32Type>(),
+            &Int32Array::from(vec![-1, 2]),
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_string_soundex() -> Result<()> {
+        let r = string_soundex(&[ColumnarValue::Array(Arc::new(StringArray::from(vec![
+            Some("Robert"),
+            Some("Rupert"),
+            Some("Ashcraft"),
+            Some("Tymczak"),
+            Some("a"),
+            Some("Zz-z"),
+            Some("123abc"),
+            Some("Über"),
+            Some(""),
+            None,
+        ])))])?;
+        assert_eq!(
+            as_string_array(&r.into_array(10)?)?,
+            &StringArray::from(vec![
+                Some("R163"),
+                Some("R163"),
+                Some("A261"),
+                Some("T522"),
+                Some("A000"),
+                Some("Z200"),
+                Some("123abc"),
+                Some("Über"),
+                Some(""),
+                None,
+            ]),
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_string_instr() -> Result<()> {
+        let r = string_instr(&[
+            ColumnarValue::Array(Arc::new(StringArray::from(vec![
+                Some("SparkSQL"),
+                Some("SparkSQL"),
+                Some("数据砖头"),
+                Some("abc"),
+                Some(""),
+                None,
+                Some("abc"),
+            ]))),
+            ColumnarValue::Array(Arc::new(StringArray::from(vec![
+                Some("SQL"),
+                Some("Core"),
+                Some("砖头"),
+                Some(""),
+                Some("a"),
+                Some("a"),
+                None,
+            ]))),
+        ])?;
+        assert_eq!(
+            r.into_array(7)?.as_primitive::<Int32Type>(),
+            &Int32Array::from(vec![
+                Some(6),
+                Some(0),
+                Some(3),
+                Some(1),
+                Some(0),
+                None,
+                None
+            ]),
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_string_locate() -> Result<()> {
+        let r = string_locate(&[
+            ColumnarValue::Scalar(ScalarValue::from("a")),
+            ColumnarValue::Array(Arc::new(StringArray::from(vec![
+                Some("banana"),
+                Some("banana"),
+                Some("banana"),
+                Some("banana"),
+                Some("banana"),
+                Some("砖a砖a"),
+                None,
+            ]))),
+            ColumnarValue::Array(Arc::new(Int32Array::from(vec![
+                Some(1),
+                Some(3),
+                Some(7),
+                Some(0),
+                None,
+                Some(3),
+                Some(1),
+            ]))),
+        ])?;
+        assert_eq!(
+            r.into_array(7)?.as_primitive::<Int32Type>(),
+            &Int32Array::from(vec![
+                Some(2),
+                Some(4),
+                Some(0),
+                Some(0),
+                Some(0),
+                Some(4),
+                None
+            ]),
+        );
+
+        // default pos
+        let r = string_locate(&[
+            ColumnarValue::Scalar(ScalarValue::from("头")),
+            ColumnarValue::Scalar(ScalarValue::from("数据砖头")),
+        ])?;
+        assert_eq!(
+            r.into_array(1)?.as_primitive::<Int32Type>(),
+            &Int32Array::from(vec![4]),
+        );
+        Ok(())
+    }
 }
@@ -40,6 +40,7 @@ pub fn spark_unscaled_value(args: &[ColumnarValue]) -> Result<ColumnarValue> {
         }
     })
 }
+
 #[cfg(test)]
 mod test {
     use std::{error::Error, sync::Arc};
@@ -47,7 +48,9 @@ mod test {
     use arrow::array::{ArrayRef, Decimal128Array, Int64Array};
     use datafusion::{common::ScalarValue, logical_expr::ColumnarValue};
 
-    use crate::spark_unscaled_value::spark_unscaled_value;
+    use crate::{
+        spark_make_decimal::spark_make_decimal, spark_unscaled_value::spark_unscaled_value,
+    };
 
     #[test]
     fn test_unscaled_value_array() -> Result<(), Box<dyn Error>> {
@@ -88,4 +91,22 @@ mod test {
         assert_eq!(&result, &expected);
         Ok(())
     }
+
+    #[test]
+    fn test_unscaled_value_make_decimal_round_trip() -> Result<(), Box<dyn Error>> {
+        let values: ArrayRef = Arc::new(Int64Array::from(vec![
+            Some(0),
+            Some(-123456789012345678),
+            None,
+            Some(999999999999999999),
+        ]));
+        let decimals = spark_make_decimal(&vec![
+            ColumnarValue::Array(values.clone()),
+            ColumnarValue::Scalar(ScalarValue::Int32(Some(18))), // precision
+            ColumnarValue::Scalar(ScalarValue::Int32(Some(6))),  // scale
+        ])?;
+        let result = spark_unscaled_value(&vec![decimals])?.into_array(4)?;
+        assert_eq!(&result, &values);
+        Ok(())
+    }
 }
@@ -0,0 +1,191 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+
+use arrow::{array::*, datatypes::*};
+use datafusion::{common::Result, physical_plan::ColumnarValue};
+use datafusion_ext_commons::cast::cast;
+
+use crate::spark_null_if::{num_rows, to_columnar_value};
+
+/// implements org.apache.spark.sql.catalyst.expressions.WidthBucket
+///
+/// value/min/max are casted to double and num_buckets to long, like spark's
+/// implicit input casting.
+pub fn spark_width_bucket(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    let num_rows = num_rows(&args[..4]);
+    let to_array = |arg: &ColumnarValue, data_type: &DataType| -> Result<ArrayRef> {
+        let array = arg.clone().into_array(num_rows.unwrap_or(1))?;
+        match array.data_type() == data_type {
+            true => Ok(array),
+            false => cast(&array, data_type),
+        }
+    };
+    let values = to_array(&args[0], &DataType::Float64)?;
+    let mins = to_array(&args[1], &DataType::Float64)?;
+    let maxs = to_array(&args[2], &DataType::Float64)?;
+    let num_buckets = to_array(&args[3], &DataType::Int64)?;
+
+    let output: Int64Array = values
+        .as_primitive::<Float64Type>()
+        .iter()
+        .zip(mins.as_primitive::<Float64Type>())
+        .zip(maxs.as_primitive::<Float64Type>())
+        .zip(num_buckets.as_primitive::<Int64Type>())
+        .map(|(((value, min), max), num_buckets)| width_bucket(value?, min?, max?, num_buckets?))
+        .collect();
+    to_columnar_value(num_rows, Arc::new(output))
+}
+
+fn width_bucket(value: f64, min: f64, max: f64, num_buckets: i64) -> Option<i64> {
+    if num_buckets <= 0
+        || num_buckets == i64::MAX
+        || value.is_nan()
+        || min == max
+        || !min.is_finite()
+        || !max.is_finite()
+    {
+        return None;
+    }
+    let lower = min.min(max);
+    let upper = min.max(max);
+
+    if min < max {
+        return Some(if value < lower {
+            0
+        } else if value >= upper {
+            num_buckets + 1
+        } else {
+            (num_buckets as f64 * (value - lower) / (upper - lower)) as i64 + 1
+        });
+    }
+
+    // buckets count down if min > max
+    Some(if value > upper {
+        0
+    } else if value <= lower {
+        num_buckets + 1
+    } else {
+        (num_buckets as f64 * (upper - value) / (upper - lower)) as i64 + 1
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use arrow::array::*;
+    use datafusion::{
+        common::{Result, ScalarValue},
+        physical_plan::ColumnarValue,
+    };
+
+    use crate::spark_width_bucket::spark_width_bucket;
+
+    #[test]
+    fn test_width_bucket() -> Result<()> {
+        let values = ColumnarValue::Array(Arc::new(Float64Array::from(vec![
+            Some(-1.0),
+            Some(0.0),
+            Some(5.3),
+            Some(9.99),
+            Some(10.0),
+            Some(11.0),
+            None,
+            Some(f64::NAN),
+        ])));
+        let output = spark_width_bucket(&[
+            values,
+            ColumnarValue::Scalar(ScalarValue::Float64(Some(0.0))),
+            ColumnarValue::Scalar(ScalarValue::Float64(Some(10.0))),
+            ColumnarValue::Scalar(ScalarValue::Int64(Some(5))),
+        ])?
+        .into_array(8)?;
+        let expected: ArrayRef = Arc::new(Int64Array::from(vec![
+            Some(0),
+            Some(1),
+            Some(3),
+            Some(5),
+            Some(6),
+            Some(6),
+            None,
+            None,
+        ]));
+        assert_eq!(&output, &expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_width_bucket_reversed_bounds() -> Result<()> {
+        let values = ColumnarValue::Array(Arc::new(Int32Array::from(vec![
+            Some(11),
+            Some(10),
+            Some(7),
+            Some(1),
+            Some(0),
+            Some(-1),
+        ])));
+        let output = spark_width_bucket(&[
+            values,
+            ColumnarValue::Scalar(ScalarValue::Int32(Some(10))),
+            ColumnarValue::Scalar(ScalarValue::Int32(Some(0))),
+            ColumnarValue::Scalar(ScalarValue::Int32(Some(5))),
+        ])?
+        .into_array(6)?;
+        let expected: ArrayRef = Arc::new(Int64Array::from(vec![
+            Some(0),
+            Some(1),
+            Some(2),
+            Some(5),
+            Some(6),
+            Some(6),
+        ]));
+        assert_eq!(&output, &expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_width_bucket_invalid_args() -> Result<()> {
+        let num_buckets = ColumnarValue::Array(Arc::new(Int64Array::from(vec![
+            Some(0),
+            Some(-1),
+            Some(i64::MAX),
+            None,
+            Some(4),
+        ])));
+        let output = spark_width_bucket(&[
+            ColumnarValue::Scalar(ScalarValue::Float64(Some(5.0))),
+            ColumnarValue::Scalar(ScalarValue::Float64(Some(0.0))),
+            ColumnarValue::Scalar(ScalarValue::Float64(Some(10.0))),
+            num_buckets,
+        ])?
+        .into_array(5)?;
+        let expected: ArrayRef = Arc::new(Int64Array::from(vec![None, None, None, None, Some(3)]));
+        assert_eq!(&output, &expected);
+
+        // min == max
+        let output = spark_width_bucket(&[
+            ColumnarValue::Scalar(ScalarValue::Float64(Some(5.0))),
+            ColumnarValue::Scalar(ScalarValue::Float64(Some(1.0))),
+            ColumnarValue::Scalar(ScalarValue::Float64(Some(1.0))),
+            ColumnarValue::Scalar(ScalarValue::Int64(Some(4))),
+        ])?;
+        assert!(matches!(
+            output,
+            ColumnarValue::Scalar(ScalarValue::Int64(None))
+        ));
+        Ok(())
+    }
+}
@@ -53,7 +53,7 @@ pub fn spark_xxhash64(args: &[ColumnarValue]) -> Result<ColumnarValue> {
 mod test {
     use std::{error::Error, sync::Arc};
 
-    use arrow::array::{ArrayRef, Int64Array, StringArray};
+    use arrow::array::{ArrayRef, Int32Array, Int64Array, StringArray};
     use datafusion::logical_expr::ColumnarValue;
 
     use super::*;
@@ -97,4 +97,45 @@ mod test {
         assert_eq!(&result, &expected);
         Ok(())
     }
+
+    #[test]
+    fn test_xxhash64_mixed_types() -> Result<(), Box<dyn Error>> {
+        // xxhash64(i, s, l), null values leave the running hash unchanged
+        let result = spark_xxhash64(&vec![
+            ColumnarValue::Array(Arc::new(Int32Array::from(vec![Some(1), None, Some(1)]))),
+            ColumnarValue::Array(Arc::new(StringArray::from(vec![
+                Some("a"),
+                Some("a"),
+                None,
+            ]))),
+            ColumnarValue::Array(Arc::new(Int64Array::from(vec![Some(2), Some(2), Some(2)]))),
+        ])?
+        .into_array(3)?;
+
+        let expected = Int64Array::from(vec![
+            Some(3570513804248678426),
+            Some(-8186034249051070164),
+            Some(2360248972619621018),
+        ]);
+        let expected: ArrayRef = Arc::new(expected);
+        assert_eq!(&result, &expected);
+
+        // xxhash64(1, null)
+        let result = spark_xxhash64(&vec![
+            ColumnarValue::Array(Arc::new(Int32Array::from(vec![Some(1)]))),
+            ColumnarValue::Array(Arc::new(StringArray::from(vec![None::<&str>]))),
+        ])?
+        .into_array(1)?;
+        let expected: ArrayRef = Arc::new(Int64Array::from(vec![-6698625589789238999]));
+        assert_eq!(&result, &expected);
+
+        // long strings go through the 32-byte stripe loop
+        let result = spark_xxhash64(&vec![ColumnarValue::Array(Arc::new(StringArray::from(
+            vec!["abcdefghijklmnopqrstuvwxyz0123456789"],
+        )))])?
+        .into_array(1)?;
+        let expected: ArrayRef = Arc::new(Int64Array::from(vec![2724237963427677065]));
+        assert_eq!(&result, &expected);
+        Ok(())
+    }
 }
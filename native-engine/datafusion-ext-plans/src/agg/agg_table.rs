@@ -165,10 +165,12 @@ impl AggTable {
         sender: Arc<WrappedRecordBatchSender>,
     ) -> Result<()> {
         let mut timer = self.baseline_metrics.elapsed_compute().timer();
+        let mut in_mem_guard = self.in_mem.lock().await;
         self.set_spillable(false);
+        let old_in_mem = in_mem_guard.renew(InMemMode::PartialSkipped);
+        drop(in_mem_guard);
 
         let batch_num_rows = input_batch.num_rows();
-        let old_in_mem = self.renew_in_mem_table(InMemMode::PartialSkipped).await;
         assert_eq!(old_in_mem.num_records(), 0); // old table must be cleared
 
         let mut acc_store = AccStore::new(self.agg_ctx.initial_acc.clone());
@@ -213,10 +215,11 @@ impl AggTable {
 
     pub async fn output(&self, sender: Arc<WrappedRecordBatchSender>) -> Result<()> {
         let mut timer = self.baseline_metrics.elapsed_compute().timer();
+        let mut in_mem_guard = self.in_mem.lock().await;
         self.set_spillable(false);
-
-        let in_mem = self.renew_in_mem_table(InMemMode::PartialSkipped).await;
+        let in_mem = in_mem_guard.renew(InMemMode::PartialSkipped);
         let spills = std::mem::take(&mut *self.spills.lock().await);
+        drop(in_mem_guard);
         let target_batch_mem_size = suggested_output_batch_mem_size();
         let batch_size = if is_jni_bridge_inited() {
             BATCH_SIZE.value()? as usize
@@ -398,12 +401,18 @@ impl MemConsumer for AggTable {
             .expect("consumer info not set")
     }
 
-    async fn spill(&self) -> Result<()> {
+    async fn spill(&self) -> Result<usize> {
+        // nothing is spilled once outputting has started, which is marked
+        // under the in_mem lock
         let mut in_mem = self.in_mem.lock().await;
+        if !self.is_spillable() {
+            return Ok(0);
+        }
         let mut spills = self.spills.lock().await;
 
         // do not spill anything if triggered partial skipping
         // regardless minRows configuration
+        let mut freed = 0;
         in_mem.check_trigger_partial_skipping();
         if in_mem.mode != InMemMode::PartialSkipped {
             let mut next_in_mem_mode = InMemMode::Merging;
@@ -424,9 +433,10 @@ impl MemConsumer for AggTable {
                 .aggs
                 .iter()
                 .for_each(|agg| agg.agg.reset_mem_used());
+            freed = self.mem_used();
             self.update_mem_used(0).await?;
         }
-        Ok(())
+        Ok(freed)
     }
 }
 
@@ -661,7 +671,7 @@ impl HashingData {
         let bucket_counts =
             radix_sort_u16_ranged_by(&mut bucketed_records, NUM_SPILL_BUCKETS, |v| v.2);
 
-        let mut writer = spill.get_compressed_writer();
+        let mut writer = spill.get_compressed_writer()?;
         let mut beg = 0;
 
         for i in 0..NUM_SPILL_BUCKETS {
@@ -865,7 +875,7 @@ impl MergingData {
             NUM_SPILL_BUCKETS,
         );
 
-        let mut writer = spill.get_compressed_writer();
+        let mut writer = spill.get_compressed_writer()?;
         for bucket_id in 0..NUM_SPILL_BUCKETS {
             let bucket_count = self.bucket_counts[bucket_id];
             if bucket_count == 0 {
@@ -903,7 +913,7 @@ pub struct RecordsSpillCursor<'a> {
 
 impl<'a> RecordsSpillCursor<'a> {
     fn try_from_spill(spill: &'a mut Box<dyn Spill>, agg_ctx: &Arc<AggContext>) -> Result<Self> {
-        let mut input = spill.get_compressed_reader();
+        let mut input = spill.get_compressed_reader()?;
         Ok(Self {
             agg_ctx: agg_ctx.clone(),
             cur_bucket_idx: read_len(&mut input)?,
@@ -25,6 +25,8 @@ pub mod first;
 pub mod first_ignores_null;
 pub mod maxmin;
 pub mod sum;
+pub mod try_avg;
+pub mod try_sum;
 
 use std::{
     any::Any,
@@ -90,6 +92,8 @@ pub enum AggFunction {
     CollectList,
     CollectSet,
     BloomFilter,
+    TrySum,
+    TryAvg,
     BrickhouseCollect,
     BrickhouseCombineUnique,
 }
@@ -223,6 +227,38 @@ pub fn create_agg(
                 return_type,
             )?)
         }
+        AggFunction::TrySum => {
+            let arg_type = children[0].data_type(input_schema)?;
+            let return_type = aggregate_function::AggregateFunction::return_type(
+                &aggregate_function::AggregateFunction::Sum,
+                &[arg_type],
+            )?;
+            Arc::new(try_sum::AggTrySum::try_new(
+                Arc::new(TryCastExpr::new(children[0].clone(), return_type.clone())),
+                return_type,
+            )?)
+        }
+        AggFunction::TryAvg => {
+            let arg_type = children[0].data_type(input_schema)?;
+            let return_type = aggregate_function::AggregateFunction::return_type(
+                &aggregate_function::AggregateFunction::Avg,
+                &[arg_type.clone()],
+            )?;
+            // decimals are summed in the same type as try_sum(), which is
+            // decimal(p + 10, s) like spark
+            let sum_type = match &return_type {
+                DataType::Decimal128(..) => aggregate_function::AggregateFunction::return_type(
+                    &aggregate_function::AggregateFunction::Sum,
+                    &[arg_type],
+                )?,
+                _ => DataType::Int64,
+            };
+            Arc::new(try_avg::AggTryAvg::try_new(
+                Arc::new(TryCastExpr::new(children[0].clone(), sum_type.clone())),
+                return_type,
+                sum_type,
+            )?)
+        }
         AggFunction::Max => {
             let dt = children[0].data_type(input_schema)?;
             Arc::new(maxmin::AggMax::try_new(children[0].clone(), dt)?)
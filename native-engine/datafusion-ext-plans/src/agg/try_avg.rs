@@ -0,0 +1,260 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    any::Any,
+    fmt::{Debug, Formatter},
+    sync::{atomic::AtomicUsize, Arc},
+};
+
+use arrow::{array::*, datatypes::*};
+use datafusion::{
+    common::{
+        cast::{as_decimal128_array, as_int64_array},
+        Result, ScalarValue,
+    },
+    physical_expr::PhysicalExpr,
+};
+use datafusion_ext_commons::df_unimplemented_err;
+
+use crate::agg::{
+    acc::{AccumInitialValue, AccumStateValAddr, RefAccumStateRow},
+    count::AggCount,
+    try_sum::AggTrySum,
+    Agg, WithAggBufAddrs, WithMemTracking,
+};
+
+/// spark's try_avg(): same as avg() except that the result of the whole
+/// group is null if the underlying sum overflows.
+///
+/// unlike avg(), integral inputs are summed as int64 (instead of float64)
+/// so that overflow can be detected. decimal(p, s) inputs are summed as
+/// decimal(p + 10, s) like spark, and the average is rounded half up to the
+/// result scale.
+pub struct AggTryAvg {
+    child: Arc<dyn PhysicalExpr>,
+    data_type: DataType,
+    agg_sum: AggTrySum,
+    agg_count: AggCount,
+    accums_initial: Vec<AccumInitialValue>,
+    mem_used_tracker: AtomicUsize,
+}
+
+impl WithAggBufAddrs for AggTryAvg {
+    fn set_accum_state_val_addrs(&mut self, accum_state_val_addrs: &[AccumStateValAddr]) {
+        self.agg_sum
+            .set_accum_state_val_addrs(accum_state_val_addrs);
+        self.agg_count
+            .set_accum_state_val_addrs(&accum_state_val_addrs[2..]);
+    }
+}
+
+impl WithMemTracking for AggTryAvg {
+    fn mem_used_tracker(&self) -> &AtomicUsize {
+        &self.mem_used_tracker
+    }
+}
+
+impl AggTryAvg {
+    pub fn try_new(
+        child: Arc<dyn PhysicalExpr>,
+        data_type: DataType,
+        sum_type: DataType,
+    ) -> Result<Self> {
+        match (&data_type, &sum_type) {
+            (DataType::Decimal128(..), DataType::Decimal128(..)) => {}
+            (DataType::Float64, DataType::Int64) => {}
+            (other, _) => df_unimplemented_err!("unsupported data type in try_avg(): {other}")?,
+        }
+        let agg_sum = AggTrySum::try_new(child.clone(), sum_type)?;
+        let agg_count = AggCount::try_new(child.clone(), DataType::Int64)?;
+        let accums_initial = [agg_sum.accums_initial(), agg_count.accums_initial()].concat();
+
+        Ok(Self {
+            child,
+            data_type,
+            agg_sum,
+            agg_count,
+            accums_initial,
+            mem_used_tracker: AtomicUsize::new(0),
+        })
+    }
+}
+
+impl Debug for AggTryAvg {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "TryAvg({:?})", self.child)
+    }
+}
+
+impl Agg for AggTryAvg {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn exprs(&self) -> Vec<Arc<dyn PhysicalExpr>> {
+        vec![self.child.clone()]
+    }
+
+    fn with_new_exprs(&self, exprs: Vec<Arc<dyn PhysicalExpr>>) -> Result<Arc<dyn Agg>> {
+        Ok(Arc::new(Self::try_new(
+            exprs[0].clone(),
+            self.data_type.clone(),
+            self.agg_sum.data_type().clone(),
+        )?))
+    }
+
+    fn data_type(&self) -> &DataType {
+        &self.data_type
+    }
+
+    fn nullable(&self) -> bool {
+        true
+    }
+
+    fn accums_initial(&self) -> &[AccumInitialValue] {
+        &self.accums_initial
+    }
+
+    fn prepare_partial_args(&self, partial_inputs: &[ArrayRef]) -> Result<Vec<ArrayRef>> {
+        // cast arg1 to sum data type
+        self.agg_sum.prepare_partial_args(partial_inputs)
+    }
+
+    fn increase_acc_mem_used(&self, _acc: &mut RefAccumStateRow) {
+        // do nothing
+    }
+
+    fn partial_update(
+        &self,
+        acc: &mut RefAccumStateRow,
+        values: &[ArrayRef],
+        row_idx: usize,
+    ) -> Result<()> {
+        self.agg_sum.partial_update(acc, values, row_idx)?;
+        self.agg_count.partial_update(acc, values, row_idx)?;
+        Ok(())
+    }
+
+    fn partial_batch_update(
+        &self,
+        accs: &mut [RefAccumStateRow],
+        values: &[ArrayRef],
+    ) -> Result<()> {
+        self.agg_sum.partial_batch_update(accs, values)?;
+        self.agg_count.partial_batch_update(accs, values)?;
+        Ok(())
+    }
+
+    fn partial_update_all(&self, acc: &mut RefAccumStateRow, values: &[ArrayRef]) -> Result<()> {
+        self.agg_sum.partial_update_all(acc, values)?;
+        self.agg_count.partial_update_all(acc, values)?;
+        Ok(())
+    }
+
+    fn partial_merge(
+        &self,
+        acc1: &mut RefAccumStateRow,
+        acc2: &mut RefAccumStateRow,
+    ) -> Result<()> {
+        self.agg_sum.partial_merge(acc1, acc2)?;
+        self.agg_count.partial_merge(acc1, acc2)?;
+        Ok(())
+    }
+
+    fn partial_batch_merge(
+        &self,
+        accs: &mut [RefAccumStateRow],
+        merging_accs: &mut [RefAccumStateRow],
+    ) -> Result<()> {
+        self.agg_sum.partial_batch_merge(accs, merging_accs)?;
+        self.agg_count.partial_batch_merge(accs, merging_accs)?;
+        Ok(())
+    }
+
+    fn final_merge(&self, acc: &mut RefAccumStateRow) -> Result<ScalarValue> {
+        let sum = self.agg_sum.final_merge(acc)?;
+        let count = match self.agg_count.final_merge(acc)? {
+            ScalarValue::Int64(Some(count)) => count,
+            _ => unreachable!(),
+        };
+        Ok(match (sum, &self.data_type) {
+            (ScalarValue::Int64(sum), _) => {
+                ScalarValue::Float64(sum.and_then(|sum| avg_int64(sum, count)))
+            }
+            (ScalarValue::Decimal128(sum, _, sum_scale), &DataType::Decimal128(prec, scale)) => {
+                ScalarValue::Decimal128(
+                    sum.and_then(|sum| avg_decimal(sum, sum_scale, count, prec, scale)),
+                    prec,
+                    scale,
+                )
+            }
+            _ => unreachable!(),
+        })
+    }
+
+    fn final_batch_merge(&self, accs: &mut [RefAccumStateRow]) -> Result<ArrayRef> {
+        let sums = self.agg_sum.final_batch_merge(accs)?;
+        let counts = self.agg_count.final_batch_merge(accs)?;
+
+        let counts = as_int64_array(&counts)?;
+
+        if let (&DataType::Decimal128(_, sum_scale), &DataType::Decimal128(prec, scale)) =
+            (self.agg_sum.data_type(), self.data_type())
+        {
+            let sums = as_decimal128_array(&sums)?;
+            let avgs: Decimal128Array = sums
+                .iter()
+                .zip(counts.iter())
+                .map(|(sum, count)| avg_decimal(sum?, sum_scale, count?, prec, scale))
+                .collect();
+            Ok(Arc::new(avgs.with_precision_and_scale(prec, scale)?))
+        } else {
+            let sums = as_int64_array(&sums)?;
+            let avgs: Float64Array = sums
+                .iter()
+                .zip(counts.iter())
+                .map(|(sum, count)| avg_int64(sum?, count?))
+                .collect();
+            Ok(Arc::new(avgs))
+        }
+    }
+}
+
+fn avg_int64(sum: i64, count: i64) -> Option<f64> {
+    (count != 0).then(|| sum as f64 / count as f64)
+}
+
+/// divides the decimal sum by count, rounding half up to the result scale.
+/// returns null if count is zero or the result overflows its precision.
+fn avg_decimal(sum: i128, sum_scale: i8, count: i64, prec: u8, scale: i8) -> Option<i128> {
+    if count == 0 {
+        return None;
+    }
+    let rescale = i256::from_i128(10).checked_pow((scale - sum_scale) as u32)?;
+    let dividend = i256::from_i128(sum).checked_mul(rescale)?;
+    let divisor = i256::from_i128(count as i128);
+    let quotient = dividend.checked_div(divisor)?;
+    let remainder = dividend.checked_rem(divisor)?;
+    let round_up = remainder.wrapping_abs().wrapping_mul(i256::from_i128(2)) >= divisor;
+    let rounded = match (round_up, dividend.is_negative()) {
+        (true, false) => quotient.checked_add(i256::ONE)?,
+        (true, true) => quotient.checked_sub(i256::ONE)?,
+        (false, _) => quotient,
+    };
+    let avg = rounded.to_i128()?;
+    Decimal128Type::validate_decimal_precision(avg, prec)
+        .ok()
+        .map(|_| avg)
+}
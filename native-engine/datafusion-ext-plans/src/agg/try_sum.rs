@@ -0,0 +1,298 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    any::Any,
+    fmt::{Debug, Formatter},
+    sync::{atomic::AtomicUsize, Arc},
+};
+
+use arrow::{array::*, compute::kernels::nullif::nullif, datatypes::*};
+use datafusion::{
+    common::{Result, ScalarValue},
+    physical_expr::PhysicalExpr,
+};
+use datafusion_ext_commons::df_unimplemented_err;
+use paste::paste;
+
+use crate::agg::{
+    acc::{AccumInitialValue, AccumStateRow, AccumStateValAddr, RefAccumStateRow},
+    default_final_batch_merge_with_addr, default_final_merge_with_addr, Agg, WithAggBufAddrs,
+    WithMemTracking,
+};
+
+/// spark's try_sum(): same as sum() except that the result of the whole
+/// group is null if overflow happens during accumulation.
+///
+/// accumulator layout: [sum, overflowed], where overflowed is a boolean
+/// value whose valid bit is set once overflow is detected.
+pub struct AggTrySum {
+    child: Arc<dyn PhysicalExpr>,
+    data_type: DataType,
+    accums_initial: Vec<AccumInitialValue>,
+    accum_state_val_addr_sum: AccumStateValAddr,
+    accum_state_val_addr_overflowed: AccumStateValAddr,
+    partial_updater: fn(&Self, &mut RefAccumStateRow, &ArrayRef, usize),
+    partial_buf_merger: fn(&Self, &mut RefAccumStateRow, &mut RefAccumStateRow),
+    mem_used_tracker: AtomicUsize,
+}
+
+impl WithAggBufAddrs for AggTrySum {
+    fn set_accum_state_val_addrs(&mut self, accum_state_val_addrs: &[AccumStateValAddr]) {
+        self.accum_state_val_addr_sum = accum_state_val_addrs[0];
+        self.accum_state_val_addr_overflowed = accum_state_val_addrs[1];
+    }
+}
+
+impl WithMemTracking for AggTrySum {
+    fn mem_used_tracker(&self) -> &AtomicUsize {
+        &self.mem_used_tracker
+    }
+}
+
+impl AggTrySum {
+    pub fn try_new(child: Arc<dyn PhysicalExpr>, data_type: DataType) -> Result<Self> {
+        let accums_initial = vec![
+            AccumInitialValue::Scalar(ScalarValue::try_from(&data_type)?),
+            AccumInitialValue::Scalar(ScalarValue::Boolean(None)),
+        ];
+        let partial_updater = get_partial_updater(&data_type)?;
+        let partial_buf_merger = get_partial_buf_merger(&data_type)?;
+        Ok(Self {
+            child,
+            data_type,
+            accums_initial,
+            accum_state_val_addr_sum: AccumStateValAddr::default(),
+            accum_state_val_addr_overflowed: AccumStateValAddr::default(),
+            partial_updater,
+            partial_buf_merger,
+            mem_used_tracker: AtomicUsize::new(0),
+        })
+    }
+
+    fn is_overflowed(&self, acc: &RefAccumStateRow) -> bool {
+        if acc.is_fixed_valid(self.accum_state_val_addr_overflowed) {
+            return true;
+        }
+
+        // decimal sum overflows if it cannot fit into the target precision
+        match &self.data_type {
+            &DataType::Decimal128(prec, _) if acc.is_fixed_valid(self.accum_state_val_addr_sum) => {
+                let sum = acc.fixed_value::<i128>(self.accum_state_val_addr_sum);
+                Decimal128Type::validate_decimal_precision(sum, prec).is_err()
+            }
+            _ => false,
+        }
+    }
+}
+
+impl Debug for AggTrySum {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "TrySum({:?})", self.child)
+    }
+}
+
+impl Agg for AggTrySum {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn exprs(&self) -> Vec<Arc<dyn PhysicalExpr>> {
+        vec![self.child.clone()]
+    }
+
+    fn with_new_exprs(&self, exprs: Vec<Arc<dyn PhysicalExpr>>) -> Result<Arc<dyn Agg>> {
+        Ok(Arc::new(Self::try_new(
+            exprs[0].clone(),
+            self.data_type.clone(),
+        )?))
+    }
+
+    fn data_type(&self) -> &DataType {
+        &self.data_type
+    }
+
+    fn nullable(&self) -> bool {
+        true
+    }
+
+    fn accums_initial(&self) -> &[AccumInitialValue] {
+        &self.accums_initial
+    }
+
+    fn increase_acc_mem_used(&self, _acc: &mut RefAccumStateRow) {
+        // do nothing
+    }
+
+    fn prepare_partial_args(&self, partial_inputs: &[ArrayRef]) -> Result<Vec<ArrayRef>> {
+        // cast arg1 to target data type
+        Ok(vec![datafusion_ext_commons::cast::cast(
+            &partial_inputs[0],
+            &self.data_type,
+        )?])
+    }
+
+    fn partial_update(
+        &self,
+        acc: &mut RefAccumStateRow,
+        values: &[ArrayRef],
+        row_idx: usize,
+    ) -> Result<()> {
+        let partial_updater = self.partial_updater;
+        partial_updater(self, acc, &values[0], row_idx);
+        Ok(())
+    }
+
+    fn partial_batch_update(
+        &self,
+        accs: &mut [RefAccumStateRow],
+        values: &[ArrayRef],
+    ) -> Result<()> {
+        let partial_updater = self.partial_updater;
+        for (row_idx, acc) in accs.iter_mut().enumerate() {
+            partial_updater(self, acc, &values[0], row_idx);
+        }
+        Ok(())
+    }
+
+    fn partial_update_all(&self, acc: &mut RefAccumStateRow, values: &[ArrayRef]) -> Result<()> {
+        // arrow::compute::sum() wraps on overflow, so update row by row
+        let partial_updater = self.partial_updater;
+        for row_idx in 0..values[0].len() {
+            partial_updater(self, acc, &values[0], row_idx);
+        }
+        Ok(())
+    }
+
+    fn partial_merge(
+        &self,
+        acc1: &mut RefAccumStateRow,
+        acc2: &mut RefAccumStateRow,
+    ) -> Result<()> {
+        let partial_buf_merger = self.partial_buf_merger;
+        partial_buf_merger(self, acc1, acc2);
+        Ok(())
+    }
+
+    fn partial_batch_merge(
+        &self,
+        accs: &mut [RefAccumStateRow],
+        merging_accs: &mut [RefAccumStateRow],
+    ) -> Result<()> {
+        let partial_buf_merger = self.partial_buf_merger;
+        for (acc, merging_acc) in accs.iter_mut().zip(merging_accs) {
+            partial_buf_merger(self, acc, merging_acc);
+        }
+        Ok(())
+    }
+
+    fn final_merge(&self, acc: &mut RefAccumStateRow) -> Result<ScalarValue> {
+        if self.is_overflowed(acc) {
+            return ScalarValue::try_from(&self.data_type);
+        }
+        default_final_merge_with_addr(self, acc, self.accum_state_val_addr_sum)
+    }
+
+    fn final_batch_merge(&self, accs: &mut [RefAccumStateRow]) -> Result<ArrayRef> {
+        let overflowed = accs
+            .iter()
+            .map(|acc| Some(self.is_overflowed(acc)))
+            .collect::<BooleanArray>();
+        let sums = default_final_batch_merge_with_addr(self, accs, self.accum_state_val_addr_sum)?;
+        Ok(nullif(&sums, &overflowed)?)
+    }
+}
+
+fn partial_update_checked<T: ArrowNativeTypeOp>(
+    acc: &mut RefAccumStateRow,
+    addr: AccumStateValAddr,
+    overflowed_addr: AccumStateValAddr,
+    v: T,
+) {
+    if acc.is_fixed_valid(overflowed_addr) {
+        return;
+    }
+    if acc.is_fixed_valid(addr) {
+        match acc.fixed_value::<T>(addr).add_checked(v) {
+            Ok(sum) => acc.set_fixed_value::<T>(addr, sum),
+            Err(_) => acc.set_fixed_valid(overflowed_addr, true),
+        }
+    } else {
+        acc.set_fixed_value::<T>(addr, v);
+        acc.set_fixed_valid(addr, true);
+    }
+}
+
+fn get_partial_updater(
+    dt: &DataType,
+) -> Result<fn(&AggTrySum, &mut RefAccumStateRow, &ArrayRef, usize)> {
+    macro_rules! fn_fixed {
+        ($ty:ident) => {{
+            Ok(|this, acc, v, i| {
+                type TArray = paste! {[<$ty Array>]};
+                let value = v.as_any().downcast_ref::<TArray>().unwrap();
+                if value.is_valid(i) {
+                    partial_update_checked(
+                        acc,
+                        this.accum_state_val_addr_sum,
+                        this.accum_state_val_addr_overflowed,
+                        value.value(i),
+                    );
+                }
+            })
+        }};
+    }
+    match dt {
+        DataType::Null => Ok(|_, _, _, _| ()),
+        DataType::Int8 => fn_fixed!(Int8),
+        DataType::Int16 => fn_fixed!(Int16),
+        DataType::Int32 => fn_fixed!(Int32),
+        DataType::Int64 => fn_fixed!(Int64),
+        DataType::Decimal128(..) => fn_fixed!(Decimal128),
+        other => df_unimplemented_err!("unsupported data type in try_sum(): {other}"),
+    }
+}
+
+fn get_partial_buf_merger(
+    dt: &DataType,
+) -> Result<fn(&AggTrySum, &mut RefAccumStateRow, &mut RefAccumStateRow)> {
+    macro_rules! fn_fixed {
+        ($ty:ident) => {{
+            Ok(|this, acc1, acc2| {
+                type TType = paste! {[<$ty Type>]};
+                type TNative = <TType as ArrowPrimitiveType>::Native;
+                if acc2.is_fixed_valid(this.accum_state_val_addr_overflowed) {
+                    acc1.set_fixed_valid(this.accum_state_val_addr_overflowed, true);
+                } else if acc2.is_fixed_valid(this.accum_state_val_addr_sum) {
+                    let v = acc2.fixed_value::<TNative>(this.accum_state_val_addr_sum);
+                    partial_update_checked(
+                        acc1,
+                        this.accum_state_val_addr_sum,
+                        this.accum_state_val_addr_overflowed,
+                        v,
+                    );
+                }
+            })
+        }};
+    }
+    match dt {
+        DataType::Null => Ok(|_, _, _| ()),
+        DataType::Int8 => fn_fixed!(Int8),
+        DataType::Int16 => fn_fixed!(Int16),
+        DataType::Int32 => fn_fixed!(Int32),
+        DataType::Int64 => fn_fixed!(Int64),
+        DataType::Decimal128(..) => fn_fixed!(Decimal128),
+        other => df_unimplemented_err!("unsupported data type in try_sum(): {other}"),
+    }
+}
@@ -375,7 +375,7 @@ mod test {
     use std::sync::Arc;
 
     use arrow::{
-        array::Int32Array,
+        array::{Decimal128Array, Int32Array, Int64Array},
         datatypes::{DataType, Field, Schema},
         record_batch::RecordBatch,
     };
@@ -632,4 +632,117 @@ mod test {
         assert_batches_sorted_eq!(expected, &batches);
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_try_sum_avg_overflow() -> Result<()> {
+        MemManager::init(10000);
+
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("k", DataType::Int32, false),
+            Field::new("v", DataType::Int64, false),
+            Field::new("d", DataType::Decimal128(38, 0), false),
+        ]));
+        let max_decimal = 10i128.pow(38) - 1;
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from(vec![1, 1, 2, 2])),
+                Arc::new(Int64Array::from(vec![i64::MAX, 1, 1, 2])),
+                Arc::new(
+                    Decimal128Array::from(vec![max_decimal, 1, 1, 2])
+                        .with_precision_and_scale(38, 0)?,
+                ),
+            ],
+        )?;
+        let input = Arc::new(MemoryExec::try_new(&[vec![batch]], schema.clone(), None)?);
+
+        let aggs_agg_expr = vec![
+            AggExpr {
+                field_name: "try_sum_v".to_string(),
+                mode: Partial,
+                agg: create_agg(
+                    AggFunction::TrySum,
+                    &[phys_expr::col("v", &schema)?],
+                    &schema,
+                )?,
+            },
+            AggExpr {
+                field_name: "try_avg_v".to_string(),
+                mode: Partial,
+                agg: create_agg(
+                    AggFunction::TryAvg,
+                    &[phys_expr::col("v", &schema)?],
+                    &schema,
+                )?,
+            },
+            AggExpr {
+                field_name: "try_sum_d".to_string(),
+                mode: Partial,
+                agg: create_agg(
+                    AggFunction::TrySum,
+                    &[phys_expr::col("d", &schema)?],
+                    &schema,
+                )?,
+            },
+            AggExpr {
+                field_name: "try_avg_d".to_string(),
+                mode: Partial,
+                agg: create_agg(
+                    AggFunction::TryAvg,
+                    &[phys_expr::col("d", &schema)?],
+                    &schema,
+                )?,
+            },
+        ];
+
+        let agg_exec_partial = AggExec::try_new(
+            HashAgg,
+            vec![GroupingExpr {
+                field_name: "k".to_string(),
+                expr: Arc::new(Column::new("k", 0)),
+            }],
+            aggs_agg_expr.clone(),
+            0,
+            false,
+            input,
+        )?;
+
+        let agg_exec_final = AggExec::try_new(
+            HashAgg,
+            vec![GroupingExpr {
+                field_name: "k".to_string(),
+                expr: Arc::new(Column::new("k", 0)),
+            }],
+            aggs_agg_expr
+                .into_iter()
+                .map(|mut agg| {
+                    agg.agg = agg
+                        .agg
+                        .with_new_exprs(vec![Arc::new(phys_expr::Literal::new(
+                            ScalarValue::Null,
+                        ))])?;
+                    agg.mode = Final;
+                    Ok(agg)
+                })
+                .collect::<Result<_>>()?,
+            0,
+            false,
+            Arc::new(agg_exec_partial),
+        )?;
+
+        let session_ctx = SessionContext::new();
+        let task_ctx = session_ctx.task_ctx();
+        let output_final = agg_exec_final.execute(0, task_ctx)?;
+        let batches = common::collect(output_final).await?;
+        let expected = vec![
+            "+---+-----------+-----------+-----------+-----------+",
+            "| k | try_sum_v | try_avg_v | try_sum_d | try_avg_d |",
+            "+---+-----------+-----------+-----------+-----------+",
+            "| 1 |           |           |           |           |",
+            "| 2 | 3         | 1.5       | 3         | 1.5000    |",
+            "+---+-----------+-----------+-----------+-----------+",
+        ];
+        assert_batches_sorted_eq!(expected, &batches);
+        Ok(())
+    }
 }
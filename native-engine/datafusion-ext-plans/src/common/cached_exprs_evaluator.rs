@@ -34,13 +34,17 @@ use datafusion::{
         tree_node::{Transformed, TreeNode},
         Result, ScalarValue,
     },
+    logical_expr::{BuiltinScalarFunction, Volatility},
     physical_expr::{
         expressions::{CaseExpr, Column, Literal, NoOp, SCAndExpr, SCOrExpr},
-        scatter, PhysicalExpr, PhysicalExprRef,
+        scatter, PhysicalExpr, PhysicalExprRef, ScalarFunctionExpr,
     },
     physical_plan::ColumnarValue,
 };
 use datafusion_ext_commons::{cast::cast, uda::UserDefinedArray};
+use datafusion_ext_exprs::{
+    spark_coalesce::SparkCoalesceExpr, spark_udf_wrapper::SparkUDFWrapperExpr,
+};
 use itertools::Itertools;
 use parking_lot::Mutex;
 
@@ -194,9 +198,10 @@ fn transform_to_cached_exprs(exprs: &[PhysicalExprRef]) -> Result<(Vec<PhysicalE
         }
 
         // insert exprs with occurrences more than its parent
+        // volatile exprs must be evaluated separately for each occurrence
         let expr_key = ExprKey(expr.clone());
         let current_count = expr_counts.get(&expr_key).cloned().unwrap_or(0);
-        if current_count > parent_count {
+        if current_count > parent_count && !is_volatile_expr(expr) {
             dups.insert(expr_key);
         }
 
@@ -204,6 +209,7 @@ fn transform_to_cached_exprs(exprs: &[PhysicalExprRef]) -> Result<(Vec<PhysicalE
         if expr.as_any().downcast_ref::<CaseExpr>().is_some()
             || expr.as_any().downcast_ref::<SCAndExpr>().is_some()
             || expr.as_any().downcast_ref::<SCOrExpr>().is_some()
+            || expr.as_any().downcast_ref::<SparkCoalesceExpr>().is_some()
         {
             // short circuiting expression - only first child can be cached
             // first `when` expr can also be cached
@@ -251,6 +257,7 @@ fn transform_to_cached_exprs(exprs: &[PhysicalExprRef]) -> Result<(Vec<PhysicalE
         let transformed_expr = if expr.as_any().downcast_ref::<CaseExpr>().is_some()
             || expr.as_any().downcast_ref::<SCAndExpr>().is_some()
             || expr.as_any().downcast_ref::<SCOrExpr>().is_some()
+            || expr.as_any().downcast_ref::<SparkCoalesceExpr>().is_some()
         {
             // short circuiting expression - only first child can be cached
             // first `when` expr can also be cached
@@ -288,6 +295,24 @@ fn transform_to_cached_exprs(exprs: &[PhysicalExprRef]) -> Result<(Vec<PhysicalE
     Ok((transformed_exprs, cache))
 }
 
+/// returns true if the expr or any of its children is nondeterministic (like
+/// rand() and uuid()), such exprs must not be cached or constant-folded.
+pub fn is_volatile_expr(expr: &PhysicalExprRef) -> bool {
+    let volatile = if let Some(func) = expr.as_any().downcast_ref::<ScalarFunctionExpr>() {
+        func.name()
+            .to_lowercase()
+            .parse::<BuiltinScalarFunction>()
+            .map(|fun| fun.volatility() == Volatility::Volatile)
+            .unwrap_or(false)
+    } else if let Some(udf) = expr.as_any().downcast_ref::<SparkUDFWrapperExpr>() {
+        // spark udfs are evaluated in jvm, use spark's deterministic flag
+        !udf.deterministic
+    } else {
+        false
+    };
+    volatile || expr.children().iter().any(is_volatile_expr)
+}
+
 /// A physical expr wrapper to use in HashSet/HashMap
 #[derive(Clone, Debug, Hash)]
 struct ExprKey(PhysicalExprRef);
@@ -488,3 +513,214 @@ fn filter_one_pred(
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::{
+        any::Any,
+        fmt::{Display, Formatter},
+        hash::Hasher,
+        sync::{
+            atomic::{AtomicUsize, Ordering::SeqCst},
+            Arc,
+        },
+    };
+
+    use arrow::{
+        array::{ArrayRef, Int32Array},
+        datatypes::{DataType, Field, Schema},
+        record_batch::RecordBatch,
+    };
+    use datafusion::{
+        common::{Result, ScalarValue},
+        execution::context::ExecutionProps,
+        logical_expr::{BuiltinScalarFunction, Operator},
+        physical_expr::{
+            expressions::{BinaryExpr, Column, Literal},
+            functions::create_physical_fun,
+            PhysicalExpr, PhysicalExprRef, ScalarFunctionExpr,
+        },
+        physical_plan::ColumnarValue,
+    };
+
+    use crate::common::cached_exprs_evaluator::{is_volatile_expr, CachedExprsEvaluator};
+
+    /// wraps an expr and counts how many times it is evaluated
+    #[derive(Debug)]
+    struct CountingExpr {
+        inner: PhysicalExprRef,
+        num_evaluated: Arc<AtomicUsize>,
+    }
+
+    impl Display for CountingExpr {
+        fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+            write!(f, "Counting({})", self.inner)
+        }
+    }
+
+    impl PartialEq<dyn Any> for CountingExpr {
+        fn eq(&self, other: &dyn Any) -> bool {
+            other
+                .downcast_ref::<Self>()
+                .map(|other| self.inner.eq(other.inner.as_any()))
+                .unwrap_or(false)
+        }
+    }
+
+    impl PhysicalExpr for CountingExpr {
+        fn as_any(&self) -> &dyn Any {
+            self
+        }
+
+        fn data_type(&self, input_schema: &Schema) -> Result<DataType> {
+            self.inner.data_type(input_schema)
+        }
+
+        fn nullable(&self, input_schema: &Schema) -> Result<bool> {
+            self.inner.nullable(input_schema)
+        }
+
+        fn evaluate(&self, batch: &RecordBatch) -> Result<ColumnarValue> {
+            self.num_evaluated.fetch_add(1, SeqCst);
+            self.inner.evaluate(batch)
+        }
+
+        fn children(&self) -> Vec<PhysicalExprRef> {
+            vec![self.inner.clone()]
+        }
+
+        fn with_new_children(
+            self: Arc<Self>,
+            children: Vec<PhysicalExprRef>,
+        ) -> Result<PhysicalExprRef> {
+            Ok(Arc::new(Self {
+                inner: children[0].clone(),
+                num_evaluated: self.num_evaluated.clone(),
+            }))
+        }
+
+        fn dyn_hash(&self, state: &mut dyn Hasher) {
+            self.inner.dyn_hash(state);
+        }
+    }
+
+    fn binary(l: PhysicalExprRef, op: Operator, r: PhysicalExprRef) -> PhysicalExprRef {
+        Arc::new(BinaryExpr::new(l, op, r))
+    }
+
+    fn lit(v: i32) -> PhysicalExprRef {
+        Arc::new(Literal::new(ScalarValue::Int32(Some(v))))
+    }
+
+    #[test]
+    fn test_shared_subexpr_evaluated_once() -> Result<()> {
+        let input_schema = Arc::new(Schema::new(vec![
+            Field::new("a", DataType::Int32, false),
+            Field::new("b", DataType::Int32, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            input_schema,
+            vec![
+                Arc::new(Int32Array::from(vec![1, 2, 3])),
+                Arc::new(Int32Array::from(vec![4, 5, 6])),
+            ],
+        )?;
+        let output_schema = Arc::new(Schema::new(vec![
+            Field::new("x", DataType::Int32, false),
+            Field::new("y", DataType::Int32, false),
+        ]));
+
+        // SELECT a+b AS x, (a+b)*2 AS y [WHERE a+b > 5]
+        let num_evaluated = Arc::new(AtomicUsize::new(0));
+        let a_plus_b: PhysicalExprRef = Arc::new(CountingExpr {
+            inner: binary(
+                Arc::new(Column::new("a", 0)),
+                Operator::Plus,
+                Arc::new(Column::new("b", 1)),
+            ),
+            num_evaluated: num_evaluated.clone(),
+        });
+        let projection_exprs = vec![
+            a_plus_b.clone(),
+            binary(a_plus_b.clone(), Operator::Multiply, lit(2)),
+        ];
+
+        let evaluator =
+            CachedExprsEvaluator::try_new(vec![], projection_exprs.clone(), output_schema.clone())?;
+        let output = evaluator.filter_project(&batch)?;
+        assert_eq!(num_evaluated.load(SeqCst), 1);
+        let expected: Vec<ArrayRef> = vec![
+            Arc::new(Int32Array::from(vec![5, 7, 9])),
+            Arc::new(Int32Array::from(vec![10, 14, 18])),
+        ];
+        assert_eq!(output.columns(), &expected);
+
+        // shared between filter and projection
+        num_evaluated.store(0, SeqCst);
+        let filter_exprs = vec![binary(a_plus_b.clone(), Operator::Gt, lit(5))];
+        let evaluator =
+            CachedExprsEvaluator::try_new(filter_exprs, projection_exprs, output_schema)?;
+        let output = evaluator.filter_project(&batch)?;
+        assert_eq!(num_evaluated.load(SeqCst), 1);
+        let expected: Vec<ArrayRef> = vec![
+            Arc::new(Int32Array::from(vec![7, 9])),
+            Arc::new(Int32Array::from(vec![14, 18])),
+        ];
+        assert_eq!(output.columns(), &expected);
+        Ok(())
+    }
+
+    fn rand() -> Result<PhysicalExprRef> {
+        Ok(Arc::new(ScalarFunctionExpr::new(
+            "random",
+            create_physical_fun(&BuiltinScalarFunction::Random, &ExecutionProps::new())?,
+            vec![],
+            DataType::Float64,
+            None,
+            false,
+        )))
+    }
+
+    #[test]
+    fn test_volatile_exprs_not_cached() -> Result<()> {
+        let input_schema = Arc::new(Schema::new(vec![Field::new("a", DataType::Int32, false)]));
+        let batch = RecordBatch::try_new(
+            input_schema,
+            vec![Arc::new(Int32Array::from_iter_values(0..100))],
+        )?;
+        let output_schema = Arc::new(Schema::new(vec![
+            Field::new("x", DataType::Float64, false),
+            Field::new("y", DataType::Float64, false),
+        ]));
+
+        assert!(is_volatile_expr(&rand()?));
+        assert!(is_volatile_expr(&binary(rand()?, Operator::Plus, rand()?)));
+        assert!(!is_volatile_expr(&binary(
+            Arc::new(Column::new("a", 0)),
+            Operator::Plus,
+            lit(1)
+        )));
+
+        // SELECT rand() AS x, rand() AS y
+        let evaluator =
+            CachedExprsEvaluator::try_new(vec![], vec![rand()?, rand()?], output_schema.clone())?;
+        let output = evaluator.filter_project(&batch)?;
+        assert_ne!(output.column(0), output.column(1));
+
+        // SELECT counting(rand()) AS x, counting(rand()) AS y
+        let num_evaluated = Arc::new(AtomicUsize::new(0));
+        let counting_rand: PhysicalExprRef = Arc::new(CountingExpr {
+            inner: rand()?,
+            num_evaluated: num_evaluated.clone(),
+        });
+        let evaluator = CachedExprsEvaluator::try_new(
+            vec![],
+            vec![counting_rand.clone(), counting_rand.clone()],
+            output_schema,
+        )?;
+        let output = evaluator.filter_project(&batch)?;
+        assert_eq!(num_evaluated.load(SeqCst), 2);
+        assert_ne!(output.column(0), output.column(1));
+        Ok(())
+    }
+}
@@ -16,23 +16,32 @@
 // under the License.
 
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{BTreeSet, HashMap, HashSet},
     sync::Arc,
 };
 
-use arrow::record_batch::RecordBatch;
+use arrow::{
+    array::{ArrayRef, StructArray},
+    datatypes::{DataType, Fields, Schema, SchemaRef},
+    record_batch::{RecordBatch, RecordBatchOptions},
+};
 use datafusion::{
     common::{
+        cast::as_struct_array,
         tree_node::{Transformed, TreeNode},
-        Result,
+        Result, ScalarValue,
     },
     execution::{SendableRecordBatchStream, TaskContext},
     physical_expr::{expressions::Column, utils::collect_columns, PhysicalExprRef},
     physical_plan::{stream::RecordBatchStreamAdapter, ExecutionPlan},
 };
+use datafusion_ext_commons::df_execution_err;
+use datafusion_ext_exprs::get_indexed_field::GetIndexedFieldExpr;
 use futures::StreamExt;
 use itertools::Itertools;
 
+use crate::project_exec::ProjectExec;
+
 pub trait ExecuteWithColumnPruning {
     fn execute_projected(
         &self,
@@ -40,6 +49,21 @@ pub trait ExecuteWithColumnPruning {
         context: Arc<TaskContext>,
         projection: &[usize],
     ) -> Result<SendableRecordBatchStream>;
+
+    /// same as execute_projected(), and struct columns with Some(fields) in
+    /// nested_projection are narrowed to the given fields. by default the full
+    /// structs are produced and narrowed afterwards, plans able to skip the
+    /// unreferenced fields should override it.
+    fn execute_nested_projected(
+        &self,
+        partition: usize,
+        context: Arc<TaskContext>,
+        projection: &[usize],
+        nested_projection: &[Option<Vec<usize>>],
+    ) -> Result<SendableRecordBatchStream> {
+        let input = self.execute_projected(partition, context, projection)?;
+        project_struct_fields(input, nested_projection)
+    }
 }
 
 impl ExecuteWithColumnPruning for dyn ExecutionPlan {
@@ -58,6 +82,25 @@ impl ExecuteWithColumnPruning for dyn ExecutionPlan {
                 });
         Ok(Box::pin(RecordBatchStreamAdapter::new(schema, stream)))
     }
+
+    fn execute_nested_projected(
+        &self,
+        partition: usize,
+        context: Arc<TaskContext>,
+        projection: &[usize],
+        nested_projection: &[Option<Vec<usize>>],
+    ) -> Result<SendableRecordBatchStream> {
+        if let Some(project) = self.as_any().downcast_ref::<ProjectExec>() {
+            return project.execute_nested_projected(
+                partition,
+                context,
+                projection,
+                nested_projection,
+            );
+        }
+        let input = self.execute_projected(partition, context, projection)?;
+        project_struct_fields(input, nested_projection)
+    }
 }
 
 pub fn prune_columns(exprs: &[PhysicalExprRef]) -> Result<(Vec<PhysicalExprRef>, Vec<usize>)> {
@@ -94,3 +137,167 @@ pub fn prune_columns(exprs: &[PhysicalExprRef]) -> Result<(Vec<PhysicalExprRef>,
         required_columns.into_iter().map(|c| c.index()).collect(),
     ))
 }
+
+/// prunes struct columns which are only referenced by field access (like
+/// `struct_col.field_a`) down to the referenced fields. returns the exprs
+/// rewritten to access the narrowed structs, and the referenced fields of
+/// each input column (None if the column is not narrowed).
+pub fn prune_struct_fields(
+    exprs: &[PhysicalExprRef],
+    input_schema: &Schema,
+) -> Result<(Vec<PhysicalExprRef>, Vec<Option<Vec<usize>>>)> {
+    // referenced fields of each struct column, None if the whole column is
+    // referenced
+    fn collect_fields(expr: &PhysicalExprRef, fields: &mut [Option<BTreeSet<usize>>]) {
+        if let Some((column_idx, field_idx)) = get_struct_field(expr) {
+            if let Some(column_fields) = &mut fields[column_idx] {
+                column_fields.insert(field_idx);
+            }
+            return;
+        }
+        if let Some(column) = expr.as_any().downcast_ref::<Column>() {
+            fields[column.index()] = None;
+            return;
+        }
+        for child in expr.children() {
+            collect_fields(&child, fields);
+        }
+    }
+    let mut referenced_fields: Vec<Option<BTreeSet<usize>>> = input_schema
+        .fields()
+        .iter()
+        .map(|field| matches!(field.data_type(), DataType::Struct(_)).then(BTreeSet::new))
+        .collect();
+    for expr in exprs {
+        collect_fields(expr, &mut referenced_fields);
+    }
+
+    let nested_projection: Vec<Option<Vec<usize>>> = referenced_fields
+        .into_iter()
+        .zip(input_schema.fields())
+        .map(|(fields, field)| match (fields, field.data_type()) {
+            (Some(fields), DataType::Struct(struct_fields))
+                if !fields.is_empty() && fields.len() < struct_fields.len() =>
+            {
+                Some(fields.into_iter().collect())
+            }
+            _ => None,
+        })
+        .collect();
+
+    let mapped_exprs: Vec<PhysicalExprRef> = exprs
+        .iter()
+        .map(|expr| {
+            expr.clone().transform_down(&|node: PhysicalExprRef| {
+                let mapped_field_idx =
+                    get_struct_field(&node).and_then(|(column_idx, field_idx)| {
+                        let fields = nested_projection[column_idx].as_ref()?;
+                        fields.iter().position(|&i| i == field_idx)
+                    });
+                Ok(match mapped_field_idx {
+                    Some(mapped_idx) => {
+                        let get_field = node
+                            .as_any()
+                            .downcast_ref::<GetIndexedFieldExpr>()
+                            .expect("GetIndexedFieldExpr");
+                        Transformed::Yes(Arc::new(GetIndexedFieldExpr::new(
+                            get_field.arg().clone(),
+                            ScalarValue::Int32(Some(mapped_idx as i32)),
+                        )))
+                    }
+                    None => Transformed::No(node),
+                })
+            })
+        })
+        .collect::<Result<_>>()?;
+    Ok((mapped_exprs, nested_projection))
+}
+
+/// returns (column index, field index) if expr is a field access of a column
+fn get_struct_field(expr: &PhysicalExprRef) -> Option<(usize, usize)> {
+    let get_field = expr.as_any().downcast_ref::<GetIndexedFieldExpr>()?;
+    let column = get_field.arg().as_any().downcast_ref::<Column>()?;
+    match get_field.key() {
+        &ScalarValue::Int32(Some(field_idx)) if field_idx >= 0 => {
+            Some((column.index(), field_idx as usize))
+        }
+        _ => None,
+    }
+}
+
+/// narrows a struct type to the given fields
+pub fn narrow_struct_type(data_type: &DataType, fields: &[usize]) -> Result<DataType> {
+    match data_type {
+        DataType::Struct(struct_fields) => Ok(DataType::Struct(
+            fields
+                .iter()
+                .map(|&i| struct_fields[i].clone())
+                .collect::<Fields>(),
+        )),
+        other => df_execution_err!("cannot narrow fields of non-struct type: {other}"),
+    }
+}
+
+/// narrows struct fields of the schema with nested_projection
+pub fn project_schema_struct_fields(
+    schema: &Schema,
+    nested_projection: &[Option<Vec<usize>>],
+) -> Result<SchemaRef> {
+    let fields = schema
+        .fields()
+        .iter()
+        .zip(nested_projection)
+        .map(|(field, fields)| match fields {
+            Some(fields) => {
+                let data_type = narrow_struct_type(field.data_type(), fields)?;
+                Ok(Arc::new(field.as_ref().clone().with_data_type(data_type)))
+            }
+            None => Ok(field.clone()),
+        })
+        .collect::<Result<Fields>>()?;
+    Ok(Arc::new(Schema::new(fields)))
+}
+
+/// narrows struct columns of the input stream with nested_projection
+pub fn project_struct_fields(
+    input: SendableRecordBatchStream,
+    nested_projection: &[Option<Vec<usize>>],
+) -> Result<SendableRecordBatchStream> {
+    if nested_projection.iter().all(|fields| fields.is_none()) {
+        return Ok(input);
+    }
+    let nested_projection = nested_projection.to_vec();
+    let schema = project_schema_struct_fields(&input.schema(), &nested_projection)?;
+    let output_schema = schema.clone();
+    let stream = input.map(move |batch_result: Result<RecordBatch>| {
+        let batch = batch_result?;
+        let columns = batch
+            .columns()
+            .iter()
+            .zip(&nested_projection)
+            .zip(output_schema.fields())
+            .map(
+                |((column, fields), output_field)| match (fields, output_field.data_type()) {
+                    (Some(fields), DataType::Struct(output_fields)) => {
+                        let struct_array = as_struct_array(column)?;
+                        Ok(Arc::new(StructArray::try_new(
+                            output_fields.clone(),
+                            fields
+                                .iter()
+                                .map(|&i| struct_array.column(i).clone())
+                                .collect(),
+                            struct_array.nulls().cloned(),
+                        )?) as ArrayRef)
+                    }
+                    _ => Ok(column.clone()),
+                },
+            )
+            .collect::<Result<Vec<_>>>()?;
+        Ok(RecordBatch::try_new_with_options(
+            output_schema.clone(),
+            columns,
+            &RecordBatchOptions::new().with_row_count(Some(batch.num_rows())),
+        )?)
+    });
+    Ok(Box::pin(RecordBatchStreamAdapter::new(schema, stream)))
+}
@@ -18,7 +18,11 @@
 use std::io::{BufReader, Cursor, Read, Take, Write};
 
 use arrow::{datatypes::SchemaRef, record_batch::RecordBatch};
-use blaze_jni_bridge::{conf, conf::StringConf};
+use blaze_jni_bridge::{
+    conf,
+    conf::{BooleanConf, IntConf, StringConf},
+    is_jni_bridge_inited,
+};
 use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 use datafusion::common::Result;
 use datafusion_ext_commons::{
@@ -27,48 +31,69 @@ use datafusion_ext_commons::{
 };
 
 pub const DEFAULT_SHUFFLE_COMPRESSION_TARGET_BUF_SIZE: usize = 4194304;
-const ZSTD_LEVEL: i32 = 1;
 
+/// writes batches as a sequence of blocks, each block is a 4-byte header
+/// (highest bit for compression, the rest for block length) followed by
+/// length-prefixed batches encoded with batch_serde. note that this is not
+/// the arrow ipc format, blocks are only decoded by IpcCompressionReader, so
+/// there is no ipc metadata version or buffer alignment to configure.
 pub struct IpcCompressionWriter<W: Write> {
     output: W,
     compressed: bool,
-    buf: Box<dyn CompressibleBlockWriter>,
-    buf_empty: bool,
+    buf: Option<Box<dyn CompressibleBlockWriter>>,
+    spare_block_buf: Vec<u8>,
+    batch_buf: Vec<u8>,
+    uncompressed_size: usize,
 }
 unsafe impl<W: Write> Send for IpcCompressionWriter<W> {}
 
 impl<W: Write> IpcCompressionWriter<W> {
     pub fn new(output: W, compressed: bool) -> Self {
+        Self::with_capacity_hint(output, compressed, 0)
+    }
+
+    /// Create a writer whose block buffer is preallocated with the hinted
+    /// capacity (capped by the target block size). block buffers are recycled
+    /// after each flush, so a writer reused for many partitions only allocates
+    /// once.
+    pub fn with_capacity_hint(output: W, compressed: bool, capacity_hint: usize) -> Self {
+        let capacity = capacity_hint.min(DEFAULT_SHUFFLE_COMPRESSION_TARGET_BUF_SIZE);
         Self {
             output,
             compressed,
-            buf: create_block_writer(compressed),
-            buf_empty: true,
+            buf: None,
+            spare_block_buf: Vec::with_capacity(capacity),
+            batch_buf: vec![],
+            uncompressed_size: 0,
         }
     }
 
-    /// Write a batch, returning uncompressed bytes size
+    /// Write a batch
     pub fn write_batch(&mut self, batch: RecordBatch) -> Result<()> {
-        let mut batch_buf = vec![];
-        write_one_batch(&batch, &mut Cursor::new(&mut batch_buf))?;
-        self.buf.write_all(&mut batch_buf)?;
-        self.buf_empty = false;
-        drop(batch_buf);
-
-        if self.buf.buf_len() as f64 >= DEFAULT_SHUFFLE_COMPRESSION_TARGET_BUF_SIZE as f64 * 0.9 {
+        self.batch_buf.clear();
+        write_one_batch(&batch, &mut Cursor::new(&mut self.batch_buf))?;
+        self.uncompressed_size += self.batch_buf.len();
+
+        let compressed = self.compressed;
+        let spare_block_buf = &mut self.spare_block_buf;
+        let buf = self.buf.get_or_insert_with(|| {
+            create_block_writer(compressed, std::mem::take(spare_block_buf))
+        });
+        buf.write_all(&self.batch_buf)?;
+
+        if buf.buf_len() as f64 >= DEFAULT_SHUFFLE_COMPRESSION_TARGET_BUF_SIZE as f64 * 0.9 {
             self.flush()?;
         }
         Ok(())
     }
 
     pub fn flush(&mut self) -> Result<()> {
-        if !self.buf_empty {
-            // finish current buf and open next
-            let next_buf = create_block_writer(self.compressed);
-            let block_data = std::mem::replace(&mut self.buf, next_buf).finish()?;
+        if let Some(buf) = self.buf.take() {
+            // finish current buf, the next buf is opened on next write
+            let block_data = buf.finish()?;
             self.output.write_all(&block_data)?;
             self.output.flush()?;
-            self.buf_empty = true;
+            self.spare_block_buf = block_data;
         }
         Ok(())
     }
@@ -77,6 +102,19 @@ impl<W: Write> IpcCompressionWriter<W> {
         self.flush()?;
         Ok(self.output)
     }
+
+    pub fn inner(&self) -> &W {
+        &self.output
+    }
+
+    pub fn inner_mut(&mut self) -> &mut W {
+        &mut self.output
+    }
+
+    /// Total size of ipc-encoded batches before compression
+    pub fn uncompressed_size(&self) -> usize {
+        self.uncompressed_size
+    }
 }
 
 pub struct IpcCompressionReader<R: Read + 'static> {
@@ -135,6 +173,36 @@ impl<R: Read> IpcCompressionReader<R> {
     }
 }
 
+/// decodes all batches in a single block written by IpcCompressionWriter,
+/// returns an error if the block header or content is truncated
+pub fn decode_shuffle_block(mut input: impl Read, schema: &SchemaRef) -> Result<Vec<RecordBatch>> {
+    let header = match input.read_u32::<LittleEndian>() {
+        Ok(value) => Header::from_u32(value),
+        Err(err) => return df_execution_err!("error reading shuffle block header: {err}"),
+    };
+    let mut block_data = vec![0u8; header.block_len];
+    if let Err(err) = input.read_exact(&mut block_data) {
+        return df_execution_err!(
+            "error reading shuffle block (expected {} bytes): {err}",
+            header.block_len
+        );
+    }
+
+    let mut block_reader: Box<dyn Read> = if header.compressed {
+        Box::new(IoCompressionReader::try_new(
+            io_compression_codec(),
+            Cursor::new(block_data),
+        )?)
+    } else {
+        Box::new(Cursor::new(block_data))
+    };
+    let mut batches = vec![];
+    while let Some(batch) = read_one_batch(&mut block_reader, schema)? {
+        batches.push(batch);
+    }
+    Ok(batches)
+}
+
 #[derive(Clone, Copy)]
 struct Header {
     compressed: bool,
@@ -168,9 +236,9 @@ trait CompressibleBlockWriter: Write {
 struct ZWriter(IoCompressionWriter<Vec<u8>>);
 
 impl ZWriter {
-    fn new() -> Self {
+    fn new(block_buf: Vec<u8>) -> Self {
         Self(
-            IoCompressionWriter::try_new(io_compression_codec(), vec![0u8; 4])
+            IoCompressionWriter::try_new(io_compression_codec(), block_buf)
                 .expect("error creating compression encoder"),
         )
     }
@@ -204,8 +272,8 @@ impl CompressibleBlockWriter for ZWriter {
 struct UncompressedWriter(Vec<u8>);
 
 impl UncompressedWriter {
-    fn new() -> Self {
-        Self(vec![0u8; 4])
+    fn new(block_buf: Vec<u8>) -> Self {
+        Self(block_buf)
     }
 }
 
@@ -252,11 +320,18 @@ impl<R: Read> CompressibleBlockReader<R> for Take<R> {
     }
 }
 
-fn create_block_writer(compressed: bool) -> Box<dyn CompressibleBlockWriter> {
+fn create_block_writer(
+    compressed: bool,
+    mut block_buf: Vec<u8>,
+) -> Box<dyn CompressibleBlockWriter> {
+    // reserve space for header
+    block_buf.clear();
+    block_buf.extend_from_slice(&[0u8; 4]);
+
     if compressed {
-        Box::new(ZWriter::new())
+        Box::new(ZWriter::new(block_buf))
     } else {
-        Box::new(UncompressedWriter::new())
+        Box::new(UncompressedWriter::new(block_buf))
     }
 }
 
@@ -285,6 +360,7 @@ fn create_block_reader<R: Read + 'static>(
 
 enum IoCompressionWriter<W: Write> {
     LZ4(lz4_flex::frame::FrameEncoder<W>),
+    Snappy(snap::write::FrameEncoder<W>),
     ZSTD(zstd::Encoder<'static, W>),
 }
 
@@ -292,7 +368,11 @@ impl<W: Write> IoCompressionWriter<W> {
     fn try_new(codec: &str, inner: W) -> Result<Self> {
         match codec {
             "lz4" => Ok(Self::LZ4(lz4_flex::frame::FrameEncoder::new(inner))),
-            "zstd" => Ok(Self::ZSTD(zstd::Encoder::new(inner, ZSTD_LEVEL)?)),
+            "snappy" => Ok(Self::Snappy(snap::write::FrameEncoder::new(inner))),
+            "zstd" => Ok(Self::ZSTD(zstd::Encoder::new(
+                inner,
+                io_compression_zstd_level(),
+            )?)),
             _ => df_execution_err!("unsupported codec: {}", codec),
         }
     }
@@ -300,6 +380,7 @@ impl<W: Write> IoCompressionWriter<W> {
     fn get_ref(&self) -> &W {
         match self {
             IoCompressionWriter::LZ4(w) => w.get_ref(),
+            IoCompressionWriter::Snappy(w) => w.get_ref(),
             IoCompressionWriter::ZSTD(w) => w.get_ref(),
         }
     }
@@ -307,6 +388,9 @@ impl<W: Write> IoCompressionWriter<W> {
     fn finish(self) -> Result<W> {
         match self {
             IoCompressionWriter::LZ4(w) => Ok(w.finish().or_else(|e| df_execution_err!("{e}"))?),
+            IoCompressionWriter::Snappy(w) => Ok(w
+                .into_inner()
+                .or_else(|e| df_execution_err!("{}", e.error()))?),
             IoCompressionWriter::ZSTD(w) => Ok(w.finish().or_else(|e| df_execution_err!("{e}"))?),
         }
     }
@@ -316,6 +400,7 @@ impl<W: Write> Write for IoCompressionWriter<W> {
     fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
         match self {
             IoCompressionWriter::LZ4(w) => w.write(buf),
+            IoCompressionWriter::Snappy(w) => w.write(buf),
             IoCompressionWriter::ZSTD(w) => w.write(buf),
         }
     }
@@ -323,6 +408,7 @@ impl<W: Write> Write for IoCompressionWriter<W> {
     fn flush(&mut self) -> std::io::Result<()> {
         match self {
             IoCompressionWriter::LZ4(w) => w.flush(),
+            IoCompressionWriter::Snappy(w) => w.flush(),
             IoCompressionWriter::ZSTD(w) => w.flush(),
         }
     }
@@ -330,6 +416,7 @@ impl<W: Write> Write for IoCompressionWriter<W> {
 
 enum IoCompressionReader<'a, R: Read> {
     LZ4(lz4_flex::frame::FrameDecoder<R>),
+    Snappy(snap::read::FrameDecoder<R>),
     ZSTD(zstd::Decoder<'a, BufReader<R>>),
 }
 
@@ -337,6 +424,7 @@ impl<R: Read> IoCompressionReader<'_, R> {
     fn try_new(codec: &str, inner: R) -> Result<Self> {
         match codec {
             "lz4" => Ok(Self::LZ4(lz4_flex::frame::FrameDecoder::new(inner))),
+            "snappy" => Ok(Self::Snappy(snap::read::FrameDecoder::new(inner))),
             "zstd" => Ok(Self::ZSTD(zstd::Decoder::new(inner)?)),
             _ => df_execution_err!("unsupported codec: {}", codec),
         }
@@ -345,6 +433,7 @@ impl<R: Read> IoCompressionReader<'_, R> {
     fn finish_into_inner(self) -> Result<R> {
         match self {
             Self::LZ4(r) => Ok(r.into_inner()),
+            Self::Snappy(r) => Ok(r.into_inner()),
             Self::ZSTD(r) => Ok(r.finish().into_inner()),
         }
     }
@@ -354,11 +443,261 @@ impl<R: Read> Read for IoCompressionReader<'_, R> {
     fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
         match self {
             Self::LZ4(r) => r.read(buf),
+            Self::Snappy(r) => r.read(buf),
             Self::ZSTD(r) => r.read(buf),
         }
     }
 }
 
 fn io_compression_codec() -> &'static str {
-    conf::SPARK_IO_COMPRESSION_CODEC.value().unwrap()
+    if is_jni_bridge_inited() {
+        conf::SPARK_IO_COMPRESSION_CODEC.value().unwrap()
+    } else {
+        "lz4" // for testing
+    }
+}
+
+fn io_compression_zstd_level() -> i32 {
+    if is_jni_bridge_inited() {
+        conf::SPARK_IO_COMPRESSION_ZSTD_LEVEL.value().unwrap()
+    } else {
+        1 // for testing
+    }
+}
+
+/// whether shuffle output is compressed, same as spark.shuffle.compress.
+/// readers decide by block headers, so they need not know this setting
+pub fn shuffle_compression_enabled() -> bool {
+    if is_jni_bridge_inited() {
+        conf::SPARK_SHUFFLE_COMPRESS.value().unwrap()
+    } else {
+        true // for testing
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        io::{Cursor, Read, Write},
+        sync::Arc,
+    };
+
+    use arrow::{
+        array::{Array, DictionaryArray, Int32Array, StringArray},
+        compute::cast,
+        datatypes::{DataType, Field, Int32Type, Schema},
+        record_batch::RecordBatch,
+    };
+    use datafusion::common::Result;
+    use datafusion_ext_commons::io::write_one_batch;
+
+    use crate::common::ipc_compression::{
+        decode_shuffle_block, IoCompressionReader, IoCompressionWriter, IpcCompressionReader,
+        IpcCompressionWriter,
+    };
+
+    #[test]
+    fn test_io_compression_codecs() -> Result<()> {
+        let data = (0..100000)
+            .flat_map(|i: i32| (i % 1000).to_le_bytes())
+            .collect::<Vec<u8>>();
+        for codec in ["lz4", "snappy", "zstd"] {
+            let mut writer = IoCompressionWriter::try_new(codec, vec![])?;
+            writer.write_all(&data)?;
+            let compressed = writer.finish()?;
+            assert!(compressed.len() < data.len());
+
+            let mut reader = IoCompressionReader::try_new(codec, Cursor::new(compressed))?;
+            let mut decompressed = vec![];
+            reader.read_to_end(&mut decompressed)?;
+            assert_eq!(decompressed, data, "codec: {codec}");
+        }
+        assert!(IoCompressionWriter::try_new("unknown", vec![]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_ipc_compression_uncompressed_size() -> Result<()> {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("a", DataType::Int32, false),
+            Field::new("b", DataType::Utf8, true),
+        ]));
+        let batches = (0..3)
+            .map(|i| {
+                RecordBatch::try_new(
+                    schema.clone(),
+                    vec![
+                        Arc::new(Int32Array::from_iter_values(i * 100..i * 100 + 100)),
+                        Arc::new(StringArray::from_iter(
+                            (0..100).map(|j| (j % 3 != 0).then(|| format!("value-{i}-{j}"))),
+                        )),
+                    ],
+                )
+            })
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        let mut expected_uncompressed_size = 0;
+        for batch in &batches {
+            let mut buf = vec![];
+            write_one_batch(batch, &mut Cursor::new(&mut buf))?;
+            expected_uncompressed_size += buf.len();
+        }
+
+        let mut writer = IpcCompressionWriter::new(vec![], true);
+        for batch in &batches {
+            writer.write_batch(batch.clone())?;
+        }
+        assert_eq!(writer.uncompressed_size(), expected_uncompressed_size);
+        let compressed = writer.finish_into_inner()?;
+
+        // read back
+        let mut reader = IpcCompressionReader::new(Cursor::new(compressed), schema);
+        let mut read_batches = vec![];
+        while let Some(batch) = reader.read_batch()? {
+            read_batches.push(batch);
+        }
+        assert_eq!(read_batches, batches);
+        Ok(())
+    }
+
+    #[test]
+    fn test_ipc_compression_recycle_block_buf() -> Result<()> {
+        let schema = Arc::new(Schema::new(vec![Field::new("a", DataType::Int32, false)]));
+        let batches = (0..1000)
+            .map(|i| {
+                RecordBatch::try_new(
+                    schema.clone(),
+                    vec![Arc::new(Int32Array::from_iter_values(i..i + 10))],
+                )
+            })
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        // write a stream of small batches, flushing every 10 batches like
+        // writing many small partitions, and count block buffer reallocations
+        let count_block_buf_reallocs = |capacity_hint: usize| -> Result<(usize, Vec<u8>)> {
+            let mut writer = IpcCompressionWriter::with_capacity_hint(vec![], true, capacity_hint);
+            let mut num_reallocs = 0;
+            let mut last_block_buf_ptr = writer.spare_block_buf.as_ptr();
+            for chunk in batches.chunks(10) {
+                for batch in chunk {
+                    writer.write_batch(batch.clone())?;
+                }
+                writer.flush()?;
+                if writer.spare_block_buf.as_ptr() != last_block_buf_ptr {
+                    num_reallocs += 1;
+                    last_block_buf_ptr = writer.spare_block_buf.as_ptr();
+                }
+            }
+            Ok((num_reallocs, writer.finish_into_inner()?))
+        };
+        let (num_reallocs_no_hint, output_no_hint) = count_block_buf_reallocs(0)?;
+        let (num_reallocs_hinted, output_hinted) = count_block_buf_reallocs(65536)?;
+        assert_eq!(output_no_hint, output_hinted);
+        assert_eq!(num_reallocs_hinted, 0);
+        assert!(num_reallocs_no_hint <= 2); // recycled after first growth
+
+        // read back
+        let mut reader = IpcCompressionReader::new(Cursor::new(output_hinted), schema);
+        let mut read_batches = vec![];
+        while let Some(batch) = reader.read_batch()? {
+            read_batches.push(batch);
+        }
+        assert_eq!(read_batches, batches);
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_shuffle_block() -> Result<()> {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("a", DataType::Int32, false),
+            Field::new("b", DataType::Utf8, true),
+        ]));
+        let batches = (0..2)
+            .map(|i| {
+                RecordBatch::try_new(
+                    schema.clone(),
+                    vec![
+                        Arc::new(Int32Array::from_iter_values(i * 100..i * 100 + 100)),
+                        Arc::new(StringArray::from_iter(
+                            (0..100).map(|j| (j % 3 != 0).then(|| format!("value-{i}-{j}"))),
+                        )),
+                    ],
+                )
+            })
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        for compressed in [true, false] {
+            let mut writer = IpcCompressionWriter::new(vec![], compressed);
+            for batch in &batches {
+                writer.write_batch(batch.clone())?;
+            }
+            let block = writer.finish_into_inner()?;
+            assert_eq!(decode_shuffle_block(Cursor::new(&block), &schema)?, batches);
+
+            // truncated header or content
+            assert!(decode_shuffle_block(Cursor::new(&block[..2]), &schema).is_err());
+            assert!(decode_shuffle_block(Cursor::new(&block[..block.len() - 1]), &schema).is_err());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_ipc_compression_dictionary_blocks() -> Result<()> {
+        let dict_type = DataType::Dictionary(Box::new(DataType::Int32), Box::new(DataType::Utf8));
+        let schema = Arc::new(Schema::new(vec![Field::new("a", dict_type.clone(), true)]));
+
+        // same keys refer to different values in the two blocks
+        let dict1: DictionaryArray<Int32Type> = vec![Some("x"), Some("y"), None, Some("x")]
+            .into_iter()
+            .collect();
+        let dict2: DictionaryArray<Int32Type> =
+            vec![Some("z"), Some("x"), Some("z")].into_iter().collect();
+        assert_eq!(dict1.keys().value(0), dict2.keys().value(0));
+
+        let mut writer = IpcCompressionWriter::new(vec![], true);
+        for dict in [dict1, dict2] {
+            writer.write_batch(RecordBatch::try_new(schema.clone(), vec![Arc::new(dict)])?)?;
+            writer.flush()?; // one block per batch
+        }
+
+        // a slice of a large dictionary does not carry the whole dictionary
+        let large_values = (0..10000).map(|i| format!("value-{i}")).collect::<Vec<_>>();
+        let large_dict: DictionaryArray<Int32Type> =
+            large_values.iter().map(|v| v.as_str()).collect();
+        let num_bytes_before_slice = writer.inner().len();
+        writer.write_batch(RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(large_dict.slice(0, 2))],
+        )?)?;
+        writer.flush()?;
+        assert!(writer.inner().len() - num_bytes_before_slice < 1000);
+        let output = writer.finish_into_inner()?;
+
+        // each block is decoded with its own dictionary
+        let mut reader = IpcCompressionReader::new(Cursor::new(output), schema);
+        let mut values = vec![];
+        while let Some(batch) = reader.read_batch()? {
+            assert_eq!(batch.column(0).data_type(), &dict_type);
+            let strings = cast(batch.column(0), &DataType::Utf8)?;
+            let strings = strings.as_any().downcast_ref::<StringArray>().unwrap();
+            values.extend(strings.iter().map(|v| v.map(|v| v.to_string())));
+        }
+        assert_eq!(
+            values,
+            [
+                Some("x"),
+                Some("y"),
+                None,
+                Some("x"),
+                Some("z"),
+                Some("x"),
+                Some("z"),
+                Some("value-0"),
+                Some("value-1"),
+            ]
+            .map(|v| v.map(|v| v.to_string()))
+            .to_vec(),
+        );
+        Ok(())
+    }
 }
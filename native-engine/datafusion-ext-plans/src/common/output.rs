@@ -21,7 +21,10 @@ use std::{
 
 use arrow::{datatypes::SchemaRef, record_batch::RecordBatch};
 use async_trait::async_trait;
-use blaze_jni_bridge::is_task_running;
+use blaze_jni_bridge::{
+    conf::{IntConf, OUTPUT_MAX_IN_FLIGHT_BATCHES},
+    is_jni_bridge_inited, is_task_running,
+};
 use datafusion::{
     common::Result,
     execution::context::TaskContext,
@@ -32,7 +35,7 @@ use datafusion::{
     },
 };
 use datafusion_ext_commons::{
-    df_execution_err,
+    batch_size, df_execution_err,
     io::{read_one_batch, write_one_batch},
 };
 use futures::{FutureExt, StreamExt};
@@ -47,6 +50,29 @@ fn working_senders() -> &'static Mutex<Vec<Weak<WrappedRecordBatchSender>>> {
     WORKING_SENDERS.get_or_init(|| Mutex::default())
 }
 
+/// max number of output batches buffered between the producer and consumer of
+/// output_with_sender(). sending waits when the channel is full, so a fast
+/// producer cannot hold unbounded batches in memory.
+/// 0 (default) means about 8192 rows in flight, which is a single batch with
+/// the default batch size.
+pub(crate) fn output_channel_capacity() -> usize {
+    static CAPACITY: OnceCell<usize> = OnceCell::new();
+    *CAPACITY.get_or_init(|| {
+        let configured = if is_jni_bridge_inited() {
+            OUTPUT_MAX_IN_FLIGHT_BATCHES
+                .value()
+                .expect("error getting configured max in-flight batches")
+                .max(0) as usize
+        } else {
+            0 // for testing
+        };
+        match configured {
+            0 => (8192 / batch_size()).clamp(1, 16),
+            n => n,
+        }
+    })
+}
+
 pub struct WrappedRecordBatchSender {
     task_context: Arc<TaskContext>,
     sender: Sender<Result<RecordBatch>>,
@@ -125,7 +151,8 @@ impl TaskOutputter for Arc<TaskContext> {
         output_schema: SchemaRef,
         output: impl FnOnce(Arc<WrappedRecordBatchSender>) -> Fut + Send + 'static,
     ) -> Result<SendableRecordBatchStream> {
-        let mut stream_builder = RecordBatchReceiverStream::builder(output_schema, 1);
+        let mut stream_builder =
+            RecordBatchReceiverStream::builder(output_schema, output_channel_capacity());
         let err_sender = stream_builder.tx().clone();
         let wrapped_sender =
             WrappedRecordBatchSender::new(self.clone(), stream_builder.tx().clone());
@@ -191,7 +218,7 @@ impl TaskOutputter for Arc<TaskContext> {
                         mem_consumer.name(),
                     );
                     let mut spill = try_new_spill(&spill_metrics)?;
-                    let mut spill_writer = spill.get_compressed_writer();
+                    let mut spill_writer = spill.get_compressed_writer()?;
 
                     // write all batches to spill, releasing all holding memory
                     while let Some(batch) = stream.next().await.transpose()? {
@@ -204,7 +231,7 @@ impl TaskOutputter for Arc<TaskContext> {
                     drop(spill_writer);
 
                     // read all batches from spill and output
-                    let mut spill_reader = spill.get_compressed_reader();
+                    let mut spill_reader = spill.get_compressed_reader()?;
                     while let Some(batch) = read_one_batch(&mut spill_reader, &schema)? {
                         sender.send(Ok(batch), Some(&mut timer)).await;
                     }
@@ -253,3 +280,71 @@ impl NextBatchWithTimer for SendableRecordBatchStream {
         self.next().await.transpose()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::{
+        sync::{
+            atomic::{AtomicUsize, Ordering::SeqCst},
+            Arc,
+        },
+        time::Duration,
+    };
+
+    use arrow::{
+        array::Int32Array,
+        datatypes::{DataType, Field, Schema},
+        record_batch::RecordBatch,
+    };
+    use datafusion::{
+        common::Result,
+        physical_plan::metrics::{BaselineMetrics, ExecutionPlanMetricsSet},
+        prelude::SessionContext,
+    };
+    use futures::StreamExt;
+
+    use crate::common::output::{output_channel_capacity, TaskOutputter};
+
+    #[tokio::test]
+    async fn test_output_with_slow_consumer() -> Result<()> {
+        let schema = Arc::new(Schema::new(vec![Field::new("a", DataType::Int32, false)]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(Int32Array::from(vec![1, 2, 3]))],
+        )?;
+        let num_batches = 20;
+        let num_sent = Arc::new(AtomicUsize::new(0));
+        let metrics = ExecutionPlanMetricsSet::new();
+        let baseline_metrics = BaselineMetrics::new(&metrics, 0);
+
+        let task_ctx = SessionContext::new().task_ctx();
+        let num_sent_cloned = num_sent.clone();
+        let elapsed_compute = baseline_metrics.elapsed_compute().clone();
+        let mut output = task_ctx.output_with_sender("Test", schema, move |sender| async move {
+            let mut timer = elapsed_compute.timer();
+            for _ in 0..num_batches {
+                sender.send(Ok(batch.clone()), Some(&mut timer)).await;
+                num_sent_cloned.fetch_add(1, SeqCst);
+            }
+            Ok(())
+        })?;
+
+        // the producer runs ahead at most by the channel capacity
+        let mut num_received = 0;
+        while let Some(batch) = output.next().await.transpose()? {
+            num_received += 1;
+            assert_eq!(batch.num_rows(), 3);
+            assert!(num_sent.load(SeqCst) <= num_received + output_channel_capacity());
+            for _ in 0..10 {
+                tokio::task::yield_now().await;
+            }
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        assert_eq!(num_received, num_batches);
+
+        // time blocked by the slow consumer is excluded from elapsed_compute
+        let blocked_nanos = Duration::from_millis(5 * num_batches as u64).as_nanos() as usize;
+        assert!(baseline_metrics.elapsed_compute().value() < blocked_nanos / 2);
+        Ok(())
+    }
+}
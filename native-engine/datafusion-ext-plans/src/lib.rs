@@ -34,6 +34,8 @@ pub mod parquet_sink_exec;
 pub mod project_exec;
 pub mod rename_columns_exec;
 pub mod rss_shuffle_writer_exec;
+pub mod sample_exec;
+pub mod shuffle_reader_exec;
 pub mod shuffle_writer_exec;
 pub mod sort_exec;
 pub mod sort_merge_join_exec;
@@ -23,14 +23,21 @@ use futures::{Stream, StreamExt};
 pub struct LimitExec {
     input: Arc<dyn ExecutionPlan>,
     limit: u64,
+    offset: u64,
     pub metrics: ExecutionPlanMetricsSet,
 }
 
 impl LimitExec {
-    pub fn new(input: Arc<dyn ExecutionPlan>, limit: u64) -> Self {
+    /// takes at most `limit` rows of each partition and skips the first
+    /// `offset` of them, so at most `limit - offset` rows are output. this
+    /// follows spark's GlobalLimitExec, whose limit already includes the
+    /// offset. for a global limit with offset, the input should be gathered
+    /// into a single partition.
+    pub fn new(input: Arc<dyn ExecutionPlan>, limit: u64, offset: u64) -> Self {
         Self {
             input,
             limit,
+            offset,
             metrics: ExecutionPlanMetricsSet::new(),
         }
     }
@@ -38,7 +45,7 @@ impl LimitExec {
 
 impl DisplayAs for LimitExec {
     fn fmt_as(&self, _t: DisplayFormatType, f: &mut Formatter) -> std::fmt::Result {
-        write!(f, "LimitExec(limit={})", self.limit)
+        write!(f, "LimitExec(limit={}, offset={})", self.limit, self.offset)
     }
 }
 
@@ -67,7 +74,11 @@ impl ExecutionPlan for LimitExec {
         self: Arc<Self>,
         children: Vec<Arc<dyn ExecutionPlan>>,
     ) -> Result<Arc<dyn ExecutionPlan>> {
-        Ok(Arc::new(Self::new(children[0].clone(), self.limit)))
+        Ok(Arc::new(Self::new(
+            children[0].clone(),
+            self.limit,
+            self.offset,
+        )))
     }
 
     fn execute(
@@ -78,7 +89,9 @@ impl ExecutionPlan for LimitExec {
         let input_stream = self.input.execute(partition, context)?;
         Ok(Box::pin(LimitStream {
             input_stream,
-            limit: self.limit,
+            limit: self.limit.saturating_sub(self.offset),
+            offset: self.offset,
+            skipped: 0,
             cur: 0,
             baseline_metrics: BaselineMetrics::new(&self.metrics, partition),
         }))
@@ -92,6 +105,8 @@ impl ExecutionPlan for LimitExec {
 struct LimitStream {
     input_stream: SendableRecordBatchStream,
     limit: u64,
+    offset: u64,
+    skipped: u64,
     cur: u64,
     baseline_metrics: BaselineMetrics,
 }
@@ -111,21 +126,35 @@ impl Stream for LimitStream {
             return Poll::Ready(None);
         }
 
-        match self.input_stream.poll_next_unpin(cx) {
-            Poll::Pending => Poll::Pending,
-            Poll::Ready(None) => Poll::Ready(None),
-            Poll::Ready(Some(Err(e))) => Poll::Ready(Some(Err(e))),
-            Poll::Ready(Some(Ok(batch))) => {
-                let batch = if batch.num_rows() <= rest as usize {
-                    self.cur += batch.num_rows() as u64;
-                    batch
-                } else {
-                    self.cur += rest;
-                    batch.slice(0, rest as usize)
-                };
-                self.baseline_metrics
-                    .record_poll(Poll::Ready(Some(Ok(batch))))
+        loop {
+            let mut batch = match self.input_stream.poll_next_unpin(cx) {
+                Poll::Pending => return Poll::Pending,
+                Poll::Ready(None) => return Poll::Ready(None),
+                Poll::Ready(Some(Err(e))) => return Poll::Ready(Some(Err(e))),
+                Poll::Ready(Some(Ok(batch))) => batch,
+            };
+
+            // skip offset rows, which may span multiple batches
+            let to_skip = self.offset - self.skipped;
+            if to_skip > 0 {
+                if batch.num_rows() as u64 <= to_skip {
+                    self.skipped += batch.num_rows() as u64;
+                    continue;
+                }
+                self.skipped = self.offset;
+                batch = batch.slice(to_skip as usize, batch.num_rows() - to_skip as usize);
             }
+
+            let batch = if batch.num_rows() <= rest as usize {
+                self.cur += batch.num_rows() as u64;
+                batch
+            } else {
+                self.cur += rest;
+                batch.slice(0, rest as usize)
+            };
+            return self
+                .baseline_metrics
+                .record_poll(Poll::Ready(Some(Ok(batch))));
         }
     }
 }
@@ -188,7 +217,7 @@ mod test {
             ("b", &vec![0, 1, 2, 3, 4, 5, 6, 7, 8, 9]),
             ("c", &vec![5, 6, 7, 8, 9, 0, 1, 2, 3, 4]),
         );
-        let limit_exec = LimitExec::new(input, 2_u64);
+        let limit_exec = LimitExec::new(input, 2_u64, 0);
         let session_ctx = SessionContext::new();
         let task_ctx = session_ctx.task_ctx();
         let output = limit_exec.execute(0, task_ctx).unwrap();
@@ -205,4 +234,68 @@ mod test {
         assert_batches_eq!(expected, &batches);
         Ok(())
     }
+
+    fn build_multi_batch_table(num_batches: i32, batch_size: i32) -> Arc<dyn ExecutionPlan> {
+        let batches = (0..num_batches)
+            .map(|i| {
+                let a = (i * batch_size..(i + 1) * batch_size).collect::<Vec<_>>();
+                build_table_i32(("a", &a), ("b", &a), ("c", &a))
+            })
+            .collect::<Vec<_>>();
+        let schema = batches[0].schema();
+        Arc::new(MemoryExec::try_new(&[batches], schema, None).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_limit_exec_with_offset() -> Result<()> {
+        MemManager::init(10000);
+
+        // offset and limit both spanning batch boundaries, limit includes offset
+        let input = build_multi_batch_table(4, 3);
+        let limit_exec = LimitExec::new(input, 9_u64, 4);
+        let session_ctx = SessionContext::new();
+        let task_ctx = session_ctx.task_ctx();
+        let output = limit_exec.execute(0, task_ctx).unwrap();
+        let batches = common::collect(output).await?;
+
+        let expected = vec![
+            "+---+---+---+",
+            "| a | b | c |",
+            "+---+---+---+",
+            "| 4 | 4 | 4 |",
+            "| 5 | 5 | 5 |",
+            "| 6 | 6 | 6 |",
+            "| 7 | 7 | 7 |",
+            "| 8 | 8 | 8 |",
+            "+---+---+---+",
+        ];
+        assert_batches_eq!(expected, &batches);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_limit_exec_with_offset_beyond_input() -> Result<()> {
+        MemManager::init(10000);
+        let input = build_multi_batch_table(4, 3);
+        let limit_exec = LimitExec::new(input, 5_u64, 12);
+        let session_ctx = SessionContext::new();
+        let task_ctx = session_ctx.task_ctx();
+        let output = limit_exec.execute(0, task_ctx).unwrap();
+        let batches = common::collect(output).await?;
+        assert_eq!(batches.iter().map(|b| b.num_rows()).sum::<usize>(), 0);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_limit_exec_with_offset_beyond_limit() -> Result<()> {
+        MemManager::init(10000);
+        let input = build_multi_batch_table(4, 3);
+        let limit_exec = LimitExec::new(input, 3_u64, 5);
+        let session_ctx = SessionContext::new();
+        let task_ctx = session_ctx.task_ctx();
+        let output = limit_exec.execute(0, task_ctx).unwrap();
+        let batches = common::collect(output).await?;
+        assert_eq!(batches.iter().map(|b| b.num_rows()).sum::<usize>(), 0);
+        Ok(())
+    }
 }
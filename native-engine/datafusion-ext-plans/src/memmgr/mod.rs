@@ -74,6 +74,7 @@ impl MemManager {
 
     pub fn register_consumer(mut consumer: Arc<dyn MemConsumer>, spillable: bool) {
         let consumer_info = Arc::new(MemConsumerInfo {
+            consumer: Arc::downgrade(&consumer),
             status: Mutex::new(MemConsumerStatus {
                 mem_used: 0,
                 spillable,
@@ -131,6 +132,40 @@ impl MemManager {
         }
         unreachable!("deregistering non-registered memory consumer")
     }
+
+    /// finds the largest other spillable consumer using more memory than both
+    /// `mem_used` and `threshold`, such a consumer should be spilled before
+    /// the current one
+    fn find_larger_spillable(
+        &self,
+        consumer_info: &Arc<MemConsumerInfo>,
+        mem_used: usize,
+        threshold: usize,
+    ) -> Option<Arc<dyn MemConsumer>> {
+        let consumers = self.consumers.lock();
+        find_larger_spillable(&consumers, consumer_info, mem_used, threshold)
+            .and_then(|larger| larger.consumer.upgrade())
+    }
+}
+
+fn find_larger_spillable<'a>(
+    consumers: &'a [Arc<MemConsumerInfo>],
+    consumer_info: &Arc<MemConsumerInfo>,
+    mem_used: usize,
+    threshold: usize,
+) -> Option<&'a Arc<MemConsumerInfo>> {
+    consumers
+        .iter()
+        .filter(|other| !Arc::ptr_eq(other, consumer_info))
+        .filter_map(|other| {
+            let other_status = *other.status.lock();
+            let larger = other_status.spillable
+                && other_status.mem_used > mem_used
+                && other_status.mem_used > threshold;
+            larger.then_some((other_status.mem_used, other))
+        })
+        .max_by_key(|(other_mem_used, _)| *other_mem_used)
+        .map(|(_, other)| other)
 }
 
 #[derive(Default, Clone, Copy)]
@@ -156,8 +191,8 @@ impl MemManagerStatus {
     }
 }
 
-#[derive(Debug)]
 pub struct MemConsumerInfo {
+    consumer: Weak<dyn MemConsumer>,
     status: Mutex<MemConsumerStatus>,
 }
 
@@ -179,6 +214,10 @@ pub trait MemConsumer: Send + Sync {
             .expect("consumer deregistered")
     }
 
+    fn mem_used(&self) -> usize {
+        self.consumer_info().status.lock().mem_used
+    }
+
     fn mem_used_percent(&self) -> f64 {
         let mm = MemManager::get();
         let total = mm.total;
@@ -188,7 +227,7 @@ pub trait MemConsumer: Send + Sync {
         let total_managed = total
             .saturating_sub(get_mem_jvm_direct_used())
             .saturating_sub(mem_unspillable);
-        let mem_used = self.consumer_info().status.lock().mem_used;
+        let mem_used = self.mem_used();
         let consumer_mem_max = total_managed / mm_status.num_spillables.max(1);
         mem_used as f64 / consumer_mem_max as f64
     }
@@ -211,6 +250,13 @@ pub trait MemConsumer: Send + Sync {
         consumer_status.spillable = spillable;
     }
 
+    /// spill() may be triggered by other consumers at any time, it should do
+    /// nothing once this consumer becomes unspillable (like it has started
+    /// outputting)
+    fn is_spillable(&self) -> bool {
+        self.consumer_info().status.lock().spillable
+    }
+
     async fn update_mem_used(&self, new_used: usize) -> Result<()>
     where
         Self: Sized,
@@ -239,8 +285,8 @@ pub trait MemConsumer: Send + Sync {
         .await
     }
 
-    /// spills this consumer and returns used memory after spilling
-    async fn spill(&self) -> Result<()> {
+    /// spills this consumer and returns the amount of freed memory
+    async fn spill(&self) -> Result<usize> {
         unimplemented!()
     }
 }
@@ -254,14 +300,14 @@ async fn update_consumer_mem_used_with_custom_updater(
     let consumer_info = consumer.consumer_info();
     let total = mm.total;
 
-    #[derive(Clone, Copy, PartialEq)]
     enum Operation {
-        Spill,   // spill this consumer
-        Wait,    // wait other consumers to spill
-        Nothing, // do nothing
+        Spill,                          // spill this consumer
+        SpillOther(Arc<dyn MemConsumer>), // spill another larger consumer
+        Wait,                           // wait other consumers to spill
+        Nothing,                        // do nothing
     }
 
-    let (mem_unspillable, mem_jvm_direct_used);
+    let (mem_unspillable, mem_jvm_direct_used, total_managed);
     let (mem_used, total_used, operation) = {
         let mut mm_status = mm.status.lock();
         let mut consumer_status = consumer_info.status.lock();
@@ -297,7 +343,7 @@ async fn update_consumer_mem_used_with_custom_updater(
         // get jvm direct memory used
         mem_jvm_direct_used = get_mem_jvm_direct_used();
 
-        let total_managed = total
+        total_managed = total
             .saturating_sub(mem_jvm_direct_used) // jvm direct memory
             .saturating_sub(mem_unspillable); // unspillable memory
         let consumer_mem_max = total_managed / num_spillables;
@@ -310,7 +356,18 @@ async fn update_consumer_mem_used_with_custom_updater(
             && new_used > old_used
         {
             if spillable && new_used > consumer_mem_min {
-                Operation::Spill
+                // prefer spilling the largest consumer if this one is not
+                // exceeding its own share. the larger one may never grow again,
+                // so it is spilled here instead of waiting for it
+                let larger = if consumer_overflowed {
+                    None
+                } else {
+                    mm.find_larger_spillable(&consumer_info, new_used, consumer_mem_max)
+                };
+                match larger {
+                    Some(larger) => Operation::SpillOther(larger),
+                    None => Operation::Spill,
+                }
             } else {
                 Operation::Wait
             }
@@ -321,14 +378,39 @@ async fn update_consumer_mem_used_with_custom_updater(
     };
     let mut operation = operation;
 
+    // trigger spilling the larger consumer, this one is spilled instead if
+    // nothing is freed (like the larger one has started outputting)
+    if let Operation::SpillOther(larger) = &operation {
+        let larger_name = larger.name();
+        log::info!(
+            "mem manager spilling {larger_name} for {consumer_name} (mem_used: {}), total: {}/{}, unspillable: {}, jvm_direct: {}",
+            ByteSize(mem_used as u64),
+            ByteSize(total_used as u64),
+            ByteSize(mm.total as u64),
+            ByteSize(mem_unspillable as u64),
+            ByteSize(mem_jvm_direct_used as u64),
+        );
+        let freed = larger.spill().await?;
+        log::info!(
+            "mem manager spilled {larger_name}, freed: {}",
+            ByteSize(freed as u64),
+        );
+        if freed > 0 {
+            return Ok(());
+        }
+        operation = Operation::Spill;
+    }
+
     // trigger waiting for resources
-    if operation == Operation::Wait {
+    if let Operation::Wait = operation {
         const WAIT_TIME: Duration = Duration::from_millis(10000);
 
         let mut mm_status = mm.status.lock();
-        let wait = mm
-            .cv
-            .wait_while_for(&mut mm_status, |s| total < s.total_used, WAIT_TIME);
+        let wait = mm.cv.wait_while_for(
+            &mut mm_status,
+            |s| total_managed < s.total_used,
+            WAIT_TIME,
+        );
 
         if wait.timed_out() {
             log::warn!("mem manager: consumer {consumer_name} timeout waiting for resources");
@@ -337,7 +419,7 @@ async fn update_consumer_mem_used_with_custom_updater(
     }
 
     // trigger spilling
-    if operation == Operation::Spill {
+    if let Operation::Spill = operation {
         log::info!(
             "mem manager spilling {consumer_name} (mem_used: {}), total: {}/{}, unspillable: {}, jvm_direct: {}",
             ByteSize(mem_used as u64),
@@ -346,7 +428,11 @@ async fn update_consumer_mem_used_with_custom_updater(
             ByteSize(mem_unspillable as u64),
             ByteSize(mem_jvm_direct_used as u64),
         );
-        consumer.spill().await?;
+        let freed = consumer.spill().await?;
+        log::info!(
+            "mem manager spilled {consumer_name}, freed: {}",
+            ByteSize(freed as u64),
+        );
         return Ok(());
     }
     Ok(())
@@ -359,3 +445,68 @@ fn get_mem_jvm_direct_used() -> usize {
         0
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::sync::{Arc, Weak};
+
+    use async_trait::async_trait;
+    use datafusion::common::Result;
+    use parking_lot::Mutex;
+
+    use crate::memmgr::{find_larger_spillable, MemConsumer, MemConsumerInfo, MemConsumerStatus};
+
+    struct NoopConsumer;
+
+    #[async_trait]
+    impl MemConsumer for NoopConsumer {
+        fn name(&self) -> &str {
+            "NoopConsumer"
+        }
+
+        fn set_consumer_info(&mut self, _consumer_info: Weak<MemConsumerInfo>) {}
+
+        fn get_consumer_info(&self) -> &Weak<MemConsumerInfo> {
+            unimplemented!()
+        }
+
+        async fn spill(&self) -> Result<usize> {
+            Ok(0)
+        }
+    }
+
+    #[test]
+    fn test_find_larger_spillable() {
+        let new_consumer_info = |mem_used: usize, spillable: bool| {
+            Arc::new(MemConsumerInfo {
+                consumer: Weak::<NoopConsumer>::new(),
+                status: Mutex::new(MemConsumerStatus {
+                    mem_used,
+                    spillable,
+                }),
+            })
+        };
+        const MB: usize = 1 << 20;
+        let small = new_consumer_info(20 * MB, true);
+        let medium = new_consumer_info(60 * MB, true);
+        let large = new_consumer_info(100 * MB, true);
+        let consumers = vec![small.clone(), medium.clone(), large.clone()];
+        let find_larger = |consumer_info: &Arc<MemConsumerInfo>, threshold: usize| {
+            let mem_used = consumer_info.status.lock().mem_used;
+            find_larger_spillable(&consumers, consumer_info, mem_used, threshold).cloned()
+        };
+
+        // the small consumer spills the largest one first
+        assert!(find_larger(&small, 50 * MB).is_some_and(|c| Arc::ptr_eq(&c, &large)));
+
+        // the large consumer spills itself
+        assert!(find_larger(&large, 50 * MB).is_none());
+
+        // larger consumers not exceeding threshold are not expected to spill
+        assert!(find_larger(&small, 200 * MB).is_none());
+
+        // unspillable consumers are never chosen
+        large.status.lock().spillable = false;
+        assert!(find_larger(&small, 50 * MB).is_some_and(|c| Arc::ptr_eq(&c, &medium)));
+    }
+}
@@ -15,36 +15,113 @@
 use std::{
     any::Any,
     fs::{File, OpenOptions},
-    io::{BufReader, BufWriter, Cursor, Read, Seek, Write},
+    io::{BufReader, BufWriter, Cursor, ErrorKind, Read, Seek, Write},
+    path::PathBuf,
     sync::Arc,
     time::Duration,
 };
 
 use blaze_jni_bridge::{
-    is_jni_bridge_inited, jni_bridge::LocalRef, jni_call, jni_call_static, jni_get_string,
-    jni_new_direct_byte_buffer, jni_new_global_ref,
+    conf, conf::BooleanConf, is_jni_bridge_inited, jni_bridge::LocalRef, jni_call, jni_call_static,
+    jni_get_string, jni_new_direct_byte_buffer, jni_new_global_ref,
 };
 use datafusion::{common::Result, parquet::file::reader::Length, physical_plan::metrics::Time};
 use jni::{objects::GlobalRef, sys::jlong};
 
 use crate::memmgr::metrics::SpillMetrics;
 
-pub type SpillCompressedReader<'a> =
-    lz4_flex::frame::FrameDecoder<BufReader<Box<dyn Read + Send + 'a>>>;
-pub type SpillCompressedWriter<'a> =
-    lz4_flex::frame::AutoFinishEncoder<BufWriter<Box<dyn Write + Send + 'a>>>;
-
 pub trait Spill: Send + Sync {
     fn as_any(&self) -> &dyn Any;
     fn get_buf_reader<'a>(&'a self) -> BufReader<Box<dyn Read + Send + 'a>>;
     fn get_buf_writer<'a>(&'a mut self) -> BufWriter<Box<dyn Write + Send + 'a>>;
 
-    fn get_compressed_reader(&self) -> SpillCompressedReader<'_> {
-        lz4_flex::frame::FrameDecoder::new(self.get_buf_reader())
+    /// returns the underlying file of file-backed spills
+    fn as_file(&self) -> Option<&File> {
+        None
+    }
+
+    fn get_compressed_reader(&self) -> Result<SpillCompressedReader<'_>> {
+        SpillCompressedReader::try_new(self.get_buf_reader())
+    }
+
+    fn get_compressed_writer(&mut self) -> Result<SpillCompressedWriter<'_>> {
+        SpillCompressedWriter::try_new(self.get_buf_writer(), spill_compression_enabled())
+    }
+}
+
+fn spill_compression_enabled() -> bool {
+    if is_jni_bridge_inited() {
+        conf::SPILL_COMPRESSION_ENABLE.value().unwrap_or(true)
+    } else {
+        true // for testing
+    }
+}
+
+// first byte of spill data, indicating whether the following data is compressed
+const SPILL_UNCOMPRESSED: u8 = 0;
+const SPILL_LZ4_COMPRESSED: u8 = 1;
+
+pub enum SpillCompressedWriter<'a> {
+    Uncompressed(BufWriter<Box<dyn Write + Send + 'a>>),
+    Lz4(lz4_flex::frame::AutoFinishEncoder<BufWriter<Box<dyn Write + Send + 'a>>>),
+}
+
+impl<'a> SpillCompressedWriter<'a> {
+    fn try_new(mut inner: BufWriter<Box<dyn Write + Send + 'a>>, compressed: bool) -> Result<Self> {
+        if compressed {
+            inner.write_all(&[SPILL_LZ4_COMPRESSED])?;
+            Ok(Self::Lz4(
+                lz4_flex::frame::FrameEncoder::new(inner).auto_finish(),
+            ))
+        } else {
+            inner.write_all(&[SPILL_UNCOMPRESSED])?;
+            Ok(Self::Uncompressed(inner))
+        }
+    }
+}
+
+impl Write for SpillCompressedWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            Self::Uncompressed(w) => w.write(buf),
+            Self::Lz4(w) => w.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            Self::Uncompressed(w) => w.flush(),
+            Self::Lz4(w) => w.flush(),
+        }
+    }
+}
+
+pub enum SpillCompressedReader<'a> {
+    Uncompressed(BufReader<Box<dyn Read + Send + 'a>>),
+    Lz4(lz4_flex::frame::FrameDecoder<BufReader<Box<dyn Read + Send + 'a>>>),
+}
+
+impl<'a> SpillCompressedReader<'a> {
+    fn try_new(mut inner: BufReader<Box<dyn Read + Send + 'a>>) -> Result<Self> {
+        let mut header = [SPILL_UNCOMPRESSED];
+        match inner.read_exact(&mut header) {
+            Ok(()) => {}
+            Err(err) if err.kind() == ErrorKind::UnexpectedEof => {} // spill with nothing written
+            Err(err) => return Err(err.into()),
+        }
+        Ok(match header[0] {
+            SPILL_LZ4_COMPRESSED => Self::Lz4(lz4_flex::frame::FrameDecoder::new(inner)),
+            _ => Self::Uncompressed(inner),
+        })
     }
+}
 
-    fn get_compressed_writer(&mut self) -> SpillCompressedWriter<'_> {
-        lz4_flex::frame::FrameEncoder::new(self.get_buf_writer()).auto_finish()
+impl Read for SpillCompressedReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            Self::Uncompressed(r) => r.read(buf),
+            Self::Lz4(r) => r.read(buf),
+        }
     }
 }
 
@@ -81,23 +158,36 @@ pub fn try_new_spill(spill_metrics: &SpillMetrics) -> Result<Box<dyn Spill>> {
 struct FileSpill(File, SpillMetrics);
 impl FileSpill {
     fn try_new(spill_metrics: &SpillMetrics) -> Result<Self> {
-        if is_jni_bridge_inited() {
-            let file_name = jni_get_string!(
-                jni_call_static!(JniBridge.getDirectWriteSpillToDiskFile() -> JObject)?
-                    .as_obj()
-                    .into()
-            )?;
-            let file = OpenOptions::new() // create file and open under rw mode
-                .create(true)
-                .truncate(true)
-                .write(true)
-                .read(true)
-                .open(&file_name)?;
-            Ok(Self(file, spill_metrics.clone()))
-        } else {
-            let file = tempfile::tempfile()?;
-            Ok(Self(file, spill_metrics.clone()))
-        }
+        Ok(Self(try_new_spill_file()?, spill_metrics.clone()))
+    }
+}
+
+/// creates a temporary file opened under rw mode
+pub fn try_new_spill_file() -> Result<File> {
+    Ok(try_new_named_spill_file()?.0)
+}
+
+/// creates a temporary file opened under rw mode, also returns its path if the
+/// file is named and is to be deleted by the caller
+pub fn try_new_named_spill_file() -> Result<(File, Option<PathBuf>)> {
+    if is_jni_bridge_inited() {
+        // spill files are created by spark's DiskBlockManager, which
+        // hashes block ids over all configured spark.local.dir paths, so
+        // spills of concurrent tasks are already spread across disks
+        let file_name = jni_get_string!(
+            jni_call_static!(JniBridge.getDirectWriteSpillToDiskFile() -> JObject)?
+                .as_obj()
+                .into()
+        )?;
+        let file = OpenOptions::new() // create file and open under rw mode
+            .create(true)
+            .truncate(true)
+            .write(true)
+            .read(true)
+            .open(&file_name)?;
+        Ok((file, Some(PathBuf::from(file_name))))
+    } else {
+        Ok((tempfile::tempfile()?, None))
     }
 }
 
@@ -129,6 +219,10 @@ impl Spill for FileSpill {
             )),
         )
     }
+
+    fn as_file(&self) -> Option<&File> {
+        Some(&self.0)
+    }
 }
 
 impl Drop for FileSpill {
@@ -258,3 +352,71 @@ impl<W: Write> Write for IoTimeWriteWrapper<W> {
         self.0.flush()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::{
+        io::{Cursor, Write},
+        sync::Arc,
+    };
+
+    use arrow::{
+        array::{Int32Array, StringArray},
+        datatypes::{DataType, Field, Schema},
+        record_batch::RecordBatch,
+    };
+    use datafusion::common::Result;
+    use datafusion_ext_commons::io::{read_one_batch, write_one_batch};
+
+    use crate::memmgr::spill::{Spill, SpillCompressedWriter};
+
+    #[test]
+    fn test_spill_compression_modes() -> Result<()> {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("k", DataType::Int32, false),
+            Field::new("v", DataType::Utf8, true),
+        ]));
+        let batches = (0..3)
+            .map(|i| {
+                RecordBatch::try_new(
+                    schema.clone(),
+                    vec![
+                        Arc::new(Int32Array::from_iter_values(i * 1000..i * 1000 + 1000)),
+                        Arc::new(StringArray::from_iter(
+                            (0..1000).map(|j| (j % 7 != 0).then(|| format!("value-{}", j % 10))),
+                        )),
+                    ],
+                )
+            })
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        let mut spills = vec![];
+        for compressed in [true, false] {
+            let mut spill: Vec<u8> = vec![];
+            let mut writer = SpillCompressedWriter::try_new(spill.get_buf_writer(), compressed)?;
+            for batch in &batches {
+                let mut buf = vec![];
+                write_one_batch(batch, &mut Cursor::new(&mut buf))?;
+                writer.write_all(&buf)?;
+            }
+            drop(writer);
+
+            // both modes are read back with the same reader
+            let mut reader = spill.get_compressed_reader()?;
+            let mut read_batches = vec![];
+            while let Some(batch) = read_one_batch(&mut reader, &schema)? {
+                read_batches.push(batch);
+            }
+            assert_eq!(read_batches, batches);
+            drop(reader);
+            spills.push(spill);
+        }
+        assert!(spills[0].len() < spills[1].len());
+
+        // empty spill
+        let spill: Vec<u8> = vec![];
+        let mut reader = spill.get_compressed_reader()?;
+        assert!(read_one_batch(&mut reader, &schema)?.is_none());
+        Ok(())
+    }
+}
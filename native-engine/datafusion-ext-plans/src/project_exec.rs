@@ -17,26 +17,50 @@
 
 use std::{any::Any, fmt::Formatter, sync::Arc};
 
-use arrow::datatypes::{Field, Fields, Schema, SchemaRef};
+use arrow::{
+    datatypes::{Field, Fields, Schema, SchemaRef},
+    record_batch::{RecordBatch, RecordBatchOptions},
+};
+use blaze_jni_bridge::{
+    conf::{IntConf, PROJECT_BATCH_MEM_SIZE},
+    is_jni_bridge_inited,
+};
 use datafusion::{
-    common::{Result, Statistics},
+    common::{
+        stats::Precision,
+        tree_node::{Transformed, TreeNode},
+        ColumnStatistics, DataFusionError, Result, ScalarValue, Statistics,
+    },
     execution::TaskContext,
-    physical_expr::{PhysicalExprRef, PhysicalSortExpr},
+    logical_expr::ColumnarValue,
+    physical_expr::{
+        expressions::{Column, Literal},
+        PhysicalExprRef, PhysicalSortExpr,
+    },
     physical_plan::{
-        metrics::{BaselineMetrics, ExecutionPlanMetricsSet, MetricsSet},
+        metrics::{BaselineMetrics, ExecutionPlanMetricsSet, MetricBuilder, MetricsSet},
         stream::RecordBatchStreamAdapter,
         DisplayAs, DisplayFormatType, ExecutionPlan, Partitioning, SendableRecordBatchStream,
     },
 };
-use datafusion_ext_commons::streams::coalesce_stream::CoalesceInput;
-use futures::{stream::once, FutureExt, StreamExt, TryStreamExt};
+use datafusion_ext_commons::{
+    split_batch_by_estimated_size, streams::coalesce_stream::CoalesceInput,
+};
+use datafusion_ext_exprs::named_struct::NamedStructExpr;
+use futures::{
+    stream::{self, once},
+    FutureExt, StreamExt, TryStreamExt,
+};
 use itertools::Itertools;
 
 use crate::{
     common::{
         batch_statisitcs::{stat_input, InputBatchStatistics},
-        cached_exprs_evaluator::CachedExprsEvaluator,
-        column_pruning::{prune_columns, ExecuteWithColumnPruning},
+        cached_exprs_evaluator::{is_volatile_expr, CachedExprsEvaluator},
+        column_pruning::{
+            narrow_struct_type, project_schema_struct_fields, project_struct_fields, prune_columns,
+            prune_struct_fields, ExecuteWithColumnPruning,
+        },
         output::TaskOutputter,
     },
     filter_exec::FilterExec,
@@ -47,6 +71,7 @@ pub struct ProjectExec {
     expr: Vec<(PhysicalExprRef, String)>,
     input: Arc<dyn ExecutionPlan>,
     schema: SchemaRef,
+    output_batch_mem_size: Option<usize>,
     metrics: ExecutionPlanMetricsSet,
 }
 
@@ -68,13 +93,83 @@ impl ProjectExec {
                 .collect::<Result<Fields>>()?,
         ));
 
+        // schema is built from the original exprs, so fusing and folding do not
+        // change the output nullability
+        let (expr, input) = match input.as_any().downcast_ref::<ProjectExec>() {
+            Some(inner) => match fuse_project_exprs(&expr, inner)? {
+                Some(fused_expr) => (fused_expr, inner.input.clone()),
+                None => (expr, input),
+            },
+            None => (expr, input),
+        };
+        let expr = expr
+            .into_iter()
+            .map(|(e, name)| (fold_constant_expr(e), name))
+            .collect();
+
         Ok(Self {
             expr,
             input,
             schema,
+            output_batch_mem_size: project_batch_mem_size(),
             metrics: ExecutionPlanMetricsSet::new(),
         })
     }
+
+    /// overrides the target memory size of output batches, None means using
+    /// the default output batch memory size
+    pub fn with_output_batch_mem_size(mut self, output_batch_mem_size: Option<usize>) -> Self {
+        self.output_batch_mem_size = output_batch_mem_size;
+        self
+    }
+
+    pub fn exprs(&self) -> &[(PhysicalExprRef, String)] {
+        &self.expr
+    }
+
+    pub fn input(&self) -> &Arc<dyn ExecutionPlan> {
+        &self.input
+    }
+
+    /// executes the projection without coalescing small output batches. used
+    /// by consumers which buffer and copy input batches anyway (like shuffle
+    /// writers), to avoid materializing the projected batches twice.
+    pub fn execute_without_coalescing(
+        &self,
+        partition: usize,
+        context: Arc<TaskContext>,
+    ) -> Result<SendableRecordBatchStream> {
+        let exprs: Vec<PhysicalExprRef> = self.expr.iter().map(|(e, _name)| e.clone()).collect();
+
+        let fut = if let Some(filter_exec) = self.input.as_any().downcast_ref::<FilterExec>() {
+            execute_project_with_filtering(
+                filter_exec.children()[0].clone(),
+                partition,
+                context.clone(),
+                self.schema(),
+                filter_exec.predicates().to_vec(),
+                exprs,
+                self.metrics.clone(),
+            )
+            .boxed()
+        } else {
+            execute_project_with_filtering(
+                self.input.clone(),
+                partition,
+                context.clone(),
+                self.schema(),
+                vec![],
+                exprs,
+                self.metrics.clone(),
+            )
+            .boxed()
+        };
+
+        Ok(Box::pin(RecordBatchStreamAdapter::new(
+            self.schema(),
+            once(fut).try_flatten(),
+        )))
+    }
 }
 
 impl DisplayAs for ProjectExec {
@@ -115,10 +210,10 @@ impl ExecutionPlan for ProjectExec {
         self: Arc<Self>,
         children: Vec<Arc<dyn ExecutionPlan>>,
     ) -> Result<Arc<dyn ExecutionPlan>> {
-        Ok(Arc::new(Self::try_new(
-            self.expr.clone(),
-            children[0].clone(),
-        )?))
+        Ok(Arc::new(
+            Self::try_new(self.expr.clone(), children[0].clone())?
+                .with_output_batch_mem_size(self.output_batch_mem_size),
+        ))
     }
 
     fn execute(
@@ -127,37 +222,25 @@ impl ExecutionPlan for ProjectExec {
         context: Arc<TaskContext>,
     ) -> Result<SendableRecordBatchStream> {
         let baseline_metrics = BaselineMetrics::new(&self.metrics, partition);
-        let exprs: Vec<PhysicalExprRef> = self.expr.iter().map(|(e, _name)| e.clone()).collect();
-
-        let fut = if let Some(filter_exec) = self.input.as_any().downcast_ref::<FilterExec>() {
-            execute_project_with_filtering(
-                filter_exec.children()[0].clone(),
-                partition,
-                context.clone(),
-                self.schema(),
-                filter_exec.predicates().to_vec(),
-                exprs,
-                self.metrics.clone(),
-            )
-            .boxed()
-        } else {
-            execute_project_with_filtering(
-                self.input.clone(),
-                partition,
-                context.clone(),
-                self.schema(),
-                vec![],
-                exprs,
-                self.metrics.clone(),
-            )
-            .boxed()
+        let output = self.execute_without_coalescing(partition, context.clone())?;
+        let batch_mem_size = match self.output_batch_mem_size {
+            Some(batch_mem_size) => batch_mem_size,
+            None => return Ok(context.coalesce_with_default_batch_size(output, &baseline_metrics)?),
         };
 
-        let output = Box::pin(RecordBatchStreamAdapter::new(
-            self.schema(),
-            once(fut).try_flatten(),
-        ));
-        Ok(context.coalesce_with_default_batch_size(output, &baseline_metrics)?)
+        // split big batches into smaller ones before coalescing
+        let schema = output.schema();
+        let split = output
+            .map_ok(move |batch| {
+                stream::iter(
+                    split_batch_by_estimated_size(batch, batch_mem_size)
+                        .into_iter()
+                        .map(Ok::<_, DataFusionError>),
+                )
+            })
+            .try_flatten();
+        let split = Box::pin(RecordBatchStreamAdapter::new(schema, split));
+        Ok(context.coalesce_with_batch_mem_size(split, batch_mem_size, &baseline_metrics)?)
     }
 
     fn metrics(&self) -> Option<MetricsSet> {
@@ -165,7 +248,27 @@ impl ExecutionPlan for ProjectExec {
     }
 
     fn statistics(&self) -> Result<Statistics> {
-        todo!()
+        let input_stats = self.input.statistics()?;
+
+        // only plain column references carry through the input column stats,
+        // byte size is unknown since expressions may change the output width
+        let column_statistics = self
+            .expr
+            .iter()
+            .map(|(e, _name)| match e.as_any().downcast_ref::<Column>() {
+                Some(col) => input_stats
+                    .column_statistics
+                    .get(col.index())
+                    .cloned()
+                    .unwrap_or_else(ColumnStatistics::new_unknown),
+                None => ColumnStatistics::new_unknown(),
+            })
+            .collect();
+        Ok(Statistics {
+            num_rows: input_stats.num_rows,
+            total_byte_size: Precision::Absent,
+            column_statistics,
+        })
     }
 }
 
@@ -180,10 +283,152 @@ impl ExecuteWithColumnPruning for ProjectExec {
             input: self.input.clone(),
             expr: projection.iter().map(|&i| self.expr[i].clone()).collect(),
             schema: Arc::new(self.schema.project(projection)?),
+            output_batch_mem_size: self.output_batch_mem_size,
             metrics: self.metrics.clone(),
         });
         projected_project.execute(partition, context)
     }
+
+    fn execute_nested_projected(
+        &self,
+        partition: usize,
+        context: Arc<TaskContext>,
+        projection: &[usize],
+        nested_projection: &[Option<Vec<usize>>],
+    ) -> Result<SendableRecordBatchStream> {
+        // named structs are built with only the referenced fields, so the
+        // unreferenced field values are never evaluated. other struct columns
+        // are narrowed after evaluation.
+        let input_schema = self.input.schema();
+        let mut exprs = vec![];
+        let mut named_struct_projection = vec![];
+        let mut remaining_nested_projection = vec![];
+        for (&i, fields) in projection.iter().zip(nested_projection) {
+            let (expr, name) = &self.expr[i];
+            match (fields, expr.as_any().downcast_ref::<NamedStructExpr>()) {
+                (Some(fields), Some(_)) => {
+                    let values = expr.children();
+                    let return_type = narrow_struct_type(&expr.data_type(&input_schema)?, fields)?;
+                    let narrowed: PhysicalExprRef = Arc::new(NamedStructExpr::try_new(
+                        fields.iter().map(|&i| values[i].clone()).collect(),
+                        return_type,
+                    )?);
+                    exprs.push((narrowed, name.clone()));
+                    named_struct_projection.push(Some(fields.clone()));
+                    remaining_nested_projection.push(None);
+                }
+                _ => {
+                    exprs.push((expr.clone(), name.clone()));
+                    named_struct_projection.push(None);
+                    remaining_nested_projection.push(fields.clone());
+                }
+            }
+        }
+        let schema = project_schema_struct_fields(
+            &self.schema.project(projection)?,
+            &named_struct_projection,
+        )?;
+        let projected_project: Arc<dyn ExecutionPlan> = Arc::new(ProjectExec {
+            input: self.input.clone(),
+            expr: exprs,
+            schema,
+            output_batch_mem_size: self.output_batch_mem_size,
+            metrics: self.metrics.clone(),
+        });
+        let input = projected_project.execute(partition, context)?;
+        project_struct_fields(input, &remaining_nested_projection)
+    }
+}
+
+/// substitutes column references of outer project exprs with the inner
+/// project exprs, so that two adjacent projects are executed as one.
+/// returns None if a volatile inner expr is referenced more than once, which
+/// would otherwise be evaluated multiple times with different results.
+fn fuse_project_exprs(
+    outer_exprs: &[(PhysicalExprRef, String)],
+    inner: &ProjectExec,
+) -> Result<Option<Vec<(PhysicalExprRef, String)>>> {
+    fn count_column_refs(expr: &PhysicalExprRef, counts: &mut [usize]) {
+        if let Some(col) = expr.as_any().downcast_ref::<Column>() {
+            counts[col.index()] += 1;
+        }
+        for child in expr.children() {
+            count_column_refs(&child, counts);
+        }
+    }
+    let mut ref_counts = vec![0; inner.expr.len()];
+    for (expr, _) in outer_exprs {
+        count_column_refs(expr, &mut ref_counts);
+    }
+    if inner
+        .expr
+        .iter()
+        .zip(&ref_counts)
+        .any(|((inner_expr, _), &count)| count > 1 && is_volatile_expr(inner_expr))
+    {
+        return Ok(None);
+    }
+
+    let fused_exprs = outer_exprs
+        .iter()
+        .map(|(expr, name)| {
+            // transform bottom-up so the substituted inner exprs are not visited again
+            let fused = expr.clone().transform_up(&|e: PhysicalExprRef| {
+                Ok(match e.as_any().downcast_ref::<Column>() {
+                    Some(col) => Transformed::Yes(inner.expr[col.index()].0.clone()),
+                    None => Transformed::No(e),
+                })
+            })?;
+            Ok((fused, name.clone()))
+        })
+        .collect::<Result<_>>()?;
+    Ok(Some(fused_exprs))
+}
+
+/// replaces an expr whose leaves are all literals with its evaluated value, so
+/// that it is not evaluated again for every batch. volatile exprs and exprs
+/// failing to evaluate are kept unchanged.
+fn fold_constant_expr(expr: PhysicalExprRef) -> PhysicalExprRef {
+    fn all_leaves_literal(expr: &PhysicalExprRef) -> bool {
+        let children = expr.children();
+        if children.is_empty() {
+            return expr.as_any().is::<Literal>();
+        }
+        children.iter().all(all_leaves_literal)
+    }
+
+    if expr.as_any().is::<Literal>() || !all_leaves_literal(&expr) || is_volatile_expr(&expr) {
+        return expr;
+    }
+    let folded = RecordBatch::try_new_with_options(
+        Arc::new(Schema::empty()),
+        vec![],
+        &RecordBatchOptions::new().with_row_count(Some(1)),
+    )
+    .ok()
+    .and_then(|batch| expr.evaluate(&batch).ok())
+    .and_then(|value| match value {
+        ColumnarValue::Scalar(scalar) => Some(scalar),
+        ColumnarValue::Array(array) if array.len() == 1 => {
+            ScalarValue::try_from_array(&array, 0).ok()
+        }
+        ColumnarValue::Array(_) => None,
+    });
+    match folded {
+        Some(scalar) => Arc::new(Literal::new(scalar)),
+        None => expr,
+    }
+}
+
+fn project_batch_mem_size() -> Option<usize> {
+    if is_jni_bridge_inited() {
+        let batch_mem_size = PROJECT_BATCH_MEM_SIZE
+            .value()
+            .expect("error getting configured project batch mem size");
+        (batch_mem_size > 0).then_some(batch_mem_size as usize)
+    } else {
+        None // for testing
+    }
 }
 
 async fn execute_project_with_filtering(
@@ -197,6 +442,7 @@ async fn execute_project_with_filtering(
 ) -> Result<SendableRecordBatchStream> {
     // execute input with pruning
     let baseline_metrics = BaselineMetrics::new(&metrics, partition);
+    let rows_filtered = MetricBuilder::new(&metrics).counter("rows_filtered", partition);
     let num_exprs = exprs.len();
     let (pruned_exprs, projection) = prune_columns(&[exprs, filters].concat())?;
     let exprs = pruned_exprs
@@ -209,18 +455,50 @@ async fn execute_project_with_filtering(
         .skip(num_exprs)
         .cloned()
         .collect::<Vec<PhysicalExprRef>>();
+
+    // struct columns only accessed by fields are narrowed to the accessed fields
+    let projected_input_schema = input.schema().project(&projection)?;
+    let (pruned_exprs, nested_projection) =
+        prune_struct_fields(&[exprs, filters].concat(), &projected_input_schema)?;
+    let exprs = pruned_exprs[..num_exprs].to_vec();
+    let filters = pruned_exprs[num_exprs..].to_vec();
+
+    // fast path: no filters and all exprs are bare columns, output columns can be
+    // reselected from input without evaluating any expr
+    let identity_projection = filters
+        .is_empty()
+        .then(|| {
+            exprs
+                .iter()
+                .map(|expr| Some(expr.as_any().downcast_ref::<Column>()?.index()))
+                .collect::<Option<Vec<_>>>()
+        })
+        .flatten();
     let cached_expr_evaluator =
         CachedExprsEvaluator::try_new(filters, exprs, output_schema.clone())?;
 
     let mut input = stat_input(
         InputBatchStatistics::from_metrics_set_and_blaze_conf(&metrics, partition)?,
-        input.execute_projected(partition, context.clone(), &projection)?,
+        input.execute_nested_projected(
+            partition,
+            context.clone(),
+            &projection,
+            &nested_projection,
+        )?,
     )?;
 
-    context.output_with_sender("Project", output_schema, move |sender| async move {
+    context.output_with_sender("Project", output_schema.clone(), move |sender| async move {
         while let Some(batch) = input.next().await.transpose()? {
             let mut timer = baseline_metrics.elapsed_compute().timer();
-            let output_batch = cached_expr_evaluator.filter_project(&batch)?;
+            let output_batch = match &identity_projection {
+                Some(cols) => RecordBatch::try_new_with_options(
+                    output_schema.clone(),
+                    batch.project(cols)?.columns().to_vec(),
+                    &RecordBatchOptions::new().with_row_count(Some(batch.num_rows())),
+                )?,
+                None => cached_expr_evaluator.filter_project(&batch)?,
+            };
+            rows_filtered.add(batch.num_rows() - output_batch.num_rows());
             drop(batch);
 
             baseline_metrics.record_output(output_batch.num_rows());
@@ -229,3 +507,434 @@ async fn execute_project_with_filtering(
         Ok(())
     })
 }
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use arrow::{
+        array::Int32Array,
+        compute::concat_batches,
+        datatypes::{DataType, Field, Fields, Schema},
+        record_batch::RecordBatch,
+    };
+    use datafusion::{
+        assert_batches_eq,
+        common::{stats::Precision, ColumnStatistics, Result, ScalarValue},
+        execution::context::ExecutionProps,
+        logical_expr::{BuiltinScalarFunction, Operator},
+        physical_expr::{
+            expressions::{BinaryExpr, Column, Literal},
+            functions::create_physical_fun,
+            PhysicalExprRef, ScalarFunctionExpr,
+        },
+        physical_plan::{common, memory::MemoryExec, ExecutionPlan},
+        prelude::SessionContext,
+    };
+    use datafusion_ext_exprs::{
+        get_indexed_field::GetIndexedFieldExpr, named_struct::NamedStructExpr,
+    };
+
+    use crate::{
+        common::cached_exprs_evaluator::CachedExprsEvaluator, filter_exec::FilterExec,
+        memmgr::MemManager, project_exec::ProjectExec,
+    };
+
+    fn build_input() -> Result<Arc<dyn ExecutionPlan>> {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("a", DataType::Int32, true),
+            Field::new("b", DataType::Int32, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from(vec![Some(1), None, Some(3), None])),
+                Arc::new(Int32Array::from(vec![4, 5, 6, 7])),
+            ],
+        )?;
+        Ok(Arc::new(MemoryExec::try_new(&[vec![batch]], schema, None)?))
+    }
+
+    #[test]
+    fn test_project_statistics_identity() -> Result<()> {
+        let input = build_input()?;
+        let input_stats = input.statistics()?;
+        let project = ProjectExec::try_new(
+            vec![
+                (Arc::new(Column::new("b", 1)), "b".to_string()),
+                (Arc::new(Column::new("a", 0)), "a".to_string()),
+            ],
+            input,
+        )?;
+
+        let stats = project.statistics()?;
+        assert_eq!(stats.num_rows, Precision::Exact(4));
+        assert_eq!(stats.total_byte_size, Precision::Absent);
+        assert_eq!(
+            stats.column_statistics,
+            vec![
+                input_stats.column_statistics[1].clone(),
+                input_stats.column_statistics[0].clone(),
+            ]
+        );
+        assert_eq!(stats.column_statistics[1].null_count, Precision::Exact(2));
+        Ok(())
+    }
+
+    #[test]
+    fn test_project_statistics_arithmetic() -> Result<()> {
+        let input = build_input()?;
+        let project = ProjectExec::try_new(
+            vec![(
+                Arc::new(BinaryExpr::new(
+                    Arc::new(Column::new("a", 0)),
+                    Operator::Plus,
+                    Arc::new(Column::new("b", 1)),
+                )),
+                "a_plus_b".to_string(),
+            )],
+            input,
+        )?;
+
+        let stats = project.statistics()?;
+        assert_eq!(stats.num_rows, Precision::Exact(4));
+        assert_eq!(stats.total_byte_size, Precision::Absent);
+        assert_eq!(
+            stats.column_statistics,
+            vec![ColumnStatistics::new_unknown()]
+        );
+        Ok(())
+    }
+    fn lit(v: i32) -> PhysicalExprRef {
+        Arc::new(Literal::new(ScalarValue::Int32(Some(v))))
+    }
+
+    #[tokio::test]
+    async fn test_project_fold_constant() -> Result<()> {
+        MemManager::init(10000);
+        let input = build_input()?;
+        let project = ProjectExec::try_new(
+            vec![
+                (Arc::new(Column::new("b", 1)), "b".to_string()),
+                (
+                    Arc::new(BinaryExpr::new(lit(1), Operator::Plus, lit(2))),
+                    "one_plus_two".to_string(),
+                ),
+            ],
+            input,
+        )?;
+
+        let folded = project.expr[1].0.as_any().downcast_ref::<Literal>();
+        assert_eq!(
+            folded.map(|lit| lit.value()),
+            Some(&ScalarValue::Int32(Some(3)))
+        );
+        assert_eq!(
+            project.schema().field(1),
+            &Field::new("one_plus_two", DataType::Int32, false)
+        );
+
+        let session_ctx = SessionContext::new();
+        let output = project.execute(0, session_ctx.task_ctx())?;
+        let batches = common::collect(output).await?;
+        let expected = vec![
+            "+---+--------------+",
+            "| b | one_plus_two |",
+            "+---+--------------+",
+            "| 4 | 3            |",
+            "| 5 | 3            |",
+            "| 6 | 3            |",
+            "| 7 | 3            |",
+            "+---+--------------+",
+        ];
+        assert_batches_eq!(expected, &batches);
+        Ok(())
+    }
+
+    #[test]
+    fn test_project_not_fold_volatile() -> Result<()> {
+        let input = build_input()?;
+
+        // rand(42)
+        let rand = Arc::new(ScalarFunctionExpr::new(
+            "Random",
+            create_physical_fun(&BuiltinScalarFunction::Random, &ExecutionProps::new())?,
+            vec![lit(42)],
+            DataType::Float64,
+            None,
+            false,
+        ));
+        let project = ProjectExec::try_new(vec![(rand, "rand".to_string())], input)?;
+        assert!(project.expr[0]
+            .0
+            .as_any()
+            .downcast_ref::<ScalarFunctionExpr>()
+            .is_some());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_project_reorder_columns() -> Result<()> {
+        MemManager::init(10000);
+        let input = build_input()?;
+        let exprs: Vec<PhysicalExprRef> =
+            vec![Arc::new(Column::new("b", 1)), Arc::new(Column::new("a", 0))];
+        let project = ProjectExec::try_new(
+            vec![
+                (exprs[0].clone(), "b".to_string()),
+                (exprs[1].clone(), "a".to_string()),
+            ],
+            input.clone(),
+        )?;
+
+        let session_ctx = SessionContext::new();
+        let output = project.execute(0, session_ctx.task_ctx())?;
+        let batches = common::collect(output).await?;
+
+        // compare with the general path
+        let input_batches = common::collect(input.execute(0, session_ctx.task_ctx())?).await?;
+        let evaluator = CachedExprsEvaluator::try_new(vec![], exprs, project.schema())?;
+        let expected_batches = input_batches
+            .iter()
+            .map(|batch| evaluator.filter_project(batch))
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(batches, expected_batches);
+        assert_eq!(batches[0].schema(), project.schema());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_project_fuse_adjacent() -> Result<()> {
+        MemManager::init(10000);
+        let session_ctx = SessionContext::new();
+
+        // SELECT b2 + 1 AS x, a AS y FROM (SELECT a, b * 2 AS b2 FROM input)
+        let inner = Arc::new(ProjectExec::try_new(
+            vec![
+                (Arc::new(Column::new("a", 0)), "a".to_string()),
+                (
+                    Arc::new(BinaryExpr::new(
+                        Arc::new(Column::new("b", 1)),
+                        Operator::Multiply,
+                        lit(2),
+                    )),
+                    "b2".to_string(),
+                ),
+            ],
+            build_input()?,
+        )?);
+        let outer_exprs: Vec<(PhysicalExprRef, String)> = vec![
+            (
+                Arc::new(BinaryExpr::new(
+                    Arc::new(Column::new("b2", 1)),
+                    Operator::Plus,
+                    lit(1),
+                )),
+                "x".to_string(),
+            ),
+            (Arc::new(Column::new("a", 0)), "y".to_string()),
+        ];
+        let fused = ProjectExec::try_new(outer_exprs.clone(), inner.clone())?;
+        assert!(fused.input.as_any().downcast_ref::<MemoryExec>().is_some());
+
+        // compare with non-fused execution
+        let unfused = ProjectExec {
+            expr: outer_exprs,
+            input: inner,
+            schema: fused.schema(),
+            output_batch_mem_size: None,
+            metrics: Default::default(),
+        };
+        let fused_batches = common::collect(fused.execute(0, session_ctx.task_ctx())?).await?;
+        let unfused_batches = common::collect(unfused.execute(0, session_ctx.task_ctx())?).await?;
+        assert_eq!(fused_batches, unfused_batches);
+        let expected = vec![
+            "+----+---+",
+            "| x  | y |",
+            "+----+---+",
+            "| 9  | 1 |",
+            "| 11 |   |",
+            "| 13 | 3 |",
+            "| 15 |   |",
+            "+----+---+",
+        ];
+        assert_batches_eq!(expected, &fused_batches);
+        Ok(())
+    }
+
+    #[test]
+    fn test_project_not_fuse_shared_volatile() -> Result<()> {
+        let rand: PhysicalExprRef = Arc::new(ScalarFunctionExpr::new(
+            "Random",
+            create_physical_fun(&BuiltinScalarFunction::Random, &ExecutionProps::new())?,
+            vec![],
+            DataType::Float64,
+            None,
+            false,
+        ));
+        let inner = Arc::new(ProjectExec::try_new(
+            vec![(rand, "r".to_string())],
+            build_input()?,
+        )?);
+        let r: PhysicalExprRef = Arc::new(Column::new("r", 0));
+
+        // referenced once: fused
+        let project = ProjectExec::try_new(vec![(r.clone(), "x".to_string())], inner.clone())?;
+        assert!(project
+            .input
+            .as_any()
+            .downcast_ref::<MemoryExec>()
+            .is_some());
+
+        // referenced twice: not fused
+        let project = ProjectExec::try_new(
+            vec![(r.clone(), "x".to_string()), (r.clone(), "y".to_string())],
+            inner,
+        )?;
+        assert!(project
+            .input
+            .as_any()
+            .downcast_ref::<ProjectExec>()
+            .is_some());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_project_rows_filtered_metric() -> Result<()> {
+        MemManager::init(10000);
+        let session_ctx = SessionContext::new();
+
+        // SELECT b FROM input WHERE b > 5
+        let filter = Arc::new(FilterExec::try_new(
+            vec![Arc::new(BinaryExpr::new(
+                Arc::new(Column::new("b", 1)),
+                Operator::Gt,
+                lit(5),
+            ))],
+            build_input()?,
+        )?);
+        let project = ProjectExec::try_new(
+            vec![(Arc::new(Column::new("b", 1)), "b".to_string())],
+            filter,
+        )?;
+        let batches = common::collect(project.execute(0, session_ctx.task_ctx())?).await?;
+        assert_eq!(batches.iter().map(|b| b.num_rows()).sum::<usize>(), 2);
+
+        let metrics = project.metrics().unwrap();
+        assert_eq!(metrics.sum_by_name("rows_filtered").unwrap().as_usize(), 2);
+        assert_eq!(metrics.output_rows(), Some(2));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_project_prune_struct_fields() -> Result<()> {
+        MemManager::init(10000);
+        let session_ctx = SessionContext::new();
+
+        // SELECT s.a, s.b FROM (
+        //   SELECT named_struct('x', b / 0, 'a', a, 'b', b) AS s FROM input
+        // ) WHERE s.a > 1
+        let struct_type = DataType::Struct(Fields::from(vec![
+            Field::new("x", DataType::Int32, false),
+            Field::new("a", DataType::Int32, true),
+            Field::new("b", DataType::Int32, false),
+        ]));
+        let named_struct: PhysicalExprRef = Arc::new(NamedStructExpr::try_new(
+            vec![
+                Arc::new(BinaryExpr::new(
+                    Arc::new(Column::new("b", 1)),
+                    Operator::Divide,
+                    lit(0),
+                )),
+                Arc::new(Column::new("a", 0)),
+                Arc::new(Column::new("b", 1)),
+            ],
+            struct_type,
+        )?);
+        let inner = Arc::new(ProjectExec::try_new(
+            vec![(named_struct, "s".to_string())],
+            build_input()?,
+        )?);
+        let get_field = |idx: i32| -> PhysicalExprRef {
+            Arc::new(GetIndexedFieldExpr::new(
+                Arc::new(Column::new("s", 0)),
+                ScalarValue::Int32(Some(idx)),
+            ))
+        };
+        let filter = Arc::new(FilterExec::try_new(
+            vec![Arc::new(BinaryExpr::new(
+                get_field(1),
+                Operator::Gt,
+                lit(1),
+            ))],
+            inner,
+        )?);
+        let project = ProjectExec::try_new(
+            vec![
+                (get_field(1), "a".to_string()),
+                (get_field(2), "b".to_string()),
+            ],
+            filter,
+        )?;
+
+        // b / 0 fails if evaluated, so the unreferenced field must be pruned
+        let batches = common::collect(project.execute(0, session_ctx.task_ctx())?).await?;
+        let expected = vec![
+            "+---+---+",
+            "| a | b |",
+            "+---+---+",
+            "| 3 | 6 |",
+            "+---+---+",
+        ];
+        assert_batches_eq!(expected, &batches);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_project_output_batch_mem_size() -> Result<()> {
+        MemManager::init(1000000);
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("a", DataType::Int32, false),
+            Field::new("b", DataType::Int32, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from_iter_values(0..8000)),
+                Arc::new(Int32Array::from_iter_values(8000..16000)),
+            ],
+        )?;
+        let input: Arc<dyn ExecutionPlan> =
+            Arc::new(MemoryExec::try_new(&[vec![batch]], schema, None)?);
+        let project = |output_batch_mem_size| -> Result<ProjectExec> {
+            Ok(ProjectExec::try_new(
+                vec![(
+                    Arc::new(BinaryExpr::new(
+                        Arc::new(Column::new("a", 0)),
+                        Operator::Plus,
+                        Arc::new(Column::new("b", 1)),
+                    )),
+                    "a_plus_b".to_string(),
+                )],
+                input.clone(),
+            )?
+            .with_output_batch_mem_size(output_batch_mem_size))
+        };
+        let session_ctx = SessionContext::new();
+
+        let default_batches =
+            common::collect(project(None)?.execute(0, session_ctx.task_ctx())?).await?;
+        assert_eq!(default_batches.len(), 1);
+
+        // smaller target produces more and smaller batches with the same rows
+        let batches =
+            common::collect(project(Some(8192))?.execute(0, session_ctx.task_ctx())?).await?;
+        assert!(batches.len() > default_batches.len());
+        assert!(batches.iter().all(|batch| batch.num_rows() < 8000));
+        assert_eq!(
+            concat_batches(&batches[0].schema(), &batches)?,
+            default_batches[0]
+        );
+        Ok(())
+    }
+}
@@ -35,8 +35,9 @@ use futures::{stream::once, TryStreamExt};
 use crate::{
     memmgr::MemManager,
     shuffle::{
-        rss_single_repartitioner::RssSingleShuffleRepartitioner,
-        rss_sort_repartitioner::RssSortShuffleRepartitioner, ShuffleRepartitioner,
+        execute_shuffle_input, rss_single_repartitioner::RssSingleShuffleRepartitioner,
+        rss_sort_repartitioner::RssSortShuffleRepartitioner, FusedShuffleInput,
+        ShuffleRepartitioner,
     },
 };
 
@@ -49,6 +50,8 @@ pub struct RssShuffleWriterExec {
     input: Arc<dyn ExecutionPlan>,
     /// Partitioning scheme to use
     partitioning: Partitioning,
+    /// Seed of murmur3 hashes used in hash partitioning
+    hash_seed: i32,
     /// scala rssShuffleWriter
     pub rss_partition_writer_resource_id: String,
     /// Metrics
@@ -98,6 +101,7 @@ impl ExecutionPlan for RssShuffleWriterExec {
                 children[0].clone(),
                 self.partitioning.clone(),
                 self.rss_partition_writer_resource_id.clone(),
+                self.hash_seed,
             )?)),
             _ => Err(DataFusionError::Internal(
                 "RssShuffleWriterExec wrong number of children".to_string(),
@@ -119,22 +123,36 @@ impl ExecutionPlan for RssShuffleWriterExec {
         // record uncompressed data size
         let data_size_metric = MetricBuilder::new(&self.metrics).counter("data_size", partition);
 
-        let input = self.input.execute(partition, context.clone())?;
+        let mut fused_input = None;
         let repartitioner: Arc<dyn ShuffleRepartitioner> = match &self.partitioning {
             p if p.partition_count() == 1 => {
                 Arc::new(RssSingleShuffleRepartitioner::new(rss_partition_writer))
             }
-            Partitioning::Hash(..) => {
-                let partitioner = Arc::new(RssSortShuffleRepartitioner::new(
-                    partition,
-                    rss_partition_writer,
-                    self.partitioning.clone(),
-                ));
+            Partitioning::Hash(..) | Partitioning::RoundRobinBatch(..) => {
+                // a ProjectExec input is evaluated lazily per output partition
+                fused_input = FusedShuffleInput::try_new(&self.input, &self.partitioning)?;
+                let (partitioning, projection) = match &fused_input {
+                    Some(fused) => (fused.partitioning().clone(), Some(fused.projection())),
+                    None => (self.partitioning.clone(), None),
+                };
+                let partitioner = Arc::new(
+                    RssSortShuffleRepartitioner::new(
+                        partition,
+                        rss_partition_writer,
+                        partitioning,
+                        self.hash_seed,
+                    )
+                    .with_projection(projection),
+                );
                 MemManager::register_consumer(partitioner.clone(), true);
                 partitioner
             }
             p => unreachable!("unsupported partitioning: {:?}", p),
         };
+        let input = match &fused_input {
+            Some(fused) => fused.execute(partition, context.clone())?,
+            None => execute_shuffle_input(&self.input, partition, context.clone())?,
+        };
         Ok(Box::pin(RecordBatchStreamAdapter::new(
             self.schema(),
             once(repartitioner.execute(
@@ -163,10 +181,12 @@ impl RssShuffleWriterExec {
         input: Arc<dyn ExecutionPlan>,
         partitioning: Partitioning,
         rss_partition_writer_resource_id: String,
+        hash_seed: i32,
     ) -> Result<Self> {
         Ok(RssShuffleWriterExec {
             input,
             partitioning,
+            hash_seed,
             rss_partition_writer_resource_id,
             metrics: ExecutionPlanMetricsSet::new(),
         })
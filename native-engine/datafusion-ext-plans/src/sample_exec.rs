@@ -0,0 +1,400 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{any::Any, fmt::Formatter, sync::Arc};
+
+use arrow::datatypes::SchemaRef;
+use datafusion::{
+    common::{Result, Statistics},
+    execution::context::TaskContext,
+    physical_expr::PhysicalSortExpr,
+    physical_plan::{
+        metrics::{BaselineMetrics, ExecutionPlanMetricsSet, MetricsSet},
+        stream::RecordBatchStreamAdapter,
+        DisplayAs, DisplayFormatType, ExecutionPlan, Partitioning, SendableRecordBatchStream,
+    },
+};
+use datafusion_ext_commons::{df_execution_err, streams::coalesce_stream::CoalesceInput};
+use futures::{stream::once, StreamExt, TryStreamExt};
+
+use crate::common::{batch_selection::take_batch, output::TaskOutputter};
+
+/// Samples rows of each partition like spark's SampleExec.
+///
+/// without replacement, rows are sampled by a bernoulli cell sampler seeded
+/// with `seed + partition`, which produces exactly the same rows as spark.
+/// with replacement, each row is repeated by a poisson distributed count,
+/// which is reproducible under a fixed seed but not identical to spark.
+#[derive(Debug, Clone)]
+pub struct SampleExec {
+    input: Arc<dyn ExecutionPlan>,
+    lower_bound: f64,
+    upper_bound: f64,
+    with_replacement: bool,
+    seed: i64,
+    metrics: ExecutionPlanMetricsSet,
+}
+
+impl SampleExec {
+    pub fn try_new(
+        input: Arc<dyn ExecutionPlan>,
+        lower_bound: f64,
+        upper_bound: f64,
+        with_replacement: bool,
+        seed: i64,
+    ) -> Result<Self> {
+        if !(0.0..=upper_bound).contains(&lower_bound) {
+            df_execution_err!("SampleExec: invalid bounds: [{lower_bound}, {upper_bound})")?;
+        }
+        if !with_replacement && upper_bound > 1.0 {
+            df_execution_err!("SampleExec: upper bound must be <= 1.0 without replacement")?;
+        }
+        Ok(Self {
+            input,
+            lower_bound,
+            upper_bound,
+            with_replacement,
+            seed,
+            metrics: ExecutionPlanMetricsSet::new(),
+        })
+    }
+}
+
+impl DisplayAs for SampleExec {
+    fn fmt_as(&self, _t: DisplayFormatType, f: &mut Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "SampleExec(lower_bound={}, upper_bound={}, with_replacement={}, seed={})",
+            self.lower_bound, self.upper_bound, self.with_replacement, self.seed,
+        )
+    }
+}
+
+impl ExecutionPlan for SampleExec {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.input.schema()
+    }
+
+    fn output_partitioning(&self) -> Partitioning {
+        self.input.output_partitioning()
+    }
+
+    fn output_ordering(&self) -> Option<&[PhysicalSortExpr]> {
+        self.input.output_ordering()
+    }
+
+    fn children(&self) -> Vec<Arc<dyn ExecutionPlan>> {
+        vec![self.input.clone()]
+    }
+
+    fn with_new_children(
+        self: Arc<Self>,
+        children: Vec<Arc<dyn ExecutionPlan>>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        Ok(Arc::new(Self::try_new(
+            children[0].clone(),
+            self.lower_bound,
+            self.upper_bound,
+            self.with_replacement,
+            self.seed,
+        )?))
+    }
+
+    fn execute(
+        &self,
+        partition: usize,
+        context: Arc<TaskContext>,
+    ) -> Result<SendableRecordBatchStream> {
+        let metrics = BaselineMetrics::new(&self.metrics, partition);
+        let input = self.input.execute(partition, context.clone())?;
+
+        // spark seeds sampler of each partition with seed + partition index
+        let sampler = Sampler {
+            rng: XORShiftRandom::new(self.seed.wrapping_add(partition as i64)),
+            lower_bound: self.lower_bound,
+            upper_bound: self.upper_bound,
+            with_replacement: self.with_replacement,
+        };
+        let sampled = Box::pin(RecordBatchStreamAdapter::new(
+            self.schema(),
+            once(execute_sample(
+                input,
+                context.clone(),
+                sampler,
+                metrics.clone(),
+            ))
+            .try_flatten(),
+        ));
+        let coalesced = context.coalesce_with_default_batch_size(sampled, &metrics)?;
+        Ok(coalesced)
+    }
+
+    fn metrics(&self) -> Option<MetricsSet> {
+        Some(self.metrics.clone_inner())
+    }
+
+    fn statistics(&self) -> Result<Statistics> {
+        Ok(Statistics::new_unknown(&self.schema()))
+    }
+}
+
+async fn execute_sample(
+    mut input: SendableRecordBatchStream,
+    context: Arc<TaskContext>,
+    mut sampler: Sampler,
+    metrics: BaselineMetrics,
+) -> Result<SendableRecordBatchStream> {
+    context.output_with_sender("Sample", input.schema(), move |sender| async move {
+        while let Some(batch) = input.next().await.transpose()? {
+            let mut timer = metrics.elapsed_compute().timer();
+            let indices = sampler.sample_indices(batch.num_rows());
+            let sampled_batch = take_batch(batch, indices)?;
+            metrics.record_output(sampled_batch.num_rows());
+            sender.send(Ok(sampled_batch), Some(&mut timer)).await;
+        }
+        Ok(())
+    })
+}
+
+struct Sampler {
+    rng: XORShiftRandom,
+    lower_bound: f64,
+    upper_bound: f64,
+    with_replacement: bool,
+}
+
+impl Sampler {
+    // returns indices of sampled rows, a row may be repeated with replacement
+    fn sample_indices(&mut self, num_rows: usize) -> Vec<u32> {
+        let fraction = self.upper_bound - self.lower_bound;
+        let mut indices = vec![];
+        if fraction <= 0.0 {
+            return indices;
+        }
+
+        if self.with_replacement {
+            // same distribution as spark's PoissonSampler, but not the same rows
+            // since spark draws poisson samples with commons-math's generator
+            let exp_neg_fraction = (-fraction).exp();
+            for i in 0..num_rows as u32 {
+                let count = self.next_poisson(exp_neg_fraction);
+                indices.extend(std::iter::repeat(i).take(count));
+            }
+        } else {
+            // same as spark's BernoulliCellSampler
+            for i in 0..num_rows as u32 {
+                let x = self.rng.next_double();
+                if x >= self.lower_bound && x < self.upper_bound {
+                    indices.push(i);
+                }
+            }
+        }
+        indices
+    }
+
+    // knuth's algorithm, efficient enough for the small means used in sampling
+    fn next_poisson(&mut self, exp_neg_mean: f64) -> usize {
+        let mut count = 0;
+        let mut p = self.rng.next_double();
+        while p > exp_neg_mean {
+            count += 1;
+            p *= self.rng.next_double();
+        }
+        count
+    }
+}
+
+/// Port of spark's org.apache.spark.util.random.XORShiftRandom, producing
+/// the same sequence as the JVM for the same seed.
+struct XORShiftRandom {
+    seed: i64,
+}
+
+impl XORShiftRandom {
+    fn new(seed: i64) -> Self {
+        Self {
+            seed: Self::hash_seed(seed),
+        }
+    }
+
+    fn hash_seed(seed: i64) -> i64 {
+        const ARRAY_SEED: u32 = 0x3c074a61;
+        let bytes = seed.to_be_bytes();
+        let low_bits = scala_murmur3_bytes_hash(&bytes, ARRAY_SEED);
+        let high_bits = scala_murmur3_bytes_hash(&bytes, low_bits);
+        ((high_bits as i64) << 32) | (low_bits as i64)
+    }
+
+    fn next(&mut self, bits: u32) -> i64 {
+        let mut next_seed = self.seed ^ (self.seed << 21);
+        next_seed ^= ((next_seed as u64) >> 35) as i64;
+        next_seed ^= next_seed << 4;
+        self.seed = next_seed;
+        next_seed & ((1i64 << bits) - 1)
+    }
+
+    // same as java.util.Random.nextDouble()
+    fn next_double(&mut self) -> f64 {
+        ((self.next(26) << 27) + self.next(27)) as f64 * (1.0 / (1u64 << 53) as f64)
+    }
+}
+
+// scala.util.hashing.MurmurHash3.bytesHash()
+fn scala_murmur3_bytes_hash(data: &[u8], seed: u32) -> u32 {
+    fn mix_last(h: u32, k: u32) -> u32 {
+        h ^ k
+            .wrapping_mul(0xcc9e2d51)
+            .rotate_left(15)
+            .wrapping_mul(0x1b873593)
+    }
+    fn mix(h: u32, k: u32) -> u32 {
+        mix_last(h, k)
+            .rotate_left(13)
+            .wrapping_mul(5)
+            .wrapping_add(0xe6546b64)
+    }
+
+    let mut h = seed;
+    let chunks = data.chunks_exact(4);
+    let tail = chunks.remainder();
+    for chunk in chunks {
+        h = mix(h, u32::from_le_bytes(chunk.try_into().unwrap()));
+    }
+    if !tail.is_empty() {
+        let k = tail
+            .iter()
+            .enumerate()
+            .fold(0u32, |k, (i, &b)| k ^ ((b as u32) << (i * 8)));
+        h = mix_last(h, k);
+    }
+
+    // finalization
+    h ^= data.len() as u32;
+    h ^= h >> 16;
+    h = h.wrapping_mul(0x85ebca6b);
+    h ^= h >> 13;
+    h = h.wrapping_mul(0xc2b2ae35);
+    h ^= h >> 16;
+    h
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use arrow::{
+        array::{AsArray, Int32Array},
+        datatypes::{DataType, Field, Int32Type, Schema},
+        record_batch::RecordBatch,
+    };
+    use datafusion::{
+        common::Result,
+        physical_plan::{common, memory::MemoryExec, ExecutionPlan},
+        prelude::SessionContext,
+    };
+
+    use crate::{
+        memmgr::MemManager,
+        sample_exec::{SampleExec, XORShiftRandom},
+    };
+
+    fn build_input(num_rows: i32, num_partitions: usize) -> Arc<dyn ExecutionPlan> {
+        let schema = Arc::new(Schema::new(vec![Field::new("a", DataType::Int32, false)]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(Int32Array::from_iter_values(0..num_rows))],
+        )
+        .unwrap();
+        let partitions = vec![vec![batch]; num_partitions];
+        Arc::new(MemoryExec::try_new(&partitions, schema, None).unwrap())
+    }
+
+    async fn sample(
+        input: Arc<dyn ExecutionPlan>,
+        partition: usize,
+        upper_bound: f64,
+        with_replacement: bool,
+        seed: i64,
+    ) -> Result<Vec<i32>> {
+        let sample_exec = SampleExec::try_new(input, 0.0, upper_bound, with_replacement, seed)?;
+        let task_ctx = SessionContext::new().task_ctx();
+        let output = sample_exec.execute(partition, task_ctx)?;
+        let batches = common::collect(output).await?;
+        Ok(batches
+            .iter()
+            .flat_map(|batch| {
+                batch
+                    .column(0)
+                    .as_primitive::<Int32Type>()
+                    .values()
+                    .to_vec()
+            })
+            .collect())
+    }
+
+    #[test]
+    fn test_xorshift_random() {
+        // generated by spark's XORShiftRandom
+        assert_eq!(XORShiftRandom::hash_seed(0), -9171266021732529057);
+        assert_eq!(XORShiftRandom::hash_seed(42), 4037347896089686661);
+        assert_eq!(XORShiftRandom::hash_seed(-1), -4346519531656778685);
+
+        let mut rng = XORShiftRandom::new(42);
+        assert_eq!(rng.next_double(), 0.619189370225301);
+        assert_eq!(rng.next_double(), 0.5096018842446481);
+        assert_eq!(rng.next_double(), 0.8325259388871524);
+        assert_eq!(rng.next_double(), 0.26322809041172357);
+        assert_eq!(rng.next_double(), 0.6702867696264135);
+    }
+
+    #[tokio::test]
+    async fn test_sample_without_replacement() -> Result<()> {
+        MemManager::init(1000000);
+        let input = build_input(20, 2);
+
+        // partition 1 is seeded with 42 + 1, same as spark
+        let sampled = sample(input.clone(), 1, 0.5, false, 42).await?;
+        assert_eq!(sampled, vec![2, 3, 8, 9, 15, 17, 19]);
+
+        // deterministic under a fixed seed
+        let input = build_input(10000, 2);
+        let sampled1 = sample(input.clone(), 0, 0.3, false, 12345).await?;
+        let sampled2 = sample(input.clone(), 0, 0.3, false, 12345).await?;
+        assert_eq!(sampled1, sampled2);
+        assert!((2700..3300).contains(&sampled1.len()));
+        assert!(sampled1.windows(2).all(|w| w[0] < w[1]));
+
+        // different partitions are sampled differently
+        let sampled_part1 = sample(input.clone(), 1, 0.3, false, 12345).await?;
+        assert_ne!(sampled1, sampled_part1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sample_with_replacement() -> Result<()> {
+        MemManager::init(1000000);
+        let input = build_input(10000, 1);
+
+        let sampled1 = sample(input.clone(), 0, 1.5, true, 12345).await?;
+        let sampled2 = sample(input.clone(), 0, 1.5, true, 12345).await?;
+        assert_eq!(sampled1, sampled2);
+        assert!((14000..16000).contains(&sampled1.len()));
+        assert!(sampled1.windows(2).any(|w| w[0] == w[1])); // has repeated rows
+        Ok(())
+    }
+}
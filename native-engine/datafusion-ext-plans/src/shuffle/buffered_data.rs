@@ -14,53 +14,113 @@
 
 use std::{io::Write, mem::size_of};
 
-use arrow::record_batch::RecordBatch;
+use arrow::{compute::concat_batches, record_batch::RecordBatch};
 use blaze_jni_bridge::jni_call;
 use bytesize::ByteSize;
 use count_write::CountWrite;
-use datafusion::{common::Result, physical_plan::Partitioning};
+use datafusion::{
+    common::Result,
+    physical_plan::{metrics::Count, Partitioning},
+};
 use datafusion_ext_commons::{
-    array_size::ArraySize,
-    compute_suggested_batch_size_for_output,
-    ds::rdx_tournament_tree::{KeyForRadixTournamentTree, RadixTournamentTree},
-    rdxsort::radix_sort_u16_ranged_by,
+    array_size::ArraySize, compute_suggested_batch_size_for_output,
     staging_mem_size_for_partial_sort,
 };
 use jni::objects::GlobalRef;
 
 use crate::{
-    common::{batch_selection::interleave_batches, ipc_compression::IpcCompressionWriter},
-    shuffle::{evaluate_hashes, evaluate_partition_ids, rss::RssWriter},
+    common::{
+        batch_selection::interleave_batches,
+        ipc_compression::{shuffle_compression_enabled, IpcCompressionWriter},
+    },
+    shuffle::{
+        checksum::{ChecksumWrite, PartitionChecksums},
+        evaluate_output_partition_ids, round_robin_start_pos,
+        rss::RssWriter,
+        ShuffleProjection,
+    },
 };
 
 pub struct BufferedData {
     partition_id: usize,
+    hash_seed: i32,
+    round_robin_pos: i32,
+    staging_round_robin_pos: i32,
     staging_batches: Vec<RecordBatch>,
     sorted_batches: Vec<RecordBatch>,
     sorted_partition_indices: Vec<Vec<u32>>,
     num_rows: usize,
     staging_mem_used: usize,
     sorted_mem_used: usize,
+    sorted_partition_mem_used: Vec<usize>,
+    projection: Option<ShuffleProjection>,
 }
 
 impl BufferedData {
-    pub fn new(partition_id: usize) -> Self {
+    pub fn new(partition_id: usize, partitioning: &Partitioning, hash_seed: i32) -> Self {
+        let round_robin_pos = round_robin_start_pos(partition_id, partitioning);
+        Self::with_round_robin_pos(partition_id, hash_seed, round_robin_pos, None)
+    }
+
+    // buffered rows are projected when they are written
+    pub fn set_projection(&mut self, projection: Option<ShuffleProjection>) {
+        self.projection = projection;
+    }
+
+    fn with_round_robin_pos(
+        partition_id: usize,
+        hash_seed: i32,
+        round_robin_pos: i32,
+        projection: Option<ShuffleProjection>,
+    ) -> Self {
         Self {
             partition_id,
+            hash_seed,
+            round_robin_pos,
+            staging_round_robin_pos: round_robin_pos,
             staging_batches: vec![],
             sorted_batches: vec![],
             sorted_partition_indices: vec![],
             num_rows: 0,
             staging_mem_used: 0,
             sorted_mem_used: 0,
+            sorted_partition_mem_used: vec![],
+            projection,
         }
     }
 
+    // round-robin positions continue in the remaining data, so that rows are
+    // distributed the same way no matter when spilling happens
     pub fn drain(&mut self) -> Self {
-        std::mem::replace(self, Self::new(self.partition_id))
+        let remaining = Self::with_round_robin_pos(
+            self.partition_id,
+            self.hash_seed,
+            self.round_robin_pos,
+            self.projection.clone(),
+        );
+        std::mem::replace(self, remaining)
+    }
+
+    // estimates memory usage after adding the batch, including the transient
+    // partition/row indices used when sorting staging batches by partition id
+    pub fn mem_used_for_adding(&self, batch: &RecordBatch, partitioning: &Partitioning) -> usize {
+        let num_staging_rows = self
+            .staging_batches
+            .iter()
+            .map(|batch| batch.num_rows())
+            .sum::<usize>()
+            + batch.num_rows();
+        let sort_indices_mem_size = num_staging_rows
+            * (size_of::<(u32, u32, u32)>() + size_of::<u32>() + size_of::<(usize, usize)>())
+            + partitioning.partition_count() * size_of::<usize>();
+        self.mem_used() + batch.get_array_memory_size() * 2 + sort_indices_mem_size
     }
 
     pub fn add_batch(&mut self, batch: RecordBatch, partitioning: &Partitioning) -> Result<()> {
+        if self.staging_batches.is_empty() {
+            self.staging_round_robin_pos = self.round_robin_pos;
+        }
+        self.round_robin_pos = self.round_robin_pos.wrapping_add(batch.num_rows() as i32);
         self.num_rows += batch.num_rows();
         self.staging_mem_used += batch.get_array_mem_size();
         self.staging_batches.push(batch);
@@ -81,19 +141,104 @@ impl BufferedData {
         let staging_batches = std::mem::take(&mut self.staging_batches);
         self.staging_mem_used = 0;
 
-        let (partition_indices, sorted_batch) =
-            sort_batches_by_partition_id(staging_batches, partitioning)?;
+        let (partition_indices, sorted_batch) = sort_batches_by_partition_id(
+            staging_batches,
+            partitioning,
+            self.hash_seed,
+            self.staging_round_robin_pos,
+        )?;
 
-        self.sorted_mem_used +=
+        let sorted_batch_mem_size =
             sorted_batch.get_array_mem_size() + partition_indices.len() * size_of::<u32>();
+        self.sorted_mem_used += sorted_batch_mem_size;
+
+        // estimate memory usage of each partition
+        let row_mem_size = sorted_batch_mem_size / partition_indices.len().max(1);
+        self.sorted_partition_mem_used
+            .resize(partitioning.partition_count(), 0);
+        for &part_id in &partition_indices {
+            self.sorted_partition_mem_used[part_id as usize] += row_mem_size;
+        }
         self.sorted_batches.push(sorted_batch);
         self.sorted_partition_indices.push(partition_indices);
         Ok(())
     }
 
-    // write buffered data to spill/target file, returns uncompressed size and
-    // offsets to each partition
-    pub fn write<W: Write>(self, mut w: W, partitioning: &Partitioning) -> Result<Vec<u64>> {
+    // returns the partition whose sorted data exceeds the threshold
+    pub fn skewed_partition(&self, threshold: usize) -> Option<u32> {
+        self.sorted_partition_mem_used
+            .iter()
+            .enumerate()
+            .max_by_key(|&(_, mem_used)| *mem_used)
+            .filter(|&(_, mem_used)| *mem_used >= threshold)
+            .map(|(part_id, _)| part_id as u32)
+    }
+
+    // takes all sorted data of the specified partition out, so that a skewed
+    // partition can be spilled alone
+    pub fn drain_partition(&mut self, part_id: u32) -> Result<Self> {
+        let mut drained = Self::with_round_robin_pos(
+            self.partition_id,
+            self.hash_seed,
+            self.round_robin_pos,
+            self.projection.clone(),
+        );
+        let sorted_batches = std::mem::take(&mut self.sorted_batches);
+        let sorted_partition_indices = std::mem::take(&mut self.sorted_partition_indices);
+        self.sorted_mem_used = 0;
+
+        for (batch, partition_indices) in sorted_batches.into_iter().zip(sorted_partition_indices) {
+            // partition indices are sorted, so rows of one partition are contiguous
+            let start = partition_indices.partition_point(|&p| p < part_id);
+            let end = partition_indices.partition_point(|&p| p <= part_id);
+            let (batch, partition_indices) = if start < end {
+                let num_rows = end - start;
+                let drained_batch = batch.slice(start, num_rows);
+                drained.num_rows += num_rows;
+                drained.sorted_mem_used +=
+                    drained_batch.get_array_mem_size() + num_rows * size_of::<u32>();
+                drained.sorted_batches.push(drained_batch);
+                drained
+                    .sorted_partition_indices
+                    .push(vec![part_id; num_rows]);
+                self.num_rows -= num_rows;
+
+                let remaining_batch = concat_batches(
+                    &batch.schema(),
+                    &[
+                        batch.slice(0, start),
+                        batch.slice(end, batch.num_rows() - end),
+                    ],
+                )?;
+                let remaining_partition_indices =
+                    [&partition_indices[..start], &partition_indices[end..]].concat();
+                (remaining_batch, remaining_partition_indices)
+            } else {
+                (batch, partition_indices)
+            };
+
+            if batch.num_rows() > 0 {
+                self.sorted_mem_used +=
+                    batch.get_array_mem_size() + partition_indices.len() * size_of::<u32>();
+                self.sorted_batches.push(batch);
+                self.sorted_partition_indices.push(partition_indices);
+            }
+        }
+        if let Some(mem_used) = self.sorted_partition_mem_used.get_mut(part_id as usize) {
+            *mem_used = 0;
+        }
+        Ok(drained)
+    }
+
+    // write buffered data to spill/target file, returns offsets to each partition
+    // and adds uncompressed size to the given metric
+    pub fn write<W: Write>(
+        self,
+        mut w: W,
+        partitioning: &Partitioning,
+        uncompressed_bytes: &Count,
+        checksums: &mut PartitionChecksums,
+    ) -> Result<Vec<u64>> {
         let partition_id = self.partition_id;
         log::info!(
             "[partition={partition_id}] draining all buffered data, total_mem={}",
@@ -105,6 +250,15 @@ impl BufferedData {
         }
         let mut offsets = vec![];
         let mut offset = 0;
+
+        // one writer is shared by all partitions, so its buffers are allocated
+        // once with a hint of average partition size and then recycled
+        let capacity_hint = self.mem_used() / partitioning.partition_count();
+        let mut writer = IpcCompressionWriter::with_capacity_hint(
+            ChecksumWrite::new(CountWrite::from(&mut w), None),
+            shuffle_compression_enabled(),
+            capacity_hint,
+        );
         let mut iter = self.into_sorted_batches(partitioning)?;
 
         while (iter.cur_part_id() as usize) < partitioning.partition_count() {
@@ -113,14 +267,18 @@ impl BufferedData {
                 offsets.push(offset); // fill offsets of empty partitions
             }
 
-            // write all batches with this part id
-            let mut writer = IpcCompressionWriter::new(CountWrite::from(&mut w), true);
+            // write all batches with this part id, updating its checksum
+            let checksum = checksums.get_mut(cur_part_id as usize);
+            std::mem::swap(writer.inner_mut().checksum_mut(), checksum);
             while iter.cur_part_id() == cur_part_id {
-                writer.write_batch(iter.next_batch())?;
+                writer.write_batch(iter.next_batch()?)?;
             }
-            offset += writer.finish_into_inner()?.count();
+            writer.flush()?;
+            std::mem::swap(writer.inner_mut().checksum_mut(), checksum);
+            offset = writer.inner().get_ref().count();
             offsets.push(offset);
         }
+        uncompressed_bytes.add(writer.uncompressed_size());
         while offsets.len() <= partitioning.partition_count() {
             offsets.push(offset); // fill offsets of empty partitions
         }
@@ -151,12 +309,12 @@ impl BufferedData {
             let cur_part_id = iter.cur_part_id();
             let mut writer = IpcCompressionWriter::new(
                 RssWriter::new(rss_partition_writer.clone(), cur_part_id as usize),
-                true,
+                shuffle_compression_enabled(),
             );
 
             // write all batches with this part id
             while iter.cur_part_id() == cur_part_id {
-                writer.write_batch(iter.next_batch())?;
+                writer.write_batch(iter.next_batch()?)?;
             }
             writer.finish_into_inner()?;
         }
@@ -177,69 +335,86 @@ impl BufferedData {
         let sub_batch_size =
             compute_suggested_batch_size_for_output(self.mem_used(), self.num_rows);
 
+        let cursors = self
+            .sorted_partition_indices
+            .into_iter()
+            .enumerate()
+            .map(|(idx, partition_indices)| PartCursor {
+                idx,
+                part_id: partition_indices[0],
+                row_idx: 0,
+                partition_indices,
+            })
+            .collect::<Vec<_>>();
         Ok(PartitionedBatchesIterator {
             batches: self.sorted_batches.clone(),
-            cursors: RadixTournamentTree::new(
-                self.sorted_partition_indices
-                    .into_iter()
-                    .enumerate()
-                    .map(|(idx, partition_indices)| PartCursor {
-                        idx,
-                        part_id: partition_indices[0],
-                        row_idx: 0,
-                        partition_indices,
-                    })
-                    .collect(),
-                partitioning.partition_count(),
-            ),
+            cur_part_id: cursors.iter().map(|c| c.part_id).min().unwrap_or(u32::MAX),
+            cursors,
             num_output_rows: 0,
             num_rows: self.num_rows,
             batch_size: sub_batch_size,
+            projection: self.projection,
         })
     }
 
     pub fn mem_used(&self) -> usize {
-        self.staging_mem_used + self.sorted_mem_used
+        self.staging_mem_used
+            + self.sorted_mem_used
+            + self.sorted_partition_mem_used.capacity() * size_of::<usize>()
     }
 }
 
 struct PartitionedBatchesIterator {
     batches: Vec<RecordBatch>,
-    cursors: RadixTournamentTree<PartCursor>,
+    cursors: Vec<PartCursor>,
+    cur_part_id: u32,
     num_output_rows: usize,
     num_rows: usize,
     batch_size: usize,
+    projection: Option<ShuffleProjection>,
 }
 
 impl PartitionedBatchesIterator {
     pub fn cur_part_id(&self) -> u32 {
-        self.cursors.peek().part_id
+        self.cur_part_id
     }
 
-    fn next_batch(&mut self) -> RecordBatch {
+    fn next_batch(&mut self) -> Result<RecordBatch> {
         let cur_batch_size = self.batch_size.min(self.num_rows - self.num_output_rows);
-        let cur_part_id = self.cur_part_id();
+        let cur_part_id = self.cur_part_id;
         let mut indices = Vec::with_capacity(cur_batch_size);
 
-        // add rows with same parition id under this cursor
-        while indices.len() < cur_batch_size {
-            let mut min_cursor = self.cursors.peek_mut();
-            if min_cursor.part_id != cur_part_id {
-                break;
-            }
-            while indices.len() < cur_batch_size && min_cursor.part_id == cur_part_id {
-                indices.push((min_cursor.idx, min_cursor.row_idx));
-                min_cursor.row_idx += 1;
-                min_cursor.part_id = *min_cursor
+        // add rows with same parition id. cursors are visited in the order of
+        // sorted batches, so rows of a partition are output in input order
+        for cursor in &mut self.cursors {
+            while indices.len() < cur_batch_size && cursor.part_id == cur_part_id {
+                indices.push((cursor.idx, cursor.row_idx));
+                cursor.row_idx += 1;
+                cursor.part_id = *cursor
                     .partition_indices
-                    .get(min_cursor.row_idx)
+                    .get(cursor.row_idx)
                     .unwrap_or(&u32::MAX);
             }
+            if indices.len() == cur_batch_size {
+                break;
+            }
         }
-        let output_batch = interleave_batches(self.batches[0].schema(), &self.batches, &indices)
-            .expect("error merging sorted batches: interleaving error");
+
+        // forward to the next partition if current one is exhausted
+        self.cursors.retain(|cursor| cursor.part_id != u32::MAX);
+        self.cur_part_id = self
+            .cursors
+            .iter()
+            .map(|cursor| cursor.part_id)
+            .min()
+            .unwrap_or(u32::MAX);
+
+        let output_batch = interleave_batches(self.batches[0].schema(), &self.batches, &indices)?;
         self.num_output_rows += output_batch.num_rows();
-        output_batch
+        match &self.projection {
+            Some(projection) => projection.project(&output_batch),
+            None => Ok(output_batch),
+        }
     }
 }
 
@@ -250,40 +425,59 @@ struct PartCursor {
     part_id: u32,
 }
 
-impl KeyForRadixTournamentTree for PartCursor {
-    fn rdx(&self) -> usize {
-        self.part_id as usize
-    }
-}
-
 fn sort_batches_by_partition_id(
     batches: Vec<RecordBatch>,
     partitioning: &Partitioning,
+    hash_seed: i32,
+    round_robin_pos: i32,
 ) -> Result<(Vec<u32>, RecordBatch)> {
     let num_rows = batches.iter().map(|batch| batch.num_rows()).sum::<usize>();
     let num_partitions = partitioning.partition_count();
     let schema = batches[0].schema();
 
-    let mut indices = batches // partition_id, batch_idx, row_idx
-        .iter()
-        .enumerate()
-        .flat_map(|(batch_idx, batch)| {
-            let hashes = evaluate_hashes(partitioning, batch)
-                .expect(&format!("error evaluating hashes with {partitioning}"));
-            evaluate_partition_ids(&hashes, partitioning.partition_count())
+    // partition expressions may contain fused projections, whose evaluation
+    // errors (like ansi casting errors) are returned to the caller
+    let mut round_robin_pos = round_robin_pos;
+    let mut indices = Vec::with_capacity(num_rows); // partition_id, batch_idx, row_idx
+    for (batch_idx, batch) in batches.iter().enumerate() {
+        let part_ids =
+            evaluate_output_partition_ids(partitioning, batch, hash_seed, round_robin_pos)
+                .map_err(|err| {
+                    err.context(format!(
+                        "error evaluating partition ids with {partitioning}"
+                    ))
+                })?;
+        round_robin_pos = round_robin_pos.wrapping_add(batch.num_rows() as i32);
+        indices.extend(
+            part_ids
                 .into_iter()
                 .enumerate()
-                .map(move |(row_idx, part_id)| (part_id, batch_idx as u32, row_idx as u32))
-        })
-        .collect::<Vec<_>>();
+                .map(|(row_idx, part_id)| (part_id, batch_idx as u32, row_idx as u32)),
+        );
+    }
 
-    // use quick sort if there are too many partitions or too few rows, otherwise
-    // use radix sort
-    if num_partitions < 65536 && num_rows >= num_partitions {
-        radix_sort_u16_ranged_by(&mut indices, num_partitions, |v| v.0 as u16);
+    // use merge sort if there are too few rows, otherwise use counting sort.
+    // both are stable, so rows of a partition keep their input order
+    let indices = if num_rows >= num_partitions {
+        let mut offsets = vec![0; num_partitions + 1];
+        for &(part_id, ..) in &indices {
+            offsets[part_id as usize + 1] += 1;
+        }
+        for i in 0..num_partitions {
+            offsets[i + 1] += offsets[i];
+        }
+        let mut sorted_indices = vec![(0, 0, 0); num_rows];
+        for v in indices {
+            let offset = &mut offsets[v.0 as usize];
+            sorted_indices[*offset] = v;
+            *offset += 1;
+        }
+        sorted_indices
     } else {
-        indices.sort_unstable_by_key(|v| v.0);
-    }
+        let mut indices = indices;
+        indices.sort_by_key(|v| v.0);
+        indices
+    };
 
     // get sorted batches
     let (sorted_partition_indices, sorted_row_indices): (Vec<u32>, Vec<_>) = indices
@@ -293,3 +487,431 @@ fn sort_batches_by_partition_id(
     let sorted_batch = interleave_batches(schema, &batches, &sorted_row_indices)?;
     return Ok((sorted_partition_indices, sorted_batch));
 }
+
+#[cfg(test)]
+mod test {
+    use std::{io::Cursor, sync::Arc};
+
+    use arrow::{
+        array::{ArrayRef, AsArray, Int32Array, Int64Array, StringArray, UInt32Array},
+        compute::{concat_batches, take},
+        datatypes::{DataType, Field, Int64Type, Schema, SchemaRef},
+        record_batch::RecordBatch,
+    };
+    use datafusion::{
+        common::{Result, ScalarValue},
+        logical_expr::Operator,
+        physical_expr::{
+            expressions::{BinaryExpr, Column, Literal},
+            PhysicalExprRef,
+        },
+        physical_plan::{memory::MemoryExec, metrics::Count, ExecutionPlan, Partitioning},
+    };
+    use datafusion_ext_commons::spark_hash::create_murmur3_hashes;
+
+    use crate::{
+        common::{
+            cached_exprs_evaluator::CachedExprsEvaluator, ipc_compression::IpcCompressionReader,
+        },
+        project_exec::ProjectExec,
+        shuffle::{
+            buffered_data::{sort_batches_by_partition_id, BufferedData},
+            checksum::{PartitionChecksums, ShuffleChecksumAlgorithm},
+            evaluate_hashes, evaluate_partition_ids, FusedShuffleInput, DEFAULT_HASH_SEED,
+        },
+    };
+
+    #[test]
+    fn test_drain_skewed_partition() -> Result<()> {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("k", DataType::Int32, false),
+            Field::new("v", DataType::Int32, false),
+        ]));
+        let partitioning = Partitioning::Hash(vec![Arc::new(Column::new("k", 0))], 8);
+
+        // key 1 is dominant
+        let keys = (0..10000)
+            .map(|i| if i % 10 == 0 { i } else { 1 })
+            .collect::<Vec<_>>();
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from(keys)),
+                Arc::new(Int32Array::from_iter_values(0..10000)),
+            ],
+        )?;
+        let part_ids = evaluate_partition_ids(
+            &evaluate_hashes(&partitioning, &batch, DEFAULT_HASH_SEED)?,
+            8,
+        );
+        let dominant_part_id = part_ids[1];
+        let num_dominant_rows = part_ids.iter().filter(|&&p| p == dominant_part_id).count();
+
+        let mut data = BufferedData::new(0, &partitioning, DEFAULT_HASH_SEED);
+        for _ in 0..3 {
+            data.add_batch(batch.clone(), &partitioning)?;
+            data.flush_staging_batches(&partitioning)?;
+        }
+        let mem_used = data.mem_used();
+        assert_eq!(data.skewed_partition(mem_used), None);
+        assert_eq!(data.skewed_partition(mem_used / 2), Some(dominant_part_id));
+
+        let drained = data.drain_partition(dominant_part_id)?;
+        assert_eq!(drained.num_rows, num_dominant_rows * 3);
+        assert_eq!(data.num_rows, (10000 - num_dominant_rows) * 3);
+        assert!(data.mem_used() < mem_used / 2);
+        assert_eq!(data.skewed_partition(mem_used / 2), None);
+        assert!(data
+            .sorted_partition_indices
+            .iter()
+            .flatten()
+            .all(|&p| p != dominant_part_id));
+
+        // drained spill only contains data for the skewed partition
+        let mut spill = vec![];
+        let uncompressed_bytes = Count::new();
+        let offsets = drained.write(
+            &mut spill,
+            &partitioning,
+            &uncompressed_bytes,
+            &mut PartitionChecksums::new(None, partitioning.partition_count()),
+        )?;
+        assert!(uncompressed_bytes.value() > 0);
+        assert_eq!(offsets.len(), 9);
+        for part_id in 0..8 {
+            let len = offsets[part_id + 1] - offsets[part_id];
+            assert_eq!(len > 0, part_id as u32 == dominant_part_id);
+        }
+        assert_eq!(offsets[8] as usize, spill.len());
+        Ok(())
+    }
+
+    #[test]
+    fn test_mem_used_with_many_partitions() -> Result<()> {
+        let schema = Arc::new(Schema::new(vec![Field::new("k", DataType::Int32, false)]));
+        let num_partitions = 10000;
+        let partitioning = Partitioning::Hash(vec![Arc::new(Column::new("k", 0))], num_partitions);
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(Int32Array::from_iter_values(0..1000))],
+        )?;
+        let batch_mem_size = batch.get_array_memory_size();
+
+        // reserved memory includes the sort indices and partition vectors
+        let mut data = BufferedData::new(0, &partitioning, DEFAULT_HASH_SEED);
+        let reserved = data.mem_used_for_adding(&batch, &partitioning);
+        assert!(reserved > batch_mem_size * 2 + num_partitions * 8);
+
+        // partition vectors are still tracked after flushing
+        data.add_batch(batch.clone(), &partitioning)?;
+        data.flush_staging_batches(&partitioning)?;
+        assert!(data.mem_used() > batch_mem_size + num_partitions * 8);
+        assert!(data.mem_used() <= reserved);
+
+        // staging rows are accounted when adding more batches
+        data.add_batch(batch.clone(), &partitioning)?;
+        assert!(data.mem_used_for_adding(&batch, &partitioning) > reserved);
+        Ok(())
+    }
+
+    #[test]
+    fn test_hash_seed() -> Result<()> {
+        let schema = Arc::new(Schema::new(vec![Field::new("k", DataType::Int32, false)]));
+        let partitioning = Partitioning::Hash(vec![Arc::new(Column::new("k", 0))], 16);
+        let keys: ArrayRef = Arc::new(Int32Array::from_iter_values(0..1000));
+        let batch = RecordBatch::try_new(schema, vec![keys.clone()])?;
+
+        // default seed is identical to spark's murmur3 hash partitioning
+        let mut spark_hashes = vec![42; 1000];
+        create_murmur3_hashes(&[keys], &mut spark_hashes)?;
+        let spark_part_ids = evaluate_partition_ids(&spark_hashes, 16);
+        let part_ids = evaluate_partition_ids(
+            &evaluate_hashes(&partitioning, &batch, DEFAULT_HASH_SEED)?,
+            16,
+        );
+        assert_eq!(part_ids, spark_part_ids);
+
+        // different seeds produce different partition assignments
+        let part_ids1 = evaluate_partition_ids(&evaluate_hashes(&partitioning, &batch, 1)?, 16);
+        let part_ids2 = evaluate_partition_ids(&evaluate_hashes(&partitioning, &batch, 2)?, 16);
+        assert_ne!(part_ids1, part_ids2);
+        assert_ne!(part_ids1, part_ids);
+
+        // seed is used when writing buffered data
+        let write_offsets = |hash_seed| -> Result<Vec<u64>> {
+            let mut data = BufferedData::new(0, &partitioning, hash_seed);
+            data.add_batch(batch.clone(), &partitioning)?;
+            data.write(
+                &mut Vec::<u8>::new(),
+                &partitioning,
+                &Count::new(),
+                &mut PartitionChecksums::new(None, partitioning.partition_count()),
+            )
+        };
+        assert_eq!(
+            write_offsets(DEFAULT_HASH_SEED)?,
+            write_offsets(DEFAULT_HASH_SEED)?
+        );
+        assert_ne!(write_offsets(DEFAULT_HASH_SEED)?, write_offsets(1)?);
+        Ok(())
+    }
+
+    fn build_wide_batch(num_rows: usize, num_columns: usize) -> Result<RecordBatch> {
+        let mut fields = vec![
+            Field::new("k", DataType::Int32, false),
+            Field::new("v", DataType::Int64, false),
+        ];
+        let mut columns: Vec<ArrayRef> = vec![
+            Arc::new(Int32Array::from_iter_values(
+                (0..num_rows as i32).map(|i| i.wrapping_mul(7919) % 100000),
+            )),
+            Arc::new(Int64Array::from_iter_values(0..num_rows as i64)),
+        ];
+        for col_idx in 0..num_columns {
+            fields.push(Field::new(format!("s{col_idx}"), DataType::Utf8, true));
+            columns.push(Arc::new(StringArray::from_iter((0..num_rows).map(|i| {
+                Some(format!("value-{col_idx}-{i}")).filter(|_| i % 13 != col_idx)
+            }))));
+        }
+        Ok(RecordBatch::try_new(
+            Arc::new(Schema::new(fields)),
+            columns,
+        )?)
+    }
+
+    // reference implementation: group row indices by partition and take every
+    // column once for each non-empty partition
+    fn take_by_partition(
+        batch: &RecordBatch,
+        partitioning: &Partitioning,
+    ) -> Result<Vec<Option<RecordBatch>>> {
+        let hashes = evaluate_hashes(partitioning, batch, DEFAULT_HASH_SEED)?;
+        let part_ids = evaluate_partition_ids(&hashes, partitioning.partition_count());
+        let mut partition_rows = vec![vec![]; partitioning.partition_count()];
+        for (row_idx, &part_id) in part_ids.iter().enumerate() {
+            partition_rows[part_id as usize].push(row_idx as u32);
+        }
+        partition_rows
+            .into_iter()
+            .map(|rows| {
+                if rows.is_empty() {
+                    return Ok(None);
+                }
+                let indices = UInt32Array::from(rows);
+                let columns = batch
+                    .columns()
+                    .iter()
+                    .map(|column| Ok(take(column, &indices, None)?))
+                    .collect::<Result<Vec<_>>>()?;
+                Ok(Some(RecordBatch::try_new(batch.schema(), columns)?))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_sort_batches_by_partition_id() -> Result<()> {
+        let batch = build_wide_batch(10000, 8)?;
+        let partitioning = Partitioning::Hash(vec![Arc::new(Column::new("k", 0))], 200);
+        let expected = take_by_partition(&batch, &partitioning)?;
+
+        // split into multiple staging batches
+        let staging_batches = vec![batch.slice(0, 3000), batch.slice(3000, 7000)];
+        let (partition_indices, sorted_batch) =
+            sort_batches_by_partition_id(staging_batches, &partitioning, DEFAULT_HASH_SEED, 0)?;
+        assert_eq!(sorted_batch.num_rows(), 10000);
+        assert!(partition_indices.windows(2).all(|w| w[0] <= w[1]));
+
+        for (part_id, expected) in expected.into_iter().enumerate() {
+            let start = partition_indices.partition_point(|&p| p < part_id as u32);
+            let end = partition_indices.partition_point(|&p| p <= part_id as u32);
+            match expected {
+                Some(expected) => {
+                    // rows of a partition are in input order
+                    assert_eq!(sorted_batch.slice(start, end - start), expected);
+                }
+                None => assert_eq!(start, end),
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_sort_batches_by_partition_id_with_error() -> Result<()> {
+        let batch = build_wide_batch(100, 1)?;
+
+        // k / 0 fails evaluating, the error is returned instead of panicking
+        let partitioning = Partitioning::Hash(
+            vec![Arc::new(BinaryExpr::new(
+                Arc::new(Column::new("k", 0)),
+                Operator::Divide,
+                Arc::new(Literal::new(ScalarValue::Int32(Some(0)))),
+            ))],
+            4,
+        );
+        let result = sort_batches_by_partition_id(vec![batch], &partitioning, DEFAULT_HASH_SEED, 0);
+        assert!(result.is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_partition_rows_in_input_order() -> Result<()> {
+        let partitioning = Partitioning::Hash(vec![Arc::new(Column::new("k", 0))], 2);
+        let batch = build_wide_batch(36000, 1)?;
+
+        // rows of each partition come from multiple sorted batches and are
+        // output in more than one batch
+        let mut data = BufferedData::new(0, &partitioning, DEFAULT_HASH_SEED);
+        for i in 0..3 {
+            data.add_batch(batch.slice(i * 12000, 12000), &partitioning)?;
+            data.flush_staging_batches(&partitioning)?;
+        }
+        let mut output = vec![];
+        let algorithm = ShuffleChecksumAlgorithm::Crc32;
+        let mut checksums = PartitionChecksums::new(Some(algorithm), 2);
+        let offsets = data.write(&mut output, &partitioning, &Count::new(), &mut checksums)?;
+
+        for part_id in 0..2 {
+            let partition_data =
+                output[offsets[part_id] as usize..offsets[part_id + 1] as usize].to_vec();
+            assert_eq!(
+                checksums.values()[part_id],
+                algorithm.checksum(&partition_data[..])?
+            );
+            let mut reader = IpcCompressionReader::new(Cursor::new(partition_data), batch.schema());
+            let mut values = vec![];
+            let mut num_batches = 0;
+            while let Some(batch) = reader.read_batch()? {
+                values.extend(batch.column(1).as_primitive::<Int64Type>().values());
+                num_batches += 1;
+            }
+            assert!(num_batches > 1);
+            assert!(!values.is_empty());
+            assert!(values.windows(2).all(|w| w[0] < w[1]));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_round_robin_partitioning() -> Result<()> {
+        let partitioning = Partitioning::RoundRobinBatch(7);
+        let batch = build_wide_batch(30, 1)?;
+
+        // positions continue across staging batches and drained data. the
+        // starting position of partition 3 is XORShiftRandom(3).nextInt(7) = 6
+        let mut data = BufferedData::new(3, &partitioning, DEFAULT_HASH_SEED);
+        data.add_batch(batch.slice(0, 10), &partitioning)?;
+        data.add_batch(batch.slice(10, 10), &partitioning)?;
+        let drained = data.drain();
+        data.add_batch(batch.slice(20, 10), &partitioning)?;
+
+        for (data, range) in [(drained, 0..20), (data, 20..30)] {
+            let mut output = vec![];
+            let offsets = data.write(
+                &mut output,
+                &partitioning,
+                &Count::new(),
+                &mut PartitionChecksums::new(None, partitioning.partition_count()),
+            )?;
+            for part_id in 0..7 {
+                let partition_data =
+                    output[offsets[part_id] as usize..offsets[part_id + 1] as usize].to_vec();
+                let mut reader =
+                    IpcCompressionReader::new(Cursor::new(partition_data), batch.schema());
+                let mut values = vec![];
+                while let Some(batch) = reader.read_batch()? {
+                    values.extend(batch.column(1).as_primitive::<Int64Type>().values());
+                }
+                let expected = range
+                    .clone()
+                    .filter(|&i| (6 + i + 1) % 7 == part_id as i64)
+                    .collect::<Vec<_>>();
+                assert_eq!(values, expected);
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_fused_projection() -> Result<()> {
+        // SELECT k, v + 0 AS c0, ..., v + 15 AS c15, partitioned by c0
+        let input_batch = build_wide_batch(10000, 1)?;
+        let mut exprs: Vec<(PhysicalExprRef, String)> =
+            vec![(Arc::new(Column::new("k", 0)), "k".to_string())];
+        for i in 0..16 {
+            let expr = Arc::new(BinaryExpr::new(
+                Arc::new(Column::new("v", 1)),
+                Operator::Plus,
+                Arc::new(Literal::new(ScalarValue::Int64(Some(i)))),
+            ));
+            exprs.push((expr, format!("c{i}")));
+        }
+        let input = Arc::new(MemoryExec::try_new(
+            &[vec![input_batch.clone()]],
+            input_batch.schema(),
+            None,
+        )?);
+        let project = Arc::new(ProjectExec::try_new(exprs, input)?);
+        let partitioning = Partitioning::Hash(vec![Arc::new(Column::new("c0", 1))], 16);
+
+        // unfused: projected batches are buffered
+        let projected = CachedExprsEvaluator::try_new(
+            vec![],
+            project.exprs().iter().map(|(e, _)| e.clone()).collect(),
+            project.schema(),
+        )?
+        .filter_project(&input_batch)?;
+        let mut data = BufferedData::new(0, &partitioning, DEFAULT_HASH_SEED);
+        data.add_batch(projected.clone(), &partitioning)?;
+        data.flush_staging_batches(&partitioning)?;
+
+        // fused: only the referenced input columns (k, v) are buffered
+        let fused =
+            FusedShuffleInput::try_new(&(project as Arc<dyn ExecutionPlan>), &partitioning)?
+                .expect("not fused");
+        assert_eq!(fused.input_projection, vec![0, 1]);
+        let mut fused_data = BufferedData::new(0, fused.partitioning(), DEFAULT_HASH_SEED);
+        fused_data.set_projection(Some(fused.projection()));
+        fused_data.add_batch(
+            input_batch.project(&fused.input_projection)?,
+            fused.partitioning(),
+        )?;
+        fused_data.flush_staging_batches(fused.partitioning())?;
+
+        // bytes copied when sorting rows by partition id: 2 input columns
+        // instead of 17 projected columns
+        assert!(fused_data.mem_used() * 4 < data.mem_used());
+
+        // output partitions are identical
+        fn read_partitions(
+            data: BufferedData,
+            partitioning: &Partitioning,
+            schema: SchemaRef,
+        ) -> Result<Vec<RecordBatch>> {
+            let mut output = vec![];
+            let offsets = data.write(
+                &mut output,
+                partitioning,
+                &Count::new(),
+                &mut PartitionChecksums::new(None, partitioning.partition_count()),
+            )?;
+            (0..partitioning.partition_count())
+                .map(|part_id| {
+                    let partition_data =
+                        output[offsets[part_id] as usize..offsets[part_id + 1] as usize].to_vec();
+                    let mut reader =
+                        IpcCompressionReader::new(Cursor::new(partition_data), schema.clone());
+                    let mut batches = vec![];
+                    while let Some(batch) = reader.read_batch()? {
+                        batches.push(batch);
+                    }
+                    Ok(concat_batches(&schema, &batches)?)
+                })
+                .collect()
+        }
+        let expected = read_partitions(data, &partitioning, projected.schema())?;
+        let output = read_partitions(fused_data, fused.partitioning(), projected.schema())?;
+        assert_eq!(output.iter().map(|b| b.num_rows()).sum::<usize>(), 10000);
+        assert_eq!(output, expected);
+        Ok(())
+    }
+}
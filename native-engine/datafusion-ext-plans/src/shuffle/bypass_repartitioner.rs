@@ -0,0 +1,501 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    fs::{File, OpenOptions},
+    io::{Seek, Write},
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicI32, Ordering::SeqCst},
+        Weak,
+    },
+};
+
+use arrow::{array::UInt32Array, compute::take_record_batch, record_batch::RecordBatch};
+use async_trait::async_trait;
+use blaze_jni_bridge::{
+    conf::{IntConf, SHUFFLE_BYPASS_MERGE_THRESHOLD, SHUFFLE_BYPASS_PARTITION_BUFFER_SIZE},
+    is_jni_bridge_inited,
+};
+use datafusion::{
+    common::{DataFusionError, Result},
+    physical_plan::{
+        metrics::{BaselineMetrics, ExecutionPlanMetricsSet},
+        Partitioning,
+    },
+};
+use datafusion_ext_commons::df_execution_err;
+use futures::lock::Mutex;
+
+use crate::{
+    common::ipc_compression::{
+        shuffle_compression_enabled, IpcCompressionWriter,
+        DEFAULT_SHUFFLE_COMPRESSION_TARGET_BUF_SIZE,
+    },
+    memmgr::{spill::try_new_named_spill_file, MemConsumer, MemConsumerInfo, MemManager},
+    shuffle::{
+        checksum::{
+            write_checksum_file, Checksum, ChecksumWrite, PartitionChecksums,
+            ShuffleChecksumAlgorithm,
+        },
+        evaluate_output_partition_ids,
+        hash_validation::HashValidator,
+        round_robin_start_pos, ShuffleRepartitioner, ShuffleWriteMetrics,
+    },
+};
+
+/// repartitioner for hash/round-robin shuffles with few output partitions, like
+/// spark's BypassMergeSortShuffleWriter. rows of each partition are written to
+/// a separate temporary file as batches are inserted, and the files are
+/// concatenated into the output in shuffle_write(), so no data is buffered
+/// for sorting. spilling just flushes the buffered blocks of all partitions.
+pub struct BypassMergeShuffleRepartitioner {
+    name: String,
+    mem_consumer_info: Option<Weak<MemConsumerInfo>>,
+    output_data_file: String,
+    output_index_file: String,
+    output_checksum: Option<(String, ShuffleChecksumAlgorithm)>,
+    partitioning: Partitioning,
+    hash_seed: i32,
+    hash_validator: HashValidator,
+    round_robin_pos: AtomicI32,
+    partition_writers: Mutex<Vec<Option<PartitionWriter>>>,
+    partition_buf_size: usize,
+    metrics: BaselineMetrics,
+    shuffle_write_metrics: ShuffleWriteMetrics,
+}
+
+struct PartitionWriter {
+    writer: IpcCompressionWriter<ChecksumWrite<File>>,
+    buffered_size: usize,
+    file_deleter: PartitionFileDeleter,
+}
+
+impl PartitionWriter {
+    fn try_new(checksum_algorithm: Option<ShuffleChecksumAlgorithm>) -> Result<Self> {
+        let (file, path) = try_new_named_spill_file()?;
+        Ok(Self {
+            writer: IpcCompressionWriter::new(
+                ChecksumWrite::new(file, checksum_algorithm.map(Checksum::new)),
+                shuffle_compression_enabled(),
+            ),
+            buffered_size: 0,
+            file_deleter: PartitionFileDeleter(path),
+        })
+    }
+}
+
+/// deletes the named partition file when dropped, either after it is copied
+/// into the output or when the task fails
+struct PartitionFileDeleter(Option<PathBuf>);
+
+impl Drop for PartitionFileDeleter {
+    fn drop(&mut self) {
+        if let Some(path) = &self.0 {
+            if let Err(e) = std::fs::remove_file(path) {
+                log::warn!("error deleting bypass partition file {path:?}: {e}");
+            }
+        }
+    }
+}
+
+impl BypassMergeShuffleRepartitioner {
+    pub fn new(
+        partition_id: usize,
+        output_data_file: String,
+        output_index_file: String,
+        partitioning: Partitioning,
+        hash_seed: i32,
+        metrics: &ExecutionPlanMetricsSet,
+    ) -> Result<Self> {
+        let num_output_partitions = partitioning.partition_count();
+        let partition_buf_size = bypass_partition_buf_size(num_output_partitions);
+        let round_robin_pos = round_robin_start_pos(partition_id, &partitioning);
+        Ok(Self {
+            name: format!("BypassMergeShufflePartitioner[partition={}]", partition_id),
+            mem_consumer_info: None,
+            output_data_file,
+            output_index_file,
+            output_checksum: None,
+            partitioning,
+            hash_seed,
+            hash_validator: HashValidator::new(hash_seed),
+            round_robin_pos: AtomicI32::new(round_robin_pos),
+            partition_writers: Mutex::new((0..num_output_partitions).map(|_| None).collect()),
+            partition_buf_size,
+            metrics: BaselineMetrics::new(metrics, partition_id),
+            shuffle_write_metrics: ShuffleWriteMetrics::new(metrics, partition_id)?,
+        })
+    }
+
+    /// computes checksums of output partitions while writing them, and writes
+    /// them into the checksum file
+    pub fn with_output_checksum(
+        mut self,
+        output_checksum: Option<(String, ShuffleChecksumAlgorithm)>,
+    ) -> Self {
+        self.output_checksum = output_checksum;
+        self
+    }
+}
+
+#[async_trait]
+impl MemConsumer for BypassMergeShuffleRepartitioner {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn set_consumer_info(&mut self, consumer_info: Weak<MemConsumerInfo>) {
+        self.mem_consumer_info = Some(consumer_info);
+    }
+
+    fn get_consumer_info(&self) -> &Weak<MemConsumerInfo> {
+        self.mem_consumer_info
+            .as_ref()
+            .expect("consumer info not set")
+    }
+
+    async fn spill(&self) -> Result<usize> {
+        let mut partition_writers = self.partition_writers.lock().await;
+        if !self.is_spillable() {
+            return Ok(0);
+        }
+        for partition_writer in partition_writers.iter_mut().flatten() {
+            if partition_writer.buffered_size > 0 {
+                partition_writer.writer.flush()?;
+                partition_writer.buffered_size = 0;
+            }
+        }
+        drop(partition_writers);
+
+        let freed = self.mem_used();
+        self.update_mem_used(0).await?;
+        Ok(freed)
+    }
+}
+
+impl Drop for BypassMergeShuffleRepartitioner {
+    fn drop(&mut self) {
+        MemManager::deregister_consumer(self);
+    }
+}
+
+#[async_trait]
+impl ShuffleRepartitioner for BypassMergeShuffleRepartitioner {
+    async fn insert_batch(&self, input: RecordBatch) -> Result<()> {
+        let _timer = self.metrics.elapsed_compute().timer();
+        self.hash_validator.validate(&input, &self.partitioning)?;
+
+        let num_output_partitions = self.partitioning.partition_count();
+        let round_robin_pos = self
+            .round_robin_pos
+            .fetch_add(input.num_rows() as i32, SeqCst);
+        let part_ids = evaluate_output_partition_ids(
+            &self.partitioning,
+            &input,
+            self.hash_seed,
+            round_robin_pos,
+        )?;
+        let (grouped_batch, offsets) =
+            group_rows_by_partition(&input, &part_ids, num_output_partitions)?;
+
+        let mut partition_writers = self.partition_writers.lock().await;
+        for part_id in 0..num_output_partitions {
+            let (start, end) = (offsets[part_id], offsets[part_id + 1]);
+            if start == end {
+                continue;
+            }
+            let part_batch = grouped_batch.slice(start, end - start);
+            if partition_writers[part_id].is_none() {
+                partition_writers[part_id] = Some(PartitionWriter::try_new(
+                    self.output_checksum
+                        .as_ref()
+                        .map(|(_, algorithm)| *algorithm),
+                )?);
+            }
+            let partition_writer = partition_writers[part_id].as_mut().unwrap();
+            let uncompressed_size = partition_writer.writer.uncompressed_size();
+            partition_writer.writer.write_batch(part_batch)?;
+            partition_writer.buffered_size +=
+                partition_writer.writer.uncompressed_size() - uncompressed_size;
+            if partition_writer.buffered_size >= self.partition_buf_size {
+                partition_writer.writer.flush()?;
+                partition_writer.buffered_size = 0;
+            }
+        }
+
+        // buffered blocks of all partitions are accounted in the mem manager
+        let mem_used = partition_writers
+            .iter()
+            .flatten()
+            .map(|partition_writer| partition_writer.buffered_size)
+            .sum();
+        drop(partition_writers);
+        self.update_mem_used(mem_used).await?;
+        Ok(())
+    }
+
+    async fn shuffle_write(&self) -> Result<()> {
+        let _timer = self.metrics.elapsed_compute().timer();
+        let mut partition_writers_guard = self.partition_writers.lock().await;
+        self.set_spillable(false);
+        let partition_writers = std::mem::take(&mut *partition_writers_guard);
+        drop(partition_writers_guard);
+        let data_file = self.output_data_file.clone();
+        let index_file = self.output_index_file.clone();
+        let uncompressed_bytes = self.shuffle_write_metrics.uncompressed_bytes.clone();
+        let output_checksum = self.output_checksum.clone();
+        let mut checksums = PartitionChecksums::new(
+            output_checksum.as_ref().map(|(_, algorithm)| *algorithm),
+            partition_writers.len(),
+        );
+
+        // concatenate partition files into the output. checksums are computed
+        // while writing partition files, like spark's BypassMergeSortShuffleWriter
+        let offsets = tokio::task::spawn_blocking(move || {
+            let mut output_data = OpenOptions::new()
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .open(&data_file)?;
+            let mut offsets = vec![0];
+            let mut offset = 0;
+            for (partition_id, partition_writer) in partition_writers.into_iter().enumerate() {
+                if let Some(PartitionWriter {
+                    writer,
+                    file_deleter,
+                    ..
+                }) = partition_writer
+                {
+                    uncompressed_bytes.add(writer.uncompressed_size());
+                    let (mut partition_file, checksum) = writer.finish_into_inner()?.into_inner();
+                    *checksums.get_mut(partition_id) = checksum;
+                    partition_file.rewind()?;
+                    offset += std::io::copy(&mut partition_file, &mut output_data)?;
+                    drop(partition_file);
+                    drop(file_deleter);
+                }
+                offsets.push(offset);
+            }
+            output_data.sync_data()?;
+            output_data.flush()?;
+
+            let mut output_index = File::create(&index_file)?;
+            for &offset in &offsets {
+                output_index.write_all(&(offset as i64).to_le_bytes()[..])?;
+            }
+            output_index.sync_data()?;
+            output_index.flush()?;
+
+            if let Some((checksum_file, _)) = output_checksum {
+                write_checksum_file(&checksum_file, &checksums.values())?;
+            }
+            Ok::<_, DataFusionError>(offsets)
+        })
+        .await
+        .or_else(|e| df_execution_err!("shuffle write error: {e:?}"))??;
+        self.shuffle_write_metrics.record_output_offsets(&offsets);
+        self.update_mem_used(0).await?;
+        Ok(())
+    }
+}
+
+/// hash/round-robin shuffles with fewer output partitions than this use the
+/// bypass repartitioner
+pub fn bypass_merge_threshold() -> usize {
+    if is_jni_bridge_inited() {
+        SHUFFLE_BYPASS_MERGE_THRESHOLD
+            .value()
+            .expect("error getting configured bypass merge threshold") as usize
+    } else {
+        200 // for testing
+    }
+}
+
+/// partition data is flushed when its buffered bytes exceed this size, no
+/// matter how many rows are buffered, so wide rows do not blow up the memory
+fn bypass_partition_buf_size(num_output_partitions: usize) -> usize {
+    let configured_size = if is_jni_bridge_inited() {
+        SHUFFLE_BYPASS_PARTITION_BUFFER_SIZE
+            .value()
+            .expect("error getting configured bypass partition buffer size")
+            .max(0) as usize
+    } else {
+        0 // for testing
+    };
+    if configured_size > 0 {
+        return configured_size;
+    }
+
+    // every partition holds a block buffer, so the buffer size is bounded to
+    // keep the total buffered size about one block
+    (DEFAULT_SHUFFLE_COMPRESSION_TARGET_BUF_SIZE / num_output_partitions).max(32768)
+}
+
+// reorders rows so that rows of a partition are contiguous and in input order,
+// returns the reordered batch and offsets of each partition. all columns are
+// taken only once instead of once per partition.
+fn group_rows_by_partition(
+    batch: &RecordBatch,
+    part_ids: &[u32],
+    num_partitions: usize,
+) -> Result<(RecordBatch, Vec<usize>)> {
+    let mut offsets = vec![0; num_partitions + 1];
+    for &part_id in part_ids {
+        offsets[part_id as usize + 1] += 1;
+    }
+    for i in 0..num_partitions {
+        offsets[i + 1] += offsets[i];
+    }
+
+    let mut cur_offsets = offsets.clone();
+    let mut indices = vec![0; part_ids.len()];
+    for (row_idx, &part_id) in part_ids.iter().enumerate() {
+        let offset = &mut cur_offsets[part_id as usize];
+        indices[*offset] = row_idx as u32;
+        *offset += 1;
+    }
+    let grouped_batch = take_record_batch(batch, &UInt32Array::from(indices))?;
+    Ok((grouped_batch, offsets))
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use arrow::{
+        array::{Int32Array, StringArray, UInt32Array},
+        compute::take_record_batch,
+        datatypes::{DataType, Field, Schema},
+        record_batch::RecordBatch,
+    };
+    use datafusion::{
+        common::Result,
+        physical_expr::expressions::Column,
+        physical_plan::{metrics::ExecutionPlanMetricsSet, Partitioning},
+    };
+
+    use crate::{
+        memmgr::MemManager,
+        shuffle::{
+            bypass_repartitioner::{group_rows_by_partition, BypassMergeShuffleRepartitioner},
+            evaluate_hashes, evaluate_partition_ids,
+            sort_repartitioner::SortShuffleRepartitioner,
+            ShuffleRepartitioner, DEFAULT_HASH_SEED,
+        },
+    };
+
+    #[tokio::test]
+    async fn test_bypass_merge_same_output() -> Result<()> {
+        MemManager::init(1000000);
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("k", DataType::Int32, false),
+            Field::new("v", DataType::Utf8, false),
+        ]));
+
+        // rows of the same key are identical, so that the output does not depend
+        // on the order of rows in a partition
+        let keys = (0..1000).map(|i| i % 37).collect::<Vec<_>>();
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from(keys.clone())),
+                Arc::new(StringArray::from_iter_values(
+                    keys.iter().map(|k| format!("value-{k}")),
+                )),
+            ],
+        )?;
+        let partitioning = Partitioning::Hash(vec![Arc::new(Column::new("k", 0))], 64);
+        let tmp_dir = tempfile::tempdir()?;
+        let output_files = |name: &str| {
+            (
+                tmp_dir.path().join(format!("{name}.data")),
+                tmp_dir.path().join(format!("{name}.index")),
+            )
+        };
+
+        let (bypass_data_file, bypass_index_file) = output_files("bypass");
+        let bypass = Arc::new(BypassMergeShuffleRepartitioner::new(
+            0,
+            bypass_data_file.to_string_lossy().to_string(),
+            bypass_index_file.to_string_lossy().to_string(),
+            partitioning.clone(),
+            DEFAULT_HASH_SEED,
+            &ExecutionPlanMetricsSet::new(),
+        )?);
+        MemManager::register_consumer(bypass.clone(), true);
+        bypass.insert_batch(batch.clone()).await?;
+        bypass.shuffle_write().await?;
+
+        let (sort_data_file, sort_index_file) = output_files("sort");
+        let sort = Arc::new(SortShuffleRepartitioner::new(
+            0,
+            sort_data_file.to_string_lossy().to_string(),
+            sort_index_file.to_string_lossy().to_string(),
+            partitioning.clone(),
+            DEFAULT_HASH_SEED,
+            &ExecutionPlanMetricsSet::new(),
+        )?);
+        MemManager::register_consumer(sort.clone(), true);
+        sort.insert_batch(batch.clone()).await?;
+        sort.shuffle_write().await?;
+
+        let bypass_index = std::fs::read(&bypass_index_file)?;
+        assert_eq!(bypass_index.len(), (partitioning.partition_count() + 1) * 8);
+        assert_eq!(bypass_index, std::fs::read(&sort_index_file)?);
+        assert_eq!(
+            std::fs::read(&bypass_data_file)?,
+            std::fs::read(&sort_data_file)?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_group_rows_by_partition() -> Result<()> {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("k", DataType::Int32, false),
+            Field::new("v", DataType::Utf8, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(Int32Array::from_iter_values(0..10000)),
+                Arc::new(StringArray::from_iter(
+                    (0..10000).map(|i| Some(format!("value-{i}")).filter(|_| i % 7 != 0)),
+                )),
+            ],
+        )?;
+        let num_partitions = 2000;
+        let partitioning = Partitioning::Hash(vec![Arc::new(Column::new("k", 0))], num_partitions);
+        let part_ids = evaluate_partition_ids(
+            &evaluate_hashes(&partitioning, &batch, DEFAULT_HASH_SEED)?,
+            num_partitions,
+        );
+
+        let (grouped_batch, offsets) = group_rows_by_partition(&batch, &part_ids, num_partitions)?;
+        assert_eq!(offsets.len(), num_partitions + 1);
+        assert_eq!(offsets[num_partitions], batch.num_rows());
+
+        // same as taking rows of each partition separately
+        for part_id in 0..num_partitions {
+            let indices = UInt32Array::from_iter_values(
+                (0..batch.num_rows() as u32).filter(|&i| part_ids[i as usize] == part_id as u32),
+            );
+            let expected = take_record_batch(&batch, &indices)?;
+            let (start, end) = (offsets[part_id], offsets[part_id + 1]);
+            assert_eq!(grouped_batch.slice(start, end - start), expected);
+        }
+        Ok(())
+    }
+}
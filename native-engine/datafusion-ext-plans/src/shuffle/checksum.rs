@@ -0,0 +1,224 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    fs::File,
+    io::{BufWriter, Read, Seek, SeekFrom, Write},
+};
+
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use datafusion::common::Result;
+use datafusion_ext_commons::df_execution_err;
+
+/// checksum algorithms of spark.shuffle.checksum.algorithm. checksums are
+/// computed on partition bytes in the data file, same as spark, so that
+/// spark can use them to diagnose corrupted blocks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShuffleChecksumAlgorithm {
+    Adler32,
+    Crc32,
+}
+
+impl ShuffleChecksumAlgorithm {
+    pub fn try_from_name(name: &str) -> Result<Self> {
+        match name.to_ascii_uppercase().as_str() {
+            "ADLER32" => Ok(Self::Adler32),
+            "CRC32" => Ok(Self::Crc32),
+            _ => df_execution_err!("unsupported shuffle checksum algorithm: {name}"),
+        }
+    }
+
+    pub fn checksum(&self, mut input: impl Read) -> Result<i64> {
+        let mut checksum = Checksum::new(*self);
+        let mut buf = vec![0; 65536];
+        loop {
+            let len = input.read(&mut buf)?;
+            if len == 0 {
+                break;
+            }
+            checksum.update(&buf[..len]);
+        }
+        Ok(checksum.value())
+    }
+}
+
+/// running checksum of one partition
+#[derive(Clone)]
+pub enum Checksum {
+    Adler32(adler::Adler32),
+    Crc32(crc32fast::Hasher),
+}
+
+impl Checksum {
+    pub fn new(algorithm: ShuffleChecksumAlgorithm) -> Self {
+        match algorithm {
+            ShuffleChecksumAlgorithm::Adler32 => Self::Adler32(adler::Adler32::new()),
+            ShuffleChecksumAlgorithm::Crc32 => Self::Crc32(crc32fast::Hasher::new()),
+        }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        match self {
+            Self::Adler32(adler32) => adler32.write_slice(data),
+            Self::Crc32(crc32) => crc32.update(data),
+        }
+    }
+
+    pub fn value(&self) -> i64 {
+        match self {
+            Self::Adler32(adler32) => adler32.checksum() as i64,
+            Self::Crc32(crc32) => crc32.clone().finalize() as i64,
+        }
+    }
+}
+
+/// checksums of all output partitions, updated while partition data is
+/// written into spills or the output file. like spark's ShuffleExternalSorter,
+/// spilled blocks of a partition are copied into the output as-is and in
+/// spill order, so its checksum is continued across spills instead of
+/// reading the output file again.
+pub struct PartitionChecksums {
+    checksums: Vec<Option<Checksum>>,
+}
+
+impl PartitionChecksums {
+    /// all checksums are None if algorithm is None
+    pub fn new(algorithm: Option<ShuffleChecksumAlgorithm>, num_partitions: usize) -> Self {
+        Self {
+            checksums: vec![algorithm.map(Checksum::new); num_partitions],
+        }
+    }
+
+    pub fn get_mut(&mut self, partition: usize) -> &mut Option<Checksum> {
+        &mut self.checksums[partition]
+    }
+
+    pub fn values(&self) -> Vec<i64> {
+        self.checksums
+            .iter()
+            .map(|checksum| checksum.as_ref().map(Checksum::value).unwrap_or_default())
+            .collect()
+    }
+}
+
+/// writer updating the checksum (if any) with all written bytes
+pub struct ChecksumWrite<W: Write> {
+    inner: W,
+    checksum: Option<Checksum>,
+}
+
+impl<W: Write> ChecksumWrite<W> {
+    pub fn new(inner: W, checksum: Option<Checksum>) -> Self {
+        Self { inner, checksum }
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    pub fn checksum_mut(&mut self) -> &mut Option<Checksum> {
+        &mut self.checksum
+    }
+
+    pub fn into_inner(self) -> (W, Option<Checksum>) {
+        (self.inner, self.checksum)
+    }
+}
+
+impl<W: Write> Write for ChecksumWrite<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let len = self.inner.write(buf)?;
+        if let Some(checksum) = &mut self.checksum {
+            checksum.update(&buf[..len]);
+        }
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// writes checksums in the format of spark's checksum file: one big-endian
+/// long for each partition
+pub fn write_checksum_file(checksum_file: &str, checksums: &[i64]) -> Result<()> {
+    let mut output = BufWriter::new(File::create(checksum_file)?);
+    for &checksum in checksums {
+        output.write_i64::<BigEndian>(checksum)?;
+    }
+    output.flush()?;
+    output.get_ref().sync_data()?;
+    Ok(())
+}
+
+/// reads checksum of the specified partition from the checksum file
+pub fn read_partition_checksum(checksum_file: &str, partition: usize) -> Result<i64> {
+    let mut input = File::open(checksum_file)?;
+    input.seek(SeekFrom::Start(partition as u64 * 8))?;
+    Ok(input.read_i64::<BigEndian>()?)
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Write;
+
+    use datafusion::common::Result;
+
+    use crate::shuffle::checksum::{
+        read_partition_checksum, write_checksum_file, ChecksumWrite, PartitionChecksums,
+        ShuffleChecksumAlgorithm,
+    };
+
+    #[test]
+    fn test_partition_checksums() -> Result<()> {
+        let tmp_dir = tempfile::tempdir()?;
+        let checksum_file = tmp_dir.path().join("shuffle.checksum");
+        let checksum_file = checksum_file.to_string_lossy().to_string();
+
+        // partition 1 is empty, partition 2 is written in two parts like
+        // blocks from different spills
+        let partitions: [&[&[u8]]; 3] = [&[b"Wikipedia"], &[], &[b"hello ", b"world"]];
+        let checksums = |algorithm| -> Result<Vec<i64>> {
+            let mut checksums = PartitionChecksums::new(Some(algorithm), partitions.len());
+            let mut output = ChecksumWrite::new(vec![], None);
+            for (partition, parts) in partitions.iter().enumerate() {
+                for part in parts.iter() {
+                    std::mem::swap(output.checksum_mut(), checksums.get_mut(partition));
+                    output.write_all(part)?;
+                    std::mem::swap(output.checksum_mut(), checksums.get_mut(partition));
+                }
+            }
+            assert_eq!(output.get_ref(), b"Wikipediahello world");
+            Ok(checksums.values())
+        };
+
+        // expected values are from java.util.zip.Adler32/CRC32
+        let adler32 = checksums(ShuffleChecksumAlgorithm::try_from_name("ADLER32")?)?;
+        assert_eq!(adler32, vec![0x11e60398, 1, 0x1a0b045d]);
+        let crc32 = checksums(ShuffleChecksumAlgorithm::try_from_name("crc32")?)?;
+        assert_eq!(crc32, vec![0xadaac02e, 0, 0x0d4a1185]);
+        assert_eq!(
+            ShuffleChecksumAlgorithm::Crc32.checksum(&b"hello world"[..])?,
+            0x0d4a1185
+        );
+        assert_eq!(PartitionChecksums::new(None, 3).values(), vec![0; 3]);
+
+        write_checksum_file(&checksum_file, &crc32)?;
+        assert_eq!(std::fs::read(&checksum_file)?.len(), 24);
+        assert_eq!(read_partition_checksum(&checksum_file, 2)?, 0x0d4a1185);
+
+        assert!(ShuffleChecksumAlgorithm::try_from_name("md5").is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,326 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use arrow::{array::ArrayRef, record_batch::RecordBatch};
+use blaze_jni_bridge::{
+    conf::{IntConf, SHUFFLE_HASH_VALIDATION_SAMPLE_INTERVAL},
+    is_jni_bridge_inited,
+};
+use datafusion::{
+    common::{Result, ScalarValue},
+    physical_plan::Partitioning,
+};
+use datafusion_ext_commons::df_execution_err;
+
+use crate::shuffle::{evaluate_hashes, evaluate_partition_ids};
+
+/// validates partition ids of hash partitioning against a row-wise reference
+/// implementation of spark's murmur3 hash, so that any mismatch with the jvm
+/// fails the task immediately instead of silently routing rows to wrong
+/// partitions.
+///
+/// only every n-th row is validated (n is configured by
+/// spark.blaze.shuffle.hashValidation.sampleInterval, 0 means disabled).
+pub struct HashValidator {
+    hash_seed: i32,
+    sample_interval: usize,
+}
+
+impl HashValidator {
+    pub fn new(hash_seed: i32) -> Self {
+        let sample_interval = if is_jni_bridge_inited() {
+            SHUFFLE_HASH_VALIDATION_SAMPLE_INTERVAL
+                .value()
+                .expect("error getting configured hash validation sample interval")
+                .max(0) as usize
+        } else {
+            0 // disabled for testing
+        };
+        Self {
+            hash_seed,
+            sample_interval,
+        }
+    }
+
+    pub fn validate(&self, batch: &RecordBatch, partitioning: &Partitioning) -> Result<()> {
+        let Partitioning::Hash(exprs, num_partitions) = partitioning else {
+            return Ok(());
+        };
+        if self.sample_interval == 0 || batch.num_rows() == 0 {
+            return Ok(());
+        }
+
+        let hashes = evaluate_hashes(partitioning, batch, self.hash_seed)?;
+        let part_ids = evaluate_partition_ids(&hashes, *num_partitions);
+        let arrays = exprs
+            .iter()
+            .map(|expr| expr.evaluate(batch)?.into_array(batch.num_rows()))
+            .collect::<Result<Vec<_>>>()?;
+
+        for row_idx in (0..batch.num_rows()).step_by(self.sample_interval) {
+            self.validate_row(
+                &arrays,
+                row_idx,
+                hashes[row_idx],
+                part_ids[row_idx],
+                *num_partitions,
+            )?;
+        }
+        Ok(())
+    }
+
+    fn validate_row(
+        &self,
+        arrays: &[ArrayRef],
+        row_idx: usize,
+        hash: i32,
+        part_id: u32,
+        num_partitions: usize,
+    ) -> Result<()> {
+        let keys = arrays
+            .iter()
+            .map(|array| ScalarValue::try_from_array(array, row_idx))
+            .collect::<Result<Vec<_>>>()?;
+
+        // rows with key types not covered by the reference implementation are skipped
+        let Some(expected_hash) = reference_murmur3_hash(&keys, self.hash_seed) else {
+            return Ok(());
+        };
+        let expected_part_id = expected_hash.rem_euclid(num_partitions as i32) as u32;
+
+        if hash != expected_hash || part_id != expected_part_id {
+            log::error!(
+                "!!! shuffle hash validation failed, keys={keys:?}, \
+                    hash={hash}, expected_hash={expected_hash}, \
+                    part_id={part_id}, expected_part_id={expected_part_id}, \
+                    num_partitions={num_partitions}"
+            );
+            return df_execution_err!(
+                "shuffle hash validation failed: keys={keys:?}, \
+                    hash={hash}, expected_hash={expected_hash}, \
+                    part_id={part_id}, expected_part_id={expected_part_id}"
+            );
+        }
+        Ok(())
+    }
+}
+
+/// spark's Murmur3Hash, computed value by value as in
+/// HashExpression.computeHash(). returns None if any key has a data type not
+/// supported here.
+fn reference_murmur3_hash(keys: &[ScalarValue], seed: i32) -> Option<i32> {
+    keys.iter().try_fold(seed, |hash, key| {
+        if key.is_null() {
+            return Some(hash);
+        }
+        Some(match key {
+            ScalarValue::Boolean(Some(v)) => ref_murmur3::hash_int(*v as i32, hash),
+            ScalarValue::Int8(Some(v)) => ref_murmur3::hash_int(*v as i32, hash),
+            ScalarValue::Int16(Some(v)) => ref_murmur3::hash_int(*v as i32, hash),
+            ScalarValue::Int32(Some(v)) => ref_murmur3::hash_int(*v, hash),
+            ScalarValue::Date32(Some(v)) => ref_murmur3::hash_int(*v, hash),
+            ScalarValue::Int64(Some(v)) => ref_murmur3::hash_long(*v, hash),
+            ScalarValue::TimestampMicrosecond(Some(v), _) => ref_murmur3::hash_long(*v, hash),
+            ScalarValue::Float32(Some(v)) => {
+                let bits = match *v {
+                    v if v == 0.0 => 0, // -0.0 is hashed as 0.0
+                    v if v.is_nan() => f32::NAN.to_bits() as i32,
+                    v => v.to_bits() as i32,
+                };
+                ref_murmur3::hash_int(bits, hash)
+            }
+            ScalarValue::Float64(Some(v)) => {
+                let bits = match *v {
+                    v if v == 0.0 => 0, // -0.0 is hashed as 0.0
+                    v if v.is_nan() => f64::NAN.to_bits() as i64,
+                    v => v.to_bits() as i64,
+                };
+                ref_murmur3::hash_long(bits, hash)
+            }
+            ScalarValue::Decimal128(Some(v), precision, _) if *precision <= 18 => {
+                ref_murmur3::hash_long(*v as i64, hash)
+            }
+            ScalarValue::Utf8(Some(v)) | ScalarValue::LargeUtf8(Some(v)) => {
+                ref_murmur3::hash_bytes(v.as_bytes(), hash)
+            }
+            ScalarValue::Binary(Some(v)) | ScalarValue::LargeBinary(Some(v)) => {
+                ref_murmur3::hash_bytes(v, hash)
+            }
+            _ => return None,
+        })
+    })
+}
+
+/// straightforward port of spark's Murmur3_x86_32, kept independent from the
+/// vectorized implementation in datafusion-ext-commons.
+mod ref_murmur3 {
+    pub fn hash_int(input: i32, seed: i32) -> i32 {
+        fmix(mix_h1(seed, mix_k1(input)), 4)
+    }
+
+    pub fn hash_long(input: i64, seed: i32) -> i32 {
+        let h1 = mix_h1(seed, mix_k1(input as i32));
+        let h1 = mix_h1(h1, mix_k1((input >> 32) as i32));
+        fmix(h1, 8)
+    }
+
+    /// same as Murmur3_x86_32.hashUnsafeBytes()
+    pub fn hash_bytes(data: &[u8], seed: i32) -> i32 {
+        let aligned_len = data.len() - data.len() % 4;
+        let mut h1 = seed;
+        for chunk in data[..aligned_len].chunks_exact(4) {
+            let half_word = i32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+            h1 = mix_h1(h1, mix_k1(half_word));
+        }
+        for &b in &data[aligned_len..] {
+            h1 = mix_h1(h1, mix_k1(b as i8 as i32));
+        }
+        fmix(h1, data.len() as i32)
+    }
+
+    fn mix_k1(k1: i32) -> i32 {
+        k1.wrapping_mul(0xcc9e2d51u32 as i32)
+            .rotate_left(15)
+            .wrapping_mul(0x1b873593u32 as i32)
+    }
+
+    fn mix_h1(h1: i32, k1: i32) -> i32 {
+        (h1 ^ k1)
+            .rotate_left(13)
+            .wrapping_mul(5)
+            .wrapping_add(0xe6546b64u32 as i32)
+    }
+
+    fn fmix(h1: i32, len: i32) -> i32 {
+        let mut h1 = (h1 ^ len) as u32;
+        h1 ^= h1 >> 16;
+        h1 = h1.wrapping_mul(0x85ebca6b);
+        h1 ^= h1 >> 13;
+        h1 = h1.wrapping_mul(0xc2b2ae35);
+        h1 ^= h1 >> 16;
+        h1 as i32
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use arrow::{
+        array::{ArrayRef, Int32Array, Int64Array, StringArray},
+        datatypes::{DataType, Field, Schema},
+        record_batch::RecordBatch,
+    };
+    use datafusion::{
+        common::{Result, ScalarValue},
+        physical_expr::expressions::Column,
+        physical_plan::Partitioning,
+    };
+
+    use crate::shuffle::{
+        hash_validation::{reference_murmur3_hash, HashValidator},
+        DEFAULT_HASH_SEED,
+    };
+
+    #[test]
+    fn test_reference_murmur3_hash() {
+        // values of spark's hash() function
+        let hash = |key: ScalarValue| reference_murmur3_hash(&[key], DEFAULT_HASH_SEED);
+        assert_eq!(hash(ScalarValue::Int32(Some(1))), Some(-559580957));
+        assert_eq!(hash(ScalarValue::Int32(None)), Some(DEFAULT_HASH_SEED));
+        assert_eq!(hash(ScalarValue::from("abc")), Some(1322437556));
+        assert_eq!(hash(ScalarValue::from("abcde")), Some(814637928));
+        assert_eq!(
+            hash(ScalarValue::Float64(Some(-0.0))),
+            hash(ScalarValue::Float64(Some(0.0))),
+        );
+        assert_eq!(hash(ScalarValue::Null), Some(DEFAULT_HASH_SEED));
+        assert_eq!(
+            hash(ScalarValue::List(ScalarValue::new_list(
+                &[],
+                &DataType::Int32
+            ))),
+            None
+        );
+    }
+
+    #[test]
+    fn test_hash_validation() -> Result<()> {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("i", DataType::Int32, true),
+            Field::new("l", DataType::Int64, true),
+            Field::new("s", DataType::Utf8, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(Int32Array::from_iter(
+                    (0..1000).map(|i| (i % 7 != 0).then_some(i)),
+                )),
+                Arc::new(Int64Array::from_iter_values(
+                    (0..1000).map(|i| i * 1000000007),
+                )),
+                Arc::new(StringArray::from_iter_values(
+                    (0..1000).map(|i| format!("s{i}")),
+                )),
+            ],
+        )?;
+        let partitioning = Partitioning::Hash(
+            vec![
+                Arc::new(Column::new("i", 0)),
+                Arc::new(Column::new("l", 1)),
+                Arc::new(Column::new("s", 2)),
+            ],
+            200,
+        );
+        let validator = HashValidator {
+            hash_seed: DEFAULT_HASH_SEED,
+            sample_interval: 1,
+        };
+        validator.validate(&batch, &partitioning)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_hash_validation_mismatch() -> Result<()> {
+        let array: ArrayRef = Arc::new(Int32Array::from(vec![1]));
+        let validator = HashValidator {
+            hash_seed: DEFAULT_HASH_SEED,
+            sample_interval: 1,
+        };
+        let expected_hash = -559580957;
+        let expected_part_id = expected_hash.rem_euclid(200) as u32;
+
+        validator.validate_row(&[array.clone()], 0, expected_hash, expected_part_id, 200)?;
+        assert!(validator
+            .validate_row(
+                &[array.clone()],
+                0,
+                expected_hash + 1,
+                expected_part_id,
+                200
+            )
+            .is_err());
+        assert!(validator
+            .validate_row(
+                &[array.clone()],
+                0,
+                expected_hash,
+                expected_part_id + 1,
+                200
+            )
+            .is_err());
+        Ok(())
+    }
+}
@@ -19,29 +19,48 @@ use std::sync::{
 
 use arrow::{error::Result as ArrowResult, record_batch::RecordBatch};
 use async_trait::async_trait;
+use blaze_jni_bridge::{conf, conf::BooleanConf, is_jni_bridge_inited};
 use bytesize::ByteSize;
 use datafusion::{
-    common::Result,
+    common::{
+        tree_node::{Transformed, TreeNode},
+        Result,
+    },
     error::DataFusionError,
     execution::context::TaskContext,
+    physical_expr::{expressions::Column, PhysicalExprRef},
     physical_plan::{
-        metrics::{BaselineMetrics, Count},
-        Partitioning, SendableRecordBatchStream,
+        metrics::{BaselineMetrics, Count, ExecutionPlanMetricsSet, Gauge, MetricBuilder, Time},
+        stream::RecordBatchStreamAdapter,
+        ExecutionPlan, Partitioning, SendableRecordBatchStream,
     },
 };
 use datafusion_ext_commons::{
     array_size::ArraySize,
+    hash::mur::spark_compatible_murmur3_hash,
     spark_hash::{create_murmur3_hashes, pmod},
     streams::coalesce_stream::CoalesceInput,
 };
 use futures::StreamExt;
 
-use crate::{common::output::TaskOutputter, memmgr::spill::Spill};
+use crate::{
+    common::{
+        cached_exprs_evaluator::{is_volatile_expr, CachedExprsEvaluator},
+        column_pruning::prune_columns,
+        output::TaskOutputter,
+    },
+    memmgr::spill::Spill,
+    project_exec::ProjectExec,
+};
 
+pub mod bypass_repartitioner;
+pub mod checksum;
+pub mod object_store_output;
 pub mod single_repartitioner;
 pub mod sort_repartitioner;
 
 mod buffered_data;
+mod hash_validation;
 mod rss;
 pub mod rss_single_repartitioner;
 pub mod rss_sort_repartitioner;
@@ -105,12 +124,223 @@ impl dyn ShuffleRepartitioner {
     }
 }
 
+/// executes input of shuffle writer. a ProjectExec input is fused into the
+/// shuffle writer: its output is not coalesced because the repartitioner will
+/// coalesce and copy it again anyway.
+pub fn execute_shuffle_input(
+    input: &Arc<dyn ExecutionPlan>,
+    partition: usize,
+    context: Arc<TaskContext>,
+) -> Result<SendableRecordBatchStream> {
+    match input.as_any().downcast_ref::<ProjectExec>() {
+        Some(project) => project.execute_without_coalescing(partition, context),
+        None => input.execute(partition, context),
+    }
+}
+
+/// a ProjectExec input fused into a sort-based shuffle writer. rows of the
+/// projection's input are buffered and sorted by partition id, and the
+/// projection is evaluated on each output partition's rows when writing them.
+/// otherwise the projected columns are materialized by ProjectExec and copied
+/// again when sorting rows by partition id.
+pub struct FusedShuffleInput {
+    input: Arc<dyn ExecutionPlan>,
+    input_projection: Vec<usize>,
+    partitioning: Partitioning,
+    projection: ShuffleProjection,
+}
+
+impl FusedShuffleInput {
+    /// fuses a ProjectExec input with computed exprs. projections of plain
+    /// columns are zero-copy and not fused, neither are projections with
+    /// volatile exprs.
+    pub fn try_new(
+        input: &Arc<dyn ExecutionPlan>,
+        partitioning: &Partitioning,
+    ) -> Result<Option<Self>> {
+        let Some(project) = input.as_any().downcast_ref::<ProjectExec>() else {
+            return Ok(None);
+        };
+        let exprs: Vec<PhysicalExprRef> = project.exprs().iter().map(|(e, _)| e.clone()).collect();
+        if exprs.iter().all(|e| e.as_any().is::<Column>()) || exprs.iter().any(is_volatile_expr) {
+            return Ok(None);
+        }
+
+        // partition exprs are evaluated on the projection's input
+        let partition_exprs = match partitioning {
+            Partitioning::Hash(partition_exprs, _) => partition_exprs
+                .iter()
+                .map(|expr| {
+                    expr.clone().transform_up(&|e: PhysicalExprRef| {
+                        Ok(match e.as_any().downcast_ref::<Column>() {
+                            Some(col) => Transformed::Yes(exprs[col.index()].clone()),
+                            None => Transformed::No(e),
+                        })
+                    })
+                })
+                .collect::<Result<Vec<_>>>()?,
+            Partitioning::RoundRobinBatch(..) => vec![],
+            _ => return Ok(None),
+        };
+
+        // only columns referenced by the projection are buffered
+        let num_exprs = exprs.len();
+        let (mut pruned_exprs, input_projection) =
+            prune_columns(&[exprs, partition_exprs].concat())?;
+        if input_projection.is_empty() {
+            return Ok(None);
+        }
+        let pruned_partition_exprs = pruned_exprs.split_off(num_exprs);
+        let partitioning = match partitioning {
+            Partitioning::Hash(_, num_partitions) => {
+                Partitioning::Hash(pruned_partition_exprs, *num_partitions)
+            }
+            partitioning => partitioning.clone(),
+        };
+        let projection = ShuffleProjection {
+            evaluator: Arc::new(CachedExprsEvaluator::try_new(
+                vec![],
+                pruned_exprs,
+                project.schema(),
+            )?),
+        };
+        Ok(Some(Self {
+            input: project.input().clone(),
+            input_projection,
+            partitioning,
+            projection,
+        }))
+    }
+
+    /// partitioning over the pruned input columns
+    pub fn partitioning(&self) -> &Partitioning {
+        &self.partitioning
+    }
+
+    pub fn projection(&self) -> ShuffleProjection {
+        self.projection.clone()
+    }
+
+    /// executes the projection's input, pruned to the referenced columns
+    pub fn execute(
+        &self,
+        partition: usize,
+        context: Arc<TaskContext>,
+    ) -> Result<SendableRecordBatchStream> {
+        let input = self.input.execute(partition, context)?;
+        let input_projection = self.input_projection.clone();
+        Ok(Box::pin(RecordBatchStreamAdapter::new(
+            Arc::new(self.input.schema().project(&self.input_projection)?),
+            input.map(move |batch| -> Result<RecordBatch> {
+                Ok(batch?.project(&input_projection)?)
+            }),
+        )))
+    }
+}
+
+/// projection evaluated by shuffle repartitioners on the rows of each output
+/// partition, see [`FusedShuffleInput`]
+#[derive(Clone)]
+pub struct ShuffleProjection {
+    evaluator: Arc<CachedExprsEvaluator>,
+}
+
+impl ShuffleProjection {
+    pub fn project(&self, batch: &RecordBatch) -> Result<RecordBatch> {
+        self.evaluator.filter_project(batch)
+    }
+}
+
 struct ShuffleSpill {
     spill: Box<dyn Spill>,
     offsets: Vec<u64>,
 }
 
-fn evaluate_hashes(partitioning: &Partitioning, batch: &RecordBatch) -> ArrowResult<Vec<i32>> {
+#[derive(Clone)]
+pub struct ShuffleWriteMetrics {
+    pub compressed_bytes: Count,
+    pub uncompressed_bytes: Count,
+    pub peak_mem_used: Gauge,
+    pub partition_skew_ratio: Gauge,
+    pub merge_time: Time,
+    metrics: ExecutionPlanMetricsSet,
+    partition: usize,
+    partition_size_histogram_enabled: bool,
+}
+
+impl ShuffleWriteMetrics {
+    pub fn new(metrics: &ExecutionPlanMetricsSet, partition: usize) -> Result<Self> {
+        let partition_size_histogram_enabled =
+            is_jni_bridge_inited() && conf::SHUFFLE_PARTITION_SIZE_HISTOGRAM_ENABLE.value()?;
+        Ok(Self {
+            compressed_bytes: MetricBuilder::new(metrics)
+                .counter("shuffle_write_compressed_bytes", partition),
+            uncompressed_bytes: MetricBuilder::new(metrics)
+                .counter("shuffle_write_uncompressed_bytes", partition),
+            peak_mem_used: MetricBuilder::new(metrics)
+                .gauge("shuffle_write_peak_mem_used", partition),
+            partition_skew_ratio: MetricBuilder::new(metrics)
+                .gauge("shuffle_write_partition_skew_ratio", partition),
+            merge_time: MetricBuilder::new(metrics)
+                .subset_time("shuffle_write_merge_time", partition),
+            metrics: metrics.clone(),
+            partition,
+            partition_size_histogram_enabled,
+        })
+    }
+
+    /// records memory used by the repartitioner, keeping the peak value
+    pub fn record_mem_used(&self, mem_used: usize) {
+        if mem_used > self.peak_mem_used.value() {
+            self.peak_mem_used.set(mem_used);
+        }
+    }
+
+    /// records sizes of output partitions from the offsets in index file
+    pub fn record_output_offsets(&self, offsets: &[u64]) {
+        self.compressed_bytes
+            .add(offsets.last().cloned().unwrap_or_default() as usize);
+
+        // ratio of the largest partition size to the average (x100), a ratio far
+        // above 100 means a few reducers will be hotspots
+        let total_size = offsets.last().cloned().unwrap_or_default();
+        let num_partitions = offsets.len().saturating_sub(1) as u64;
+        if total_size > 0 {
+            let max_size = offsets.windows(2).map(|w| w[1] - w[0]).max().unwrap_or(0);
+            let skew_ratio = max_size * num_partitions * 100 / total_size;
+            self.partition_skew_ratio.set(skew_ratio as usize);
+        }
+
+        // histogram of non-empty output partition sizes, bucketed by powers of 2
+        if self.partition_size_histogram_enabled {
+            let mut buckets = vec![0usize; 64];
+            for size in offsets
+                .windows(2)
+                .map(|w| w[1] - w[0])
+                .filter(|&size| size > 0)
+            {
+                buckets[size.next_power_of_two().trailing_zeros() as usize] += 1;
+            }
+            for (bucket, &count) in buckets.iter().enumerate().filter(|&(_, &count)| count > 0) {
+                MetricBuilder::new(&self.metrics)
+                    .counter(
+                        format!("output_partitions_le_{}_bytes", 1u64 << bucket),
+                        self.partition,
+                    )
+                    .add(count);
+            }
+        }
+    }
+}
+
+/// default seed of spark's murmur3 hash partitioning
+pub const DEFAULT_HASH_SEED: i32 = 42;
+
+fn evaluate_hashes(
+    partitioning: &Partitioning,
+    batch: &RecordBatch,
+    hash_seed: i32,
+) -> ArrowResult<Vec<i32>> {
     match partitioning {
         Partitioning::Hash(exprs, _) => {
             let mut hashes_buf = vec![];
@@ -119,8 +349,7 @@ fn evaluate_hashes(partitioning: &Partitioning, batch: &RecordBatch) -> ArrowRes
                 .map(|expr| Ok(expr.evaluate(batch)?.into_array(batch.num_rows())?))
                 .collect::<Result<Vec<_>>>()?;
 
-            // use identical seed as spark hash partition
-            hashes_buf.resize(arrays[0].len(), 42);
+            hashes_buf.resize(arrays[0].len(), hash_seed);
 
             // compute hash array
             create_murmur3_hashes(&arrays, &mut hashes_buf)?;
@@ -136,3 +365,88 @@ fn evaluate_partition_ids(hashes: &[i32], num_partitions: usize) -> Vec<u32> {
         .map(|hash| pmod(*hash, num_partitions) as u32)
         .collect()
 }
+
+/// evaluates output partition ids of rows in the batch. with round-robin
+/// partitioning, rows are assigned to successive partitions following
+/// round_robin_pos, which is the position of the row before this batch
+fn evaluate_output_partition_ids(
+    partitioning: &Partitioning,
+    batch: &RecordBatch,
+    hash_seed: i32,
+    round_robin_pos: i32,
+) -> Result<Vec<u32>> {
+    let num_partitions = partitioning.partition_count();
+    match partitioning {
+        Partitioning::RoundRobinBatch(_) => Ok((1..=batch.num_rows())
+            .map(|i| pmod(round_robin_pos.wrapping_add(i as i32), num_partitions) as u32)
+            .collect()),
+        _ => {
+            let hashes = evaluate_hashes(partitioning, batch, hash_seed)?;
+            Ok(evaluate_partition_ids(&hashes, num_partitions))
+        }
+    }
+}
+
+/// starting position of round-robin partitioning in a map task, same as
+/// `new XORShiftRandom(partitionId).nextInt(numPartitions)` in spark
+fn round_robin_start_pos(partition_id: usize, partitioning: &Partitioning) -> i32 {
+    let &Partitioning::RoundRobinBatch(num_partitions) = partitioning else {
+        return 0;
+    };
+    let bound = num_partitions as i32;
+
+    // XORShiftRandom.hashSeed()
+    let seed_bytes = (partition_id as i64).to_be_bytes();
+    let low_bits = spark_compatible_murmur3_hash(seed_bytes, 0x3c074a61);
+    let high_bits = spark_compatible_murmur3_hash(seed_bytes, low_bits);
+    let mut seed = ((high_bits as i64) << 32) | (low_bits as u32 as i64);
+
+    // XORShiftRandom.next(31)
+    let mut next = || {
+        seed ^= seed << 21;
+        seed ^= ((seed as u64) >> 35) as i64;
+        seed ^= seed << 4;
+        (seed & 0x7fffffff) as i32
+    };
+
+    // java.util.Random.nextInt(bound)
+    let r = next();
+    let m = bound - 1;
+    if bound & m == 0 {
+        return ((bound as i64 * r as i64) >> 31) as i32;
+    }
+    let mut u = r;
+    loop {
+        let r = u % bound;
+        if u.wrapping_sub(r).wrapping_add(m) >= 0 {
+            return r;
+        }
+        u = next();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use datafusion::physical_plan::Partitioning;
+
+    use crate::shuffle::round_robin_start_pos;
+
+    #[test]
+    fn test_round_robin_start_pos() {
+        // expected values are from new XORShiftRandom(partitionId).nextInt(n)
+        let start_pos = |num_partitions| {
+            (0..8)
+                .map(|partition_id| {
+                    round_robin_start_pos(
+                        partition_id,
+                        &Partitioning::RoundRobinBatch(num_partitions),
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(start_pos(200), vec![28, 103, 28, 47, 53, 25, 90, 160]);
+        assert_eq!(start_pos(7), vec![2, 5, 1, 6, 1, 1, 3, 1]);
+        assert_eq!(start_pos(16), vec![3, 3, 15, 11, 9, 14, 12, 6]);
+        assert_eq!(start_pos(1), vec![0; 8]);
+    }
+}
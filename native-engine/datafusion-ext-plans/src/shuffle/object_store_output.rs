@@ -0,0 +1,157 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{path::PathBuf, sync::Arc};
+
+use bytes::Bytes;
+use datafusion::{
+    common::{DataFusionError, Result},
+    datasource::listing::ListingTableUrl,
+    execution::context::TaskContext,
+};
+use datafusion_ext_commons::df_execution_err;
+use object_store::{path::Path, ObjectStore};
+use tempfile::NamedTempFile;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+use crate::memmgr::spill::try_new_named_spill_file;
+
+// size of each part uploaded in multipart put
+const UPLOAD_PART_SIZE: usize = 8388608;
+
+/// shuffle output written to an object store (like s3/hdfs) instead of local
+/// files. object stores cannot seek, so repartitioners write the output into
+/// local temporary files as usual, and the assembled data file is uploaded
+/// with a multipart put after all partitions are written. the index is put
+/// as a separate object.
+pub struct ObjectStoreShuffleOutput {
+    store: Arc<dyn ObjectStore>,
+    data_location: Path,
+    index_location: Path,
+    local_data_file: LocalFile,
+    local_index_file: LocalFile,
+}
+
+impl ObjectStoreShuffleOutput {
+    /// returns None if the output paths are local files
+    pub fn try_new(
+        output_data_file: &str,
+        output_index_file: &str,
+        context: &TaskContext,
+    ) -> Result<Option<Self>> {
+        if !is_object_store_url(output_data_file) {
+            return Ok(None);
+        }
+        let data_url = ListingTableUrl::parse(output_data_file)?;
+        let index_url = ListingTableUrl::parse(output_index_file)?;
+        if data_url.object_store() != index_url.object_store() {
+            return df_execution_err!(
+                "shuffle data and index must be written to the same object store: \
+                 {output_data_file}, {output_index_file}"
+            );
+        }
+        Ok(Some(Self {
+            store: context
+                .runtime_env()
+                .object_store(data_url.object_store())?,
+            data_location: data_url.prefix().clone(),
+            index_location: index_url.prefix().clone(),
+            local_data_file: LocalFile::try_new()?,
+            local_index_file: LocalFile::try_new()?,
+        }))
+    }
+
+    pub fn local_data_file(&self) -> String {
+        self.local_data_file.path.to_string_lossy().to_string()
+    }
+
+    pub fn local_index_file(&self) -> String {
+        self.local_index_file.path.to_string_lossy().to_string()
+    }
+
+    /// uploads the written data and index, local files are removed on drop
+    pub async fn upload(self) -> Result<()> {
+        let (multipart_id, mut writer) = self.store.put_multipart(&self.data_location).await?;
+        let uploaded = async {
+            let mut local_data = tokio::fs::File::open(&self.local_data_file.path).await?;
+            let mut buf = vec![0; UPLOAD_PART_SIZE];
+            loop {
+                let len = local_data.read(&mut buf).await?;
+                if len == 0 {
+                    break;
+                }
+                writer.write_all(&buf[..len]).await?;
+            }
+            writer.shutdown().await?;
+            Ok::<_, DataFusionError>(())
+        }
+        .await;
+        if let Err(err) = uploaded {
+            let _ = self
+                .store
+                .abort_multipart(&self.data_location, &multipart_id)
+                .await;
+            return Err(err);
+        }
+
+        let index = tokio::fs::read(&self.local_index_file.path).await?;
+        self.store
+            .put(&self.index_location, Bytes::from(index))
+            .await?;
+        Ok(())
+    }
+}
+
+/// local file which the output is written into before uploading, removed on
+/// drop
+struct LocalFile {
+    path: PathBuf,
+    temp_file: Option<NamedTempFile>,
+}
+
+impl LocalFile {
+    fn try_new() -> Result<Self> {
+        // created under spark's local dirs like spill files, instead of the
+        // system temp dir which is usually small
+        match try_new_named_spill_file()? {
+            (_, Some(path)) => Ok(Self {
+                path,
+                temp_file: None,
+            }),
+            (_, None) => {
+                // for testing
+                let temp_file = NamedTempFile::new()?;
+                Ok(Self {
+                    path: temp_file.path().to_path_buf(),
+                    temp_file: Some(temp_file),
+                })
+            }
+        }
+    }
+}
+
+impl Drop for LocalFile {
+    fn drop(&mut self) {
+        // named temp files are removed by themselves
+        if self.temp_file.is_none() {
+            if let Err(e) = std::fs::remove_file(&self.path) {
+                log::warn!("error deleting local shuffle output {:?}: {e}", self.path);
+            }
+        }
+    }
+}
+
+fn is_object_store_url(path: &str) -> bool {
+    path.contains("://") && !path.starts_with("file://")
+}
@@ -21,7 +21,7 @@ use jni::objects::GlobalRef;
 use parking_lot::Mutex;
 
 use crate::{
-    common::ipc_compression::IpcCompressionWriter,
+    common::ipc_compression::{shuffle_compression_enabled, IpcCompressionWriter},
     shuffle::{rss::RssWriter, ShuffleRepartitioner},
 };
 
@@ -34,7 +34,7 @@ impl RssSingleShuffleRepartitioner {
         Self {
             rss_partition_writer: Arc::new(Mutex::new(IpcCompressionWriter::new(
                 RssWriter::new(rss_partition_writer, 0),
-                true,
+                shuffle_compression_enabled(),
             ))),
         }
     }
@@ -23,7 +23,10 @@ use jni::objects::GlobalRef;
 
 use crate::{
     memmgr::{MemConsumer, MemConsumerInfo, MemManager},
-    shuffle::{buffered_data::BufferedData, ShuffleRepartitioner},
+    shuffle::{
+        buffered_data::BufferedData, hash_validation::HashValidator, ShuffleProjection,
+        ShuffleRepartitioner,
+    },
 };
 
 pub struct RssSortShuffleRepartitioner {
@@ -31,6 +34,7 @@ pub struct RssSortShuffleRepartitioner {
     mem_consumer_info: Option<Weak<MemConsumerInfo>>,
     data: Mutex<BufferedData>,
     partitioning: Partitioning,
+    hash_validator: HashValidator,
     rss: GlobalRef,
 }
 
@@ -39,15 +43,35 @@ impl RssSortShuffleRepartitioner {
         partition_id: usize,
         rss_partition_writer: GlobalRef,
         partitioning: Partitioning,
+        hash_seed: i32,
     ) -> Self {
         Self {
             name: format!("RssSortShufflePartitioner[partition={}]", partition_id),
             mem_consumer_info: None,
-            data: Mutex::new(BufferedData::new(partition_id)),
+            data: Mutex::new(BufferedData::new(partition_id, &partitioning, hash_seed)),
             partitioning,
+            hash_validator: HashValidator::new(hash_seed),
             rss: rss_partition_writer,
         }
     }
+
+    /// evaluates the projection on each output partition's rows when writing
+    /// them, see [`crate::shuffle::FusedShuffleInput`]
+    pub fn with_projection(mut self, projection: Option<ShuffleProjection>) -> Self {
+        self.data.get_mut().set_projection(projection);
+        self
+    }
+
+    async fn push_to_rss(&self, data: &mut BufferedData) -> Result<()> {
+        let data = data.drain();
+        let rss = self.rss.clone();
+        let partitioning = self.partitioning.clone();
+
+        tokio::task::spawn_blocking(move || data.write_rss(rss, &partitioning))
+            .await
+            .or_else(|err| df_execution_err!("{err}"))??;
+        Ok(())
+    }
 }
 
 #[async_trait]
@@ -66,16 +90,19 @@ impl MemConsumer for RssSortShuffleRepartitioner {
             .expect("consumer info not set")
     }
 
-    async fn spill(&self) -> Result<()> {
-        let data = self.data.lock().await.drain();
-        let rss = self.rss.clone();
-        let partitioning = self.partitioning.clone();
-
-        tokio::task::spawn_blocking(move || data.write_rss(rss, &partitioning))
-            .await
-            .or_else(|err| df_execution_err!("{err}"))??;
+    async fn spill(&self) -> Result<usize> {
+        // data is locked until pushed, so shuffle_write() does not return
+        // while the rss writer is still in use. nothing is pushed once
+        // shuffle_write() has started
+        let mut data = self.data.lock().await;
+        if !self.is_spillable() {
+            return Ok(0);
+        }
+        self.push_to_rss(&mut data).await?;
+        drop(data);
+        let freed = self.mem_used();
         self.update_mem_used(0).await?;
-        Ok(())
+        Ok(freed)
     }
 }
 
@@ -88,8 +115,14 @@ impl Drop for RssSortShuffleRepartitioner {
 #[async_trait]
 impl ShuffleRepartitioner for RssSortShuffleRepartitioner {
     async fn insert_batch(&self, input: RecordBatch) -> Result<()> {
+        self.hash_validator.validate(&input, &self.partitioning)?;
+
         // update memory usage before adding to buffered data
-        let mem_used = self.data.lock().await.mem_used() + input.get_array_memory_size() * 2;
+        let mem_used = self
+            .data
+            .lock()
+            .await
+            .mem_used_for_adding(&input, &self.partitioning);
         self.update_mem_used(mem_used).await?;
 
         // add batch to buffered data
@@ -110,11 +143,13 @@ impl ShuffleRepartitioner for RssSortShuffleRepartitioner {
     }
 
     async fn shuffle_write(&self) -> Result<()> {
+        let mut data = self.data.lock().await;
         self.set_spillable(false);
-        let has_data = self.data.lock().await.mem_used() > 0;
-        if has_data {
-            self.spill().await?;
+        if data.mem_used() > 0 {
+            self.push_to_rss(&mut data).await?;
         }
+        drop(data);
+        self.update_mem_used(0).await?;
         Ok(())
     }
 }
@@ -23,13 +23,24 @@ use async_trait::async_trait;
 use datafusion::{common::Result, physical_plan::metrics::BaselineMetrics};
 use tokio::sync::Mutex;
 
-use crate::{common::ipc_compression::IpcCompressionWriter, shuffle::ShuffleRepartitioner};
+use crate::{
+    common::ipc_compression::{shuffle_compression_enabled, IpcCompressionWriter},
+    shuffle::{
+        checksum::{write_checksum_file, Checksum, ChecksumWrite, ShuffleChecksumAlgorithm},
+        ShuffleRepartitioner, ShuffleWriteMetrics,
+    },
+};
 
+/// repartitioner for shuffles with only one output partition. batches are
+/// written to the data file as they are inserted, without evaluating
+/// partition ids or buffering, and the index file only holds the total size.
 pub struct SingleShuffleRepartitioner {
     output_data_file: String,
     output_index_file: String,
-    output_data: Arc<Mutex<Option<IpcCompressionWriter<File>>>>,
+    output_checksum: Option<(String, ShuffleChecksumAlgorithm)>,
+    output_data: Arc<Mutex<Option<IpcCompressionWriter<ChecksumWrite<File>>>>>,
     metrics: BaselineMetrics,
+    shuffle_write_metrics: ShuffleWriteMetrics,
 }
 
 impl SingleShuffleRepartitioner {
@@ -37,27 +48,47 @@ impl SingleShuffleRepartitioner {
         output_data_file: String,
         output_index_file: String,
         metrics: BaselineMetrics,
+        shuffle_write_metrics: ShuffleWriteMetrics,
     ) -> Self {
         Self {
             output_data_file,
             output_index_file,
+            output_checksum: None,
             output_data: Arc::new(Mutex::default()),
             metrics,
+            shuffle_write_metrics,
         }
     }
 
+    /// computes checksum of the output partition while writing it, and writes
+    /// it into the checksum file
+    pub fn with_output_checksum(
+        mut self,
+        output_checksum: Option<(String, ShuffleChecksumAlgorithm)>,
+    ) -> Self {
+        self.output_checksum = output_checksum;
+        self
+    }
+
+    fn new_checksum(&self) -> Option<Checksum> {
+        self.output_checksum
+            .as_ref()
+            .map(|(_, algorithm)| Checksum::new(*algorithm))
+    }
+
     fn get_output_writer<'a>(
         &self,
-        output_data: &'a mut Option<IpcCompressionWriter<File>>,
-    ) -> Result<&'a mut IpcCompressionWriter<File>> {
+        output_data: &'a mut Option<IpcCompressionWriter<ChecksumWrite<File>>>,
+    ) -> Result<&'a mut IpcCompressionWriter<ChecksumWrite<File>>> {
         if output_data.is_none() {
+            let output_file = OpenOptions::new()
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .open(&self.output_data_file)?;
             *output_data = Some(IpcCompressionWriter::new(
-                OpenOptions::new()
-                    .write(true)
-                    .create(true)
-                    .truncate(true)
-                    .open(&self.output_data_file)?,
-                true,
+                ChecksumWrite::new(output_file, self.new_checksum()),
+                shuffle_compression_enabled(),
             ));
         }
         Ok(output_data.as_mut().unwrap())
@@ -79,13 +110,19 @@ impl ShuffleRepartitioner for SingleShuffleRepartitioner {
         let output_data = std::mem::take(&mut *self.output_data.lock().await);
 
         // write index file
-        if let Some(output_writer) = output_data {
-            let mut output_file = output_writer.finish_into_inner()?;
+        let checksum = if let Some(output_writer) = output_data {
+            self.shuffle_write_metrics
+                .uncompressed_bytes
+                .add(output_writer.uncompressed_size());
+            let (mut output_file, checksum) = output_writer.finish_into_inner()?.into_inner();
             let offset = output_file.stream_position()?;
+            self.shuffle_write_metrics
+                .record_output_offsets(&[0, offset]);
             let mut output_index = File::create(&self.output_index_file)?;
             output_index.write_all(&[0u8; 8])?;
             output_index.write_all(&(offset as i64).to_le_bytes()[..])?;
             output_index.sync_data()?;
+            checksum
         } else {
             // write empty data file and index file
             let output_data = File::create(&self.output_data_file)?;
@@ -93,6 +130,15 @@ impl ShuffleRepartitioner for SingleShuffleRepartitioner {
             let mut output_index = File::create(&self.output_index_file)?;
             output_index.write_all(&[0u8; 16])?;
             output_index.sync_data()?;
+            self.shuffle_write_metrics.record_output_offsets(&[0, 0]);
+            self.new_checksum()
+        };
+
+        if let Some((checksum_file, _)) = &self.output_checksum {
+            let checksum = checksum
+                .map(|checksum| checksum.value())
+                .unwrap_or_default();
+            write_checksum_file(checksum_file, &[checksum])?;
         }
         Ok(())
     }
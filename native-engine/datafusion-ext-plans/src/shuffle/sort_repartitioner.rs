@@ -14,20 +14,25 @@
 
 use std::{
     fs::{File, OpenOptions},
-    io::{BufReader, Read, Seek, Write},
+    io::{BufReader, BufWriter, Read, Seek, Write},
+    ops::Range,
     sync::Weak,
 };
 
 use arrow::record_batch::RecordBatch;
 use async_trait::async_trait;
+use blaze_jni_bridge::{
+    conf::{
+        BooleanConf, IntConf, SHUFFLE_MERGE_SPILLS_BY_FILE_COPY,
+        SHUFFLE_SKEWED_PARTITION_SPILL_THRESHOLD,
+    },
+    is_jni_bridge_inited,
+};
 use datafusion::{
     common::{DataFusionError, Result},
     physical_plan::{metrics::ExecutionPlanMetricsSet, Partitioning},
 };
-use datafusion_ext_commons::{
-    df_execution_err,
-    ds::rdx_tournament_tree::{KeyForRadixTournamentTree, RadixTournamentTree},
-};
+use datafusion_ext_commons::df_execution_err;
 use futures::lock::Mutex;
 
 use crate::{
@@ -36,7 +41,12 @@ use crate::{
         spill::{try_new_spill, Spill},
         MemConsumer, MemConsumerInfo, MemManager,
     },
-    shuffle::{buffered_data::BufferedData, ShuffleRepartitioner, ShuffleSpill},
+    shuffle::{
+        buffered_data::BufferedData,
+        checksum::{write_checksum_file, PartitionChecksums, ShuffleChecksumAlgorithm},
+        hash_validation::HashValidator,
+        ShuffleProjection, ShuffleRepartitioner, ShuffleSpill, ShuffleWriteMetrics,
+    },
 };
 
 pub struct SortShuffleRepartitioner {
@@ -44,11 +54,16 @@ pub struct SortShuffleRepartitioner {
     mem_consumer_info: Option<Weak<MemConsumerInfo>>,
     output_data_file: String,
     output_index_file: String,
+    output_checksum_file: Option<String>,
     data: Mutex<BufferedData>,
     spills: Mutex<Vec<ShuffleSpill>>,
+    checksums: Mutex<PartitionChecksums>,
     partitioning: Partitioning,
+    hash_validator: HashValidator,
     num_output_partitions: usize,
+    skewed_partition_spill_threshold: usize,
     spill_metrics: SpillMetrics,
+    shuffle_write_metrics: ShuffleWriteMetrics,
 }
 
 impl SortShuffleRepartitioner {
@@ -57,20 +72,49 @@ impl SortShuffleRepartitioner {
         output_data_file: String,
         output_index_file: String,
         partitioning: Partitioning,
+        hash_seed: i32,
         metrics: &ExecutionPlanMetricsSet,
-    ) -> Self {
+    ) -> Result<Self> {
         let num_output_partitions = partitioning.partition_count();
-        Self {
+        Ok(Self {
             name: format!("SortShufflePartitioner[partition={}]", partition_id),
             mem_consumer_info: None,
             output_data_file,
             output_index_file,
-            data: Mutex::new(BufferedData::new(partition_id)),
+            output_checksum_file: None,
+            data: Mutex::new(BufferedData::new(partition_id, &partitioning, hash_seed)),
             spills: Mutex::default(),
+            checksums: Mutex::new(PartitionChecksums::new(None, num_output_partitions)),
             partitioning,
+            hash_validator: HashValidator::new(hash_seed),
             num_output_partitions,
+            skewed_partition_spill_threshold: skewed_partition_spill_threshold(),
             spill_metrics: SpillMetrics::new(metrics, partition_id),
+            shuffle_write_metrics: ShuffleWriteMetrics::new(metrics, partition_id)?,
+        })
+    }
+
+    /// evaluates the projection on each output partition's rows when writing
+    /// them, see [`crate::shuffle::FusedShuffleInput`]
+    pub fn with_projection(mut self, projection: Option<ShuffleProjection>) -> Self {
+        self.data.get_mut().set_projection(projection);
+        self
+    }
+
+    /// computes checksums of output partitions while writing them, and writes
+    /// them into the checksum file
+    pub fn with_output_checksum(
+        mut self,
+        output_checksum: Option<(String, ShuffleChecksumAlgorithm)>,
+    ) -> Self {
+        if let Some((output_checksum_file, algorithm)) = output_checksum {
+            self.output_checksum_file = Some(output_checksum_file);
+            self.checksums = Mutex::new(PartitionChecksums::new(
+                Some(algorithm),
+                self.num_output_partitions,
+            ));
         }
+        self
     }
 }
 
@@ -90,17 +134,38 @@ impl MemConsumer for SortShuffleRepartitioner {
             .expect("consumer info not set")
     }
 
-    async fn spill(&self) -> Result<()> {
-        let data = self.data.lock().await.drain();
+    async fn spill(&self) -> Result<usize> {
+        // data is locked until the spill is added, so shuffle_write() either
+        // takes the spill or sees data drained. nothing is spilled once
+        // shuffle_write() has started
+        let mut data_guard = self.data.lock().await;
+        if !self.is_spillable() {
+            return Ok(0);
+        }
+        let data = data_guard.drain();
         let mut spill = try_new_spill(&self.spill_metrics)?;
 
-        let offsets = data.write(spill.get_buf_writer(), &self.partitioning)?;
+        // shuffle spills are written in the final shuffle format instead of
+        // respecting spark.blaze.spill.compression.enable, since spilled blocks
+        // are copied into the output file as-is and would otherwise have to be
+        // compressed at merging time anyway. checksums are locked until the
+        // spill is added, so they are updated in the order of merging
+        let mut checksums = self.checksums.lock().await;
+        let offsets = data.write(
+            spill.get_buf_writer(),
+            &self.partitioning,
+            &self.shuffle_write_metrics.uncompressed_bytes,
+            &mut checksums,
+        )?;
         self.spills
             .lock()
             .await
             .push(ShuffleSpill { spill, offsets });
+        drop(checksums);
+        drop(data_guard);
+        let freed = self.mem_used();
         self.update_mem_used(0).await?;
-        Ok(())
+        Ok(freed)
     }
 }
 
@@ -113,14 +178,43 @@ impl Drop for SortShuffleRepartitioner {
 #[async_trait]
 impl ShuffleRepartitioner for SortShuffleRepartitioner {
     async fn insert_batch(&self, input: RecordBatch) -> Result<()> {
+        self.hash_validator.validate(&input, &self.partitioning)?;
+
         // update memory usage before adding to buffered data
-        let mem_used = self.data.lock().await.mem_used() + input.get_array_memory_size() * 2;
+        let mem_used = self
+            .data
+            .lock()
+            .await
+            .mem_used_for_adding(&input, &self.partitioning);
+        self.shuffle_write_metrics.record_mem_used(mem_used);
         self.update_mem_used(mem_used).await?;
 
         // add batch to buffered data
         let mem_used = {
             let mut data = self.data.lock().await;
             data.add_batch(input, &self.partitioning)?;
+
+            // spill skewed partition alone without waiting for the memory manager
+            if let Some(part_id) = data.skewed_partition(self.skewed_partition_spill_threshold) {
+                let part_data = data.drain_partition(part_id)?;
+                log::info!(
+                    "{} spilling skewed partition {part_id}, mem_used={}",
+                    self.name,
+                    part_data.mem_used(),
+                );
+                let mut spill = try_new_spill(&self.spill_metrics)?;
+                let mut checksums = self.checksums.lock().await;
+                let offsets = part_data.write(
+                    spill.get_buf_writer(),
+                    &self.partitioning,
+                    &self.shuffle_write_metrics.uncompressed_bytes,
+                    &mut checksums,
+                )?;
+                self.spills
+                    .lock()
+                    .await
+                    .push(ShuffleSpill { spill, offsets });
+            }
             data.mem_used()
         };
         self.update_mem_used(mem_used).await?;
@@ -134,9 +228,15 @@ impl ShuffleRepartitioner for SortShuffleRepartitioner {
     }
 
     async fn shuffle_write(&self) -> Result<()> {
+        let mut data_guard = self.data.lock().await;
         self.set_spillable(false);
         let mut spills = std::mem::take(&mut *self.spills.lock().await);
-        let data = self.data.lock().await.drain();
+        let data = data_guard.drain();
+        drop(data_guard);
+        let mut checksums = std::mem::replace(
+            &mut *self.checksums.lock().await,
+            PartitionChecksums::new(None, self.num_output_partitions),
+        );
 
         log::info!(
             "{} starts outputting ({} spills)",
@@ -146,138 +246,801 @@ impl ShuffleRepartitioner for SortShuffleRepartitioner {
 
         let data_file = self.output_data_file.clone();
         let index_file = self.output_index_file.clone();
+        let checksum_file = self.output_checksum_file.clone();
 
         // no spills - directly write current batches into final file
         if spills.is_empty() {
             let partitioning = self.partitioning.clone();
-            tokio::task::spawn_blocking(move || {
+            let uncompressed_bytes = self.shuffle_write_metrics.uncompressed_bytes.clone();
+            let offsets = tokio::task::spawn_blocking(move || {
                 let mut output_data = OpenOptions::new()
                     .write(true)
                     .create(true)
                     .truncate(true)
                     .open(&data_file)?;
 
-                let offsets = data.write(&mut output_data, &partitioning)?;
+                let offsets = data.write(
+                    &mut output_data,
+                    &partitioning,
+                    &uncompressed_bytes,
+                    &mut checksums,
+                )?;
                 output_data.sync_data()?;
                 output_data.flush()?;
 
                 let mut output_index = File::create(&index_file)?;
-                for offset in offsets {
+                for &offset in &offsets {
                     output_index.write_all(&(offset as i64).to_le_bytes()[..])?;
                 }
                 output_index.sync_data()?;
                 output_index.flush()?;
-                Ok::<(), DataFusionError>(())
+
+                if let Some(checksum_file) = checksum_file {
+                    write_checksum_file(&checksum_file, &checksums.values())?;
+                }
+                Ok::<_, DataFusionError>(offsets)
             })
             .await
             .or_else(|e| df_execution_err!("shuffle write error: {e:?}"))??;
+            self.shuffle_write_metrics.record_output_offsets(&offsets);
             self.update_mem_used(0).await?;
             return Ok(());
         }
 
-        struct SpillCursor<'a> {
-            cur: usize,
-            reader: BufReader<Box<dyn Read + Send + 'a>>,
-            offsets: Vec<u64>,
-        }
-
-        impl<'a> KeyForRadixTournamentTree for SpillCursor<'a> {
-            fn rdx(&self) -> usize {
-                self.cur
-            }
-        }
-
-        impl<'a> SpillCursor<'a> {
-            fn skip_empty_partitions(&mut self) {
-                let offsets = &self.offsets;
-                while self.cur + 1 < offsets.len() && offsets[self.cur + 1] == offsets[self.cur] {
-                    self.cur += 1;
-                }
-            }
-        }
-
         // write rest data into an in-memory buffer
+        let mut mem_used = 0;
         if data.mem_used() > 0 {
             let mut spill = Box::new(vec![]);
             let writer = spill.get_buf_writer();
-            let offsets = data.write(writer, &self.partitioning)?;
-            self.update_mem_used(spill.len()).await?;
+            let offsets = data.write(
+                writer,
+                &self.partitioning,
+                &self.shuffle_write_metrics.uncompressed_bytes,
+                &mut checksums,
+            )?;
+            self.shuffle_write_metrics.record_mem_used(spill.len());
+            mem_used += spill.len();
             spills.push(ShuffleSpill { spill, offsets });
         }
 
+        // merge_spills() buffers at most one window of partitions at a time
+        let merge_by_file_copy = merge_spills_by_file_copy_enabled();
+        if !merge_by_file_copy {
+            let total_spill_size = spills
+                .iter()
+                .map(|spill| spill.offsets[self.num_output_partitions] as usize)
+                .sum::<usize>();
+            mem_used += total_spill_size.min(MERGE_WINDOW_MEM_SIZE);
+        }
+        self.update_mem_used(mem_used).await?;
+
         let num_output_partitions = self.num_output_partitions;
-        let mut offsets = vec![0];
+        let merge_time = self.shuffle_write_metrics.merge_time.clone();
 
         // append partition in each spills
-        tokio::task::spawn_blocking(move || {
+        let offsets = tokio::task::spawn_blocking(move || {
+            let _timer = merge_time.timer();
             let mut output_data = OpenOptions::new()
                 .write(true)
                 .create(true)
                 .truncate(true)
                 .open(data_file)?;
-            let mut cur_partition_id = 0;
-
-            if !spills.is_empty() {
-                // select partitions from spills
-                let mut cursors = RadixTournamentTree::new(
-                    spills
-                        .iter_mut()
-                        .map(|spill| SpillCursor {
-                            cur: 0,
-                            reader: spill.spill.get_buf_reader(),
-                            offsets: std::mem::take(&mut spill.offsets),
-                        })
-                        .map(|mut spill| {
-                            spill.skip_empty_partitions();
-                            spill
-                        })
-                        .filter(|spill| spill.cur < spill.offsets.len())
-                        .collect(),
+            let offsets = if merge_by_file_copy {
+                merge_spills_by_file_copy(&spills, num_output_partitions, &output_data)?
+            } else {
+                merge_spills(
+                    &spills,
                     num_output_partitions,
-                );
-
-                loop {
-                    let mut min_spill = cursors.peek_mut();
-                    if min_spill.cur + 1 >= min_spill.offsets.len() {
-                        break;
-                    }
-
-                    while cur_partition_id < min_spill.cur {
-                        offsets.push(output_data.stream_position()?);
-                        cur_partition_id += 1;
-                    }
-                    let (spill_offset_start, spill_offset_end) = (
-                        min_spill.offsets[cur_partition_id],
-                        min_spill.offsets[cur_partition_id + 1],
-                    );
-
-                    let spill_range = spill_offset_start as usize..spill_offset_end as usize;
-                    let reader = &mut min_spill.reader;
-                    std::io::copy(&mut reader.take(spill_range.len() as u64), &mut output_data)?;
-
-                    // forward partition id in min_spill
-                    min_spill.cur += 1;
-                    min_spill.skip_empty_partitions();
-                }
-            }
+                    MERGE_WINDOW_MEM_SIZE,
+                    &mut output_data,
+                )?
+            };
             output_data.sync_data()?;
             output_data.flush()?;
 
-            // add one extra offset at last to ease partition length computation
-            offsets.resize(num_output_partitions + 1, output_data.stream_position()?);
-
             let mut output_index = File::create(index_file)?;
-            for offset in offsets {
+            for &offset in &offsets {
                 output_index.write_all(&(offset as i64).to_le_bytes()[..])?;
             }
             output_index.sync_data()?;
             output_index.flush()?;
-            Ok::<(), DataFusionError>(())
+
+            // spilled blocks are merged as-is, so checksums updated while
+            // spilling are the checksums of merged partitions
+            if let Some(checksum_file) = checksum_file {
+                write_checksum_file(&checksum_file, &checksums.values())?;
+            }
+            Ok::<_, DataFusionError>(offsets)
         })
         .await
         .or_else(|e| df_execution_err!("shuffle write error: {e:?}"))??;
 
+        self.shuffle_write_metrics.record_output_offsets(&offsets);
         self.update_mem_used(0).await?;
         Ok(())
     }
 }
+
+fn skewed_partition_spill_threshold() -> usize {
+    if is_jni_bridge_inited() {
+        SHUFFLE_SKEWED_PARTITION_SPILL_THRESHOLD
+            .value()
+            .expect("error getting configured skewed partition spill threshold") as usize
+    } else {
+        268435456 // for testing
+    }
+}
+
+fn merge_spills_by_file_copy_enabled() -> bool {
+    if is_jni_bridge_inited() {
+        SHUFFLE_MERGE_SPILLS_BY_FILE_COPY
+            .value()
+            .expect("error getting configured merge spills by file copy")
+    } else {
+        false // for testing
+    }
+}
+
+// max total size of partitions read from spills in one merging round
+const MERGE_WINDOW_MEM_SIZE: usize = 67108864;
+
+// max number of threads reading spills concurrently
+const MAX_MERGE_THREADS: usize = 8;
+
+// output buffer size, avoiding small writes when there are many small
+// partitions
+const MERGE_OUTPUT_BUF_SIZE: usize = 262144;
+
+/// merges partitioned spills into output, returns offsets of each partition
+/// (with an extra offset at last to ease partition length computation).
+///
+/// partitions are merged in windows of consecutive partitions whose total
+/// size is bounded by window_mem_size. in each window, spills are read
+/// concurrently by a bounded number of worker threads, then the window is
+/// written in partition order, with blocks of the same partition ordered by
+/// spill index. a partition larger than window_mem_size (like a skewed one)
+/// forms no window, it is streamed from each spill in chunks of at most
+/// window_mem_size instead. spill readers are sequential, so each worker
+/// opens its readers once and keeps them across windows, no seeking is needed
+/// since spill offsets are monotonically increasing.
+fn merge_spills(
+    spills: &[ShuffleSpill],
+    num_output_partitions: usize,
+    window_mem_size: usize,
+    output: &mut impl Write,
+) -> Result<Vec<u64>> {
+    type WindowData = Result<Vec<(usize, Vec<u8>)>>;
+
+    enum MergeWindow {
+        Buffered(Range<usize>),
+        Streamed(usize),
+    }
+
+    enum MergeTask {
+        // reads partitions in the range from all spills of the worker
+        Window(Range<usize>),
+        // reads the next bytes of a spill of the worker
+        Chunk { spill_idx: usize, len: u64 },
+    }
+
+    let partition_len = |spill: &ShuffleSpill, range: Range<usize>| {
+        spill.offsets[range.end] - spill.offsets[range.start]
+    };
+
+    // split partitions into windows
+    let mut windows = vec![];
+    let mut window_start = 0;
+    let mut window_size = 0;
+    for partition_id in 0..num_output_partitions {
+        let size = spills
+            .iter()
+            .map(|spill| partition_len(spill, partition_id..partition_id + 1) as usize)
+            .sum::<usize>();
+        if size > window_mem_size {
+            if window_start < partition_id {
+                windows.push(MergeWindow::Buffered(window_start..partition_id));
+            }
+            windows.push(MergeWindow::Streamed(partition_id));
+            window_start = partition_id + 1;
+            window_size = 0;
+            continue;
+        }
+        if window_size + size > window_mem_size {
+            windows.push(MergeWindow::Buffered(window_start..partition_id));
+            window_start = partition_id;
+            window_size = 0;
+        }
+        window_size += size;
+    }
+    if window_start < num_output_partitions {
+        windows.push(MergeWindow::Buffered(window_start..num_output_partitions));
+    }
+
+    let num_threads = std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1)
+        .min(MAX_MERGE_THREADS)
+        .min(spills.len())
+        .max(1);
+
+    let mut output = BufWriter::with_capacity(MERGE_OUTPUT_BUF_SIZE, output);
+    std::thread::scope(|scope| {
+        let workers = (0..num_threads)
+            .map(|worker_id| {
+                let (task_tx, task_rx) = std::sync::mpsc::channel::<MergeTask>();
+                let (data_tx, data_rx) = std::sync::mpsc::sync_channel::<WindowData>(1);
+                scope.spawn(move || {
+                    let mut readers = (worker_id..spills.len())
+                        .step_by(num_threads)
+                        .map(|spill_idx| (spill_idx, spills[spill_idx].spill.get_buf_reader()))
+                        .collect::<Vec<_>>();
+
+                    while let Ok(task) = task_rx.recv() {
+                        let window_data = readers
+                            .iter_mut()
+                            .filter(|(spill_idx, _)| match &task {
+                                MergeTask::Window(_) => true,
+                                MergeTask::Chunk { spill_idx: idx, .. } => idx == spill_idx,
+                            })
+                            .map(|(spill_idx, reader)| {
+                                let len = match &task {
+                                    MergeTask::Window(window) => {
+                                        partition_len(&spills[*spill_idx], window.clone())
+                                    }
+                                    MergeTask::Chunk { len, .. } => *len,
+                                };
+                                let mut buf = Vec::with_capacity(len as usize);
+                                reader.take(len).read_to_end(&mut buf)?;
+                                Ok((*spill_idx, buf))
+                            })
+                            .collect::<Result<Vec<_>>>();
+                        if data_tx.send(window_data).is_err() {
+                            break;
+                        }
+                    }
+                });
+                (task_tx, data_rx)
+            })
+            .collect::<Vec<_>>();
+
+        let mut offsets = vec![0];
+        let mut cur_offset = 0;
+        for window in windows {
+            let window = match window {
+                MergeWindow::Buffered(window) => window,
+                MergeWindow::Streamed(partition_id) => {
+                    for (spill_idx, spill) in spills.iter().enumerate() {
+                        let (task_tx, data_rx) = &workers[spill_idx % num_threads];
+                        let mut remaining = partition_len(spill, partition_id..partition_id + 1);
+                        while remaining > 0 {
+                            let len = remaining.min(window_mem_size as u64);
+                            task_tx
+                                .send(MergeTask::Chunk { spill_idx, len })
+                                .or_else(|_| df_execution_err!("spill merging worker exited"))?;
+                            let data = data_rx
+                                .recv()
+                                .or_else(|_| df_execution_err!("spill merging worker exited"))??;
+                            for (_, buf) in data {
+                                if buf.len() as u64 != len {
+                                    return df_execution_err!(
+                                        "unexpected end of spill: {} < {len}",
+                                        buf.len(),
+                                    );
+                                }
+                                output.write_all(&buf)?;
+                            }
+                            remaining -= len;
+                            cur_offset += len;
+                        }
+                    }
+                    offsets.push(cur_offset);
+                    continue;
+                }
+            };
+            for (task_tx, _) in &workers {
+                task_tx
+                    .send(MergeTask::Window(window.clone()))
+                    .or_else(|_| df_execution_err!("spill merging worker exited"))?;
+            }
+
+            let mut window_data: Vec<Vec<u8>> = vec![vec![]; spills.len()];
+            for (_, data_rx) in &workers {
+                let data = data_rx
+                    .recv()
+                    .or_else(|_| df_execution_err!("spill merging worker exited"))??;
+                for (spill_idx, buf) in data {
+                    window_data[spill_idx] = buf;
+                }
+            }
+
+            for partition_id in window.clone() {
+                for (spill, buf) in spills.iter().zip(&window_data) {
+                    let start = partition_len(spill, window.start..partition_id) as usize;
+                    let len = partition_len(spill, partition_id..partition_id + 1) as usize;
+                    output.write_all(&buf[start..][..len])?;
+                    cur_offset += len as u64;
+                }
+                offsets.push(cur_offset);
+            }
+        }
+        output.flush()?;
+        offsets.resize(num_output_partitions + 1, cur_offset);
+        Ok(offsets)
+    })
+}
+
+/// merges partitioned spills into output like merge_spills, but copies
+/// partition ranges of file spills directly from file to file. on linux
+/// std::io::copy() uses copy_file_range() for this, so the spilled data is
+/// not copied through user-space buffers. other spills are read sequentially.
+fn merge_spills_by_file_copy(
+    spills: &[ShuffleSpill],
+    num_output_partitions: usize,
+    output: &File,
+) -> Result<Vec<u64>> {
+    enum SpillReader<'a> {
+        File(File),
+        Buffered(BufReader<Box<dyn Read + Send + 'a>>),
+    }
+
+    let mut readers = spills
+        .iter()
+        .map(|spill| match spill.spill.as_file() {
+            Some(file) => {
+                let mut file = file.try_clone()?;
+                file.rewind()?;
+                Ok(SpillReader::File(file))
+            }
+            None => Ok(SpillReader::Buffered(spill.spill.get_buf_reader())),
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut output = BufWriter::with_capacity(MERGE_OUTPUT_BUF_SIZE, output);
+    let mut offsets = vec![0];
+    let mut cur_offset = 0;
+    for partition_id in 0..num_output_partitions {
+        for (spill, reader) in spills.iter().zip(&mut readers) {
+            let len = spill.offsets[partition_id + 1] - spill.offsets[partition_id];
+            if len == 0 {
+                continue;
+            }
+            let copied_len = match reader {
+                SpillReader::File(file) => std::io::copy(&mut (&*file).take(len), &mut output)?,
+                SpillReader::Buffered(reader) => std::io::copy(&mut reader.take(len), &mut output)?,
+            };
+            if copied_len != len {
+                return df_execution_err!("unexpected end of spill: {copied_len} < {len}");
+            }
+            cur_offset += len;
+        }
+        offsets.push(cur_offset);
+    }
+    output.flush()?;
+    Ok(offsets)
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        any::Any,
+        io::{BufReader, BufWriter, Cursor, Read, Seek, Write},
+        sync::{
+            atomic::{AtomicUsize, Ordering::SeqCst},
+            Arc,
+        },
+    };
+
+    use arrow::{
+        array::{AsArray, Int32Array},
+        datatypes::{DataType, Field, Int32Type, Schema},
+        record_batch::RecordBatch,
+    };
+    use datafusion::{
+        common::Result,
+        physical_expr::expressions::Column,
+        physical_plan::{metrics::ExecutionPlanMetricsSet, Partitioning},
+    };
+
+    use crate::{
+        common::ipc_compression::IpcCompressionReader,
+        memmgr::{
+            metrics::SpillMetrics,
+            spill::{try_new_spill, Spill},
+            MemConsumer, MemManager,
+        },
+        shuffle::{
+            sort_repartitioner::{
+                merge_spills, merge_spills_by_file_copy, SortShuffleRepartitioner,
+            },
+            ShuffleRepartitioner, ShuffleSpill, DEFAULT_HASH_SEED,
+        },
+    };
+
+    // spill counting how many times its reader is opened
+    struct CountingSpill(Vec<u8>, Arc<AtomicUsize>);
+
+    impl Spill for CountingSpill {
+        fn as_any(&self) -> &dyn Any {
+            self
+        }
+
+        fn get_buf_reader<'a>(&'a self) -> BufReader<Box<dyn Read + Send + 'a>> {
+            self.1.fetch_add(1, SeqCst);
+            BufReader::new(Box::new(Cursor::new(&self.0)))
+        }
+
+        fn get_buf_writer<'a>(&'a mut self) -> BufWriter<Box<dyn Write + Send + 'a>> {
+            BufWriter::new(Box::new(&mut self.0))
+        }
+    }
+
+    // writer counting how many write calls reach it, and the max size of them
+    #[derive(Default)]
+    struct CountingWriter(Vec<u8>, usize, usize);
+
+    impl Write for CountingWriter {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.1 += 1;
+            self.2 = self.2.max(buf.len());
+            self.0.write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn sequential_merge_spills(spills: &[ShuffleSpill], num_partitions: usize) -> Vec<u8> {
+        let mut readers = spills
+            .iter()
+            .map(|spill| spill.spill.get_buf_reader())
+            .collect::<Vec<_>>();
+        let mut output = vec![];
+        for partition_id in 0..num_partitions {
+            for (spill, reader) in spills.iter().zip(&mut readers) {
+                let len = spill.offsets[partition_id + 1] - spill.offsets[partition_id];
+                reader.take(len).read_to_end(&mut output).unwrap();
+            }
+        }
+        output
+    }
+
+    #[test]
+    fn test_merge_spills() -> Result<()> {
+        let num_partitions = 10;
+        let spills = (0..5)
+            .map(|spill_idx| {
+                let mut data = vec![];
+                let mut offsets = vec![0];
+                for partition_id in 0..num_partitions {
+                    // leave some partitions empty
+                    if (partition_id + spill_idx) % 3 != 0 {
+                        let len = (partition_id * 7 + spill_idx * 13) % 50 + 1;
+                        data.extend((0..len).map(|i| (i + partition_id * spill_idx) as u8));
+                    }
+                    offsets.push(data.len() as u64);
+                }
+                let spill: Box<dyn Spill> = Box::new(data);
+                ShuffleSpill { spill, offsets }
+            })
+            .collect::<Vec<_>>();
+        let expected = sequential_merge_spills(&spills, num_partitions);
+
+        for window_mem_size in [1, 64, 1000, usize::MAX] {
+            let mut output = vec![];
+            let offsets = merge_spills(&spills, num_partitions, window_mem_size, &mut output)?;
+            assert_eq!(output, expected);
+            assert_eq!(offsets.len(), num_partitions + 1);
+            assert_eq!(offsets[num_partitions], expected.len() as u64);
+
+            for partition_id in 0..num_partitions {
+                let expected_len = spills
+                    .iter()
+                    .map(|spill| spill.offsets[partition_id + 1] - spill.offsets[partition_id])
+                    .sum::<u64>();
+                assert_eq!(
+                    offsets[partition_id + 1] - offsets[partition_id],
+                    expected_len
+                );
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_merge_spills_many_partitions() -> Result<()> {
+        let num_partitions = 1000;
+        let num_opens = Arc::new(AtomicUsize::new(0));
+        let spills = (0..4)
+            .map(|spill_idx| {
+                let mut data = vec![];
+                let mut offsets = vec![0];
+                for partition_id in 0..num_partitions {
+                    data.extend((0..(partition_id + spill_idx) % 20).map(|i| i as u8));
+                    offsets.push(data.len() as u64);
+                }
+                let spill: Box<dyn Spill> = Box::new(CountingSpill(data, num_opens.clone()));
+                ShuffleSpill { spill, offsets }
+            })
+            .collect::<Vec<_>>();
+        let expected = sequential_merge_spills(&spills, num_partitions);
+        num_opens.store(0, SeqCst);
+
+        let mut output = CountingWriter::default();
+        merge_spills(&spills, num_partitions, 4096, &mut output)?;
+        assert_eq!(output.0, expected);
+
+        // every spill is opened once and read sequentially across all windows
+        assert_eq!(num_opens.load(SeqCst), spills.len());
+
+        // small partition blocks are coalesced into large writes
+        assert!(output.1 <= expected.len() / 262144 + 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_merge_spills_large_partition() -> Result<()> {
+        let num_partitions = 5;
+        let spills = (0..3)
+            .map(|spill_idx| {
+                let mut data = vec![];
+                let mut offsets = vec![0];
+                for partition_id in 0..num_partitions {
+                    // partition 2 is far larger than the merging window
+                    let len = if partition_id == 2 { 1048576 } else { 100 };
+                    data.extend((0..len).map(|i| (i * 7 + spill_idx) as u8));
+                    offsets.push(data.len() as u64);
+                }
+                let spill: Box<dyn Spill> = Box::new(data);
+                ShuffleSpill { spill, offsets }
+            })
+            .collect::<Vec<_>>();
+        let expected = sequential_merge_spills(&spills, num_partitions);
+
+        let mut output = CountingWriter::default();
+        let offsets = merge_spills(&spills, num_partitions, 4096, &mut output)?;
+        assert_eq!(output.0, expected);
+        assert_eq!(offsets, vec![0, 300, 600, 3146328, 3146628, 3146928]);
+
+        // the large partition is streamed in small chunks, which are coalesced
+        // by the output buffer instead of being written in one piece
+        assert!(output.2 <= 262144, "max write size: {}", output.2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_merge_spills_by_file_copy() -> Result<()> {
+        let num_partitions = 100;
+        let spill_metrics = SpillMetrics::new(&ExecutionPlanMetricsSet::new(), 0);
+        let spills = (0..4)
+            .map(|spill_idx| -> Result<ShuffleSpill> {
+                // the last spill stays in memory, like in-memory data in shuffle_write()
+                let mut spill: Box<dyn Spill> = if spill_idx < 3 {
+                    try_new_spill(&spill_metrics)?
+                } else {
+                    Box::new(vec![])
+                };
+                let mut offsets = vec![0];
+                let mut writer = spill.get_buf_writer();
+                for partition_id in 0..num_partitions {
+                    let len = (partition_id * 7 + spill_idx * 13) % 50 * (partition_id % 3);
+                    writer
+                        .write_all(&(0..len).map(|i| (i + spill_idx) as u8).collect::<Vec<_>>())?;
+                    offsets.push(offsets[partition_id] + len as u64);
+                }
+                writer.flush()?;
+                drop(writer);
+                Ok(ShuffleSpill { spill, offsets })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        assert!(spills[0].spill.as_file().is_some());
+        let expected = sequential_merge_spills(&spills, num_partitions);
+
+        let output_file = tempfile::tempfile()?;
+        let offsets = merge_spills_by_file_copy(&spills, num_partitions, &output_file)?;
+        let mut output = vec![];
+        (&output_file).rewind()?;
+        (&output_file).read_to_end(&mut output)?;
+        assert_eq!(output, expected);
+        assert_eq!(
+            offsets,
+            merge_spills(&spills, num_partitions, 4096, &mut vec![])?
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_insert_batch_with_self_spill() -> Result<()> {
+        // memory limit is far smaller than the inserted data (~4MB)
+        MemManager::init(10000);
+        let schema = Arc::new(Schema::new(vec![Field::new("k", DataType::Int32, false)]));
+        let num_partitions = 4;
+        let tmp_dir = tempfile::tempdir()?;
+        let data_file = tmp_dir.path().join("shuffle.data");
+        let index_file = tmp_dir.path().join("shuffle.index");
+        let metrics = ExecutionPlanMetricsSet::new();
+
+        let repartitioner = Arc::new(SortShuffleRepartitioner::new(
+            0,
+            data_file.to_string_lossy().to_string(),
+            index_file.to_string_lossy().to_string(),
+            Partitioning::Hash(vec![Arc::new(Column::new("k", 0))], num_partitions),
+            DEFAULT_HASH_SEED,
+            &metrics,
+        )?);
+        MemManager::register_consumer(repartitioner.clone(), true);
+
+        for i in 0..10 {
+            let batch = RecordBatch::try_new(
+                schema.clone(),
+                vec![Arc::new(Int32Array::from_iter_values(
+                    i * 100000..(i + 1) * 100000,
+                ))],
+            )?;
+            repartitioner.insert_batch(batch).await?;
+        }
+
+        // the repartitioner releases its buffered data by spilling itself
+        assert!(!repartitioner.spills.lock().await.is_empty());
+
+        // peak memory includes the transient usage before spilling
+        let peak_mem_used = metrics
+            .clone_inner()
+            .sum_by_name("shuffle_write_peak_mem_used")
+            .map(|v| v.as_usize())
+            .unwrap_or_default();
+        assert!(peak_mem_used > 400000);
+        assert!(peak_mem_used > repartitioner.mem_used());
+
+        repartitioner.shuffle_write().await?;
+
+        // all rows are written to the output
+        let data = std::fs::read(&data_file)?;
+        let index = std::fs::read(&index_file)?;
+        let offsets = index
+            .chunks_exact(8)
+            .map(|offset| i64::from_le_bytes(offset.try_into().unwrap()) as usize)
+            .collect::<Vec<_>>();
+        assert_eq!(offsets.len(), num_partitions + 1);
+        assert_eq!(offsets[num_partitions], data.len());
+
+        let mut keys = vec![];
+        for partition_id in 0..num_partitions {
+            let partition_data = data[offsets[partition_id]..offsets[partition_id + 1]].to_vec();
+            let mut reader = IpcCompressionReader::new(Cursor::new(partition_data), schema.clone());
+            while let Some(batch) = reader.read_batch()? {
+                keys.extend(batch.column(0).as_primitive::<Int32Type>().values().iter());
+            }
+        }
+        keys.sort_unstable();
+        assert_eq!(keys, (0..1000000).collect::<Vec<_>>());
+
+        // time of merging spills is recorded
+        let merge_time = metrics
+            .clone_inner()
+            .sum_by_name("shuffle_write_merge_time")
+            .map(|v| v.as_usize())
+            .unwrap_or_default();
+        assert!(merge_time > 0);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_partition_skew_ratio() -> Result<()> {
+        MemManager::init(1000000);
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("k", DataType::Int32, false),
+            Field::new("v", DataType::Int32, false),
+        ]));
+        let num_partitions = 8;
+        let tmp_dir = tempfile::tempdir()?;
+        let data_file = tmp_dir.path().join("shuffle.data");
+        let index_file = tmp_dir.path().join("shuffle.index");
+        let metrics = ExecutionPlanMetricsSet::new();
+
+        let repartitioner = Arc::new(SortShuffleRepartitioner::new(
+            0,
+            data_file.to_string_lossy().to_string(),
+            index_file.to_string_lossy().to_string(),
+            Partitioning::Hash(vec![Arc::new(Column::new("k", 0))], num_partitions),
+            DEFAULT_HASH_SEED,
+            &metrics,
+        )?);
+        MemManager::register_consumer(repartitioner.clone(), true);
+
+        // most rows have the same key, values are hardly compressible so that
+        // partition sizes follow the number of rows. half of the data is spilled
+        // before writing.
+        for _ in 0..2 {
+            let batch = RecordBatch::try_new(
+                schema.clone(),
+                vec![
+                    Arc::new(Int32Array::from_iter_values((0..100000).map(|i| {
+                        if i % 10 == 0 {
+                            i
+                        } else {
+                            0
+                        }
+                    }))),
+                    Arc::new(Int32Array::from_iter_values(
+                        (0..100000u32).map(|i| i.wrapping_mul(2654435761) as i32),
+                    )),
+                ],
+            )?;
+            repartitioner.insert_batch(batch).await?;
+            repartitioner.spill().await?;
+        }
+        repartitioner.shuffle_write().await?;
+
+        // the hot partition has >90% of data, about 7x of the average size
+        let skew_ratio = metrics
+            .clone_inner()
+            .sum_by_name("shuffle_write_partition_skew_ratio")
+            .map(|v| v.as_usize())
+            .unwrap_or_default();
+        assert!(skew_ratio > 400, "skew_ratio={skew_ratio}");
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_shuffle_write_with_foreign_spill() -> Result<()> {
+        MemManager::init(10000);
+        let schema = Arc::new(Schema::new(vec![Field::new("k", DataType::Int32, false)]));
+        let num_partitions = 4;
+        let tmp_dir = tempfile::tempdir()?;
+        let metrics = ExecutionPlanMetricsSet::new();
+
+        for round in 0..10 {
+            let data_file = tmp_dir.path().join(format!("shuffle-{round}.data"));
+            let index_file = tmp_dir.path().join(format!("shuffle-{round}.index"));
+            let repartitioner = Arc::new(SortShuffleRepartitioner::new(
+                0,
+                data_file.to_string_lossy().to_string(),
+                index_file.to_string_lossy().to_string(),
+                Partitioning::Hash(vec![Arc::new(Column::new("k", 0))], num_partitions),
+                DEFAULT_HASH_SEED,
+                &metrics,
+            )?);
+            MemManager::register_consumer(repartitioner.clone(), true);
+            for i in 0..4 {
+                let batch = RecordBatch::try_new(
+                    schema.clone(),
+                    vec![Arc::new(Int32Array::from_iter_values(
+                        i * 1000..(i + 1) * 1000,
+                    ))],
+                )?;
+                repartitioner.insert_batch(batch).await?;
+            }
+
+            // spill() triggered from another consumer's task, racing with
+            // shuffle_write()
+            let foreign_spill = tokio::spawn({
+                let repartitioner = repartitioner.clone();
+                async move { repartitioner.spill().await }
+            });
+            repartitioner.shuffle_write().await?;
+            foreign_spill.await.expect("spill task panicked")?;
+
+            // no rows are lost
+            let data = std::fs::read(&data_file)?;
+            let index = std::fs::read(&index_file)?;
+            let offsets = index
+                .chunks_exact(8)
+                .map(|offset| i64::from_le_bytes(offset.try_into().unwrap()) as usize)
+                .collect::<Vec<_>>();
+            let mut keys = vec![];
+            for partition_id in 0..num_partitions {
+                let partition_data =
+                    data[offsets[partition_id]..offsets[partition_id + 1]].to_vec();
+                let mut reader =
+                    IpcCompressionReader::new(Cursor::new(partition_data), schema.clone());
+                while let Some(batch) = reader.read_batch()? {
+                    keys.extend(batch.column(0).as_primitive::<Int32Type>().values().iter());
+                }
+            }
+            keys.sort_unstable();
+            assert_eq!(keys, (0..4000).collect::<Vec<_>>());
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,563 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    any::Any,
+    fmt::Formatter,
+    fs::File,
+    io::{BufReader, Read, Seek, SeekFrom},
+    sync::Arc,
+};
+
+use arrow::datatypes::SchemaRef;
+use byteorder::{LittleEndian, ReadBytesExt};
+use datafusion::{
+    error::Result,
+    execution::context::TaskContext,
+    physical_plan::{
+        expressions::PhysicalSortExpr,
+        metrics::{BaselineMetrics, Count, ExecutionPlanMetricsSet, MetricBuilder, MetricsSet},
+        stream::RecordBatchStreamAdapter,
+        DisplayAs, DisplayFormatType, ExecutionPlan, Partitioning,
+        Partitioning::UnknownPartitioning,
+        SendableRecordBatchStream, Statistics,
+    },
+};
+use datafusion_ext_commons::{
+    array_size::ArraySize, df_execution_err, streams::coalesce_stream::CoalesceInput,
+};
+use futures::{stream::once, TryStreamExt};
+use parking_lot::Mutex;
+
+use crate::{
+    common::{ipc_compression::IpcCompressionReader, output::TaskOutputter},
+    shuffle::checksum::{read_partition_checksum, ShuffleChecksumAlgorithm},
+};
+
+/// Reads back output partitions from the data/index files produced by
+/// ShuffleWriterExec. partition `i` of this plan reads output partition `i`
+/// of the shuffle files.
+#[derive(Debug, Clone)]
+pub struct ShuffleReaderExec {
+    num_partitions: usize,
+    data_file: String,
+    index_file: String,
+    checksum: Option<(String, ShuffleChecksumAlgorithm)>,
+    schema: SchemaRef,
+    metrics: ExecutionPlanMetricsSet,
+}
+
+impl ShuffleReaderExec {
+    pub fn new(
+        num_partitions: usize,
+        data_file: String,
+        index_file: String,
+        schema: SchemaRef,
+    ) -> Self {
+        Self {
+            num_partitions,
+            data_file,
+            index_file,
+            checksum: None,
+            schema,
+            metrics: ExecutionPlanMetricsSet::new(),
+        }
+    }
+
+    /// Validate partition data against checksums written by ShuffleWriterExec
+    pub fn with_checksum_file(
+        mut self,
+        checksum_file: String,
+        algorithm: ShuffleChecksumAlgorithm,
+    ) -> Self {
+        self.checksum = Some((checksum_file, algorithm));
+        self
+    }
+}
+
+impl DisplayAs for ShuffleReaderExec {
+    fn fmt_as(&self, _t: DisplayFormatType, f: &mut Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "ShuffleReader: data_file={}, index_file={}",
+            self.data_file, self.index_file
+        )
+    }
+}
+
+impl ExecutionPlan for ShuffleReaderExec {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+
+    fn output_partitioning(&self) -> Partitioning {
+        UnknownPartitioning(self.num_partitions)
+    }
+
+    fn output_ordering(&self) -> Option<&[PhysicalSortExpr]> {
+        None
+    }
+
+    fn children(&self) -> Vec<Arc<dyn ExecutionPlan>> {
+        vec![]
+    }
+
+    fn with_new_children(
+        self: Arc<Self>,
+        _children: Vec<Arc<dyn ExecutionPlan>>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        let mut new_reader = Self::new(
+            self.num_partitions,
+            self.data_file.clone(),
+            self.index_file.clone(),
+            self.schema.clone(),
+        );
+        new_reader.checksum = self.checksum.clone();
+        Ok(Arc::new(new_reader))
+    }
+
+    fn execute(
+        &self,
+        partition: usize,
+        context: Arc<TaskContext>,
+    ) -> Result<SendableRecordBatchStream> {
+        let baseline_metrics = BaselineMetrics::new(&self.metrics, partition);
+        let size_counter = MetricBuilder::new(&self.metrics).counter("size", partition);
+
+        let shuffle_stream = Box::pin(RecordBatchStreamAdapter::new(
+            self.schema(),
+            once(read_shuffle_partition(
+                context.clone(),
+                self.clone(),
+                partition,
+                baseline_metrics.clone(),
+                size_counter,
+            ))
+            .try_flatten(),
+        ));
+        Ok(context.coalesce_with_default_batch_size(shuffle_stream, &baseline_metrics)?)
+    }
+
+    fn metrics(&self) -> Option<MetricsSet> {
+        Some(self.metrics.clone_inner())
+    }
+
+    fn statistics(&self) -> Result<Statistics> {
+        Ok(Statistics::new_unknown(&self.schema()))
+    }
+}
+
+async fn read_shuffle_partition(
+    context: Arc<TaskContext>,
+    exec: ShuffleReaderExec,
+    partition: usize,
+    baseline_metrics: BaselineMetrics,
+    size_counter: Count,
+) -> Result<SendableRecordBatchStream> {
+    let schema = exec.schema();
+    context.output_with_sender("ShuffleReader", schema, move |sender| async move {
+        let mut timer = baseline_metrics.elapsed_compute().timer();
+
+        // opening the partition reads the index file and validates checksum of
+        // the whole partition, which are blocking io like reading batches
+        let reader = tokio::task::spawn_blocking(move || {
+            get_partition_reader(
+                exec.schema(),
+                &exec.data_file,
+                &exec.index_file,
+                exec.checksum.as_ref(),
+                partition,
+            )
+        })
+        .await
+        .or_else(|err| df_execution_err!("{err}"))??;
+        let reader = Arc::new(Mutex::new(reader));
+        while let Some(batch) = {
+            let reader = reader.clone();
+            tokio::task::spawn_blocking(move || reader.lock().read_batch())
+                .await
+                .or_else(|err| df_execution_err!("{err}"))??
+        } {
+            size_counter.add(batch.get_array_mem_size());
+            baseline_metrics.record_output(batch.num_rows());
+            sender.send(Ok(batch), Some(&mut timer)).await;
+        }
+        Ok(())
+    })
+}
+
+fn get_partition_reader(
+    schema: SchemaRef,
+    data_file: &str,
+    index_file: &str,
+    checksum: Option<&(String, ShuffleChecksumAlgorithm)>,
+    partition: usize,
+) -> Result<IpcCompressionReader<Box<dyn Read + Send>>> {
+    // index file contains num_partitions + 1 offsets
+    let mut index = File::open(index_file)?;
+    let num_offsets = index.metadata()?.len() as usize / 8;
+    if partition + 1 >= num_offsets {
+        return df_execution_err!(
+            "shuffle partition {partition} out of range, num_partitions={}",
+            num_offsets.saturating_sub(1)
+        );
+    }
+    index.seek(SeekFrom::Start(partition as u64 * 8))?;
+    let start = index.read_i64::<LittleEndian>()? as u64;
+    let end = index.read_i64::<LittleEndian>()? as u64;
+    let Some(len) = end.checked_sub(start) else {
+        return df_execution_err!(
+            "corrupted shuffle index file {index_file}: partition {partition} has \
+             start offset {start} > end offset {end}"
+        );
+    };
+
+    // partition data may consist of multiple blocks, which are all read by
+    // IpcCompressionReader. an empty partition produces an empty stream
+    let mut data = File::open(data_file)?;
+    if let Some((checksum_file, algorithm)) = checksum {
+        data.seek(SeekFrom::Start(start))?;
+        let expected = read_partition_checksum(checksum_file, partition)?;
+        let actual = algorithm.checksum(BufReader::new((&mut data).take(len)))?;
+        if actual != expected {
+            return df_execution_err!(
+                "shuffle checksum mismatch for partition {partition} in {data_file}: \
+                 expected={expected}, actual={actual}, algorithm={algorithm:?}"
+            );
+        }
+    }
+    data.seek(SeekFrom::Start(start))?;
+    Ok(IpcCompressionReader::new(
+        Box::new(BufReader::with_capacity(65536, data.take(len))),
+        schema,
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use arrow::{
+        array::{
+            ArrayRef, AsArray, Int32Array, Int32Builder, ListArray, MapBuilder, StringArray,
+            StringBuilder, StructArray,
+        },
+        compute::{concat_batches, sort_to_indices, take_record_batch},
+        datatypes::{DataType, Field, Int32Type, Schema},
+        record_batch::RecordBatch,
+    };
+    use datafusion::{
+        common::Result,
+        physical_expr::expressions::Column,
+        physical_plan::{common, memory::MemoryExec, ExecutionPlan, Partitioning},
+        prelude::SessionContext,
+    };
+    use datafusion_ext_commons::spark_hash::{create_murmur3_hashes, pmod};
+
+    use crate::{
+        common::ipc_compression::IpcCompressionWriter,
+        memmgr::MemManager,
+        shuffle::checksum::ShuffleChecksumAlgorithm,
+        shuffle_reader_exec::ShuffleReaderExec,
+        shuffle_writer_exec::{ShuffleWriterExec, DEFAULT_HASH_SEED},
+    };
+
+    #[tokio::test]
+    async fn test_shuffle_write_and_read() -> Result<()> {
+        MemManager::init(1000000);
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("k", DataType::Int32, false),
+            Field::new("v", DataType::Utf8, true),
+        ]));
+        let batches = (0..3)
+            .map(|i| {
+                RecordBatch::try_new(
+                    schema.clone(),
+                    vec![
+                        Arc::new(Int32Array::from_iter_values(i * 1000..i * 1000 + 1000)),
+                        Arc::new(StringArray::from_iter(
+                            (0..1000).map(|j| (j % 7 != 0).then(|| format!("v{i}-{j}"))),
+                        )),
+                    ],
+                )
+            })
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        let num_rows = batches.iter().map(|batch| batch.num_rows()).sum::<usize>();
+
+        let tmp_dir = tempfile::tempdir()?;
+        let path = |name: &str| tmp_dir.path().join(name).to_string_lossy().to_string();
+        let (data_file, index_file) = (path("shuffle.data"), path("shuffle.index"));
+
+        // write with ShuffleWriterExec, 3000 rows into 2000 partitions so that
+        // some partitions are empty
+        let num_partitions = 2000;
+        let input = Arc::new(MemoryExec::try_new(&[batches], schema.clone(), None)?);
+        let writer = ShuffleWriterExec::try_new(
+            input,
+            Partitioning::Hash(vec![Arc::new(Column::new("k", 0))], num_partitions),
+            data_file.clone(),
+            index_file.clone(),
+            DEFAULT_HASH_SEED,
+        )?;
+        let task_ctx = SessionContext::new().task_ctx();
+        common::collect(writer.execute(0, task_ctx.clone())?).await?;
+
+        // read back all partitions
+        let reader = ShuffleReaderExec::new(num_partitions, data_file, index_file, schema);
+        let mut num_read_rows = 0;
+        let mut num_empty_partitions = 0;
+        let mut keys = vec![];
+        for partition in 0..num_partitions {
+            let output = common::collect(reader.execute(partition, task_ctx.clone())?).await?;
+            let partition_num_rows = output.iter().map(|b| b.num_rows()).sum::<usize>();
+            if partition_num_rows == 0 {
+                num_empty_partitions += 1;
+            }
+            num_read_rows += partition_num_rows;
+
+            // all rows are in the right partition
+            for batch in &output {
+                let mut hashes = vec![DEFAULT_HASH_SEED; batch.num_rows()];
+                create_murmur3_hashes(&[batch.column(0).clone()], &mut hashes)?;
+                assert!(hashes
+                    .iter()
+                    .all(|&hash| pmod(hash, num_partitions) == partition));
+                keys.extend(batch.column(0).as_primitive::<Int32Type>().values().iter());
+            }
+        }
+        assert_eq!(num_read_rows, num_rows);
+        assert!(num_empty_partitions > 0);
+        keys.sort_unstable();
+        assert_eq!(keys, (0..3000).collect::<Vec<_>>());
+
+        // out of range partition
+        assert!(common::collect(reader.execute(num_partitions, task_ctx)?)
+            .await
+            .is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_read_multiple_blocks() -> Result<()> {
+        MemManager::init(1000000);
+        let schema = Arc::new(Schema::new(vec![Field::new("k", DataType::Int32, false)]));
+        let batches = (0..4)
+            .map(|i| {
+                RecordBatch::try_new(
+                    schema.clone(),
+                    vec![Arc::new(Int32Array::from_iter_values(i * 10..i * 10 + 10))],
+                )
+            })
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        // partition 0 is empty, partition 1 consists of 3 blocks and
+        // partition 2 consists of 1 block
+        let mut data = vec![];
+        let mut writer = IpcCompressionWriter::new(&mut data, true);
+        for batch in &batches[0..3] {
+            writer.write_batch(batch.clone())?;
+            writer.flush()?;
+        }
+        writer.finish_into_inner()?;
+        let partition1_len = data.len();
+        let mut writer = IpcCompressionWriter::new(&mut data, true);
+        writer.write_batch(batches[3].clone())?;
+        writer.finish_into_inner()?;
+        let offsets = [0, 0, partition1_len, data.len()];
+
+        let tmp_dir = tempfile::tempdir()?;
+        let path = |name: &str| tmp_dir.path().join(name).to_string_lossy().to_string();
+        let (data_file, index_file) = (path("shuffle.data"), path("shuffle.index"));
+        let write_index = |offsets: &[usize]| {
+            std::fs::write(
+                &index_file,
+                offsets
+                    .iter()
+                    .flat_map(|&offset| (offset as i64).to_le_bytes())
+                    .collect::<Vec<_>>(),
+            )
+        };
+        std::fs::write(&data_file, &data)?;
+        write_index(&offsets)?;
+
+        let reader = ShuffleReaderExec::new(3, data_file, index_file.clone(), schema);
+        let task_ctx = SessionContext::new().task_ctx();
+        let read_keys = |batches: Vec<RecordBatch>| {
+            batches
+                .iter()
+                .flat_map(|b| b.column(0).as_primitive::<Int32Type>().values().to_vec())
+                .collect::<Vec<_>>()
+        };
+        let output0 = common::collect(reader.execute(0, task_ctx.clone())?).await?;
+        let output1 = common::collect(reader.execute(1, task_ctx.clone())?).await?;
+        let output2 = common::collect(reader.execute(2, task_ctx.clone())?).await?;
+        assert!(read_keys(output0).is_empty());
+        assert_eq!(read_keys(output1), (0..30).collect::<Vec<_>>());
+        assert_eq!(read_keys(output2), (30..40).collect::<Vec<_>>());
+
+        // corrupted index with decreasing offsets
+        write_index(&[0, partition1_len, 0, data.len()])?;
+        let err = reader
+            .execute(1, task_ctx)
+            .err()
+            .expect("corrupted index error expected");
+        assert!(err.to_string().contains("corrupted shuffle index"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_read_with_checksum() -> Result<()> {
+        MemManager::init(1000000);
+        let schema = Arc::new(Schema::new(vec![Field::new("k", DataType::Int32, false)]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(Int32Array::from_iter_values(0..1000))],
+        )?;
+        let tmp_dir = tempfile::tempdir()?;
+        let path = |name: &str| tmp_dir.path().join(name).to_string_lossy().to_string();
+
+        // checksums are computed by the single, bypass and sort repartitioners
+        for num_partitions in [1, 4, 300] {
+            let (data_file, index_file, checksum_file) = (
+                path(&format!("shuffle-{num_partitions}.data")),
+                path(&format!("shuffle-{num_partitions}.index")),
+                path(&format!("shuffle-{num_partitions}.checksum")),
+            );
+            let input = Arc::new(MemoryExec::try_new(
+                &[vec![batch.clone()]],
+                schema.clone(),
+                None,
+            )?);
+            let writer = ShuffleWriterExec::try_new(
+                input,
+                Partitioning::Hash(vec![Arc::new(Column::new("k", 0))], num_partitions),
+                data_file.clone(),
+                index_file.clone(),
+                DEFAULT_HASH_SEED,
+            )?
+            .with_output_checksum_file(checksum_file.clone(), ShuffleChecksumAlgorithm::Crc32);
+            let task_ctx = SessionContext::new().task_ctx();
+            common::collect(writer.execute(0, task_ctx.clone())?).await?;
+            assert_eq!(std::fs::read(&checksum_file)?.len(), num_partitions * 8);
+
+            let reader = ShuffleReaderExec::new(
+                num_partitions,
+                data_file.clone(),
+                index_file.clone(),
+                schema.clone(),
+            )
+            .with_checksum_file(checksum_file, ShuffleChecksumAlgorithm::Crc32);
+            let mut num_rows = 0;
+            for partition in 0..num_partitions {
+                let output = common::collect(reader.execute(partition, task_ctx.clone())?).await?;
+                num_rows += output.iter().map(|b| b.num_rows()).sum::<usize>();
+            }
+            assert_eq!(num_rows, 1000);
+
+            // corrupt the first byte, which belongs to the first non-empty
+            // partition
+            let offsets = std::fs::read(&index_file)?
+                .chunks(8)
+                .map(|offset| i64::from_le_bytes(offset.try_into().unwrap()))
+                .collect::<Vec<_>>();
+            let corrupted_partition = offsets.windows(2).position(|w| w[1] > 0).unwrap();
+            let mut data = std::fs::read(&data_file)?;
+            data[0] ^= 0xff;
+            std::fs::write(&data_file, &data)?;
+            let err = common::collect(reader.execute(corrupted_partition, task_ctx)?)
+                .await
+                .err()
+                .expect("checksum mismatch expected");
+            assert!(err.to_string().contains("checksum mismatch"));
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_shuffle_nested_columns() -> Result<()> {
+        MemManager::init(1000000);
+        let num_rows = 1000;
+        let keys = Arc::new(Int32Array::from_iter_values(0..num_rows)) as ArrayRef;
+        let lists = Arc::new(ListArray::from_iter_primitive::<Int32Type, _, _>(
+            (0..num_rows).map(|i| (i % 5 != 0).then(|| (0..i % 3).map(Some).collect::<Vec<_>>())),
+        )) as ArrayRef;
+        let structs = Arc::new(StructArray::from(vec![
+            (
+                Arc::new(Field::new("a", DataType::Int32, true)),
+                Arc::new(Int32Array::from_iter(
+                    (0..num_rows).map(|i| (i % 7 != 0).then_some(i)),
+                )) as ArrayRef,
+            ),
+            (
+                Arc::new(Field::new("b", DataType::Utf8, true)),
+                Arc::new(StringArray::from_iter_values(
+                    (0..num_rows).map(|i| format!("b{i}")),
+                )) as ArrayRef,
+            ),
+        ])) as ArrayRef;
+        let mut map_builder = MapBuilder::new(None, StringBuilder::new(), Int32Builder::new());
+        for i in 0..num_rows {
+            for j in 0..i % 4 {
+                map_builder.keys().append_value(format!("k{j}"));
+                map_builder.values().append_value(i + j);
+            }
+            map_builder.append(i % 9 != 0)?;
+        }
+        let maps = Arc::new(map_builder.finish()) as ArrayRef;
+        let batch = RecordBatch::try_from_iter_with_nullable(vec![
+            ("k", keys, false),
+            ("l", lists, true),
+            ("s", structs, true),
+            ("m", maps, true),
+        ])?;
+        let schema = batch.schema();
+
+        // bypass-merge and sort repartitioners
+        for num_partitions in [4, 300] {
+            let tmp_dir = tempfile::tempdir()?;
+            let path = |name: &str| tmp_dir.path().join(name).to_string_lossy().to_string();
+            let (data_file, index_file) = (path("shuffle.data"), path("shuffle.index"));
+
+            let input = Arc::new(MemoryExec::try_new(
+                &[vec![batch.clone()]],
+                schema.clone(),
+                None,
+            )?);
+            let writer = ShuffleWriterExec::try_new(
+                input,
+                Partitioning::Hash(vec![Arc::new(Column::new("k", 0))], num_partitions),
+                data_file.clone(),
+                index_file.clone(),
+                DEFAULT_HASH_SEED,
+            )?;
+            let task_ctx = SessionContext::new().task_ctx();
+            common::collect(writer.execute(0, task_ctx.clone())?).await?;
+
+            let reader =
+                ShuffleReaderExec::new(num_partitions, data_file, index_file, schema.clone());
+            let mut output = vec![];
+            for partition in 0..num_partitions {
+                output.extend(common::collect(reader.execute(partition, task_ctx.clone())?).await?);
+            }
+            let output = concat_batches(&schema, &output)?;
+            let sorted_indices = sort_to_indices(output.column(0), None, None)?;
+            assert_eq!(take_record_batch(&output, &sorted_indices)?, batch);
+        }
+        Ok(())
+    }
+}
@@ -16,10 +16,10 @@
 
 use std::{any::Any, fmt::Debug, sync::Arc};
 
-use arrow::datatypes::SchemaRef;
+use arrow::{datatypes::SchemaRef, record_batch::RecordBatch};
 use async_trait::async_trait;
 use datafusion::{
-    error::Result,
+    error::{DataFusionError, Result},
     execution::context::TaskContext,
     physical_plan::{
         expressions::PhysicalSortExpr,
@@ -32,12 +32,18 @@ use datafusion::{
 use datafusion_ext_commons::df_execution_err;
 use futures::{stream::once, TryStreamExt};
 
+pub use crate::shuffle::DEFAULT_HASH_SEED;
 use crate::{
     common::batch_statisitcs::{stat_input, InputBatchStatistics},
     memmgr::MemManager,
     shuffle::{
+        bypass_repartitioner::{bypass_merge_threshold, BypassMergeShuffleRepartitioner},
+        checksum::ShuffleChecksumAlgorithm,
+        execute_shuffle_input,
+        object_store_output::ObjectStoreShuffleOutput,
         single_repartitioner::SingleShuffleRepartitioner,
-        sort_repartitioner::SortShuffleRepartitioner, ShuffleRepartitioner,
+        sort_repartitioner::SortShuffleRepartitioner,
+        FusedShuffleInput, ShuffleRepartitioner, ShuffleWriteMetrics,
     },
 };
 
@@ -50,10 +56,14 @@ pub struct ShuffleWriterExec {
     input: Arc<dyn ExecutionPlan>,
     /// Partitioning scheme to use
     partitioning: Partitioning,
+    /// Seed of murmur3 hashes used in hash partitioning
+    hash_seed: i32,
     /// Output data file path
     output_data_file: String,
     /// Output index file path
     output_index_file: String,
+    /// Output checksum file path and algorithm, if checksums are enabled
+    output_checksum: Option<(String, ShuffleChecksumAlgorithm)>,
     /// Metrics
     metrics: ExecutionPlanMetricsSet,
 }
@@ -93,12 +103,17 @@ impl ExecutionPlan for ShuffleWriterExec {
         children: Vec<Arc<dyn ExecutionPlan>>,
     ) -> Result<Arc<dyn ExecutionPlan>> {
         match children.len() {
-            1 => Ok(Arc::new(ShuffleWriterExec::try_new(
-                children[0].clone(),
-                self.partitioning.clone(),
-                self.output_data_file.clone(),
-                self.output_index_file.clone(),
-            )?)),
+            1 => {
+                let mut new_writer = ShuffleWriterExec::try_new(
+                    children[0].clone(),
+                    self.partitioning.clone(),
+                    self.output_data_file.clone(),
+                    self.output_index_file.clone(),
+                    self.hash_seed,
+                )?;
+                new_writer.output_checksum = self.output_checksum.clone();
+                Ok(Arc::new(new_writer))
+            }
             _ => df_execution_err!("ShuffleWriterExec wrong number of children"),
         }
     }
@@ -111,20 +126,67 @@ impl ExecutionPlan for ShuffleWriterExec {
         // record uncompressed data size
         let data_size_metric = MetricBuilder::new(&self.metrics).counter("data_size", partition);
 
-        let repartitioner: Arc<dyn ShuffleRepartitioner> = match &self.partitioning {
-            p if p.partition_count() == 1 => Arc::new(SingleShuffleRepartitioner::new(
+        // output to an object store is written into local files, then uploaded
+        let object_store_output = ObjectStoreShuffleOutput::try_new(
+            &self.output_data_file,
+            &self.output_index_file,
+            &context,
+        )?;
+        let (output_data_file, output_index_file) = match &object_store_output {
+            Some(output) => (output.local_data_file(), output.local_index_file()),
+            None => (
                 self.output_data_file.clone(),
                 self.output_index_file.clone(),
-                BaselineMetrics::new(&self.metrics, partition),
-            )),
-            Partitioning::Hash(..) => {
-                let partitioner = Arc::new(SortShuffleRepartitioner::new(
-                    partition,
-                    self.output_data_file.clone(),
-                    self.output_index_file.clone(),
-                    self.partitioning.clone(),
-                    &self.metrics,
-                ));
+            ),
+        };
+
+        let mut fused_input = None;
+        let repartitioner: Arc<dyn ShuffleRepartitioner> = match &self.partitioning {
+            p if p.partition_count() == 1 => Arc::new(
+                SingleShuffleRepartitioner::new(
+                    output_data_file,
+                    output_index_file,
+                    BaselineMetrics::new(&self.metrics, partition),
+                    ShuffleWriteMetrics::new(&self.metrics, partition)?,
+                )
+                .with_output_checksum(self.output_checksum.clone()),
+            ),
+            p @ (Partitioning::Hash(..) | Partitioning::RoundRobinBatch(..))
+                if p.partition_count() < bypass_merge_threshold() =>
+            {
+                let partitioner = Arc::new(
+                    BypassMergeShuffleRepartitioner::new(
+                        partition,
+                        output_data_file,
+                        output_index_file,
+                        self.partitioning.clone(),
+                        self.hash_seed,
+                        &self.metrics,
+                    )?
+                    .with_output_checksum(self.output_checksum.clone()),
+                );
+                MemManager::register_consumer(partitioner.clone(), true);
+                partitioner
+            }
+            Partitioning::Hash(..) | Partitioning::RoundRobinBatch(..) => {
+                // a ProjectExec input is evaluated lazily per output partition
+                fused_input = FusedShuffleInput::try_new(&self.input, &self.partitioning)?;
+                let (partitioning, projection) = match &fused_input {
+                    Some(fused) => (fused.partitioning().clone(), Some(fused.projection())),
+                    None => (self.partitioning.clone(), None),
+                };
+                let partitioner = Arc::new(
+                    SortShuffleRepartitioner::new(
+                        partition,
+                        output_data_file,
+                        output_index_file,
+                        partitioning,
+                        self.hash_seed,
+                        &self.metrics,
+                    )?
+                    .with_projection(projection)
+                    .with_output_checksum(self.output_checksum.clone()),
+                );
                 MemManager::register_consumer(partitioner.clone(), true);
                 partitioner
             }
@@ -133,17 +195,37 @@ impl ExecutionPlan for ShuffleWriterExec {
 
         let input = stat_input(
             InputBatchStatistics::from_metrics_set_and_blaze_conf(&self.metrics, partition)?,
-            self.input.execute(partition, context.clone())?,
+            match &fused_input {
+                Some(fused) => fused.execute(partition, context.clone())?,
+                None => execute_shuffle_input(&self.input, partition, context.clone())?,
+            },
         )?;
+        let output = once(repartitioner.execute(
+            context.clone(),
+            partition,
+            input,
+            BaselineMetrics::new(&self.metrics, partition),
+            data_size_metric,
+        ))
+        .try_flatten();
+
+        if object_store_output.is_none() {
+            return Ok(Box::pin(RecordBatchStreamAdapter::new(
+                self.schema(),
+                output,
+            )));
+        }
         Ok(Box::pin(RecordBatchStreamAdapter::new(
             self.schema(),
-            once(repartitioner.execute(
-                context.clone(),
-                partition,
-                input,
-                BaselineMetrics::new(&self.metrics, partition),
-                data_size_metric,
-            ))
+            once(async move {
+                // shuffle writer produces no output batches
+                output.try_collect::<Vec<_>>().await?;
+
+                if let Some(object_store_output) = object_store_output {
+                    object_store_output.upload().await?;
+                }
+                Ok::<_, DataFusionError>(futures::stream::empty::<Result<RecordBatch>>())
+            })
             .try_flatten(),
         )))
     }
@@ -164,13 +246,148 @@ impl ShuffleWriterExec {
         partitioning: Partitioning,
         output_data_file: String,
         output_index_file: String,
+        hash_seed: i32,
     ) -> Result<Self> {
         Ok(ShuffleWriterExec {
             input,
             partitioning,
+            hash_seed,
             metrics: ExecutionPlanMetricsSet::new(),
             output_data_file,
             output_index_file,
+            output_checksum: None,
         })
     }
+
+    /// Write checksums of output partitions into the specified file, in the
+    /// format of spark's shuffle checksum file
+    pub fn with_output_checksum_file(
+        mut self,
+        output_checksum_file: String,
+        algorithm: ShuffleChecksumAlgorithm,
+    ) -> Self {
+        self.output_checksum = Some((output_checksum_file, algorithm));
+        self
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{io::Cursor, sync::Arc};
+
+    use arrow::{
+        array::{AsArray, Int32Array},
+        datatypes::{DataType, Field, Int32Type, Schema},
+        record_batch::RecordBatch,
+    };
+    use datafusion::{
+        common::Result,
+        execution::object_store::ObjectStoreUrl,
+        physical_expr::expressions::Column,
+        physical_plan::{common, memory::MemoryExec, ExecutionPlan, Partitioning},
+        prelude::SessionContext,
+    };
+    use object_store::{memory::InMemory, path::Path, ObjectStore};
+
+    use crate::{
+        common::ipc_compression::IpcCompressionReader,
+        memmgr::MemManager,
+        shuffle_writer_exec::{ShuffleWriterExec, DEFAULT_HASH_SEED},
+    };
+
+    #[tokio::test]
+    async fn test_shuffle_write_empty_input() -> Result<()> {
+        MemManager::init(10000);
+        let session_ctx = SessionContext::new();
+        let schema = Arc::new(Schema::new(vec![Field::new("k", DataType::Int32, false)]));
+
+        // single, bypass-merge and sort repartitioners
+        for partitioning in [
+            Partitioning::Hash(vec![Arc::new(Column::new("k", 0))], 1),
+            Partitioning::Hash(vec![Arc::new(Column::new("k", 0))], 4),
+            Partitioning::Hash(vec![Arc::new(Column::new("k", 0))], 200),
+        ] {
+            let num_output_partitions = partitioning.partition_count();
+            let tmp_dir = tempfile::tempdir()?;
+            let data_file = tmp_dir.path().join("shuffle.data");
+            let index_file = tmp_dir.path().join("shuffle.index");
+            let input = Arc::new(MemoryExec::try_new(&[vec![]], schema.clone(), None)?);
+            let shuffle_writer = ShuffleWriterExec::try_new(
+                input,
+                partitioning,
+                data_file.to_string_lossy().to_string(),
+                index_file.to_string_lossy().to_string(),
+                DEFAULT_HASH_SEED,
+            )?;
+            let output = shuffle_writer.execute(0, session_ctx.task_ctx())?;
+            assert!(common::collect(output).await?.is_empty());
+
+            // an empty data file and an index of all-zero offsets are written
+            assert_eq!(std::fs::read(&data_file)?.len(), 0);
+            let index = std::fs::read(&index_file)?;
+            let offsets = index
+                .chunks_exact(8)
+                .map(|offset| i64::from_le_bytes(offset.try_into().unwrap()))
+                .collect::<Vec<_>>();
+            assert_eq!(index.len(), (num_output_partitions + 1) * 8);
+            assert_eq!(offsets, vec![0; num_output_partitions + 1]);
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_shuffle_write_to_object_store() -> Result<()> {
+        MemManager::init(10000);
+        let session_ctx = SessionContext::new();
+        let store = Arc::new(InMemory::new());
+        session_ctx
+            .runtime_env()
+            .register_object_store(ObjectStoreUrl::parse("memory://")?.as_ref(), store.clone());
+
+        let schema = Arc::new(Schema::new(vec![Field::new("k", DataType::Int32, false)]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(Int32Array::from_iter_values(0..10000))],
+        )?;
+        let input = Arc::new(MemoryExec::try_new(&[vec![batch]], schema.clone(), None)?);
+        let num_output_partitions = 4;
+        let shuffle_writer = ShuffleWriterExec::try_new(
+            input,
+            Partitioning::Hash(vec![Arc::new(Column::new("k", 0))], num_output_partitions),
+            "memory:///shuffle/shuffle_0_0.data".to_string(),
+            "memory:///shuffle/shuffle_0_0.index".to_string(),
+            DEFAULT_HASH_SEED,
+        )?;
+        let output = shuffle_writer.execute(0, session_ctx.task_ctx())?;
+        assert!(common::collect(output).await?.is_empty());
+
+        // read uploaded partitions with the index
+        let get = |location: &'static str| {
+            let store = store.clone();
+            async move {
+                let location = Path::from(location);
+                Ok::<_, object_store::Error>(store.get(&location).await?.bytes().await?)
+            }
+        };
+        let data = get("shuffle/shuffle_0_0.data").await?;
+        let index = get("shuffle/shuffle_0_0.index").await?;
+        let offsets = index
+            .chunks_exact(8)
+            .map(|offset| i64::from_le_bytes(offset.try_into().unwrap()) as usize)
+            .collect::<Vec<_>>();
+        assert_eq!(offsets.len(), num_output_partitions + 1);
+        assert_eq!(offsets[num_output_partitions], data.len());
+
+        let mut keys = vec![];
+        for partition_id in 0..num_output_partitions {
+            let partition_data = data[offsets[partition_id]..offsets[partition_id + 1]].to_vec();
+            let mut reader = IpcCompressionReader::new(Cursor::new(partition_data), schema.clone());
+            while let Some(batch) = reader.read_batch()? {
+                keys.extend(batch.column(0).as_primitive::<Int32Type>().values().iter());
+            }
+        }
+        keys.sort_unstable();
+        assert_eq!(keys, (0..10000).collect::<Vec<_>>());
+        Ok(())
+    }
 }
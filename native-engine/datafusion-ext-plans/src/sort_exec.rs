@@ -196,9 +196,16 @@ impl MemConsumer for ExternalSorter {
             .expect("consumer info not set")
     }
 
-    async fn spill(&self) -> Result<()> {
+    async fn spill(&self) -> Result<usize> {
+        // data is locked until the spill is added, so output() either takes
+        // the spill or sees data taken. nothing is spilled once output() has
+        // started
+        let mut data_guard = self.data.lock().await;
+        if !self.is_spillable() {
+            return Ok(0);
+        }
         let mut spill = try_new_spill(&self.spill_metrics)?;
-        let data = std::mem::take(&mut *self.data.lock().await);
+        let data = std::mem::take(&mut *data_guard);
         let sub_batch_size = compute_suggested_batch_size_for_kway_merge(
             self.mem_total_size(),
             self.num_total_rows(),
@@ -209,6 +216,7 @@ impl MemConsumer for ExternalSorter {
             .lock()
             .await
             .push(LevelSpill { spill, level: 0 });
+        let freed = self.mem_used();
         self.update_mem_used(0).await?;
 
         // merge if there are too many spills
@@ -235,7 +243,9 @@ impl MemConsumer for ExternalSorter {
                 )
             }
         }
-        Ok(())
+        drop(spills);
+        drop(data_guard);
+        Ok(freed)
     }
 }
 
@@ -255,7 +265,7 @@ impl BufferedData {
         spill: &mut Box<dyn Spill>,
         sub_batch_size: usize,
     ) -> Result<()> {
-        let mut writer = spill.get_compressed_writer();
+        let mut writer = spill.get_compressed_writer()?;
         for (key_collector, batch) in
             self.into_sorted_batches::<SqueezeKeyCollector>(sub_batch_size, sorter)?
         {
@@ -575,10 +585,11 @@ impl ExternalSorter {
 
     async fn output(self: Arc<Self>, sender: Arc<WrappedRecordBatchSender>) -> Result<()> {
         let mut timer = self.baseline_metrics.elapsed_compute().timer();
+        let mut data_guard = self.data.lock().await;
         self.set_spillable(false);
-
-        let data = std::mem::take(&mut *self.data.lock().await);
+        let data = std::mem::take(&mut *data_guard);
         let spills = std::mem::take(&mut *self.spills.lock().await);
+        drop(data_guard);
         log::info!(
             "{} starts outputting ({} spills)",
             self.name(),
@@ -689,7 +700,7 @@ impl<'a> SpillCursor<'a> {
         let mut iter = SpillCursor {
             id,
             pruned_schema,
-            input: spill.get_compressed_reader(),
+            input: spill.get_compressed_reader()?,
             cur_batch_num_rows: 0,
             cur_loaded_num_rows: 0,
             cur_batches: vec![],
@@ -921,7 +932,7 @@ fn merge_spills(
     }
 
     let mut output_spill = try_new_spill(spill_metrics)?;
-    let mut output_writer = output_spill.get_compressed_writer();
+    let mut output_writer = output_spill.get_compressed_writer()?;
     let mut merger = ExternalMerger::<SqueezeKeyCollector>::try_new(
         &mut spills,
         pruned_schema,
@@ -1259,20 +1270,29 @@ mod test {
     use std::sync::Arc;
 
     use arrow::{
-        array::Int32Array,
+        array::{AsArray, Int32Array},
         compute::SortOptions,
-        datatypes::{DataType, Field, Schema},
+        datatypes::{DataType, Field, Int32Type, Schema},
         record_batch::RecordBatch,
     };
     use datafusion::{
         assert_batches_eq,
         common::Result,
         physical_expr::{expressions::Column, PhysicalSortExpr},
-        physical_plan::{common, memory::MemoryExec, ExecutionPlan},
+        physical_plan::{
+            common,
+            memory::MemoryExec,
+            metrics::{BaselineMetrics, ExecutionPlanMetricsSet},
+            ExecutionPlan,
+        },
         prelude::SessionContext,
     };
 
-    use crate::{memmgr::MemManager, sort_exec::SortExec};
+    use crate::{
+        common::output::TaskOutputter,
+        memmgr::{metrics::SpillMetrics, MemConsumer, MemManager},
+        sort_exec::{ExternalSorter, PruneSortKeysFromBatch, SortExec},
+    };
 
     fn build_table_i32(
         a: (&str, &Vec<i32>),
@@ -1340,6 +1360,72 @@ mod test {
 
         Ok(())
     }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_output_with_foreign_spill() -> Result<()> {
+        MemManager::init(10000);
+        let session_ctx = SessionContext::new();
+        let task_ctx = session_ctx.task_ctx();
+        let schema = Arc::new(Schema::new(vec![Field::new("a", DataType::Int32, false)]));
+        let exprs = vec![PhysicalSortExpr {
+            expr: Arc::new(Column::new("a", 0)),
+            options: SortOptions::default(),
+        }];
+        let metrics = ExecutionPlanMetricsSet::new();
+
+        for _ in 0..10 {
+            let sorter = Arc::new(ExternalSorter {
+                name: "ExternalSorter[partition=0]".to_string(),
+                mem_consumer_info: None,
+                prune_sort_keys_from_batch: Arc::new(PruneSortKeysFromBatch::try_new(
+                    schema.clone(),
+                    &[0],
+                    &exprs,
+                )?),
+                limit: usize::MAX,
+                data: Default::default(),
+                spills: Default::default(),
+                baseline_metrics: BaselineMetrics::new(&metrics, 0),
+                spill_metrics: SpillMetrics::new(&metrics, 0),
+                num_total_rows: Default::default(),
+                mem_total_size: Default::default(),
+            });
+            MemManager::register_consumer(sorter.clone(), true);
+            for i in 0..4 {
+                let batch = RecordBatch::try_new(
+                    schema.clone(),
+                    vec![Arc::new(Int32Array::from_iter_values(
+                        (0..1000).map(|j| j * 4 + i),
+                    ))],
+                )?;
+                sorter.insert_batch(batch).await?;
+            }
+
+            // spill() triggered from another consumer's task, racing with output()
+            let foreign_spill = tokio::spawn({
+                let sorter = sorter.clone();
+                async move { sorter.spill().await }
+            });
+            let output = task_ctx
+                .output_with_sender("Sort", schema.clone(), move |sender| sorter.output(sender))?;
+            let batches = common::collect(output).await?;
+            foreign_spill.await.expect("spill task panicked")?;
+
+            // no rows are lost
+            let values = batches
+                .iter()
+                .flat_map(|batch| {
+                    batch
+                        .column(0)
+                        .as_primitive::<Int32Type>()
+                        .values()
+                        .to_vec()
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(values, (0..4000).collect::<Vec<_>>());
+        }
+        Ok(())
+    }
 }
 
 #[cfg(test)]
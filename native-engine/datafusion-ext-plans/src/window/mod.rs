@@ -15,14 +15,19 @@
 use std::sync::Arc;
 
 use arrow::{array::ArrayRef, datatypes::FieldRef, record_batch::RecordBatch};
-use datafusion::{common::Result, physical_expr::PhysicalExpr};
+use datafusion::{
+    common::{Result, ScalarValue},
+    physical_expr::{expressions::Literal, PhysicalExpr},
+};
+use datafusion_ext_commons::df_execution_err;
 
 use crate::{
     agg::{create_agg, AggFunction},
     window::{
         processors::{
-            agg_processor::AggProcessor, rank_processor::RankProcessor,
-            row_number_processor::RowNumberProcessor,
+            agg_processor::AggProcessor, nth_value_processor::NthValueProcessor,
+            ntile_processor::NtileProcessor, rank_dist_processor::RankDistProcessor,
+            rank_processor::RankProcessor, row_number_processor::RowNumberProcessor,
         },
         window_context::WindowContext,
     },
@@ -35,6 +40,7 @@ pub mod window_context;
 pub enum WindowFunction {
     RankLike(WindowRankType),
     Agg(AggFunction),
+    NthValue { ignore_nulls: bool },
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -42,9 +48,19 @@ pub enum WindowRankType {
     RowNumber,
     Rank,
     DenseRank,
+    Ntile,
+    PercentRank,
+    CumeDist,
 }
 
 pub trait WindowFunctionProcessor: Send + Sync {
+    /// for functions requiring full partitions, every batch of complete
+    /// partitions is passed to prepare_batch() before any of them is
+    /// processed, so partition-wide statistics (like row counts) are known
+    fn prepare_batch(&mut self, _context: &WindowContext, _batch: &RecordBatch) -> Result<()> {
+        Ok(())
+    }
+
     fn process_batch(&mut self, context: &WindowContext, batch: &RecordBatch) -> Result<ArrayRef>;
     fn process_batch_without_partitions(
         &mut self,
@@ -73,6 +89,17 @@ impl WindowExpr {
         }
     }
 
+    /// whether the function needs all rows of a partition to be prepared
+    /// before processing
+    pub fn requires_full_partitions(&self) -> bool {
+        matches!(
+            self.func,
+            WindowFunction::RankLike(
+                WindowRankType::Ntile | WindowRankType::PercentRank | WindowRankType::CumeDist
+            )
+        )
+    }
+
     pub fn create_processor(
         &self,
         context: &Arc<WindowContext>,
@@ -87,6 +114,44 @@ impl WindowExpr {
             WindowFunction::RankLike(WindowRankType::DenseRank) => {
                 Ok(Box::new(RankProcessor::new(true)))
             }
+            WindowFunction::RankLike(WindowRankType::PercentRank) => {
+                Ok(Box::new(RankDistProcessor::new(false)))
+            }
+            WindowFunction::RankLike(WindowRankType::CumeDist) => {
+                Ok(Box::new(RankDistProcessor::new(true)))
+            }
+            WindowFunction::RankLike(WindowRankType::Ntile) => {
+                let num_buckets = self
+                    .children
+                    .first()
+                    .and_then(|expr| expr.as_any().downcast_ref::<Literal>())
+                    .map(|literal| literal.value());
+                match num_buckets {
+                    Some(&ScalarValue::Int32(Some(n))) if n > 0 => {
+                        Ok(Box::new(NtileProcessor::new(n)))
+                    }
+                    _ => df_execution_err!(
+                        "ntile: buckets must be a positive int literal, got {:?}",
+                        self.children
+                    ),
+                }
+            }
+            WindowFunction::NthValue { ignore_nulls } => {
+                let n = self
+                    .children
+                    .get(1)
+                    .and_then(|expr| expr.as_any().downcast_ref::<Literal>())
+                    .map(|literal| literal.value());
+                match n {
+                    Some(&ScalarValue::Int32(Some(n))) if n > 0 => Ok(Box::new(
+                        NthValueProcessor::new(self.children[0].clone(), n as usize, ignore_nulls),
+                    )),
+                    _ => df_execution_err!(
+                        "nth_value: offset must be a positive int literal, got {:?}",
+                        self.children
+                    ),
+                }
+            }
             WindowFunction::Agg(agg_func) => {
                 let agg = create_agg(agg_func, &self.children, &context.input_schema)?;
                 Ok(Box::new(AggProcessor::try_new(agg)?))
@@ -12,6 +12,69 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::collections::VecDeque;
+
+use arrow::record_batch::RecordBatch;
+use datafusion::common::Result;
+use datafusion_ext_commons::{df_execution_err, slim_bytes::SlimBytes};
+
+use crate::window::window_context::WindowContext;
+
 pub mod agg_processor;
+pub mod nth_value_processor;
+pub mod ntile_processor;
+pub mod rank_dist_processor;
 pub mod rank_processor;
 pub mod row_number_processor;
+
+/// sizes of complete partitions collected in prepare_batch(), for functions
+/// requiring full partitions. rows of a partition may then be processed
+/// across batches, with the partition size known in advance.
+#[derive(Default)]
+pub struct PartitionSizes {
+    sizes: VecDeque<usize>,
+    last_partition: Option<SlimBytes>,
+    cur_size: usize,
+    cur_row_idx: usize,
+}
+
+impl PartitionSizes {
+    pub fn prepare_batch(&mut self, context: &WindowContext, batch: &RecordBatch) -> Result<()> {
+        if !context.has_partition() {
+            // all rows are in one partition
+            match self.sizes.back_mut() {
+                Some(size) => *size += batch.num_rows(),
+                None => self.sizes.push_back(batch.num_rows()),
+            }
+            return Ok(());
+        }
+
+        let partition_rows = context.get_partition_rows(batch)?;
+        for partition_row in partition_rows.iter() {
+            if self.last_partition.as_deref() != Some(partition_row.as_ref()) {
+                self.last_partition = Some(partition_row.as_ref().into());
+                self.sizes.push_back(0);
+            }
+            *self.sizes.back_mut().unwrap() += 1;
+        }
+        Ok(())
+    }
+
+    /// returns index of the next row in its partition and size of the
+    /// partition, the next prepared partition is started if the current one
+    /// is exhausted
+    pub fn cur_position(&mut self) -> Result<(usize, usize)> {
+        if self.cur_row_idx == self.cur_size {
+            self.cur_size = match self.sizes.pop_front() {
+                Some(size) => size,
+                None => return df_execution_err!("window partition is not prepared"),
+            };
+            self.cur_row_idx = 0;
+        }
+        Ok((self.cur_row_idx, self.cur_size))
+    }
+
+    pub fn advance(&mut self, num_rows: usize) {
+        self.cur_row_idx += num_rows;
+    }
+}
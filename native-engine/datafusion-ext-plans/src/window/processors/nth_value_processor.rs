@@ -0,0 +1,107 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use arrow::{
+    array::{new_empty_array, Array, ArrayRef},
+    record_batch::RecordBatch,
+};
+use datafusion::{
+    common::{Result, ScalarValue},
+    physical_expr::PhysicalExprRef,
+};
+use datafusion_ext_commons::slim_bytes::SlimBytes;
+
+use crate::window::{window_context::WindowContext, WindowFunctionProcessor};
+
+/// spark's nth_value(input, n [, ignoreNulls]) over the running frame (rows
+/// between unbounded preceding and current row): returns the n-th (1-based)
+/// value of the frame, or null if the frame has fewer than n rows (non-null
+/// rows if ignoreNulls is set).
+pub struct NthValueProcessor {
+    cur_partition: SlimBytes,
+    child: PhysicalExprRef,
+    n: usize,
+    ignore_nulls: bool,
+    num_counted_rows: usize,
+    nth_value: Option<ScalarValue>,
+}
+
+impl NthValueProcessor {
+    pub fn new(child: PhysicalExprRef, n: usize, ignore_nulls: bool) -> Self {
+        Self {
+            cur_partition: Default::default(),
+            child,
+            n,
+            ignore_nulls,
+            num_counted_rows: 0,
+            nth_value: None,
+        }
+    }
+
+    fn process_rows(
+        &mut self,
+        context: &WindowContext,
+        batch: &RecordBatch,
+        has_partition: bool,
+    ) -> Result<ArrayRef> {
+        let values = self
+            .child
+            .evaluate(batch)
+            .and_then(|v| v.into_array(batch.num_rows()))?;
+        if batch.num_rows() == 0 {
+            return Ok(new_empty_array(values.data_type()));
+        }
+        let partition_rows = if has_partition {
+            Some(context.get_partition_rows(batch)?)
+        } else {
+            None
+        };
+        let null_value = ScalarValue::try_from(values.data_type())?;
+        let mut output = Vec::with_capacity(batch.num_rows());
+
+        for row_idx in 0..batch.num_rows() {
+            if let Some(partition_rows) = &partition_rows {
+                let partition_row = partition_rows.row(row_idx);
+                if partition_row.as_ref() != self.cur_partition.as_ref() {
+                    self.cur_partition = partition_row.as_ref().into();
+                    self.num_counted_rows = 0;
+                    self.nth_value = None;
+                }
+            }
+
+            if self.nth_value.is_none() && (!self.ignore_nulls || values.is_valid(row_idx)) {
+                self.num_counted_rows += 1;
+                if self.num_counted_rows == self.n {
+                    self.nth_value = Some(ScalarValue::try_from_array(&values, row_idx)?);
+                }
+            }
+            output.push(self.nth_value.clone().unwrap_or_else(|| null_value.clone()));
+        }
+        ScalarValue::iter_to_array(output)
+    }
+}
+
+impl WindowFunctionProcessor for NthValueProcessor {
+    fn process_batch(&mut self, context: &WindowContext, batch: &RecordBatch) -> Result<ArrayRef> {
+        self.process_rows(context, batch, context.has_partition())
+    }
+
+    fn process_batch_without_partitions(
+        &mut self,
+        context: &WindowContext,
+        batch: &RecordBatch,
+    ) -> Result<ArrayRef> {
+        self.process_rows(context, batch, false)
+    }
+}
@@ -0,0 +1,86 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+
+use arrow::{
+    array::{ArrayRef, Int32Builder},
+    record_batch::RecordBatch,
+};
+use datafusion::common::Result;
+
+use crate::window::{
+    processors::PartitionSizes, window_context::WindowContext, WindowFunctionProcessor,
+};
+
+/// spark's ntile(n): divides rows of each partition into n buckets, the
+/// first (num_rows % n) buckets have one more row than the others.
+///
+/// partition sizes are collected in prepare_batch(), so rows of a partition
+/// can be processed across batches.
+pub struct NtileProcessor {
+    num_buckets: i32,
+    partition_sizes: PartitionSizes,
+}
+
+impl NtileProcessor {
+    pub fn new(num_buckets: i32) -> Self {
+        Self {
+            num_buckets,
+            partition_sizes: PartitionSizes::default(),
+        }
+    }
+
+    fn bucket(&self, row_idx: usize, num_rows: usize) -> i32 {
+        let num_buckets = self.num_buckets as usize;
+        let bucket_size = num_rows / num_buckets;
+        let num_padded_buckets = num_rows % num_buckets;
+        let num_padded_rows = num_padded_buckets * (bucket_size + 1);
+
+        let bucket = if row_idx < num_padded_rows {
+            row_idx / (bucket_size + 1)
+        } else {
+            num_padded_buckets + (row_idx - num_padded_rows) / bucket_size
+        };
+        bucket as i32 + 1
+    }
+
+    fn process(&mut self, batch: &RecordBatch) -> Result<ArrayRef> {
+        let mut builder = Int32Builder::with_capacity(batch.num_rows());
+        for _ in 0..batch.num_rows() {
+            let (row_idx, num_rows) = self.partition_sizes.cur_position()?;
+            builder.append_value(self.bucket(row_idx, num_rows));
+            self.partition_sizes.advance(1);
+        }
+        Ok(Arc::new(builder.finish()))
+    }
+}
+
+impl WindowFunctionProcessor for NtileProcessor {
+    fn prepare_batch(&mut self, context: &WindowContext, batch: &RecordBatch) -> Result<()> {
+        self.partition_sizes.prepare_batch(context, batch)
+    }
+
+    fn process_batch(&mut self, _: &WindowContext, batch: &RecordBatch) -> Result<ArrayRef> {
+        self.process(batch)
+    }
+
+    fn process_batch_without_partitions(
+        &mut self,
+        _: &WindowContext,
+        batch: &RecordBatch,
+    ) -> Result<ArrayRef> {
+        self.process(batch)
+    }
+}
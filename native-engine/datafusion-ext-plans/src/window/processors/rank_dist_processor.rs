@@ -0,0 +1,171 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{collections::VecDeque, sync::Arc};
+
+use arrow::{
+    array::{ArrayRef, Float64Builder},
+    record_batch::RecordBatch,
+};
+use datafusion::common::Result;
+use datafusion_ext_commons::{df_execution_err, slim_bytes::SlimBytes};
+
+use crate::window::{
+    processors::PartitionSizes, window_context::WindowContext, WindowFunctionProcessor,
+};
+
+/// spark's percent_rank() and cume_dist():
+///  percent_rank = (rank - 1) / (num_rows - 1), or 0 for single-row partitions
+///  cume_dist = (num rows ordered before or equal to current row) / num_rows
+///
+/// partition sizes and peers spanning batches are collected in
+/// prepare_batch(), so rows of a partition can be processed across batches.
+pub struct RankDistProcessor {
+    is_cume_dist: bool,
+    partition_sizes: PartitionSizes,
+
+    // for each prepared batch, number of rows in the following batches which
+    // are peers of its last row
+    tail_peers: VecDeque<usize>,
+    num_open_tail_peers: usize,
+    last_prepared_keys: Option<(SlimBytes, SlimBytes)>,
+
+    // range of current peers in the current partition
+    cur_peers_start: usize,
+    cur_peers_end: usize,
+}
+
+impl RankDistProcessor {
+    pub fn new(is_cume_dist: bool) -> Self {
+        Self {
+            is_cume_dist,
+            partition_sizes: PartitionSizes::default(),
+            tail_peers: VecDeque::new(),
+            num_open_tail_peers: 0,
+            last_prepared_keys: None,
+            cur_peers_start: 0,
+            cur_peers_end: 0,
+        }
+    }
+
+    fn process(&mut self, context: &WindowContext, batch: &RecordBatch) -> Result<ArrayRef> {
+        let num_rows = batch.num_rows();
+        let mut builder = Float64Builder::with_capacity(num_rows);
+        if num_rows == 0 {
+            return Ok(Arc::new(builder.finish()));
+        }
+        let order_rows = context.get_order_rows(batch)?;
+        let tail_peers = match self.tail_peers.pop_front() {
+            Some(tail_peers) => tail_peers,
+            None => return df_execution_err!("window batch is not prepared"),
+        };
+
+        let mut row_idx = 0;
+        while row_idx < num_rows {
+            let (partition_row_idx, partition_size) = self.partition_sizes.cur_position()?;
+
+            // find end of the new peers in current partition, peers at the end
+            // of batch may continue in the following batches
+            if partition_row_idx == 0 || partition_row_idx >= self.cur_peers_end {
+                let end = num_rows.min(row_idx + partition_size - partition_row_idx);
+                let peers_end = (row_idx + 1..end)
+                    .find(|&i| order_rows.row(i) != order_rows.row(row_idx))
+                    .unwrap_or(end);
+                let mut num_peers = peers_end - row_idx;
+                if peers_end == num_rows {
+                    num_peers += tail_peers;
+                }
+                self.cur_peers_start = partition_row_idx;
+                self.cur_peers_end = partition_row_idx + num_peers;
+            }
+
+            let value = if self.is_cume_dist {
+                self.cur_peers_end as f64 / partition_size as f64
+            } else if partition_size > 1 {
+                self.cur_peers_start as f64 / (partition_size - 1) as f64
+            } else {
+                0.0
+            };
+            let num_values = (self.cur_peers_end - partition_row_idx).min(num_rows - row_idx);
+            builder.append_slice(&vec![value; num_values]);
+            self.partition_sizes.advance(num_values);
+            row_idx += num_values;
+        }
+        Ok(Arc::new(builder.finish()))
+    }
+}
+
+impl WindowFunctionProcessor for RankDistProcessor {
+    fn prepare_batch(&mut self, context: &WindowContext, batch: &RecordBatch) -> Result<()> {
+        let num_rows = batch.num_rows();
+        if num_rows == 0 {
+            return Ok(());
+        }
+        self.partition_sizes.prepare_batch(context, batch)?;
+
+        let partition_rows = context
+            .has_partition()
+            .then(|| context.get_partition_rows(batch))
+            .transpose()?;
+        let order_rows = context.get_order_rows(batch)?;
+
+        // leading peers of the last prepared row are added to tail peers of
+        // all previous batches ending with the same peers
+        let num_leading_peers = match &self.last_prepared_keys {
+            Some((last_partition_key, last_order_key)) => (0..num_rows)
+                .take_while(|&row_idx| {
+                    let same_partition = match &partition_rows {
+                        Some(partition_rows) => {
+                            partition_rows.row(row_idx).as_ref() == last_partition_key.as_ref()
+                        }
+                        None => true,
+                    };
+                    same_partition && order_rows.row(row_idx).as_ref() == last_order_key.as_ref()
+                })
+                .count(),
+            None => 0,
+        };
+        if num_leading_peers > 0 {
+            self.tail_peers
+                .iter_mut()
+                .rev()
+                .take(self.num_open_tail_peers)
+                .for_each(|tail_peers| *tail_peers += num_leading_peers);
+        }
+        if num_leading_peers < num_rows {
+            self.num_open_tail_peers = 0;
+        }
+        self.tail_peers.push_back(0);
+        self.num_open_tail_peers += 1;
+        let last_partition_key = match &partition_rows {
+            Some(partition_rows) => partition_rows.row(num_rows - 1).as_ref().into(),
+            None => SlimBytes::default(),
+        };
+        let last_order_key = order_rows.row(num_rows - 1).as_ref().into();
+        self.last_prepared_keys = Some((last_partition_key, last_order_key));
+        Ok(())
+    }
+
+    fn process_batch(&mut self, context: &WindowContext, batch: &RecordBatch) -> Result<ArrayRef> {
+        self.process(context, batch)
+    }
+
+    fn process_batch_without_partitions(
+        &mut self,
+        context: &WindowContext,
+        batch: &RecordBatch,
+    ) -> Result<ArrayRef> {
+        self.process(context, batch)
+    }
+}
@@ -12,7 +12,12 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::{any::Any, fmt::Formatter, sync::Arc};
+use std::{
+    any::Any,
+    fmt::Formatter,
+    io::{Cursor, Write},
+    sync::{Arc, Weak},
+};
 
 use arrow::{
     array::{Array, ArrayRef},
@@ -20,6 +25,7 @@ use arrow::{
     error::ArrowError,
     record_batch::{RecordBatch, RecordBatchOptions},
 };
+use async_trait::async_trait;
 use datafusion::{
     common::{Result, Statistics},
     execution::context::TaskContext,
@@ -31,11 +37,22 @@ use datafusion::{
         SendableRecordBatchStream,
     },
 };
-use datafusion_ext_commons::{cast::cast, streams::coalesce_stream::CoalesceInput};
+use datafusion_ext_commons::{
+    array_size::ArraySize,
+    cast::cast,
+    io::{read_one_batch, write_one_batch},
+    streams::coalesce_stream::CoalesceInput,
+};
 use futures::{stream::once, StreamExt, TryFutureExt, TryStreamExt};
+use parking_lot::Mutex;
 
 use crate::{
-    common::output::TaskOutputter,
+    common::output::{TaskOutputter, WrappedRecordBatchSender},
+    memmgr::{
+        metrics::SpillMetrics,
+        spill::{try_new_spill, Spill},
+        MemConsumer, MemConsumerInfo, MemManager,
+    },
     window::{window_context::WindowContext, WindowExpr, WindowFunctionProcessor},
 };
 
@@ -118,10 +135,29 @@ impl ExecutionPlan for WindowExec {
             &BaselineMetrics::new(&self.metrics, partition),
         )?;
 
+        // functions like ntile() need all rows of a partition to be processed
+        // together, in that case batches are staged until partitions are complete
+        let requires_full_partitions = self
+            .context
+            .window_exprs
+            .iter()
+            .any(|expr| expr.requires_full_partitions());
+        let staging = requires_full_partitions.then(|| {
+            let staging = Arc::new(WindowStaging {
+                name: format!("WindowStaging[partition={}]", partition),
+                mem_consumer_info: None,
+                staged: Default::default(),
+                spill_metrics: SpillMetrics::new(&self.metrics, partition),
+            });
+            MemManager::register_consumer(staging.clone(), true);
+            staging
+        });
+
         let stream = execute_window(
             coalesced,
             context.clone(),
             self.context.clone(),
+            staging,
             BaselineMetrics::new(&self.metrics, partition),
         )
         .map_err(|e| ArrowError::ExternalError(Box::new(e)));
@@ -145,9 +181,10 @@ async fn execute_window(
     mut input: SendableRecordBatchStream,
     task_context: Arc<TaskContext>,
     context: Arc<WindowContext>,
+    staging: Option<Arc<WindowStaging>>,
     metrics: BaselineMetrics,
 ) -> Result<SendableRecordBatchStream> {
-    let mut processors: Vec<Box<dyn WindowFunctionProcessor>> = context
+    let processors: Vec<Box<dyn WindowFunctionProcessor>> = context
         .window_exprs
         .iter()
         .map(|expr: &WindowExpr| expr.create_processor(&context))
@@ -156,46 +193,227 @@ async fn execute_window(
     // start processing input batches
     let output_schema = context.output_schema.clone();
     task_context.output_with_sender("Window", output_schema, |sender| async move {
+        let mut output = WindowOutput {
+            context: context.clone(),
+            processors,
+            sender,
+            metrics,
+        };
         while let Some(batch) = input.next().await.transpose()? {
-            let elapsed_time = metrics.elapsed_compute().clone();
-            let mut timer = elapsed_time.timer();
-
-            let window_cols: Vec<ArrayRef> = processors
-                .iter_mut()
-                .map(|processor| {
-                    if context.partition_spec.is_empty() {
-                        processor.process_batch_without_partitions(&context, &batch)
-                    } else {
-                        processor.process_batch(&context, &batch)
-                    }
-                })
-                .collect::<Result<_>>()?;
-
-            let outputs: Vec<ArrayRef> = batch
-                .columns()
-                .iter()
-                .chain(&window_cols)
-                .zip(context.output_schema.fields())
-                .map(|(array, field)| {
-                    if array.data_type() != field.data_type() {
-                        return cast(&array, field.data_type());
-                    }
-                    Ok(array.clone())
-                })
-                .collect::<Result<_>>()?;
-            let output_batch = RecordBatch::try_new_with_options(
-                context.output_schema.clone(),
-                outputs,
-                &RecordBatchOptions::new().with_row_count(Some(batch.num_rows())),
-            )?;
+            let Some(staging) = &staging else {
+                output.process_and_send(batch).await?;
+                continue;
+            };
+
+            // rows before the last partition complete all staged partitions,
+            // the last partition is kept staged because its following rows
+            // may be in next batches
+            let num_rows = batch.num_rows();
+            let last_partition_start = last_partition_start(&context, &batch)?;
+            if last_partition_start > 0 {
+                let staged = staging.take(Some(batch.slice(0, last_partition_start)));
+                output.process_and_send_staged(staged).await?;
+            }
+            staging
+                .push(batch.slice(last_partition_start, num_rows - last_partition_start))
+                .await?;
+        }
 
-            metrics.record_output(output_batch.num_rows());
-            sender.send(Ok(output_batch), Some(&mut timer)).await;
+        if let Some(staging) = &staging {
+            output.process_and_send_staged(staging.take(None)).await?;
+            staging.update_mem_used(0).await?;
         }
         Ok(())
     })
 }
 
+// returns start of the last partition in the batch, or 0 if all rows are in
+// one partition
+fn last_partition_start(context: &WindowContext, batch: &RecordBatch) -> Result<usize> {
+    let num_rows = batch.num_rows();
+    if !context.has_partition() || num_rows == 0 {
+        return Ok(0);
+    }
+
+    let partition_rows = context.get_partition_rows(batch)?;
+    let last_partition = partition_rows.row(num_rows - 1);
+    Ok((0..num_rows)
+        .rev()
+        .take_while(|&row_idx| partition_rows.row(row_idx) == last_partition)
+        .last()
+        .unwrap_or_default())
+}
+
+struct WindowOutput {
+    context: Arc<WindowContext>,
+    processors: Vec<Box<dyn WindowFunctionProcessor>>,
+    sender: Arc<WrappedRecordBatchSender>,
+    metrics: BaselineMetrics,
+}
+
+impl WindowOutput {
+    async fn process_and_send(&mut self, batch: RecordBatch) -> Result<()> {
+        let elapsed_time = self.metrics.elapsed_compute().clone();
+        let mut timer = elapsed_time.timer();
+
+        let context = &self.context;
+        let window_cols: Vec<ArrayRef> = self
+            .processors
+            .iter_mut()
+            .map(|processor| {
+                if context.partition_spec.is_empty() {
+                    processor.process_batch_without_partitions(context, &batch)
+                } else {
+                    processor.process_batch(context, &batch)
+                }
+            })
+            .collect::<Result<_>>()?;
+
+        let outputs: Vec<ArrayRef> = batch
+            .columns()
+            .iter()
+            .chain(&window_cols)
+            .zip(context.output_schema.fields())
+            .map(|(array, field)| {
+                if array.data_type() != field.data_type() {
+                    return cast(&array, field.data_type());
+                }
+                Ok(array.clone())
+            })
+            .collect::<Result<_>>()?;
+        let output_batch = RecordBatch::try_new_with_options(
+            context.output_schema.clone(),
+            outputs,
+            &RecordBatchOptions::new().with_row_count(Some(batch.num_rows())),
+        )?;
+
+        self.metrics.record_output(output_batch.num_rows());
+        self.sender.send(Ok(output_batch), Some(&mut timer)).await;
+        Ok(())
+    }
+
+    // staged batches are read twice instead of being concatenated: all of
+    // them are prepared in the first pass, so functions requiring full
+    // partitions know partition sizes in the second pass, which processes
+    // them in the same order
+    async fn process_and_send_staged(&mut self, staged: StagedBatches) -> Result<()> {
+        let input_schema = self.context.input_schema.clone();
+        for spill in &staged.spills {
+            let mut reader = spill.get_compressed_reader()?;
+            while let Some(batch) = read_one_batch(&mut reader, &input_schema)? {
+                self.prepare(&batch)?;
+            }
+        }
+        for batch in &staged.batches {
+            self.prepare(batch)?;
+        }
+
+        for spill in &staged.spills {
+            let mut reader = spill.get_compressed_reader()?;
+            while let Some(batch) = read_one_batch(&mut reader, &input_schema)? {
+                self.process_and_send(batch).await?;
+            }
+        }
+        for batch in staged.batches {
+            self.process_and_send(batch).await?;
+        }
+        Ok(())
+    }
+
+    fn prepare(&mut self, batch: &RecordBatch) -> Result<()> {
+        let _timer = self.metrics.elapsed_compute().timer();
+        for processor in &mut self.processors {
+            processor.prepare_batch(&self.context, batch)?;
+        }
+        Ok(())
+    }
+}
+
+/// holds batches of incomplete partitions for functions requiring full
+/// partitions. staged batches are spilled when memory is insufficient, which
+/// happens on large partitions or when there is no partition key at all.
+struct WindowStaging {
+    name: String,
+    mem_consumer_info: Option<Weak<MemConsumerInfo>>,
+    staged: Mutex<StagedBatches>,
+    spill_metrics: SpillMetrics,
+}
+
+/// staged batches in input order: spilled batches first, then in-memory ones
+#[derive(Default)]
+struct StagedBatches {
+    spills: Vec<Box<dyn Spill>>,
+    batches: Vec<RecordBatch>,
+    mem_used: usize,
+}
+
+impl WindowStaging {
+    async fn push(&self, batch: RecordBatch) -> Result<()> {
+        let mem_used = {
+            let mut staged = self.staged.lock();
+            staged.mem_used += batch.get_array_mem_size();
+            staged.batches.push(batch);
+            staged.mem_used
+        };
+        self.update_mem_used(mem_used).await
+    }
+
+    // takes out all staged batches followed by the optional tail batch. the
+    // taken batches are still accounted until the next push
+    fn take(&self, tail: Option<RecordBatch>) -> StagedBatches {
+        let mut staged = std::mem::take(&mut *self.staged.lock());
+        staged.batches.extend(tail);
+        staged
+    }
+}
+
+#[async_trait]
+impl MemConsumer for WindowStaging {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn set_consumer_info(&mut self, consumer_info: Weak<MemConsumerInfo>) {
+        self.mem_consumer_info = Some(consumer_info);
+    }
+
+    fn get_consumer_info(&self) -> &Weak<MemConsumerInfo> {
+        self.mem_consumer_info
+            .as_ref()
+            .expect("consumer info not set")
+    }
+
+    async fn spill(&self) -> Result<usize> {
+        {
+            let mut staged = self.staged.lock();
+            if !self.is_spillable() {
+                return Ok(0);
+            }
+            if !staged.batches.is_empty() {
+                let mut spill = try_new_spill(&self.spill_metrics)?;
+                let mut writer = spill.get_compressed_writer()?;
+                for batch in std::mem::take(&mut staged.batches) {
+                    let mut buf = vec![];
+                    write_one_batch(&batch, &mut Cursor::new(&mut buf))?;
+                    writer.write_all(&buf)?;
+                }
+                drop(writer);
+                staged.spills.push(spill);
+            }
+            staged.mem_used = 0;
+        }
+        let freed = self.mem_used();
+        self.update_mem_used(0).await?;
+        Ok(freed)
+    }
+}
+
+impl Drop for WindowStaging {
+    fn drop(&mut self) {
+        MemManager::deregister_consumer(self);
+    }
+}
+
 #[cfg(test)]
 mod test {
     use std::sync::Arc;
@@ -203,15 +421,21 @@ mod test {
     use arrow::{array::*, datatypes::*, record_batch::RecordBatch};
     use datafusion::{
         assert_batches_eq,
-        physical_expr::{expressions::Column, PhysicalSortExpr},
-        physical_plan::{memory::MemoryExec, ExecutionPlan},
+        common::ScalarValue,
+        physical_expr::{
+            expressions::{Column, Literal},
+            PhysicalSortExpr,
+        },
+        physical_plan::{memory::MemoryExec, ExecutionPlan, PhysicalExpr},
         prelude::SessionContext,
     };
+    use datafusion_ext_commons::io::read_one_batch;
 
     use crate::{
         agg::AggFunction,
-        window::{WindowExpr, WindowFunction, WindowRankType},
-        window_exec::WindowExec,
+        memmgr::{metrics::SpillMetrics, MemConsumer, MemManager},
+        window::{window_context::WindowContext, WindowExpr, WindowFunction, WindowRankType},
+        window_exec::{WindowExec, WindowStaging},
     };
 
     fn build_table_i32(
@@ -358,4 +582,330 @@ mod test {
         assert_batches_eq!(expected, &batches);
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_window_ntile() -> Result<(), Box<dyn std::error::Error>> {
+        MemManager::init(10000);
+        let session_ctx = SessionContext::new();
+        let task_ctx = session_ctx.task_ctx();
+
+        // partition 1 spans two input batches
+        let batch1 = build_table_i32(
+            ("a1", &vec![1, 1, 1, 1, 1]),
+            ("b1", &vec![1, 2, 3, 4, 5]),
+            ("c1", &vec![0, 0, 0, 0, 0]),
+        );
+        let batch2 = build_table_i32(
+            ("a1", &vec![1, 1, 2, 2, 3]),
+            ("b1", &vec![6, 7, 1, 2, 1]),
+            ("c1", &vec![0, 0, 0, 0, 0]),
+        );
+        let schema = batch1.schema();
+        let input = Arc::new(MemoryExec::try_new(&[vec![batch1, batch2]], schema, None)?);
+        let ntile = |n: i32| {
+            WindowExpr::new(
+                WindowFunction::RankLike(WindowRankType::Ntile),
+                vec![Arc::new(Literal::new(ScalarValue::Int32(Some(n))))],
+                Arc::new(Field::new(format!("ntile_{n}"), DataType::Int32, false)),
+            )
+        };
+        let window = Arc::new(WindowExec::try_new(
+            input,
+            vec![
+                WindowExpr::new(
+                    WindowFunction::RankLike(WindowRankType::RowNumber),
+                    vec![],
+                    Arc::new(Field::new("b1_row_number", DataType::Int32, false)),
+                ),
+                ntile(3),
+                ntile(4),
+            ],
+            vec![Arc::new(Column::new("a1", 0))],
+            vec![PhysicalSortExpr {
+                expr: Arc::new(Column::new("b1", 1)),
+                options: Default::default(),
+            }],
+        )?);
+        let stream = window.execute(0, task_ctx.clone())?;
+        let batches = datafusion::physical_plan::common::collect(stream).await?;
+
+        // 7 rows into 3 buckets: 3, 2, 2, larger buckets come first
+        let expected = vec![
+            "+----+----+----+---------------+---------+---------+",
+            "| a1 | b1 | c1 | b1_row_number | ntile_3 | ntile_4 |",
+            "+----+----+----+---------------+---------+---------+",
+            "| 1  | 1  | 0  | 1             | 1       | 1       |",
+            "| 1  | 2  | 0  | 2             | 1       | 1       |",
+            "| 1  | 3  | 0  | 3             | 1       | 2       |",
+            "| 1  | 4  | 0  | 4             | 2       | 2       |",
+            "| 1  | 5  | 0  | 5             | 2       | 3       |",
+            "| 1  | 6  | 0  | 6             | 3       | 3       |",
+            "| 1  | 7  | 0  | 7             | 3       | 4       |",
+            "| 2  | 1  | 0  | 1             | 1       | 1       |",
+            "| 2  | 2  | 0  | 2             | 2       | 2       |",
+            "| 3  | 1  | 0  | 1             | 1       | 1       |",
+            "+----+----+----+---------------+---------+---------+",
+        ];
+        assert_batches_eq!(expected, &batches);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_window_rank_dist() -> Result<(), Box<dyn std::error::Error>> {
+        MemManager::init(10000);
+        let session_ctx = SessionContext::new();
+        let task_ctx = session_ctx.task_ctx();
+
+        let input = build_table(
+            ("a1", &vec![1, 1, 1, 1, 2, 3, 3]),
+            ("b1", &vec![1, 2, 2, 3, 4, 1, 1]),
+            ("c1", &vec![0, 0, 0, 0, 0, 0, 0]),
+        );
+        let window = Arc::new(WindowExec::try_new(
+            input,
+            vec![
+                WindowExpr::new(
+                    WindowFunction::RankLike(WindowRankType::Rank),
+                    vec![],
+                    Arc::new(Field::new("b1_rank", DataType::Int32, false)),
+                ),
+                WindowExpr::new(
+                    WindowFunction::RankLike(WindowRankType::PercentRank),
+                    vec![],
+                    Arc::new(Field::new("b1_percent_rank", DataType::Float64, false)),
+                ),
+                WindowExpr::new(
+                    WindowFunction::RankLike(WindowRankType::CumeDist),
+                    vec![],
+                    Arc::new(Field::new("b1_cume_dist", DataType::Float64, false)),
+                ),
+            ],
+            vec![Arc::new(Column::new("a1", 0))],
+            vec![PhysicalSortExpr {
+                expr: Arc::new(Column::new("b1", 1)),
+                options: Default::default(),
+            }],
+        )?);
+        let stream = window.execute(0, task_ctx.clone())?;
+        let batches = datafusion::physical_plan::common::collect(stream).await?;
+        let expected = vec![
+            "+----+----+----+---------+--------------------+--------------+",
+            "| a1 | b1 | c1 | b1_rank | b1_percent_rank    | b1_cume_dist |",
+            "+----+----+----+---------+--------------------+--------------+",
+            "| 1  | 1  | 0  | 1       | 0.0                | 0.25         |",
+            "| 1  | 2  | 0  | 2       | 0.3333333333333333 | 0.75         |",
+            "| 1  | 2  | 0  | 2       | 0.3333333333333333 | 0.75         |",
+            "| 1  | 3  | 0  | 4       | 1.0                | 1.0          |",
+            "| 2  | 4  | 0  | 1       | 0.0                | 1.0          |",
+            "| 3  | 1  | 0  | 1       | 0.0                | 1.0          |",
+            "| 3  | 1  | 0  | 1       | 0.0                | 1.0          |",
+            "+----+----+----+---------+--------------------+--------------+",
+        ];
+        assert_batches_eq!(expected, &batches);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_window_prepared_across_batches() -> Result<(), Box<dyn std::error::Error>> {
+        // without partition keys, peers of b1=2 span two batches
+        let batch1 = build_table_i32(
+            ("a1", &vec![1, 1, 1]),
+            ("b1", &vec![1, 2, 2]),
+            ("c1", &vec![0, 0, 0]),
+        );
+        let batch2 = build_table_i32(
+            ("a1", &vec![1, 1]),
+            ("b1", &vec![2, 3]),
+            ("c1", &vec![0, 0]),
+        );
+        let rank_like = |rank_type: WindowRankType, name: &str, data_type: DataType| {
+            let children: Vec<Arc<dyn PhysicalExpr>> = match rank_type {
+                WindowRankType::Ntile => vec![Arc::new(Literal::new(ScalarValue::Int32(Some(2))))],
+                _ => vec![],
+            };
+            WindowExpr::new(
+                WindowFunction::RankLike(rank_type),
+                children,
+                Arc::new(Field::new(name, data_type, false)),
+            )
+        };
+        let context = Arc::new(WindowContext::try_new(
+            batch1.schema(),
+            vec![
+                rank_like(WindowRankType::Ntile, "ntile_2", DataType::Int32),
+                rank_like(
+                    WindowRankType::PercentRank,
+                    "percent_rank",
+                    DataType::Float64,
+                ),
+                rank_like(WindowRankType::CumeDist, "cume_dist", DataType::Float64),
+            ],
+            vec![],
+            vec![PhysicalSortExpr {
+                expr: Arc::new(Column::new("b1", 1)),
+                options: Default::default(),
+            }],
+        )?);
+        let mut processors = context
+            .window_exprs
+            .iter()
+            .map(|expr| expr.create_processor(&context))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        for batch in [&batch1, &batch2] {
+            for processor in &mut processors {
+                processor.prepare_batch(&context, batch)?;
+            }
+        }
+        let mut batches = vec![];
+        for batch in [&batch1, &batch2] {
+            let mut columns = batch.columns().to_vec();
+            for processor in &mut processors {
+                columns.push(processor.process_batch_without_partitions(&context, batch)?);
+            }
+            batches.push(RecordBatch::try_new(
+                context.output_schema.clone(),
+                columns,
+            )?);
+        }
+        let expected = vec![
+            "+----+----+----+---------+--------------+-----------+",
+            "| a1 | b1 | c1 | ntile_2 | percent_rank | cume_dist |",
+            "+----+----+----+---------+--------------+-----------+",
+            "| 1  | 1  | 0  | 1       | 0.0          | 0.2       |",
+            "| 1  | 2  | 0  | 1       | 0.25         | 0.8       |",
+            "| 1  | 2  | 0  | 1       | 0.25         | 0.8       |",
+            "| 1  | 2  | 0  | 2       | 0.25         | 0.8       |",
+            "| 1  | 3  | 0  | 2       | 1.0          | 1.0       |",
+            "+----+----+----+---------+--------------+-----------+",
+        ];
+        assert_batches_eq!(expected, &batches);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_window_nth_value() -> Result<(), Box<dyn std::error::Error>> {
+        let session_ctx = SessionContext::new();
+        let task_ctx = session_ctx.task_ctx();
+
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("a1", DataType::Int32, false),
+            Field::new("b1", DataType::Int32, false),
+            Field::new("c1", DataType::Int32, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from(vec![1, 1, 1, 1, 2, 2])),
+                Arc::new(Int32Array::from(vec![1, 2, 3, 4, 1, 2])),
+                Arc::new(Int32Array::from(vec![
+                    Some(10),
+                    None,
+                    Some(30),
+                    Some(40),
+                    Some(50),
+                    Some(60),
+                ])),
+            ],
+        )?;
+        let input = Arc::new(MemoryExec::try_new(&[vec![batch]], schema, None)?);
+        let nth_value = |n: i32, ignore_nulls: bool, name: &str| {
+            WindowExpr::new(
+                WindowFunction::NthValue { ignore_nulls },
+                vec![
+                    Arc::new(Column::new("c1", 2)),
+                    Arc::new(Literal::new(ScalarValue::Int32(Some(n)))),
+                ],
+                Arc::new(Field::new(name, DataType::Int32, true)),
+            )
+        };
+        let window = Arc::new(WindowExec::try_new(
+            input,
+            vec![
+                nth_value(2, false, "nth_2"),
+                nth_value(2, true, "nth_2_ignore_nulls"),
+                nth_value(3, false, "nth_3"),
+                nth_value(5, false, "nth_5"),
+            ],
+            vec![Arc::new(Column::new("a1", 0))],
+            vec![PhysicalSortExpr {
+                expr: Arc::new(Column::new("b1", 1)),
+                options: Default::default(),
+            }],
+        )?);
+        let stream = window.execute(0, task_ctx.clone())?;
+        let batches = datafusion::physical_plan::common::collect(stream).await?;
+        let expected = vec![
+            "+----+----+----+-------+--------------------+-------+-------+",
+            "| a1 | b1 | c1 | nth_2 | nth_2_ignore_nulls | nth_3 | nth_5 |",
+            "+----+----+----+-------+--------------------+-------+-------+",
+            "| 1  | 1  | 10 |       |                    |       |       |",
+            "| 1  | 2  |    |       |                    |       |       |",
+            "| 1  | 3  | 30 |       | 30                 | 30    |       |",
+            "| 1  | 4  | 40 |       | 30                 | 30    |       |",
+            "| 2  | 1  | 50 |       |                    |       |       |",
+            "| 2  | 2  | 60 | 60    | 60                 |       |       |",
+            "+----+----+----+-------+--------------------+-------+-------+",
+        ];
+        assert_batches_eq!(expected, &batches);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_window_staging_spill() -> Result<(), Box<dyn std::error::Error>> {
+        MemManager::init(10000);
+        let batch1 = build_table_i32(
+            ("a1", &vec![1, 1, 1]),
+            ("b1", &vec![1, 2, 3]),
+            ("c1", &vec![0, 0, 0]),
+        );
+        let batch2 = build_table_i32(
+            ("a1", &vec![1, 1]),
+            ("b1", &vec![4, 5]),
+            ("c1", &vec![0, 0]),
+        );
+        let batch3 = build_table_i32(("a1", &vec![1]), ("b1", &vec![6]), ("c1", &vec![0]));
+        let schema = batch1.schema();
+        let staging = Arc::new(WindowStaging {
+            name: "WindowStaging[partition=0]".to_string(),
+            mem_consumer_info: None,
+            staged: Default::default(),
+            spill_metrics: SpillMetrics::new(&Default::default(), 0),
+        });
+        MemManager::register_consumer(staging.clone(), true);
+
+        // spilled batches are read back before the in-memory ones
+        staging.push(batch1).await?;
+        staging.spill().await?;
+        assert_eq!(staging.mem_used(), 0);
+        staging.push(batch2).await?;
+        assert!(staging.mem_used() > 0);
+        let staged = staging.take(Some(batch3));
+        assert_eq!(staged.spills.len(), 1);
+        assert_eq!(staged.batches.len(), 2);
+
+        let mut batches = vec![];
+        for spill in &staged.spills {
+            let mut reader = spill.get_compressed_reader()?;
+            while let Some(batch) = read_one_batch(&mut reader, &schema)? {
+                batches.push(batch);
+            }
+        }
+        batches.extend(staged.batches);
+
+        let expected = vec![
+            "+----+----+----+",
+            "| a1 | b1 | c1 |",
+            "+----+----+----+",
+            "| 1  | 1  | 0  |",
+            "| 1  | 2  | 0  |",
+            "| 1  | 3  | 0  |",
+            "| 1  | 4  | 0  |",
+            "| 1  | 5  | 0  |",
+            "| 1  | 6  | 0  |",
+            "+----+----+----+",
+        ];
+        assert_batches_eq!(expected, &batches);
+        Ok(())
+    }
 }
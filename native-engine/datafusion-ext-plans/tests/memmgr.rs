@@ -0,0 +1,103 @@
+// Copyright 2022 The Blaze Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// the mem manager is process-global, so these tests run in their own binary
+// to control the total memory and the set of registered consumers
+
+use std::sync::{
+    atomic::{AtomicUsize, Ordering::SeqCst},
+    Arc, Weak,
+};
+
+use async_trait::async_trait;
+use datafusion::common::Result;
+use datafusion_ext_plans::memmgr::{MemConsumer, MemConsumerInfo, MemManager};
+
+const MB: usize = 1 << 20;
+
+struct TestConsumer {
+    name: String,
+    mem_consumer_info: Option<Weak<MemConsumerInfo>>,
+    num_spills: AtomicUsize,
+}
+
+impl TestConsumer {
+    fn new(name: &str) -> Arc<Self> {
+        let consumer = Arc::new(Self {
+            name: name.to_string(),
+            mem_consumer_info: None,
+            num_spills: AtomicUsize::new(0),
+        });
+        MemManager::register_consumer(consumer.clone(), true);
+        consumer
+    }
+}
+
+#[async_trait]
+impl MemConsumer for TestConsumer {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn set_consumer_info(&mut self, consumer_info: Weak<MemConsumerInfo>) {
+        self.mem_consumer_info = Some(consumer_info);
+    }
+
+    fn get_consumer_info(&self) -> &Weak<MemConsumerInfo> {
+        self.mem_consumer_info
+            .as_ref()
+            .expect("consumer info not set")
+    }
+
+    async fn spill(&self) -> Result<usize> {
+        self.num_spills.fetch_add(1, SeqCst);
+        let freed = self.mem_used();
+        self.update_mem_used(0).await?;
+        Ok(freed)
+    }
+}
+
+impl Drop for TestConsumer {
+    fn drop(&mut self) {
+        MemManager::deregister_consumer(self);
+    }
+}
+
+#[tokio::test]
+async fn test_growing_consumer_spills_larger_consumer() -> Result<()> {
+    MemManager::init(1000 * MB);
+
+    // the large consumer grows while it is the only one
+    let large = TestConsumer::new("large");
+    large.update_mem_used(MB).await?;
+    large.update_mem_used(600 * MB).await?;
+    assert_eq!(large.num_spills.load(SeqCst), 0);
+
+    // the small consumer overflows the total memory but not its own share
+    // (500MB), so the large consumer is spilled instead of itself
+    let small = TestConsumer::new("small");
+    small.update_mem_used(MB).await?;
+    small.update_mem_used(450 * MB).await?;
+    assert_eq!(large.num_spills.load(SeqCst), 1);
+    assert_eq!(small.num_spills.load(SeqCst), 0);
+    assert_eq!(large.mem_used(), 0);
+    assert_eq!(small.mem_used(), 450 * MB);
+
+    // the small consumer now exceeds its own share and spills itself
+    small.update_mem_used(800 * MB).await?;
+    assert_eq!(large.num_spills.load(SeqCst), 1);
+    assert_eq!(small.num_spills.load(SeqCst), 1);
+    assert_eq!(MemManager::get().total_used(), 0);
+    Ok(())
+}
@@ -44,6 +44,7 @@ import org.apache.spark.sql.catalyst.expressions.NamedExpression
 import org.apache.spark.sql.catalyst.expressions.SortOrder
 import org.apache.spark.sql.catalyst.expressions.StringSplit
 import org.apache.spark.sql.catalyst.expressions.TaggingExpression
+import org.apache.spark.sql.catalyst.expressions.WindowSpecDefinition
 import org.apache.spark.sql.catalyst.expressions.aggregate.AggregateExpression
 import org.apache.spark.sql.catalyst.expressions.aggregate.AggregateFunction
 import org.apache.spark.sql.catalyst.expressions.aggregate.First
@@ -52,6 +53,7 @@ import org.apache.spark.sql.catalyst.plans.physical.BroadcastMode
 import org.apache.spark.sql.catalyst.plans.physical.Partitioning
 import org.apache.spark.sql.execution.CoalescedPartitionSpec
 import org.apache.spark.sql.execution.FileSourceScanExec
+import org.apache.spark.sql.execution.GlobalLimitExec
 import org.apache.spark.sql.execution.PartialMapperPartitionSpec
 import org.apache.spark.sql.execution.PartialReducerPartitionSpec
 import org.apache.spark.sql.execution.ShufflePartitionSpec
@@ -83,6 +85,8 @@ import org.apache.spark.sql.execution.blaze.plan.NativeParquetScanBase
 import org.apache.spark.sql.execution.blaze.plan.NativeParquetScanExec
 import org.apache.spark.sql.execution.blaze.plan.NativeProjectBase
 import org.apache.spark.sql.execution.blaze.plan.NativeRenameColumnsBase
+import org.apache.spark.sql.execution.blaze.plan.NativeSampleBase
+import org.apache.spark.sql.execution.blaze.plan.NativeSampleExec
 import org.apache.spark.sql.execution.blaze.plan.NativeShuffleExchangeBase
 import org.apache.spark.sql.execution.blaze.plan.NativeShuffleExchangeExec
 import org.apache.spark.sql.execution.blaze.plan.NativeSortBase
@@ -105,6 +109,7 @@ import org.apache.spark.sql.execution.metric.SQLMetric
 import org.apache.spark.sql.hive.execution.InsertIntoHiveTable
 import org.apache.spark.sql.types.DataType
 import org.apache.spark.sql.types.IntegerType
+import org.apache.spark.sql.types.LongType
 import org.apache.spark.sql.types.StringType
 import org.apache.spark.storage.BlockManagerId
 import org.apache.spark.storage.FileSegment
@@ -221,8 +226,11 @@ class ShimsImpl extends Shims with Logging {
       child: SparkPlan): NativeGenerateBase =
     NativeGenerateExec(generator, requiredChildOutput, outer, generatorOutput, child)
 
-  override def createNativeGlobalLimitExec(limit: Long, child: SparkPlan): NativeGlobalLimitBase =
-    NativeGlobalLimitExec(limit, child)
+  override def createNativeGlobalLimitExec(
+      limit: Long,
+      offset: Long,
+      child: SparkPlan): NativeGlobalLimitBase =
+    NativeGlobalLimitExec(limit, offset, child)
 
   override def createNativeLocalLimitExec(limit: Long, child: SparkPlan): NativeLocalLimitBase =
     NativeLocalLimitExec(limit, child)
@@ -247,6 +255,14 @@ class ShimsImpl extends Shims with Logging {
       newColumnNames: Seq[String]): NativeRenameColumnsBase =
     NativeRenameColumnsExecProvider.provide(child, newColumnNames)
 
+  override def createNativeSampleExec(
+      lowerBound: Double,
+      upperBound: Double,
+      withReplacement: Boolean,
+      seed: Long,
+      child: SparkPlan): NativeSampleBase =
+    NativeSampleExec(lowerBound, upperBound, withReplacement, seed, child)
+
   override def createNativeShuffleExchangeExec(
       outputPartitioning: Partitioning,
       child: SparkPlan): NativeShuffleExchangeBase =
@@ -365,10 +381,10 @@ class ShimsImpl extends Shims with Logging {
       tempDataFile: File,
       mapId: Long,
       partitionLengths: Array[Long],
+      checksums: Array[Long],
       dataSize: Long,
       context: TaskContext): MapStatus = {
 
-    val checksums = Array[Long]()
     shuffleBlockResolver.writeMetadataFileAndCommit(
       dep.shuffleId,
       mapId,
@@ -385,6 +401,7 @@ class ShimsImpl extends Shims with Logging {
       tempDataFile: File,
       mapId: Long,
       partitionLengths: Array[Long],
+      checksums: Array[Long],
       dataSize: Long,
       context: TaskContext): MapStatus = {
 
@@ -396,6 +413,24 @@ class ShimsImpl extends Shims with Logging {
     MapStatus.apply(SparkEnv.get.blockManager.shuffleServerId, partitionLengths, mapId)
   }
 
+  @enableIf(
+    Seq("spark320", "spark324", "spark333", "spark351").contains(
+      System.getProperty("blaze.shim")))
+  override def getShuffleChecksumAlgorithm: Option[String] = {
+    import org.apache.spark.internal.config.SHUFFLE_CHECKSUM_ALGORITHM
+    import org.apache.spark.internal.config.SHUFFLE_CHECKSUM_ENABLED
+    val conf = SparkEnv.get.conf
+    if (conf.get(SHUFFLE_CHECKSUM_ENABLED)) {
+      Some(conf.get(SHUFFLE_CHECKSUM_ALGORITHM))
+    } else {
+      None
+    }
+  }
+
+  // shuffle checksums are not supported before spark 3.2
+  @enableIf(Seq("spark303").contains(System.getProperty("blaze.shim")))
+  override def getShuffleChecksumAlgorithm: Option[String] = None
+
   override def getRssPartitionWriter(
       handle: ShuffleHandle,
       mapId: Int,
@@ -462,6 +497,14 @@ class ShimsImpl extends Shims with Logging {
           case Some(v) => return Some(v)
           case None =>
         }
+        convertUnixTimestampFunctions(e, isPruningExpr, fallback) match {
+          case Some(v) => return Some(v)
+          case None =>
+        }
+        convertWidthBucket(e, isPruningExpr, fallback) match {
+          case Some(v) => return Some(v)
+          case None =>
+        }
         None
     }
   }
@@ -470,6 +513,47 @@ class ShimsImpl extends Shims with Logging {
     expr.asInstanceOf[Like].escapeChar
   }
 
+  @enableIf(Seq("spark351").contains(System.getProperty("blaze.shim")))
+  override def getGlobalLimitOffset(exec: GlobalLimitExec): Long = exec.offset.toLong
+
+  @enableIf(
+    Seq("spark303", "spark320", "spark324", "spark333").contains(
+      System.getProperty("blaze.shim")))
+  override def getGlobalLimitOffset(exec: GlobalLimitExec): Long = 0
+
+  @enableIf(
+    Seq("spark320", "spark324", "spark333", "spark351").contains(
+      System.getProperty("blaze.shim")))
+  override def convertMoreWindowFunction(
+      function: Expression,
+      spec: WindowSpecDefinition,
+      windowExprBuilder: pb.WindowExprNode.Builder): Boolean = {
+    import org.apache.spark.sql.catalyst.expressions.NthValue
+    import org.apache.spark.sql.catalyst.expressions.RowNumber
+    function match {
+      case e: NthValue =>
+        assert(
+          spec.frameSpecification == RowNumber().frame, // only supports RowFrame(Unbounded, CurrentRow)
+          s"window frame not supported: ${spec.frameSpecification}")
+        windowExprBuilder.setFuncType(pb.WindowFunctionType.Window)
+        windowExprBuilder.setWindowFunc(if (e.ignoreNulls) {
+          pb.WindowFunction.NTH_VALUE_IGNORES_NULL
+        } else {
+          pb.WindowFunction.NTH_VALUE
+        })
+        windowExprBuilder.addChildren(NativeConverters.convertExpr(e.input))
+        windowExprBuilder.addChildren(NativeConverters.convertExpr(e.offset))
+        true
+      case _ => false
+    }
+  }
+
+  @enableIf(Seq("spark303").contains(System.getProperty("blaze.shim")))
+  override def convertMoreWindowFunction(
+      function: Expression,
+      spec: WindowSpecDefinition,
+      windowExprBuilder: pb.WindowExprNode.Builder): Boolean = false
+
   override def convertMoreAggregateExpr(e: AggregateExpression): Option[pb.PhysicalExprNode] = {
     assert(getAggregateExpressionFilter(e).isEmpty)
 
@@ -486,7 +570,7 @@ class ShimsImpl extends Shims with Logging {
         Some(pb.PhysicalExprNode.newBuilder().setAggExpr(aggExpr).build())
 
       case agg =>
-        convertBloomFilterAgg(agg) match {
+        convertBloomFilterAgg(agg).orElse(convertTryAgg(agg)) match {
           case Some(aggExpr) =>
             return Some(pb.PhysicalExprNode.newBuilder().setAggExpr(aggExpr).build())
           case None =>
@@ -762,6 +846,34 @@ class ShimsImpl extends Shims with Logging {
   @enableIf(Seq("spark303", "spark320", "spark324").contains(System.getProperty("blaze.shim")))
   private def convertBloomFilterAgg(agg: AggregateFunction): Option[pb.PhysicalAggExprNode] = None
 
+  @enableIf(Seq("spark333", "spark351").contains(System.getProperty("blaze.shim")))
+  private def convertTryAgg(agg: AggregateFunction): Option[pb.PhysicalAggExprNode] = {
+    import org.apache.spark.sql.catalyst.expressions.aggregate.TryAverage
+    import org.apache.spark.sql.catalyst.expressions.aggregate.TrySum
+    import org.apache.spark.sql.types.DecimalType
+    import org.apache.spark.sql.types.IntegralType
+
+    // only integral and decimal inputs can overflow natively
+    def isSupported(child: Expression): Boolean = child.dataType match {
+      case _: IntegralType | _: DecimalType => true
+      case _ => false
+    }
+    val aggFunction = agg match {
+      case e: TrySum if isSupported(e.child) => pb.AggFunction.TRY_SUM
+      case e: TryAverage if isSupported(e.child) => pb.AggFunction.TRY_AVG
+      case _ => return None
+    }
+    Some(
+      pb.PhysicalAggExprNode
+        .newBuilder()
+        .setAggFunction(aggFunction)
+        .addChildren(NativeConverters.convertExpr(agg.children.head))
+        .build())
+  }
+
+  @enableIf(Seq("spark303", "spark320", "spark324").contains(System.getProperty("blaze.shim")))
+  private def convertTryAgg(agg: AggregateFunction): Option[pb.PhysicalAggExprNode] = None
+
   @enableIf(Seq("spark333", "spark351").contains(System.getProperty("blaze.shim")))
   private def convertBloomFilterMightContain(
       e: Expression,
@@ -789,6 +901,58 @@ class ShimsImpl extends Shims with Logging {
       isPruningExpr: Boolean,
       fallback: Expression => pb.PhysicalExprNode): Option[pb.PhysicalExprNode] = None
 
+  @enableIf(
+    Seq("spark320", "spark324", "spark333", "spark351").contains(
+      System.getProperty("blaze.shim")))
+  private def convertUnixTimestampFunctions(
+      e: Expression,
+      isPruningExpr: Boolean,
+      fallback: Expression => pb.PhysicalExprNode): Option[pb.PhysicalExprNode] = {
+    import org.apache.spark.sql.catalyst.expressions.{UnixMicros, UnixMillis, UnixSeconds}
+    val name = e match {
+      case _: UnixSeconds => "UnixSeconds"
+      case _: UnixMillis => "UnixMillis"
+      case _: UnixMicros => "UnixMicros"
+      case _ => return None
+    }
+    Some(
+      NativeConverters
+        .buildExtScalarFunctionNode(name, e.children, e.dataType, isPruningExpr, fallback))
+  }
+
+  @enableIf(Seq("spark303").contains(System.getProperty("blaze.shim")))
+  private def convertUnixTimestampFunctions(
+      e: Expression,
+      isPruningExpr: Boolean,
+      fallback: Expression => pb.PhysicalExprNode): Option[pb.PhysicalExprNode] = None
+
+  @enableIf(
+    Seq("spark320", "spark324", "spark333", "spark351").contains(
+      System.getProperty("blaze.shim")))
+  private def convertWidthBucket(
+      e: Expression,
+      isPruningExpr: Boolean,
+      fallback: Expression => pb.PhysicalExprNode): Option[pb.PhysicalExprNode] = {
+    import org.apache.spark.sql.catalyst.expressions.WidthBucket
+    e match {
+      case e: WidthBucket =>
+        Some(
+          NativeConverters.buildExtScalarFunctionNode(
+            "WidthBucket",
+            e.children,
+            LongType,
+            isPruningExpr,
+            fallback))
+      case _ => None
+    }
+  }
+
+  @enableIf(Seq("spark303").contains(System.getProperty("blaze.shim")))
+  private def convertWidthBucket(
+      e: Expression,
+      isPruningExpr: Boolean,
+      fallback: Expression => pb.PhysicalExprNode): Option[pb.PhysicalExprNode] = None
+
 }
 
 case class ForceNativeExecutionWrapper(override val child: SparkPlan)
@@ -19,8 +19,8 @@ import org.apache.spark.sql.execution.SparkPlan
 
 import com.thoughtworks.enableIf
 
-case class NativeGlobalLimitExec(limit: Long, override val child: SparkPlan)
-    extends NativeGlobalLimitBase(limit, child) {
+case class NativeGlobalLimitExec(limit: Long, offset: Long, override val child: SparkPlan)
+    extends NativeGlobalLimitBase(limit, offset, child) {
 
   @enableIf(
     Seq("spark320", "spark324", "spark333", "spark351").contains(
@@ -30,6 +30,7 @@ import org.apache.spark.sql.catalyst.InternalRow
 import org.apache.spark.sql.catalyst.plans.logical.Statistics
 import org.apache.spark.sql.catalyst.plans.physical._
 import org.apache.spark.sql.execution._
+import org.apache.spark.sql.execution.blaze.shuffle.BlazeRssShuffleWriterBase
 import org.apache.spark.sql.execution.blaze.shuffle.BlazeShuffleWriter
 import org.apache.spark.sql.execution.metric.SQLMetric
 import org.apache.spark.sql.execution.metric.SQLMetrics
@@ -133,18 +134,74 @@ case class NativeShuffleExchangeExec(
           mapId,
           context,
           createMetricsReporter(context))
-        writer
-          .asInstanceOf[BlazeShuffleWriter[_, _]]
-          .nativeShuffleWrite(
-            rdd.asInstanceOf[MapPartitionsRDD[_, _]].prev.asInstanceOf[NativeRDD],
-            dep,
-            mapId.toInt,
-            context,
-            partition)
+        val nativeShuffleRDD =
+          rdd.asInstanceOf[MapPartitionsRDD[_, _]].prev.asInstanceOf[NativeRDD]
+        writer match {
+          case rssWriter: BlazeRssShuffleWriterBase[_, _] =>
+            // partition data is streamed to the remote shuffle service. the
+            // map task must be stopped on the service if writing fails
+            try {
+              rssWriter.nativeRssShuffleWrite(
+                nativeShuffleRDD,
+                dep,
+                mapId.toInt,
+                context,
+                partition,
+                numPartitions)
+            } catch {
+              case e: Throwable =>
+                try {
+                  rssWriter.stop(success = false)
+                } catch {
+                  case stopError: Exception =>
+                    logWarning("error stopping rss shuffle writer", stopError)
+                }
+                throw e
+            }
+            rssWriter.stop(success = true).get
+          case _ =>
+            val mapStatus = writer
+              .asInstanceOf[BlazeShuffleWriter[_, _]]
+              .nativeShuffleWrite(nativeShuffleRDD, dep, mapId.toInt, context, partition)
+            initiateBlockPush(dep, mapId, writer, partition)
+            mapStatus
+        }
       }
     }
   }
 
+  // push-based shuffle: the committed data file consists of self-contained blocks
+  // in each partition, so it can be pushed to remote mergers in the same way as
+  // spark's ShuffleWriteProcessor does
+  @enableIf(
+    Seq("spark320", "spark324", "spark333", "spark351").contains(
+      System.getProperty("blaze.shim")))
+  private def initiateBlockPush(
+      dep: ShuffleDependency[_, _, _],
+      mapId: Long,
+      writer: ShuffleWriter[_, _],
+      partition: Partition): Unit = {
+    import org.apache.spark.shuffle.IndexShuffleBlockResolver
+    import org.apache.spark.shuffle.ShuffleBlockPusher
+
+    if (dep.shuffleMergeEnabled && dep.getMergerLocs.nonEmpty && !dep.shuffleMergeFinalized) {
+      SparkEnv.get.shuffleManager.shuffleBlockResolver match {
+        case resolver: IndexShuffleBlockResolver =>
+          val dataFile = resolver.getDataFile(dep.shuffleId, mapId)
+          new ShuffleBlockPusher(SparkEnv.get.conf)
+            .initiateBlockPush(dataFile, writer.getPartitionLengths(), dep, partition.index)
+        case _ =>
+      }
+    }
+  }
+
+  @enableIf(Seq("spark303").contains(System.getProperty("blaze.shim")))
+  private def initiateBlockPush(
+      dep: ShuffleDependency[_, _, _],
+      mapId: Long,
+      writer: ShuffleWriter[_, _],
+      partition: Partition): Unit = {}
+
   @enableIf(Seq("spark351").contains(System.getProperty("blaze.shim")))
   override def advisoryPartitionSize: Option[Long] = None
 
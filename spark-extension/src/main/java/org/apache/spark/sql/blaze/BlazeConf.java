@@ -69,8 +69,49 @@ public enum BlazeConf {
     // parqeut enable bloom filter
     PARQUET_ENABLE_BLOOM_FILTER("spark.blaze.parquet.enable.bloomFilter", false),
 
-    // spark io compression codec
-    SPARK_IO_COMPRESSION_CODEC("spark.io.compression.codec", "lz4");
+    // spark io compression codec, lz4/snappy/zstd are supported
+    SPARK_IO_COMPRESSION_CODEC("spark.io.compression.codec", "lz4"),
+
+    // compression level of zstd codec
+    SPARK_IO_COMPRESSION_ZSTD_LEVEL("spark.io.compression.zstd.level", 1),
+
+    // whether to compress shuffle output
+    SPARK_SHUFFLE_COMPRESS("spark.shuffle.compress", true),
+
+    // spill a single shuffle partition immediately once its buffered data exceeds this size,
+    // bounding memory usage of skewed partitions
+    SHUFFLE_SKEWED_PARTITION_SPILL_THRESHOLD("spark.blaze.shuffle.skewedPartitionSpillThreshold", 268435456),
+
+    // enable extra metrics of output partition size histogram in shuffle writer
+    SHUFFLE_PARTITION_SIZE_HISTOGRAM_ENABLE("spark.blaze.shuffle.partitionSizeHistogram.enable", false),
+
+    // validate every n-th row's shuffle partition id against a reference implementation of spark's
+    // murmur3 hash and fail the task on mismatch, 0 means disabled. expensive, for testing only
+    SHUFFLE_HASH_VALIDATION_SAMPLE_INTERVAL("spark.blaze.shuffle.hashValidation.sampleInterval", 0),
+
+    // hash shuffles with fewer output partitions than this are written to one file per partition and
+    // concatenated, bypassing in-memory sorting and spilling (like spark's bypassMergeThreshold)
+    SHUFFLE_BYPASS_MERGE_THRESHOLD("spark.blaze.shuffle.bypassMergeThreshold", 200),
+
+    // max buffered bytes of each output partition in bypass-merge shuffles before the buffer is
+    // compressed and flushed, 0 to divide the default 4MB block size among output partitions
+    SHUFFLE_BYPASS_PARTITION_BUFFER_SIZE("spark.blaze.shuffle.bypassPartitionBufferSize", 0),
+
+    // merge file spills of sort shuffles by copying partition ranges between files, which uses
+    // copy_file_range() on linux and avoids copying spilled data through user-space buffers
+    SHUFFLE_MERGE_SPILLS_BY_FILE_COPY("spark.blaze.shuffle.mergeSpillsByFileCopy", false),
+
+    // compress sort/agg spills with lz4, disabling it saves cpu at the cost of larger spills,
+    // which may be preferable on fast local disks
+    SPILL_COMPRESSION_ENABLE("spark.blaze.spill.compression.enable", true),
+
+    // max number of output batches buffered between native operators, producers wait when it is
+    // reached. 0 means about 8192 rows in flight (a single batch with the default batch size)
+    OUTPUT_MAX_IN_FLIGHT_BATCHES("spark.blaze.output.maxInFlightBatches", 0),
+
+    // target memory size of output batches of native project, smaller batches reduce peak memory
+    // usage on memory-constrained executors. 0 means using the default output batch memory size
+    PROJECT_BATCH_MEM_SIZE("spark.blaze.project.batchMemSize", 0);
 
     private final String key;
     private final Object defaultValue;
@@ -21,6 +21,7 @@ import scala.collection.mutable
 import org.apache.hadoop.hive.ql.io.parquet.MapredParquetOutputFormat
 import org.apache.spark.SparkEnv
 import org.apache.spark.broadcast.Broadcast
+import org.apache.spark.internal.config
 import org.apache.spark.internal.Logging
 import org.apache.spark.sql.blaze.BlazeConvertStrategy.childOrderingRequiredTag
 import org.apache.spark.sql.blaze.BlazeConvertStrategy.convertibleTag
@@ -41,11 +42,13 @@ import org.apache.spark.sql.catalyst.expressions.Ascending
 import org.apache.spark.sql.catalyst.expressions.SortOrder
 import org.apache.spark.sql.catalyst.plans.physical.HashPartitioning
 import org.apache.spark.sql.catalyst.plans.physical.Partitioning
+import org.apache.spark.sql.catalyst.plans.physical.RoundRobinPartitioning
 import org.apache.spark.sql.execution.FileSourceScanExec
 import org.apache.spark.sql.execution.FilterExec
 import org.apache.spark.sql.execution.GlobalLimitExec
 import org.apache.spark.sql.execution.LocalLimitExec
 import org.apache.spark.sql.execution.ProjectExec
+import org.apache.spark.sql.execution.SampleExec
 import org.apache.spark.sql.execution.SortExec
 import org.apache.spark.sql.execution.SparkPlan
 import org.apache.spark.sql.execution.TakeOrderedAndProjectExec
@@ -111,6 +114,8 @@ object BlazeConverters extends Logging {
     SparkEnv.get.conf.getBoolean("spark.blaze.enable.generate", defaultValue = true)
   val enableLocalTableScan: Boolean =
     SparkEnv.get.conf.getBoolean("spark.blaze.enable.local.table.scan", defaultValue = true)
+  val enableSample: Boolean =
+    SparkEnv.get.conf.getBoolean("spark.blaze.enable.sample", defaultValue = true)
   val enableDataWriting: Boolean =
     SparkEnv.get.conf.getBoolean("spark.blaze.enable.data.writing", defaultValue = false)
 
@@ -168,6 +173,9 @@ object BlazeConverters extends Logging {
         tryConvert(e, convertGlobalLimitExec)
       case e: TakeOrderedAndProjectExec if enableTakeOrderedAndProject =>
         tryConvert(e, convertTakeOrderedAndProjectExec)
+      // native poisson sampling does not produce the same rows as spark
+      case e: SampleExec if enableSample && !e.withReplacement => // sample
+        tryConvert(e, convertSampleExec)
 
       case e: HashAggregateExec if enableAggr => // hash aggregate
         val convertedAgg = tryConvert(e, convertHashAggregateExec)
@@ -247,17 +255,31 @@ object BlazeConverters extends Logging {
     }
   }
 
+  // the native shuffle writer has no encryption layer, neither for its data
+  // files nor for its spills, so spark's shuffle is used when
+  // spark.io.encryption.enabled is on, leaving shuffle data encrypted at rest
+  private def assertNativeShuffleUnencrypted(): Unit = {
+    assert(
+      !SparkEnv.get.conf.get(config.IO_ENCRYPTION_ENABLED),
+      "native shuffle with spark.io.encryption.enabled=true is not supported")
+  }
+
   def convertShuffleExchangeExec(exec: ShuffleExchangeExec): SparkPlan = {
     val (outputPartitioning, child) = (exec.outputPartitioning, exec.child)
     logDebug(s"Converting ShuffleExchangeExec: ${Shims.get.simpleStringWithNodeId(exec)}")
 
     assert(
-      exec.outputPartitioning.numPartitions == 1 || exec.outputPartitioning
-        .isInstanceOf[HashPartitioning],
+      exec.outputPartitioning.numPartitions == 1 ||
+        exec.outputPartitioning.isInstanceOf[HashPartitioning] ||
+        exec.outputPartitioning.isInstanceOf[RoundRobinPartitioning],
       s"partitioning not supported: ${exec.outputPartitioning}")
 
+    assertNativeShuffleUnencrypted()
+
     val convertedChild = outputPartitioning match {
-      case p if p.isInstanceOf[HashPartitioning] || p.numPartitions == 1 =>
+      case p
+          if p.isInstanceOf[HashPartitioning] || p.isInstanceOf[RoundRobinPartitioning] ||
+            p.numPartitions == 1 =>
         convertToNative(child)
       case _ => child
     }
@@ -493,11 +515,30 @@ object BlazeConverters extends Logging {
 
   def convertGlobalLimitExec(exec: GlobalLimitExec): SparkPlan = {
     logDebug(s"Converting GlobalLimitExec: ${Shims.get.simpleStringWithNodeId(exec)}")
-    Shims.get.createNativeGlobalLimitExec(exec.limit.toLong, exec.child)
+    // since spark 3.4, limit = -1 means no limit (offset only). otherwise the
+    // limit already includes the offset (take(limit).drop(offset)), which is
+    // also how the native limit treats it
+    val limit = if (exec.limit >= 0) exec.limit.toLong else Long.MaxValue
+    val offset = Shims.get.getGlobalLimitOffset(exec)
+    Shims.get.createNativeGlobalLimitExec(limit, offset, exec.child)
+  }
+
+  def convertSampleExec(exec: SampleExec): SparkPlan = {
+    logDebug(s"Converting SampleExec: ${Shims.get.simpleStringWithNodeId(exec)}")
+    Shims.get.createNativeSampleExec(
+      exec.lowerBound,
+      exec.upperBound,
+      exec.withReplacement,
+      exec.seed,
+      exec.child)
   }
 
   def convertTakeOrderedAndProjectExec(exec: TakeOrderedAndProjectExec): SparkPlan = {
     logDebug(s"Converting TakeOrderedAndProjectExec: ${Shims.get.simpleStringWithNodeId(exec)}")
+
+    // top-K of multiple partitions are merged through a native shuffle
+    assertNativeShuffleUnencrypted()
+
     val nativeTakeOrdered = Shims.get.createNativeTakeOrderedExec(
       exec.limit,
       exec.sortOrder,
@@ -27,7 +27,7 @@ import com.google.protobuf.ByteString
 import org.apache.spark.SparkEnv
 import org.blaze.{protobuf => pb}
 import org.apache.spark.internal.Logging
-import org.apache.spark.sql.catalyst.expressions.{Abs, Acos, Add, Alias, And, Asin, Atan, AttributeReference, BitwiseAnd, BitwiseOr, BoundReference, CaseWhen, Cast, Ceil, CheckOverflow, Coalesce, Concat, ConcatWs, Contains, Cos, CreateArray, CreateNamedStruct, Divide, EndsWith, EqualTo, Exp, Expression, Floor, GetArrayItem, GetMapValue, GetStructField, GreaterThan, GreaterThanOrEqual, If, In, InSet, IsNotNull, IsNull, Length, LessThan, LessThanOrEqual, Like, Literal, Log, Log10, Log2, Lower, MakeDecimal, Md5, Multiply, Murmur3Hash, Not, NullIf, OctetLength, Or, Remainder, Sha2, ShiftLeft, ShiftRight, Signum, Sin, Sqrt, StartsWith, StringRepeat, StringSpace, StringTrim, StringTrimLeft, StringTrimRight, Substring, Subtract, Tan, TruncDate, Unevaluable, UnscaledValue, Upper}
+import org.apache.spark.sql.catalyst.expressions.{Abs, Acos, Add, Alias, And, ArrayContains, ArrayDistinct, Asin, Atan, AttributeReference, Base64, BitwiseAnd, BitwiseCount, BitwiseOr, BoundReference, BRound, CaseWhen, Cast, Ceil, CheckOverflow, Coalesce, Concat, ConcatWs, Contains, Cos, Crc32, CreateArray, CreateNamedStruct, Divide, EndsWith, EqualTo, Exp, Expression, Floor, FormatNumber, FromUnixTime, GetArrayItem, GetMapValue, GetStructField, GreaterThan, GreaterThanOrEqual, Hex, If, In, InSet, IsNotNull, IsNull, LastDay, Length, LessThan, Levenshtein, LessThanOrEqual, Like, Literal, Log, Log10, Log2, Lower, MakeDecimal, Md5, MonthsBetween, Multiply, Murmur3Hash, NextDay, Not, NullIf, OctetLength, Or, Overlay, Pmod, RegExpExtract, RegExpReplace, Remainder, Round, Sha2, ShiftLeft, ShiftRight, ShiftRightUnsigned, Signum, Sin, SoundEx, Sqrt, StartsWith, StringInstr, StringLocate, StringLPad, StringRepeat, StringRPad, StringSpace, StringTranslate, StringTrim, StringTrimLeft, StringTrimRight, Substring, SubstringIndex, Subtract, Tan, TruncDate, TruncTimestamp, UnBase64, Unevaluable, Unhex, UnscaledValue, Upper}
 import org.apache.spark.sql.catalyst.expressions.aggregate.AggregateExpression
 import org.apache.spark.sql.catalyst.expressions.aggregate.Average
 import org.apache.spark.sql.catalyst.expressions.aggregate.CollectList
@@ -57,6 +57,7 @@ import org.apache.spark.sql.execution.blaze.plan.Util
 import org.apache.spark.sql.execution.ScalarSubquery
 import org.apache.spark.sql.hive.blaze.HiveUDFUtil
 import org.apache.spark.sql.hive.blaze.HiveUDFUtil.getFunctionClassName
+import org.apache.spark.sql.internal.SQLConf
 import org.apache.spark.sql.types.ArrayType
 import org.apache.spark.sql.types.AtomicType
 import org.apache.spark.sql.types.BinaryType
@@ -374,7 +375,8 @@ object NativeConverters extends Logging {
               .setSerialized(ByteString.copyFrom(serialized))
               .setReturnType(convertDataType(bound.dataType))
               .setReturnNullable(bound.nullable)
-              .addAllParams(convertedChildren.keys.asJava))
+              .addAllParams(convertedChildren.keys.asJava)
+              .setDeterministic(bound.deterministic))
           .build()
     }
   }
@@ -476,6 +478,15 @@ object NativeConverters extends Logging {
               .setReturnNullable(subquery.nullable))
         }
 
+      // cast timestamp to date in the expression's timezone
+      case cast: Cast
+          if cast.child.dataType == TimestampType && cast.dataType == DateType &&
+            cast.timeZoneId.isDefined =>
+        buildExtScalarFunction(
+          "CastTimestampToDate",
+          cast.child :: Literal(cast.timeZoneId.get) :: Nil,
+          DateType)
+
       // cast
       // not performing native cast for timestamp/dates (will use UDFWrapper instead)
       case cast: Cast if !Seq(cast.dataType, cast.child.dataType).contains(TimestampType) =>
@@ -669,6 +680,32 @@ object NativeConverters extends Logging {
                   .setOp("Modulo"))
             }
         }
+      case e: Pmod =>
+        val resultType = e.dataType
+        val ansi = Literal(SQLConf.get.ansiEnabled, BooleanType)
+        (e.left.dataType, e.right.dataType) match {
+          case (DecimalType.Fixed(p1, s1), DecimalType.Fixed(p2, s2)) =>
+            // operands are casted to a type wide enough for both of them, the result
+            // always fits into the (narrower) result type
+            val scale = Math.max(s1, s2)
+            val operandType = DecimalType.bounded(Math.max(p1 - s1, p2 - s2) + scale, scale)
+            val lhsCasted = castIfNecessary(e.left, operandType)
+            val rhsCasted = castIfNecessary(e.right, operandType)
+            buildExprNode {
+              _.setCast(
+                pb.PhysicalCastNode
+                  .newBuilder()
+                  .setArrowType(convertDataType(resultType))
+                  .setExpr(buildExtScalarFunction(
+                    "Pmod",
+                    lhsCasted :: rhsCasted :: ansi :: Nil,
+                    operandType)))
+            }
+          case _ =>
+            val lhsCasted = castIfNecessary(e.left, resultType)
+            val rhsCasted = castIfNecessary(e.right, resultType)
+            buildExtScalarFunction("Pmod", lhsCasted :: rhsCasted :: ansi :: Nil, resultType)
+        }
       case e: Like =>
         assert(Shims.get.getLikeEscapeChar(e) == '\\')
         buildExprNode {
@@ -706,10 +743,14 @@ object NativeConverters extends Logging {
         buildBinaryExprNode(lhs, castIfNecessary(rhs, lhs.dataType), "BitwiseAnd")
       case BitwiseOr(lhs, rhs) =>
         buildBinaryExprNode(lhs, castIfNecessary(rhs, lhs.dataType), "BitwiseOr")
-      case ShiftLeft(lhs, rhs) =>
-        buildBinaryExprNode(lhs, castIfNecessary(rhs, lhs.dataType), "BitwiseShiftLeft")
-      case ShiftRight(lhs, rhs) =>
-        buildBinaryExprNode(lhs, castIfNecessary(rhs, lhs.dataType), "BitwiseShiftRight")
+      case e: ShiftLeft =>
+        buildExtScalarFunction("ShiftLeft", e.children, e.dataType)
+      case e: ShiftRight =>
+        buildExtScalarFunction("ShiftRight", e.children, e.dataType)
+      case e: ShiftRightUnsigned =>
+        buildExtScalarFunction("ShiftRightUnsigned", e.children, e.dataType)
+      case e: BitwiseCount =>
+        buildExtScalarFunction("BitCount", e.children, IntegerType)
 
       // builtin scalar functions
       case e: Sqrt => buildScalarFunction(pb.ScalarFunction.Sqrt, e.children, e.dataType)
@@ -742,9 +783,14 @@ object NativeConverters extends Logging {
               .build())
         }
 
-      // TODO: datafusion's round() has different behavior from spark
-      // case e @ Round(_1, Literal(n: Int, _)) if _1.dataType.isInstanceOf[FractionalType] =>
-      //   buildScalarFunction(pb.ScalarFunction.Round, Seq(_1, Literal(n.toLong)), e.dataType)
+      // datafusion's round() has different behavior from spark, use native implementation
+      case e @ (_: Round | _: BRound) if e.children(1).isInstanceOf[Literal] =>
+        val name = if (e.isInstanceOf[BRound]) "BRound" else "Round"
+        val outputDecimalArgs = e.dataType match {
+          case t: DecimalType => Literal(t.precision) :: Literal(t.scale) :: Nil
+          case _ => Nil
+        }
+        buildExtScalarFunction(name, e.children ++ outputDecimalArgs, e.dataType)
 
       case e: Signum => buildScalarFunction(pb.ScalarFunction.Signum, e.children, e.dataType)
       case e: Abs if e.dataType.isInstanceOf[FloatType] || e.dataType.isInstanceOf[DoubleType] =>
@@ -767,24 +813,45 @@ object NativeConverters extends Logging {
         buildScalarFunction(pb.ScalarFunction.Rtrim, e.srcStr +: e.trimStr.toSeq, e.dataType)
       case e @ NullIf(left, right, _) =>
         buildExtScalarFunction("NullIf", left :: right :: Nil, e.dataType)
-      case e: TruncDate =>
-        buildScalarFunction(pb.ScalarFunction.DateTrunc, e.children, e.dataType)
+      case e @ FromUnixTime(_, Literal(format, StringType), _)
+          if format != null && e.timeZoneId.isDefined &&
+            isSupportedDatetimePattern(format.toString) =>
+        buildExtScalarFunction(
+          "FromUnixTime",
+          e.sec :: e.format :: Literal(e.timeZoneId.get) :: Nil,
+          StringType)
+      case e: TruncDate if e.format.isInstanceOf[Literal] =>
+        buildExtScalarFunction("DateTrunc", e.format :: e.date :: Nil, e.dataType)
+      case e: TruncTimestamp if e.format.isInstanceOf[Literal] && e.timeZoneId.isDefined =>
+        buildExtScalarFunction(
+          "DateTrunc",
+          e.format :: e.timestamp :: Literal(e.timeZoneId.get) :: Nil,
+          e.dataType)
+      case e: MonthsBetween if e.roundOff.isInstanceOf[Literal] && e.timeZoneId.isDefined =>
+        buildExtScalarFunction(
+          "MonthsBetween",
+          e.date1 :: e.date2 :: e.roundOff :: Literal(e.timeZoneId.get) :: Nil,
+          DoubleType)
+      case e: LastDay => buildExtScalarFunction("LastDay", e.children, DateType)
+      // invalid day of week throws in ansi mode, which is not supported natively
+      case e: NextDay if !SQLConf.get.ansiEnabled =>
+        buildExtScalarFunction("NextDay", e.children, DateType)
       case Md5(_1) =>
         buildScalarFunction(pb.ScalarFunction.MD5, Seq(unpackBinaryTypeCast(_1)), StringType)
-      case Sha2(_1, Literal(224, _)) =>
-        buildScalarFunction(pb.ScalarFunction.SHA224, Seq(unpackBinaryTypeCast(_1)), StringType)
-      case Sha2(_1, Literal(0, _)) =>
-        buildScalarFunction(pb.ScalarFunction.SHA256, Seq(unpackBinaryTypeCast(_1)), StringType)
-      case Sha2(_1, Literal(256, _)) =>
-        buildScalarFunction(pb.ScalarFunction.SHA256, Seq(unpackBinaryTypeCast(_1)), StringType)
-      case Sha2(_1, Literal(384, _)) =>
-        buildScalarFunction(pb.ScalarFunction.SHA384, Seq(unpackBinaryTypeCast(_1)), StringType)
-      case Sha2(_1, Literal(512, _)) =>
-        buildScalarFunction(pb.ScalarFunction.SHA512, Seq(unpackBinaryTypeCast(_1)), StringType)
+      case Sha2(_1, bitLength: Literal) =>
+        buildExtScalarFunction("Sha2", Seq(unpackBinaryTypeCast(_1), bitLength), StringType)
+      case Crc32(_1) =>
+        buildExtScalarFunction("Crc32", Seq(unpackBinaryTypeCast(_1)), LongType)
       case Murmur3Hash(children, 42) =>
         buildExtScalarFunction("Murmur3Hash", children, IntegerType)
       case XxHash64(children, 42L) =>
         buildExtScalarFunction("XxHash64", children, LongType)
+      case e: FormatNumber if e.right.isInstanceOf[Literal] && e.right.dataType == IntegerType =>
+        buildExtScalarFunction("FormatNumber", e.children, StringType)
+      case e: Hex => buildExtScalarFunction("Hex", e.children, StringType)
+      case e: Unhex => buildExtScalarFunction("Unhex", e.children, BinaryType)
+      case e: Base64 => buildExtScalarFunction("Base64", e.children, StringType)
+      case e: UnBase64 => buildExtScalarFunction("UnBase64", e.children, BinaryType)
 
       // startswith is converted to scalar function in pruning-expr mode
       case StartsWith(expr, Literal(prefix, StringType)) if isPruningExpr =>
@@ -843,11 +910,51 @@ object NativeConverters extends Logging {
       case e: ConcatWs
           if e.children.nonEmpty
             && e.children.head.isInstanceOf[Literal]
-            && e.children.forall(c =>
-              c.dataType == StringType || c.dataType == ArrayType(StringType)) =>
+            && e.children.forall(_.dataType match {
+              case StringType | ArrayType(StringType, _) => true
+              case _ => false
+            }) =>
         buildExtScalarFunction("StringConcatWs", e.children, e.dataType)
 
-      case e: Coalesce => buildScalarFunction(pb.ScalarFunction.Coalesce, e.children, e.dataType)
+      case e @ RegExpExtract(_, Literal(regexp, StringType), _: Literal)
+          if regexp != null && isSupportedRegex(regexp.toString) =>
+        buildExtScalarFunction("RegexpExtract", e.children, StringType)
+      case e: RegExpReplace
+          if e.children.drop(3).forall(_ == Literal(1)) && (e.regexp match {
+            case Literal(regexp, StringType) => regexp != null && isSupportedRegex(regexp.toString)
+            case _ => false
+          }) =>
+        buildExtScalarFunction("RegexpReplace", e.children.take(3), StringType)
+
+      case e: SubstringIndex
+          if e.delimExpr.isInstanceOf[Literal] && e.countExpr.isInstanceOf[Literal] =>
+        buildExtScalarFunction("StringSubstringIndex", e.children, StringType)
+
+      case e: StringTranslate
+          if e.matchingExpr.isInstanceOf[Literal] && e.replaceExpr.isInstanceOf[Literal] =>
+        buildExtScalarFunction("StringTranslate", e.children, StringType)
+
+      case e: Levenshtein if e.children.drop(2).forall(_.isInstanceOf[Literal]) =>
+        buildExtScalarFunction("StringLevenshtein", e.children, IntegerType)
+
+      case e: SoundEx => buildExtScalarFunction("StringSoundex", e.children, StringType)
+      case e: StringInstr => buildExtScalarFunction("StringInstr", e.children, IntegerType)
+      case e: StringLocate => buildExtScalarFunction("StringLocate", e.children, IntegerType)
+      case e: Overlay => buildExtScalarFunction("Overlay", e.children, e.dataType)
+      case StringLPad(str, len: Literal, pad: Literal) =>
+        buildExtScalarFunction("LPad", str :: len :: pad :: Nil, StringType)
+      case StringRPad(str, len: Literal, pad: Literal) =>
+        buildExtScalarFunction("RPad", str :: len :: pad :: Nil, StringType)
+
+      case e: Coalesce =>
+        buildExprNode {
+          _.setSparkCoalesceExpr(
+            pb.SparkCoalesceExprNode
+              .newBuilder()
+              .addAllArgs(e.children
+                .map(child => convertExprWithFallback(child, isPruningExpr, fallback))
+                .asJava))
+        }
 
       case If(predicate, trueValue, falseValue) =>
         val caseWhen = CaseWhen(Seq((predicate, trueValue)), falseValue)
@@ -882,15 +989,20 @@ object NativeConverters extends Logging {
         buildExtScalarFunction("MakeDecimal", args, DecimalType(precision, scale))
 
       case e: CheckOverflow =>
-        // case CheckOverflow(_1, DecimalType(precision, scale)) =>
+        // case CheckOverflow(_1, DecimalType(precision, scale), nullOnOverflow) =>
         val precision = e.dataType.precision
         val scale = e.dataType.scale
         val args =
           e.child :: Literal
-            .apply(precision, IntegerType) :: Literal.apply(scale, IntegerType) :: Nil
+            .apply(precision, IntegerType) :: Literal.apply(scale, IntegerType) :: Literal
+            .apply(!e.nullOnOverflow, BooleanType) :: Nil
         buildExtScalarFunction("CheckOverflow", args, DecimalType(precision, scale))
 
       case e: CreateArray => buildExtScalarFunction("MakeArray", e.children, e.dataType)
+      case e: ArrayContains =>
+        buildExtScalarFunction("ArrayContains", e.children, BooleanType)
+      case e: ArrayDistinct =>
+        buildExtScalarFunction("ArrayDistinct", e.children, e.dataType)
 
       case e: CreateNamedStruct =>
         buildExprNode {
@@ -1133,6 +1245,84 @@ object NativeConverters extends Logging {
     Cast(expr, dataType)
   }
 
+  // keep in sync with convert_datetime_pattern() in spark_dates.rs
+  def isSupportedDatetimePattern(pattern: String): Boolean = {
+    var i = 0
+    while (i < pattern.length) {
+      val c = pattern.charAt(i)
+      if (c == '\'') {
+        // quoted text, two single quotes represent a single quote
+        i += 1
+        var closed = false
+        while (!closed && i < pattern.length) {
+          if (pattern.charAt(i) != '\'') {
+            i += 1
+          } else if (i + 1 < pattern.length && pattern.charAt(i + 1) == '\'') {
+            i += 2
+          } else {
+            closed = true
+            i += 1
+          }
+        }
+        if (!closed) {
+          return false
+        }
+      } else if (c.isLetter && c < 128) {
+        var count = 1
+        while (i + count < pattern.length && pattern.charAt(i + count) == c) {
+          count += 1
+        }
+        val supported = (c, count) match {
+          case ('y' | 'M' | 'E' | 'S', _) => true
+          case ('d' | 'H' | 'h' | 'm' | 's', 1 | 2) => true
+          case ('D', 1 | 3) => true
+          case ('a', 1) => true
+          case ('z' | 'Z', 1 | 2 | 3) => true
+          case _ => false
+        }
+        if (!supported) {
+          return false
+        }
+        i += count
+      } else {
+        i += 1
+      }
+    }
+    true
+  }
+
+  // java regexp syntax which cannot be translated by translate_java_regex() in
+  // regexp_cache.rs: lookarounds, atomic groups, possessive quantifiers,
+  // backreferences and some java-only escapes. may be over-conservative since
+  // character classes are not parsed.
+  def isSupportedRegex(pattern: String): Boolean = {
+    var i = 0
+    var afterQuantifier = false
+    while (i < pattern.length) {
+      val c = pattern.charAt(i)
+      if (pattern.startsWith("\\Q", i)) {
+        val end = pattern.indexOf("\\E", i + 2)
+        i = if (end < 0) pattern.length else end + 2
+        afterQuantifier = false
+      } else if (c == '\\') {
+        if (i + 1 < pattern.length && "0123456789kceGZRXhHvVN".contains(pattern.charAt(i + 1))) {
+          return false
+        }
+        i += 2
+        afterQuantifier = false
+      } else if (c == '(' && Seq("(?=", "(?!", "(?<=", "(?<!", "(?>").exists(
+          pattern.startsWith(_, i))) {
+        return false
+      } else if (c == '+' && afterQuantifier) {
+        return false
+      } else {
+        afterQuantifier = "*+?}".contains(c)
+        i += 1
+      }
+    }
+    true
+  }
+
   def unpackBinaryTypeCast(expr: Expression): Expression =
     expr match {
       case expr: Cast if expr.dataType == BinaryType => expr.child
@@ -31,6 +31,7 @@ import org.apache.spark.sql.catalyst.expressions.aggregate.AggregateExpression
 import org.apache.spark.sql.catalyst.plans.physical.BroadcastMode
 import org.apache.spark.sql.catalyst.plans.physical.Partitioning
 import org.apache.spark.sql.execution.FileSourceScanExec
+import org.apache.spark.sql.execution.GlobalLimitExec
 import org.apache.spark.sql.execution.SparkPlan
 import org.apache.spark.sql.execution.blaze.plan._
 import org.apache.spark.sql.execution.blaze.shuffle.RssPartitionWriterBase
@@ -40,6 +41,7 @@ import org.apache.spark.sql.catalyst.expressions.Attribute
 import org.apache.spark.sql.catalyst.expressions.Generator
 import org.apache.spark.sql.catalyst.expressions.NamedExpression
 import org.apache.spark.sql.catalyst.expressions.SortOrder
+import org.apache.spark.sql.catalyst.expressions.WindowSpecDefinition
 import org.apache.spark.sql.catalyst.plans.JoinType
 import org.apache.spark.sql.execution.blaze.plan.NativeBroadcastJoinBase
 import org.apache.spark.sql.execution.blaze.plan.NativeSortMergeJoinBase
@@ -110,7 +112,10 @@ abstract class Shims {
       generatorOutput: Seq[Attribute],
       child: SparkPlan): NativeGenerateBase
 
-  def createNativeGlobalLimitExec(limit: Long, child: SparkPlan): NativeGlobalLimitBase
+  def createNativeGlobalLimitExec(
+      limit: Long,
+      offset: Long,
+      child: SparkPlan): NativeGlobalLimitBase
 
   def createNativeLocalLimitExec(limit: Long, child: SparkPlan): NativeLocalLimitBase
 
@@ -129,6 +134,13 @@ abstract class Shims {
       child: SparkPlan,
       newColumnNames: Seq[String]): NativeRenameColumnsBase
 
+  def createNativeSampleExec(
+      lowerBound: Double,
+      upperBound: Double,
+      withReplacement: Boolean,
+      seed: Long,
+      child: SparkPlan): NativeSampleBase
+
   def createNativeShuffleExchangeExec(
       outputPartitioning: Partitioning,
       child: SparkPlan): NativeShuffleExchangeBase
@@ -197,6 +209,14 @@ abstract class Shims {
 
   def getLikeEscapeChar(expr: Expression): Char
 
+  def getGlobalLimitOffset(exec: GlobalLimitExec): Long
+
+  // returns false if the window function is not supported
+  def convertMoreWindowFunction(
+      function: Expression,
+      spec: WindowSpecDefinition,
+      windowExprBuilder: pb.WindowExprNode.Builder): Boolean
+
   def getAggregateExpressionFilter(expr: Expression): Option[Expression]
 
   def createFileSegment(file: File, offset: Long, length: Long, numRecords: Long): FileSegment
@@ -207,9 +227,13 @@ abstract class Shims {
       tempDataFile: File,
       mapId: Long,
       partitionLengths: Array[Long],
+      checksums: Array[Long],
       dataSize: Long,
       context: TaskContext): MapStatus
 
+  // returns the checksum algorithm if spark.shuffle.checksum.enabled is on
+  def getShuffleChecksumAlgorithm: Option[String]
+
   def getRssPartitionWriter(
       handle: ShuffleHandle,
       mapId: Int,
@@ -24,6 +24,7 @@ import org.apache.spark.ShuffleDependency
 import org.apache.spark.SparkEnv
 import org.apache.spark.TaskContext
 import org.blaze.protobuf.{IpcReaderExecNode, PhysicalHashRepartition, PhysicalPlanNode, Schema}
+import org.apache.spark.rdd.MapPartitionsRDD
 import org.apache.spark.rdd.RDD
 import org.apache.spark.serializer.Serializer
 import org.apache.spark.shuffle.ShuffleWriteProcessor
@@ -37,7 +38,9 @@ import org.apache.spark.sql.blaze.Shims
 import org.apache.spark.sql.catalyst.plans.physical.Partitioning
 import org.apache.spark.sql.catalyst.InternalRow
 import org.apache.spark.sql.catalyst.expressions.Attribute
+import org.apache.spark.sql.catalyst.expressions.Murmur3Hash
 import org.apache.spark.sql.catalyst.plans.physical.HashPartitioning
+import org.apache.spark.sql.catalyst.plans.physical.RoundRobinPartitioning
 import org.apache.spark.sql.catalyst.plans.physical.SinglePartition
 import org.apache.spark.sql.execution.exchange.ShuffleExchangeLike
 import org.apache.spark.sql.execution.metric.SQLMetric
@@ -91,6 +94,15 @@ abstract class NativeShuffleExchangeBase(
     case _ => null
   }
 
+  // seed of murmur3 hashes, taken from spark's partition id expression
+  private def hashSeed: Int = outputPartitioning match {
+    case p: HashPartitioning =>
+      p.partitionIdExpression
+        .collectFirst { case hash: Murmur3Hash => hash.seed }
+        .getOrElse(new Murmur3Hash(Nil).seed)
+    case _ => 0
+  }
+
   // check whether native converting is supported
   nativeSchema
   nativeHashExprs
@@ -186,6 +198,7 @@ abstract class NativeShuffleExchangeBase(
         case _ =>
       }))
     val nativeHashExprs = this.nativeHashExprs
+    val hashSeed = this.hashSeed
 
     val nativeShuffleRDD = new NativeRDD(
       nativeInputRDD.sparkContext,
@@ -205,6 +218,12 @@ abstract class NativeShuffleExchangeBase(
               .newBuilder()
               .setPartitionCount(numPartitions)
               .addAllHashExpr(nativeHashExprs.asJava)
+              .setHashSeed(hashSeed)
+          case RoundRobinPartitioning(_) =>
+            PhysicalHashRepartition
+              .newBuilder()
+              .setPartitionCount(numPartitions)
+              .setRoundRobin(true)
           case p =>
             throw new NotImplementedError(s"cannot convert partitioning to native: $p")
         }
@@ -216,8 +235,17 @@ abstract class NativeShuffleExchangeBase(
       },
       friendlyName = "NativeRDD.ShuffleWrite")
 
+    // round-robin output depends on the input order. instead of sorting each
+    // map partition (spark.sql.execution.sortBeforeRepartition, which is not
+    // supported natively), the map stage is marked order-sensitive, so it is
+    // considered indeterminate and, on fetch failure, spark retries all its map
+    // tasks together with all succeeding stages instead of single partitions
+    val isOrderSensitive = outputPartitioning.isInstanceOf[RoundRobinPartitioning]
     val dependency = new BlazeShuffleDependency[Int, InternalRow, InternalRow](
-      nativeShuffleRDD.map((0, _)),
+      new MapPartitionsRDD[(Int, InternalRow), InternalRow](
+        nativeShuffleRDD,
+        (_, _, iter) => iter.map((0, _)),
+        isOrderSensitive = isOrderSensitive),
       serializer = serializer,
       shuffleWriterProcessor = createNativeShuffleWriteProcessor(metrics, numPartitions),
       partitioner = new Partitioner {
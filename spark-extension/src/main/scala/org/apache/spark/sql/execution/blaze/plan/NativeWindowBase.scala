@@ -24,6 +24,7 @@ import org.apache.spark.sql.blaze.NativeConverters
 import org.apache.spark.sql.blaze.NativeHelper
 import org.apache.spark.sql.blaze.NativeRDD
 import org.apache.spark.sql.blaze.NativeSupports
+import org.apache.spark.sql.blaze.Shims
 import org.apache.spark.sql.catalyst.expressions.Ascending
 import org.apache.spark.sql.catalyst.expressions.Attribute
 import org.apache.spark.sql.catalyst.expressions.Expression
@@ -39,7 +40,10 @@ import org.apache.spark.sql.execution.SparkPlan
 import org.apache.spark.sql.execution.UnaryExecNode
 import org.apache.spark.sql.execution.metric.SQLMetric
 import org.blaze.{protobuf => pb}
+import org.apache.spark.sql.catalyst.expressions.CumeDist
 import org.apache.spark.sql.catalyst.expressions.DenseRank
+import org.apache.spark.sql.catalyst.expressions.NTile
+import org.apache.spark.sql.catalyst.expressions.PercentRank
 import org.apache.spark.sql.catalyst.expressions.RowNumber
 import org.apache.spark.sql.catalyst.expressions.WindowExpression
 import org.apache.spark.sql.catalyst.expressions.aggregate.Average
@@ -105,6 +109,28 @@ abstract class NativeWindowBase(
             windowExprBuilder.setFuncType(pb.WindowFunctionType.Window)
             windowExprBuilder.setWindowFunc(pb.WindowFunction.DENSE_RANK)
 
+          case e: NTile =>
+            assert(
+              spec.frameSpecification == e.frame,
+              s"window frame not supported: ${spec.frameSpecification}")
+            windowExprBuilder.setFuncType(pb.WindowFunctionType.Window)
+            windowExprBuilder.setWindowFunc(pb.WindowFunction.NTILE)
+            windowExprBuilder.addChildren(NativeConverters.convertExpr(e.buckets))
+
+          case e: PercentRank =>
+            assert(
+              spec.frameSpecification == e.frame,
+              s"window frame not supported: ${spec.frameSpecification}")
+            windowExprBuilder.setFuncType(pb.WindowFunctionType.Window)
+            windowExprBuilder.setWindowFunc(pb.WindowFunction.PERCENT_RANK)
+
+          case e: CumeDist =>
+            assert(
+              spec.frameSpecification == e.frame,
+              s"window frame not supported: ${spec.frameSpecification}")
+            windowExprBuilder.setFuncType(pb.WindowFunctionType.Window)
+            windowExprBuilder.setWindowFunc(pb.WindowFunction.CUME_DIST)
+
           case e: Sum =>
             assert(
               spec.frameSpecification == RowNumber().frame, // only supports RowFrame(Unbounde, CurrentRow)
@@ -146,7 +172,9 @@ abstract class NativeWindowBase(
             windowExprBuilder.addChildren(NativeConverters.convertExpr(child))
 
           case other =>
-            throw new NotImplementedError(s"window function not supported: $other")
+            if (!Shims.get.convertMoreWindowFunction(other, spec, windowExprBuilder)) {
+              throw new NotImplementedError(s"window function not supported: $other")
+            }
         }
       case other =>
         throw new NotImplementedError(s"expect WindowExpression, got: $other")
@@ -0,0 +1,103 @@
+/*
+ * Copyright 2022 The Blaze Authors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+package org.apache.spark.sql.execution.blaze.shuffle
+
+import java.util.UUID
+
+import org.apache.spark.Partition
+import org.apache.spark.ShuffleDependency
+import org.apache.spark.TaskContext
+import org.blaze.protobuf.PhysicalPlanNode
+import org.blaze.protobuf.RssShuffleWriterExecNode
+
+import org.apache.spark.internal.Logging
+import org.apache.spark.scheduler.MapStatus
+import org.apache.spark.shuffle.ShuffleHandle
+import org.apache.spark.shuffle.ShuffleWriteMetricsReporter
+import org.apache.spark.shuffle.ShuffleWriter
+import org.apache.spark.sql.blaze.JniBridge
+import org.apache.spark.sql.blaze.NativeHelper
+import org.apache.spark.sql.blaze.NativeRDD
+
+/**
+ * Base of shuffle writers for remote shuffle services (like celeborn/uniffle).
+ * Partition data is streamed to the service through a [[RssPartitionWriterBase]]
+ * instead of being written into local data/index files.
+ */
+abstract class BlazeRssShuffleWriterBase[K, V](metrics: ShuffleWriteMetricsReporter)
+    extends ShuffleWriter[K, V]
+    with Logging {
+
+  protected var partitionLengths: Array[Long] = Array[Long]()
+
+  def getRssPartitionWriter(
+      handle: ShuffleHandle,
+      mapId: Int,
+      metrics: ShuffleWriteMetricsReporter,
+      numPartitions: Int): RssPartitionWriterBase
+
+  // finishes the map task on the remote shuffle service and returns its map status
+  def rssStop(success: Boolean): Option[MapStatus]
+
+  override def write(records: Iterator[Product2[K, V]]): Unit = {}
+
+  def nativeRssShuffleWrite(
+      nativeShuffleRDD: NativeRDD,
+      dep: ShuffleDependency[_, _, _],
+      mapId: Int,
+      context: TaskContext,
+      partition: Partition,
+      numPartitions: Int): Unit = {
+
+    val rssPartitionWriter =
+      getRssPartitionWriter(dep.shuffleHandle, mapId, metrics, numPartitions)
+    val rssPartitionWriterResourceId = s"RssPartitionWriter:${UUID.randomUUID().toString}"
+    JniBridge.resourcesMap.put(rssPartitionWriterResourceId, rssPartitionWriter)
+
+    try {
+      val shuffleWriterExec = nativeShuffleRDD.nativePlan(partition, context).getShuffleWriter
+      val nativeRssShuffleWriterExec = PhysicalPlanNode
+        .newBuilder()
+        .setRssShuffleWriter(
+          RssShuffleWriterExecNode
+            .newBuilder()
+            .setInput(shuffleWriterExec.getInput)
+            .setOutputPartitioning(shuffleWriterExec.getOutputPartitioning)
+            .setRssPartitionWriterResourceId(rssPartitionWriterResourceId)
+            .build())
+        .build()
+      val iterator = NativeHelper.executeNativePlan(
+        nativeRssShuffleWriterExec,
+        nativeShuffleRDD.metrics,
+        partition,
+        Some(context))
+      assert(iterator.toArray.isEmpty)
+
+      rssPartitionWriter.flush()
+      rssPartitionWriter.close()
+      partitionLengths = rssPartitionWriter.getPartitionLengthMap
+      metrics.incBytesWritten(partitionLengths.sum)
+    } finally {
+      JniBridge.resourcesMap.remove(rssPartitionWriterResourceId)
+    }
+  }
+
+  override def stop(success: Boolean): Option[MapStatus] = rssStop(success)
+
+  // implements ShuffleWriter.getPartitionLengths() since spark 3.2, declared
+  // without override so that it also compiles with earlier versions
+  def getPartitionLengths(): Array[Long] = partitionLengths
+}
@@ -63,15 +63,22 @@ abstract class BlazeShuffleWriterBase[K, V](metrics: ShuffleWriteMetricsReporter
     val tempIndexFilename = dataFile.getPath.replace(".data", ".index.tmp")
     val tempDataFilePath = Paths.get(tempDataFilename)
     val tempIndexFilePath = Paths.get(tempIndexFilename)
+    val tempChecksumFilename = dataFile.getPath.replace(".data", ".checksum.tmp")
+    val tempChecksumFilePath = Paths.get(tempChecksumFilename)
+    val checksumAlgorithm = Shims.get.getShuffleChecksumAlgorithm
 
+    val shuffleWriterExecBuilder = ShuffleWriterExecNode
+      .newBuilder(nativeShuffleRDD.nativePlan(partition, context).getShuffleWriter)
+      .setOutputDataFile(tempDataFilename)
+      .setOutputIndexFile(tempIndexFilename)
+    checksumAlgorithm.foreach { algorithm =>
+      shuffleWriterExecBuilder
+        .setOutputChecksumFile(tempChecksumFilename)
+        .setChecksumAlgorithm(algorithm)
+    }
     val nativeShuffleWriterExec = PhysicalPlanNode
       .newBuilder()
-      .setShuffleWriter(
-        ShuffleWriterExecNode
-          .newBuilder(nativeShuffleRDD.nativePlan(partition, context).getShuffleWriter)
-          .setOutputDataFile(tempDataFilename)
-          .setOutputIndexFile(tempIndexFilename)
-          .build())
+      .setShuffleWriter(shuffleWriterExecBuilder.build())
       .build()
     val iterator = NativeHelper.executeNativePlan(
       nativeShuffleWriterExec,
@@ -95,6 +102,15 @@ abstract class BlazeShuffleWriterBase[K, V](metrics: ShuffleWriteMetricsReporter
       })
       .toArray
 
+    // get partition checksums from shuffle write output checksum file
+    val checksums = if (checksumAlgorithm.isDefined) {
+      val checksumBuffer = ByteBuffer.wrap(Files.readAllBytes(tempChecksumFilePath))
+      Files.delete(tempChecksumFilePath)
+      Array.fill(partitionLengths.length)(checksumBuffer.getLong)
+    } else {
+      Array[Long]()
+    }
+
     // update metrics
     val dataSize = Files.size(tempDataFilePath)
     metrics.incBytesWritten(dataSize)
@@ -105,6 +121,7 @@ abstract class BlazeShuffleWriterBase[K, V](metrics: ShuffleWriteMetricsReporter
       tempDataFilePath.toFile,
       mapId,
       partitionLengths,
+      checksums,
       dataSize,
       context)
   }